@@ -0,0 +1,11 @@
+CREATE TABLE views (
+    id BIGSERIAL PRIMARY KEY,
+    organization_id VARCHAR NOT NULL,
+    created_by VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    name TEXT NOT NULL,
+    version INT NOT NULL,
+    definition TEXT NOT NULL,
+
+    UNIQUE (organization_id, name, version)
+);
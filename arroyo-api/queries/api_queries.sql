@@ -92,6 +92,25 @@ WHERE organization_id = :organization_id;
 DELETE FROM sinks
 WHERE organization_id = :organization_id AND name = :name;
 
+----------- views ----------------------
+
+--! create_view
+INSERT INTO views (organization_id, created_by, name, version, definition)
+VALUES (:organization_id, :created_by, :name,
+    (SELECT COALESCE(MAX(version), 0) + 1 FROM views WHERE organization_id = :organization_id AND name = :name),
+    :definition)
+RETURNING version;
+
+--! get_views
+SELECT DISTINCT ON (name) name, version, definition
+FROM views
+WHERE organization_id = :organization_id
+ORDER BY name, version DESC;
+
+--! delete_view
+DELETE FROM views
+WHERE organization_id = :organization_id AND name = :name;
+
 ----------- pipelines -------------------
 
 --! create_pipeline
@@ -137,8 +156,8 @@ WHERE id = :job_id AND organization_id = :organization_id;
 
 --! create_job(ttl_micros?)
 INSERT INTO job_configs
-    (id, organization_id, pipeline_name, created_by, pipeline_definition, checkpoint_interval_micros, ttl_micros)
-VALUES (:id, :organization_id, :pipeline_name, :created_by, :pipeline_definition, :checkpoint_interval_micros, :ttl_micros);
+    (id, organization_id, pipeline_name, created_by, pipeline_definition, checkpoint_interval_micros, ttl_micros, unaligned_checkpoints)
+VALUES (:id, :organization_id, :pipeline_name, :created_by, :pipeline_definition, :checkpoint_interval_micros, :ttl_micros, :unaligned_checkpoints);
 
 --! create_job_status
 INSERT INTO job_statuses (id, organization_id) VALUES (:id, :organization_id);
@@ -74,6 +74,7 @@ pub(crate) async fn create_job<'a>(
             } else {
                 None
             }),
+            &request.unaligned_checkpoints,
         )
         .await
         .map_err(log_and_map)?;
@@ -1,7 +1,8 @@
 use ::time::OffsetDateTime;
 use arroyo_rpc::grpc::api::{
-    DeleteConnectionReq, DeleteConnectionResp, DeleteJobReq, DeleteJobResp, DeleteSinkReq,
-    DeleteSinkResp, DeleteSourceReq, DeleteSourceResp, GetSinksReq, GetSinksResp, PipelineProgram,
+    CreateViewReq, CreateViewResp, DeleteConnectionReq, DeleteConnectionResp, DeleteJobReq,
+    DeleteJobResp, DeleteSinkReq, DeleteSinkResp, DeleteSourceReq, DeleteSourceResp, DeleteViewReq,
+    DeleteViewResp, GetSinksReq, GetSinksResp, GetViewsReq, GetViewsResp, PipelineProgram,
     SourceMetadataResp,
 };
 use arroyo_rpc::grpc::{
@@ -19,6 +20,7 @@ use arroyo_rpc::grpc::{
     },
     controller_grpc_client::ControllerGrpcClient,
 };
+use arroyo_server_common::tls::grpc_endpoint;
 use arroyo_server_common::{log_event, start_admin_server};
 use arroyo_types::{
     grpc_port, ports, service_port, telemetry_enabled, DatabaseConfig, API_ENDPOINT_ENV,
@@ -64,6 +66,7 @@ mod pipelines;
 mod sinks;
 mod sources;
 mod testers;
+mod views;
 
 include!(concat!(env!("OUT_DIR"), "/api-sql.rs"));
 
@@ -355,11 +358,17 @@ impl ApiServer {
             .await
             .map_err(log_and_map)?;
 
-        let pipeline_id = pipelines::create_pipeline(req, auth.clone(), &transaction).await?;
+        let Some(pipeline_id) = pipelines::create_pipeline(req, auth.clone(), &transaction).await?
+        else {
+            return Ok(Response::new(CreateJobResp {
+                job_id: String::new(),
+            }));
+        };
         let create_job = CreateJobReq {
             pipeline_id: format!("{}", pipeline_id),
             checkpoint_interval_micros: DEFAULT_CHECKPOINT_INTERVAL.as_micros() as u64,
             preview,
+            unaligned_checkpoints: false,
         };
 
         let job_id = jobs::create_job(create_job, auth, &transaction).await?;
@@ -566,6 +575,40 @@ impl ApiGrpc for ApiServer {
         Ok(Response::new(DeleteSinkResp {}))
     }
 
+    // views
+    async fn create_view(
+        &self,
+        request: Request<CreateViewReq>,
+    ) -> Result<Response<CreateViewResp>, Status> {
+        let (request, auth) = self.authenticate(request).await?;
+
+        let version = views::create_view(request.into_inner(), auth, &self.client().await?).await?;
+
+        Ok(Response::new(CreateViewResp { version }))
+    }
+
+    async fn get_views(
+        &self,
+        request: Request<GetViewsReq>,
+    ) -> Result<Response<GetViewsResp>, Status> {
+        let (_, auth) = self.authenticate(request).await?;
+
+        Ok(Response::new(GetViewsResp {
+            views: views::get_views(&auth, &self.client().await?).await?,
+        }))
+    }
+
+    async fn delete_view(
+        &self,
+        request: Request<DeleteViewReq>,
+    ) -> Result<Response<DeleteViewResp>, Status> {
+        let (request, auth) = self.authenticate(request).await?;
+
+        views::delete_view(request.into_inner(), auth, &self.client().await?).await?;
+
+        Ok(Response::new(DeleteViewResp {}))
+    }
+
     // pipelines
     async fn create_pipeline(
         &self,
@@ -576,7 +619,13 @@ impl ApiGrpc for ApiServer {
         let mut client = self.client().await?;
         let transaction = client.transaction().await.map_err(log_and_map)?;
 
-        let id = pipelines::create_pipeline(request.into_inner(), auth, &transaction).await?;
+        let Some(id) = pipelines::create_pipeline(request.into_inner(), auth, &transaction).await?
+        else {
+            // the pipeline was only validated
+            return Ok(Response::new(CreatePipelineResp {
+                pipeline_id: String::new(),
+            }));
+        };
 
         transaction.commit().await.map_err(log_and_map)?;
 
@@ -816,9 +865,13 @@ impl ApiGrpc for ApiServer {
 
         let (tx, rx) = tokio::sync::mpsc::channel(32);
 
-        let mut controller = ControllerGrpcClient::connect(self.controller_addr.clone())
-            .await
-            .map_err(log_and_map)?;
+        let mut controller = ControllerGrpcClient::new(
+            grpc_endpoint(self.controller_addr.clone())
+                .map_err(log_and_map)?
+                .connect()
+                .await
+                .map_err(log_and_map)?,
+        );
 
         info!("connected to controller");
 
@@ -6,7 +6,7 @@ use arroyo_rpc::grpc::api::{job_metrics_resp::OperatorMetrics, JobMetricsResp};
 use arroyo_rpc::grpc::api::{Metric, SubtaskMetrics};
 use arroyo_types::{
     to_millis, API_METRICS_RATE_ENV, BYTES_RECV, BYTES_SENT, MESSAGES_RECV, MESSAGES_SENT,
-    TX_QUEUE_REM, TX_QUEUE_SIZE,
+    TX_QUEUE_BLOCKED_MICROS,
 };
 use http::{header::AUTHORIZATION, HeaderMap, HeaderValue};
 use once_cell::sync::Lazy;
@@ -62,16 +62,12 @@ pub(crate) async fn get_metrics(
             )
         }
 
-        fn backpressure_query(&self, job_id: &str, run_id: u64) -> String {
-            let tx_queue_size: String = format!(
-                "{}{{job_id=\"{}\",run_id=\"{}\"}}",
-                TX_QUEUE_SIZE, job_id, run_id
-            );
-            let tx_queue_rem: String = format!(
-                "{}{{job_id=\"{}\",run_id=\"{}\"}}",
-                TX_QUEUE_REM, job_id, run_id
-            );
-            format!("({} - {}) / {}", tx_queue_size, tx_queue_rem, tx_queue_size)
+        // the fraction of time that each subtask spent blocked on its most backpressured output
+        fn backpressure_query(&self, job_id: &str, run_id: u64, rate: &str) -> String {
+            format!(
+                "max by (operator_id, subtask_idx) (rate({}{{job_id=\"{}\",run_id=\"{}\"}}[{}])) / 1000000",
+                TX_QUEUE_BLOCKED_MICROS, job_id, run_id, rate
+            )
         }
 
         fn get_query(&self, job_id: &str, run_id: u64, rate: &str) -> String {
@@ -80,7 +76,7 @@ pub(crate) async fn get_metrics(
                 BytesSent => self.simple_query(BYTES_SENT, job_id, run_id, rate),
                 MessagesRecv => self.simple_query(MESSAGES_RECV, job_id, run_id, rate),
                 MessagesSent => self.simple_query(MESSAGES_SENT, job_id, run_id, rate),
-                Backpressure => self.backpressure_query(job_id, run_id),
+                Backpressure => self.backpressure_query(job_id, run_id, rate),
             };
             return query;
         }
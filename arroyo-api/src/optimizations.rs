@@ -472,6 +472,7 @@ mod tests {
                 },
                 agg: None,
                 flatten: false,
+                allowed_lateness: Duration::ZERO,
             },
             parallelism: 5,
         });
@@ -558,6 +559,7 @@ mod tests {
                 },
                 agg: Some(WindowAgg::Count),
                 flatten: false,
+                allowed_lateness: Duration::ZERO,
             },
             graph.node_weight(window).unwrap().operator
         );
@@ -4,8 +4,8 @@ use arroyo_rpc::grpc::api::create_sql_job::Sink;
 use arroyo_rpc::grpc::api::sink::SinkType;
 use arroyo_rpc::grpc::api::{
     self, connection, create_pipeline_req, BuiltinSink, Connection, CreatePipelineReq,
-    CreateSqlJob, PipelineDef, PipelineGraphReq, PipelineGraphResp, PipelineProgram, SqlError,
-    SqlErrors, Udf, UdfLanguage,
+    CreateSqlJob, PipelineDef, PipelineGraphReq, PipelineGraphResp, PipelineProgram,
+    PlanExplanation, SqlError, SqlErrors, SqlSpan, Udf, UdfLanguage,
 };
 use arroyo_sql::{ArroyoSchemaProvider, SqlConfig};
 
@@ -26,7 +26,7 @@ use crate::types::public::PipelineType;
 use crate::{
     connections, handle_db_error, log_and_map, optimizations, required_field, sinks,
     sources::{self, Source},
-    AuthData,
+    views, AuthData,
 };
 
 async fn compile_sql<'e, E>(
@@ -60,6 +60,9 @@ where
         let connection: Connection = connections.try_into().map_err(log_and_map)?;
         schema_provider.add_connection(connection)
     }
+    for view in views::get_views(auth_data, tx).await? {
+        schema_provider.add_saved_definition(view.definition);
+    }
 
     let sinks = sinks::get_sinks(auth_data, tx).await?;
 
@@ -102,18 +105,49 @@ where
             default_parallelism: sql.parallelism as usize,
             sink,
             kafka_qps: Some(auth_data.org_metadata.kafka_qps),
+            ..Default::default()
         },
     )
     .await
     .with_context(|| "failed to generate SQL program")
     .map_err(|err| {
         warn!("{:?}", err);
-        Status::invalid_argument(format!("{}", err.root_cause()))
+        sql_error_status(&err)
     })?;
 
     Ok((program, sources, used_sink_ids))
 }
 
+/// Returns an InvalidArgument status for the error, with the `SqlErrors` that locate it in the
+/// query as its details.
+fn sql_error_status(err: &anyhow::Error) -> Status {
+    let error = arroyo_sql::errors::SqlError::from_anyhow(err);
+    let details = SqlErrors {
+        errors: vec![SqlError {
+            message: error.message.clone(),
+            span: error.span.map(|span| SqlSpan {
+                start_line: span.start.line,
+                start_column: span.start.column,
+                end_line: span.end.line,
+                end_column: span.end.column,
+            }),
+        }],
+    };
+    Status::with_details(
+        tonic::Code::InvalidArgument,
+        error.to_string(),
+        details.encode_to_vec().into(),
+    )
+}
+
+/// Replaces the query template with the query given by its parameters.
+fn expand_parameters(sql: &mut CreateSqlJob) -> Result<(), Status> {
+    sql.query = arroyo_sql::substitute_parameters(&sql.query, &sql.parameters)
+        .map_err(|e| Status::invalid_argument(format!("{}", e)))?;
+    sql.parameters.clear();
+    Ok(())
+}
+
 fn set_parallelism(program: &mut Program, parallelism: usize) {
     for node in program.graph.node_weights_mut() {
         node.parallelism = parallelism;
@@ -174,7 +208,7 @@ pub(crate) async fn create_pipeline<'a>(
     req: CreatePipelineReq,
     auth: AuthData,
     tx: &Transaction<'a>,
-) -> Result<i64, Status> {
+) -> Result<Option<i64>, Status> {
     let pipeline_type;
     let mut program;
     let sources;
@@ -203,7 +237,7 @@ pub(crate) async fn create_pipeline<'a>(
             compute_parallelism = false;
             is_preview = false;
         }
-        create_pipeline_req::Config::Sql(sql) => {
+        create_pipeline_req::Config::Sql(mut sql) => {
             if sql.parallelism > auth.org_metadata.max_parallelism as u64 {
                 return Err(Status::invalid_argument(format!(
                     "Your plan allows you to run pipelines up to parallelism {};
@@ -213,6 +247,7 @@ pub(crate) async fn create_pipeline<'a>(
             }
 
             pipeline_type = PipelineType::sql;
+            expand_parameters(&mut sql)?;
             (program, sources, sinks) = compile_sql(&sql, &auth, tx).await?;
             text = Some(sql.query);
             udfs = Some(
@@ -247,6 +282,10 @@ pub(crate) async fn create_pipeline<'a>(
         )));
     }
 
+    if req.validate_only {
+        return Ok(None);
+    }
+
     if is_preview {
         set_parallelism(&mut program, 1);
         for node in program.graph.node_weights_mut() {
@@ -312,7 +351,7 @@ pub(crate) async fn create_pipeline<'a>(
         }
     }
 
-    Ok(pipeline_id)
+    Ok(Some(pipeline_id))
 }
 
 impl TryInto<PipelineDef> for DbPipeline {
@@ -361,30 +400,56 @@ pub(crate) async fn sql_graph(
     auth: AuthData,
     client: &impl GenericClient,
 ) -> Result<PipelineGraphResp, Status> {
-    let sql = CreateSqlJob {
+    let mut sql = CreateSqlJob {
         query: req.query,
         parallelism: 1,
         udfs: req.udfs,
         sink: Some(Sink::Builtin(BuiltinSink::Null as i32)),
         preview: false,
+        parameters: req.parameters,
+    };
+    let compiled = match expand_parameters(&mut sql) {
+        Ok(()) => compile_sql(&sql, &auth, client).await,
+        Err(err) => Err(err),
     };
 
-    match compile_sql(&sql, &auth, client).await {
+    // queries that don't parse are reported by compile_sql
+    let explain = arroyo_sql::explain_options(&sql.query).ok().flatten();
+
+    match compiled {
         Ok((mut program, _, _)) => {
             optimizations::optimize(&mut program.graph);
+            let explanation = match explain {
+                Some(options) => {
+                    let explanation = program.explain(options.verbose);
+                    Some(PlanExplanation {
+                        text: explanation.to_text(),
+                        json: serde_json::to_string(&explanation).map_err(log_and_map)?,
+                    })
+                }
+                None => None,
+            };
             Ok(PipelineGraphResp {
                 result: Some(api::pipeline_graph_resp::Result::JobGraph(
                     program.as_job_graph(),
                 )),
+                explanation,
             })
         }
         Err(err) => match err.code() {
             tonic::Code::InvalidArgument => Ok(PipelineGraphResp {
-                result: Some(api::pipeline_graph_resp::Result::Errors(SqlErrors {
-                    errors: vec![SqlError {
-                        message: err.message().to_string(),
-                    }],
-                })),
+                result: Some(api::pipeline_graph_resp::Result::Errors(
+                    SqlErrors::decode(err.details())
+                        .ok()
+                        .filter(|errors| !errors.errors.is_empty())
+                        .unwrap_or_else(|| SqlErrors {
+                            errors: vec![SqlError {
+                                message: err.message().to_string(),
+                                span: None,
+                            }],
+                        }),
+                )),
+                explanation: None,
             }),
             _ => Err(err),
         },
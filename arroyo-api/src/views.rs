@@ -0,0 +1,69 @@
+use cornucopia_async::GenericClient;
+use tonic::Status;
+
+use arroyo_rpc::grpc::api::{CreateViewReq, DeleteViewReq, View};
+
+use crate::{handle_db_error, log_and_map, queries::api_queries, required_field, AuthData};
+
+pub(crate) async fn create_view(
+    req: CreateViewReq,
+    auth: AuthData,
+    client: &impl GenericClient,
+) -> Result<i32, Status> {
+    if req.definition.trim().is_empty() {
+        return Err(required_field("definition"));
+    }
+
+    let name = arroyo_sql::saved_definition_name(&req.definition)
+        .map_err(|e| Status::invalid_argument(format!("Invalid view definition: {}", e)))?;
+
+    api_queries::create_view()
+        .bind(
+            client,
+            &auth.organization_id,
+            &auth.user_id,
+            &name,
+            &req.definition,
+        )
+        .one()
+        .await
+        .map_err(|err| handle_db_error("view", err))
+}
+
+/// Returns the latest version of each view.
+pub(crate) async fn get_views<E: GenericClient>(
+    auth: &AuthData,
+    client: &E,
+) -> Result<Vec<View>, Status> {
+    let views = api_queries::get_views()
+        .bind(client, &auth.organization_id)
+        .all()
+        .await
+        .map_err(log_and_map)?;
+
+    Ok(views
+        .into_iter()
+        .map(|view| View {
+            name: view.name,
+            version: view.version,
+            definition: view.definition,
+        })
+        .collect())
+}
+
+pub(crate) async fn delete_view(
+    req: DeleteViewReq,
+    auth: AuthData,
+    client: &impl GenericClient,
+) -> Result<(), Status> {
+    let deleted = api_queries::delete_view()
+        .bind(client, &auth.organization_id, &req.name)
+        .await
+        .map_err(log_and_map)?;
+
+    if deleted == 0 {
+        return Err(Status::not_found(format!("No view with name {}", req.name)));
+    }
+
+    Ok(())
+}
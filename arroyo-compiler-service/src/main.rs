@@ -11,7 +11,7 @@ use arroyo_rpc::grpc::{
 };
 
 use arroyo_server_common::start_admin_server;
-use arroyo_types::{grpc_port, ports, S3_BUCKET_ENV, S3_REGION_ENV};
+use arroyo_types::{grpc_port, ports, S3_BUCKET_ENV, S3_REGION_ENV, UDF_CRATE_REGISTRY_ENV};
 use object_store::aws::AmazonS3Builder;
 use object_store::local::LocalFileSystem;
 use object_store::ObjectStore;
@@ -30,6 +30,58 @@ pub fn from_millis(ts: u64) -> SystemTime {
     UNIX_EPOCH + Duration::from_millis(ts)
 }
 
+/// Cargo configuration that replaces crates.io with the vendored directory or local registry
+/// at `$UDF_CRATE_REGISTRY`, so that the crates UDFs depend on are resolved offline.
+fn udf_crate_registry_config() -> Option<String> {
+    let registry = std::env::var(UDF_CRATE_REGISTRY_ENV).ok()?;
+    // local registries have an index, while vendored directories just contain the crates
+    let source = if std::path::Path::new(&registry).join("index").is_dir() {
+        "local-registry"
+    } else {
+        "directory"
+    };
+    Some(format!(
+        r#"
+[source.crates-io]
+replace-with = "udf-registry"
+
+[source.udf-registry]
+{} = "{}"
+"#,
+        source, registry
+    ))
+}
+
+/// Adds the crates that UDFs depend on to the `[dependencies]` of a pipeline's Cargo.toml. Each
+/// spec is parsed as a single TOML value, so a malformed spec can't inject other configuration.
+fn add_udf_dependencies<'a>(
+    cargo_toml: &str,
+    dependencies: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> io::Result<String> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+
+    let mut cargo_toml: toml::Table =
+        toml::from_str(cargo_toml).map_err(|e| invalid(format!("invalid Cargo.toml: {}", e)))?;
+    let Some(toml::Value::Table(deps)) = cargo_toml.get_mut("dependencies") else {
+        return Err(invalid("Cargo.toml has no [dependencies]".to_string()));
+    };
+
+    for (name, spec) in dependencies {
+        let mut parsed: toml::Table = toml::from_str(&format!("spec = {}", spec))
+            .map_err(|e| invalid(format!("invalid spec for dependency {}: {}", name, e)))?;
+        let value = parsed.remove("spec");
+        let (Some(value), true) = (value, parsed.is_empty()) else {
+            return Err(invalid(format!(
+                "invalid spec for dependency {}: {}",
+                name, spec
+            )));
+        };
+        deps.insert(name.clone(), value);
+    }
+
+    toml::to_string(&cargo_toml).map_err(|e| invalid(e.to_string()))
+}
+
 #[tokio::main]
 pub async fn main() {
     let _guard = arroyo_server_common::init_logging("compiler-service");
@@ -70,8 +122,27 @@ pub async fn main() {
 
     let last_used = Arc::new(AtomicU64::new(to_millis(SystemTime::now())));
 
+    let build_dir = PathBuf::from_str(&build_dir).unwrap();
+    let pipeline_toml = match fs::read_to_string(build_dir.join("pipeline/Cargo.toml")) {
+        Ok(pipeline_toml) => Some(pipeline_toml),
+        Err(e) => {
+            error!(
+                "Failed to read pipeline/Cargo.toml in {:?}: {}",
+                build_dir, e
+            );
+            None
+        }
+    };
+
+    if let Some(registry_config) = udf_crate_registry_config() {
+        fs::create_dir_all(build_dir.join(".cargo")).unwrap();
+        fs::write(build_dir.join(".cargo/config.toml"), registry_config)
+            .expect("Failed to write cargo config");
+    }
+
     let service = CompileService {
-        build_dir: PathBuf::from_str(&build_dir).unwrap(),
+        build_dir,
+        pipeline_toml,
         lock: Arc::new(Mutex::new(())),
         last_used: last_used.clone(),
         object_store,
@@ -159,6 +230,9 @@ pub async fn start_service(service: CompileService) {
 
 pub struct CompileService {
     build_dir: PathBuf,
+    // the pipeline's Cargo.toml before any UDF dependencies are added; None if the build
+    // directory doesn't have one, in which case every compilation fails
+    pipeline_toml: Option<String>,
     lock: Arc<Mutex<()>>,
     last_used: Arc<AtomicU64>,
     object_store: Arc<Box<dyn ObjectStore>>,
@@ -167,6 +241,14 @@ pub struct CompileService {
 }
 
 impl CompileService {
+    fn offline_args() -> Vec<&'static str> {
+        if std::env::var(UDF_CRATE_REGISTRY_ENV).is_ok() {
+            vec!["--offline"]
+        } else {
+            vec![]
+        }
+    }
+
     async fn get_output(&self) -> io::Result<Output> {
         if self.debug {
             let args = if std::env::var("VERBOSE").is_ok() {
@@ -178,6 +260,7 @@ impl CompileService {
             Command::new("cargo")
                 .current_dir(&self.build_dir)
                 .args(&args)
+                .args(Self::offline_args())
                 .output()
                 .await
         } else {
@@ -185,6 +268,7 @@ impl CompileService {
                 .current_dir(&self.build_dir)
                 .arg("build")
                 .arg("--release")
+                .args(Self::offline_args())
                 .output()
                 .await
         }
@@ -202,12 +286,22 @@ impl CompileService {
         info!("Starting compilation for {}", req.job_id);
         let start = Instant::now();
         let build_dir = &self.build_dir;
+        let Some(pipeline_toml) = &self.pipeline_toml else {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("No pipeline/Cargo.toml found in {:?}", build_dir),
+            ));
+        };
+        let pipeline_toml = add_udf_dependencies(pipeline_toml, &req.udf_dependencies)?;
+
         tokio::fs::write(build_dir.join("pipeline/src/main.rs"), &req.pipeline).await?;
 
         tokio::fs::write(build_dir.join("types/src/lib.rs"), &req.types).await?;
 
         tokio::fs::write(build_dir.join("wasm-fns/src/lib.rs"), &req.wasm_fns).await?;
 
+        tokio::fs::write(build_dir.join("pipeline/Cargo.toml"), pipeline_toml).await?;
+
         let result = self.get_output().await?;
 
         if !result.status.success() {
@@ -480,6 +480,13 @@ export class CreateSqlJob extends Message<CreateSqlJob> {
    */
   preview = false;
 
+  /**
+   * values for the ${param} placeholders in the query
+   *
+   * @generated from field: map<string, string> parameters = 7;
+   */
+  parameters: { [key: string]: string } = {};
+
   constructor(data?: PartialMessage<CreateSqlJob>) {
     super();
     proto3.util.initPartial(data, this);
@@ -494,6 +501,7 @@ export class CreateSqlJob extends Message<CreateSqlJob> {
     { no: 3, name: "builtin", kind: "enum", T: proto3.getEnumType(BuiltinSink), oneof: "sink" },
     { no: 4, name: "user", kind: "scalar", T: 9 /* ScalarType.STRING */, oneof: "sink" },
     { no: 6, name: "preview", kind: "scalar", T: 8 /* ScalarType.BOOL */ },
+    { no: 7, name: "parameters", kind: "map", K: 9 /* ScalarType.STRING */, V: {kind: "scalar", T: 9 /* ScalarType.STRING */} },
   ]);
 
   static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): CreateSqlJob {
@@ -539,6 +547,13 @@ export class CreatePipelineReq extends Message<CreatePipelineReq> {
     case: "sql";
   } | { case: undefined; value?: undefined } = { case: undefined };
 
+  /**
+   * check that the pipeline is valid without saving or running it
+   *
+   * @generated from field: bool validate_only = 4;
+   */
+  validateOnly = false;
+
   constructor(data?: PartialMessage<CreatePipelineReq>) {
     super();
     proto3.util.initPartial(data, this);
@@ -550,6 +565,7 @@ export class CreatePipelineReq extends Message<CreatePipelineReq> {
     { no: 1, name: "name", kind: "scalar", T: 9 /* ScalarType.STRING */ },
     { no: 2, name: "program", kind: "scalar", T: 12 /* ScalarType.BYTES */, oneof: "config" },
     { no: 3, name: "sql", kind: "message", T: CreateSqlJob, oneof: "config" },
+    { no: 4, name: "validate_only", kind: "scalar", T: 8 /* ScalarType.BOOL */ },
   ]);
 
   static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): CreatePipelineReq {
@@ -606,6 +622,63 @@ export class CreatePipelineResp extends Message<CreatePipelineResp> {
   }
 }
 
+/**
+ * a range of the query text; lines and columns start at 1, and the end is exclusive
+ *
+ * @generated from message arroyo_api.SqlSpan
+ */
+export class SqlSpan extends Message<SqlSpan> {
+  /**
+   * @generated from field: uint64 start_line = 1;
+   */
+  startLine = protoInt64.zero;
+
+  /**
+   * @generated from field: uint64 start_column = 2;
+   */
+  startColumn = protoInt64.zero;
+
+  /**
+   * @generated from field: uint64 end_line = 3;
+   */
+  endLine = protoInt64.zero;
+
+  /**
+   * @generated from field: uint64 end_column = 4;
+   */
+  endColumn = protoInt64.zero;
+
+  constructor(data?: PartialMessage<SqlSpan>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.SqlSpan";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "start_line", kind: "scalar", T: 4 /* ScalarType.UINT64 */ },
+    { no: 2, name: "start_column", kind: "scalar", T: 4 /* ScalarType.UINT64 */ },
+    { no: 3, name: "end_line", kind: "scalar", T: 4 /* ScalarType.UINT64 */ },
+    { no: 4, name: "end_column", kind: "scalar", T: 4 /* ScalarType.UINT64 */ },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): SqlSpan {
+    return new SqlSpan().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): SqlSpan {
+    return new SqlSpan().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): SqlSpan {
+    return new SqlSpan().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: SqlSpan | PlainMessage<SqlSpan> | undefined, b: SqlSpan | PlainMessage<SqlSpan> | undefined): boolean {
+    return proto3.util.equals(SqlSpan, a, b);
+  }
+}
+
 /**
  * @generated from message arroyo_api.SqlError
  */
@@ -615,6 +688,11 @@ export class SqlError extends Message<SqlError> {
    */
   message = "";
 
+  /**
+   * @generated from field: arroyo_api.SqlSpan span = 2;
+   */
+  span?: SqlSpan;
+
   constructor(data?: PartialMessage<SqlError>) {
     super();
     proto3.util.initPartial(data, this);
@@ -624,6 +702,7 @@ export class SqlError extends Message<SqlError> {
   static readonly typeName = "arroyo_api.SqlError";
   static readonly fields: FieldList = proto3.util.newFieldList(() => [
     { no: 1, name: "message", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+    { no: 2, name: "span", kind: "message", T: SqlSpan },
   ]);
 
   static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): SqlError {
@@ -694,6 +773,11 @@ export class PipelineGraphReq extends Message<PipelineGraphReq> {
    */
   udfs: CreateUdf[] = [];
 
+  /**
+   * @generated from field: map<string, string> parameters = 3;
+   */
+  parameters: { [key: string]: string } = {};
+
   constructor(data?: PartialMessage<PipelineGraphReq>) {
     super();
     proto3.util.initPartial(data, this);
@@ -704,6 +788,7 @@ export class PipelineGraphReq extends Message<PipelineGraphReq> {
   static readonly fields: FieldList = proto3.util.newFieldList(() => [
     { no: 1, name: "query", kind: "scalar", T: 9 /* ScalarType.STRING */ },
     { no: 2, name: "udfs", kind: "message", T: CreateUdf, repeated: true },
+    { no: 3, name: "parameters", kind: "map", K: 9 /* ScalarType.STRING */, V: {kind: "scalar", T: 9 /* ScalarType.STRING */} },
   ]);
 
   static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): PipelineGraphReq {
@@ -723,6 +808,51 @@ export class PipelineGraphReq extends Message<PipelineGraphReq> {
   }
 }
 
+/**
+ * the physical dataflow of a query, as returned for EXPLAIN
+ *
+ * @generated from message arroyo_api.PlanExplanation
+ */
+export class PlanExplanation extends Message<PlanExplanation> {
+  /**
+   * @generated from field: string text = 1;
+   */
+  text = "";
+
+  /**
+   * @generated from field: string json = 2;
+   */
+  json = "";
+
+  constructor(data?: PartialMessage<PlanExplanation>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.PlanExplanation";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "text", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+    { no: 2, name: "json", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): PlanExplanation {
+    return new PlanExplanation().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): PlanExplanation {
+    return new PlanExplanation().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): PlanExplanation {
+    return new PlanExplanation().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: PlanExplanation | PlainMessage<PlanExplanation> | undefined, b: PlanExplanation | PlainMessage<PlanExplanation> | undefined): boolean {
+    return proto3.util.equals(PlanExplanation, a, b);
+  }
+}
+
 /**
  * @generated from message arroyo_api.PipelineGraphResp
  */
@@ -744,6 +874,11 @@ export class PipelineGraphResp extends Message<PipelineGraphResp> {
     case: "errors";
   } | { case: undefined; value?: undefined } = { case: undefined };
 
+  /**
+   * @generated from field: arroyo_api.PlanExplanation explanation = 3;
+   */
+  explanation?: PlanExplanation;
+
   constructor(data?: PartialMessage<PipelineGraphResp>) {
     super();
     proto3.util.initPartial(data, this);
@@ -754,6 +889,7 @@ export class PipelineGraphResp extends Message<PipelineGraphResp> {
   static readonly fields: FieldList = proto3.util.newFieldList(() => [
     { no: 1, name: "job_graph", kind: "message", T: JobGraph, oneof: "result" },
     { no: 2, name: "errors", kind: "message", T: SqlErrors, oneof: "result" },
+    { no: 3, name: "explanation", kind: "message", T: PlanExplanation },
   ]);
 
   static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): PipelineGraphResp {
@@ -939,6 +1075,13 @@ export class CreateJobReq extends Message<CreateJobReq> {
    */
   preview = false;
 
+  /**
+   * if set, checkpoints don't wait for the data queued ahead of their barriers to be processed
+   *
+   * @generated from field: bool unaligned_checkpoints = 4;
+   */
+  unalignedCheckpoints = false;
+
   constructor(data?: PartialMessage<CreateJobReq>) {
     super();
     proto3.util.initPartial(data, this);
@@ -950,6 +1093,7 @@ export class CreateJobReq extends Message<CreateJobReq> {
     { no: 1, name: "pipeline_id", kind: "scalar", T: 9 /* ScalarType.STRING */ },
     { no: 2, name: "checkpoint_interval_micros", kind: "scalar", T: 4 /* ScalarType.UINT64 */ },
     { no: 3, name: "preview", kind: "scalar", T: 8 /* ScalarType.BOOL */ },
+    { no: 4, name: "unaligned_checkpoints", kind: "scalar", T: 8 /* ScalarType.BOOL */ },
   ]);
 
   static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): CreateJobReq {
@@ -6034,6 +6178,267 @@ export class DeleteSinkResp extends Message<DeleteSinkResp> {
   }
 }
 
+/**
+ * @generated from message arroyo_api.CreateViewReq
+ */
+export class CreateViewReq extends Message<CreateViewReq> {
+  /**
+   * a CREATE VIEW or CREATE TABLE statement
+   *
+   * @generated from field: string definition = 1;
+   */
+  definition = "";
+
+  constructor(data?: PartialMessage<CreateViewReq>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.CreateViewReq";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "definition", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): CreateViewReq {
+    return new CreateViewReq().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): CreateViewReq {
+    return new CreateViewReq().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): CreateViewReq {
+    return new CreateViewReq().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: CreateViewReq | PlainMessage<CreateViewReq> | undefined, b: CreateViewReq | PlainMessage<CreateViewReq> | undefined): boolean {
+    return proto3.util.equals(CreateViewReq, a, b);
+  }
+}
+
+/**
+ * @generated from message arroyo_api.CreateViewResp
+ */
+export class CreateViewResp extends Message<CreateViewResp> {
+  /**
+   * @generated from field: int32 version = 1;
+   */
+  version = 0;
+
+  constructor(data?: PartialMessage<CreateViewResp>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.CreateViewResp";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "version", kind: "scalar", T: 5 /* ScalarType.INT32 */ },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): CreateViewResp {
+    return new CreateViewResp().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): CreateViewResp {
+    return new CreateViewResp().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): CreateViewResp {
+    return new CreateViewResp().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: CreateViewResp | PlainMessage<CreateViewResp> | undefined, b: CreateViewResp | PlainMessage<CreateViewResp> | undefined): boolean {
+    return proto3.util.equals(CreateViewResp, a, b);
+  }
+}
+
+/**
+ * @generated from message arroyo_api.GetViewsReq
+ */
+export class GetViewsReq extends Message<GetViewsReq> {
+  constructor(data?: PartialMessage<GetViewsReq>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.GetViewsReq";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): GetViewsReq {
+    return new GetViewsReq().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): GetViewsReq {
+    return new GetViewsReq().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): GetViewsReq {
+    return new GetViewsReq().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: GetViewsReq | PlainMessage<GetViewsReq> | undefined, b: GetViewsReq | PlainMessage<GetViewsReq> | undefined): boolean {
+    return proto3.util.equals(GetViewsReq, a, b);
+  }
+}
+
+/**
+ * @generated from message arroyo_api.View
+ */
+export class View extends Message<View> {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name = "";
+
+  /**
+   * @generated from field: int32 version = 2;
+   */
+  version = 0;
+
+  /**
+   * @generated from field: string definition = 3;
+   */
+  definition = "";
+
+  constructor(data?: PartialMessage<View>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.View";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "name", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+    { no: 2, name: "version", kind: "scalar", T: 5 /* ScalarType.INT32 */ },
+    { no: 3, name: "definition", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): View {
+    return new View().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): View {
+    return new View().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): View {
+    return new View().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: View | PlainMessage<View> | undefined, b: View | PlainMessage<View> | undefined): boolean {
+    return proto3.util.equals(View, a, b);
+  }
+}
+
+/**
+ * @generated from message arroyo_api.GetViewsResp
+ */
+export class GetViewsResp extends Message<GetViewsResp> {
+  /**
+   * @generated from field: repeated arroyo_api.View views = 1;
+   */
+  views: View[] = [];
+
+  constructor(data?: PartialMessage<GetViewsResp>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.GetViewsResp";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "views", kind: "message", T: View, repeated: true },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): GetViewsResp {
+    return new GetViewsResp().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): GetViewsResp {
+    return new GetViewsResp().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): GetViewsResp {
+    return new GetViewsResp().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: GetViewsResp | PlainMessage<GetViewsResp> | undefined, b: GetViewsResp | PlainMessage<GetViewsResp> | undefined): boolean {
+    return proto3.util.equals(GetViewsResp, a, b);
+  }
+}
+
+/**
+ * @generated from message arroyo_api.DeleteViewReq
+ */
+export class DeleteViewReq extends Message<DeleteViewReq> {
+  /**
+   * @generated from field: string name = 1;
+   */
+  name = "";
+
+  constructor(data?: PartialMessage<DeleteViewReq>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.DeleteViewReq";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+    { no: 1, name: "name", kind: "scalar", T: 9 /* ScalarType.STRING */ },
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): DeleteViewReq {
+    return new DeleteViewReq().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): DeleteViewReq {
+    return new DeleteViewReq().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): DeleteViewReq {
+    return new DeleteViewReq().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: DeleteViewReq | PlainMessage<DeleteViewReq> | undefined, b: DeleteViewReq | PlainMessage<DeleteViewReq> | undefined): boolean {
+    return proto3.util.equals(DeleteViewReq, a, b);
+  }
+}
+
+/**
+ * @generated from message arroyo_api.DeleteViewResp
+ */
+export class DeleteViewResp extends Message<DeleteViewResp> {
+  constructor(data?: PartialMessage<DeleteViewResp>) {
+    super();
+    proto3.util.initPartial(data, this);
+  }
+
+  static readonly runtime = proto3;
+  static readonly typeName = "arroyo_api.DeleteViewResp";
+  static readonly fields: FieldList = proto3.util.newFieldList(() => [
+  ]);
+
+  static fromBinary(bytes: Uint8Array, options?: Partial<BinaryReadOptions>): DeleteViewResp {
+    return new DeleteViewResp().fromBinary(bytes, options);
+  }
+
+  static fromJson(jsonValue: JsonValue, options?: Partial<JsonReadOptions>): DeleteViewResp {
+    return new DeleteViewResp().fromJson(jsonValue, options);
+  }
+
+  static fromJsonString(jsonString: string, options?: Partial<JsonReadOptions>): DeleteViewResp {
+    return new DeleteViewResp().fromJsonString(jsonString, options);
+  }
+
+  static equals(a: DeleteViewResp | PlainMessage<DeleteViewResp> | undefined, b: DeleteViewResp | PlainMessage<DeleteViewResp> | undefined): boolean {
+    return proto3.util.equals(DeleteViewResp, a, b);
+  }
+}
+
 /**
  * @generated from message arroyo_api.GrpcOutputSubscription
  */
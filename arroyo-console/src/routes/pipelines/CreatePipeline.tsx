@@ -197,7 +197,12 @@ export function CreatePipeline({ client }: { client: ApiClient }) {
         setTabIndex(0);
       }
     } else if (resp.result.case == 'errors') {
-      setError(resp.result.value.errors[0].message);
+      const error = resp.result.value.errors[0];
+      setError(
+        error.span
+          ? `${error.message} (line ${error.span.startLine}, column ${error.span.startColumn})`
+          : error.message
+      );
     }
   };
 
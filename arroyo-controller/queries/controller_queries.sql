@@ -5,6 +5,7 @@ SELECT
     pipeline_name,
     pipeline_definition as definition_id,
     checkpoint_interval_micros,
+    unaligned_checkpoints,
     ttl_micros,
     parallelism_overrides,
     stop,
@@ -1,14 +1,14 @@
 use crate::states::fatal;
 use anyhow::{anyhow, Result};
 use arroyo_datastream::{
-    AggregateBehavior, EdgeType, Operator, Program, SlidingAggregatingTopN,
-    SlidingWindowAggregator, TumblingTopN, TumblingWindowAggregator, WasmBehavior, WatermarkType,
-    WindowType,
+    AggregateBehavior, EarlyTrigger, EdgeType, LookupConnector, LookupJoin, Operator, OverWindow,
+    OverWindowFrame, Program, SlidingAggregatingTopN, SlidingWindowAggregator, TumblingTopN,
+    TumblingWindowAggregator, UpdatingAggregate, WasmBehavior, WatermarkType, WindowType,
 };
 use arroyo_rpc::grpc::compiler_grpc_client::CompilerGrpcClient;
 use arroyo_rpc::grpc::CompileQueryReq;
 use arroyo_sql::types::duration_to_syn_expr;
-use arroyo_types::{to_micros, REMOTE_COMPILER_ENDPOINT_ENV};
+use arroyo_types::{to_micros, REMOTE_COMPILER_ENDPOINT_ENV, UDF_CRATE_REGISTRY_ENV};
 use petgraph::Direction;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
@@ -23,6 +23,61 @@ use tracing::info;
 
 const OUTPUT_PATH: &str = "/tmp/arroyo_binaries";
 
+// NOTE: These must be kept in sync with the ones in arroyo-compiler-service, which does the same
+// for pipelines compiled remotely
+
+/// Cargo configuration that replaces crates.io with the vendored directory or local registry
+/// at `$UDF_CRATE_REGISTRY`, so that the crates UDFs depend on are resolved offline.
+fn udf_crate_registry_config() -> Option<String> {
+    let registry = std::env::var(UDF_CRATE_REGISTRY_ENV).ok()?;
+    // local registries have an index, while vendored directories just contain the crates
+    let source = if std::path::Path::new(&registry).join("index").is_dir() {
+        "local-registry"
+    } else {
+        "directory"
+    };
+    Some(format!(
+        r#"
+[source.crates-io]
+replace-with = "udf-registry"
+
+[source.udf-registry]
+{} = "{}"
+"#,
+        source, registry
+    ))
+}
+
+/// Adds the crates that UDFs depend on to the `[dependencies]` of a pipeline's Cargo.toml. Each
+/// spec is parsed as a single TOML value, so a malformed spec can't inject other configuration.
+fn add_udf_dependencies<'a>(
+    cargo_toml: &str,
+    dependencies: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> io::Result<String> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidInput, e);
+
+    let mut cargo_toml: toml::Table =
+        toml::from_str(cargo_toml).map_err(|e| invalid(format!("invalid Cargo.toml: {}", e)))?;
+    let Some(toml::Value::Table(deps)) = cargo_toml.get_mut("dependencies") else {
+        return Err(invalid("Cargo.toml has no [dependencies]".to_string()));
+    };
+
+    for (name, spec) in dependencies {
+        let mut parsed: toml::Table = toml::from_str(&format!("spec = {}", spec))
+            .map_err(|e| invalid(format!("invalid spec for dependency {}: {}", name, e)))?;
+        let value = parsed.remove("spec");
+        let (Some(value), true) = (value, parsed.is_empty()) else {
+            return Err(invalid(format!(
+                "invalid spec for dependency {}: {}",
+                name, spec
+            )));
+        };
+        deps.insert(name.clone(), value);
+    }
+
+    toml::to_string(&cargo_toml).map_err(|e| invalid(e.to_string()))
+}
+
 #[derive(Debug, Clone)]
 pub struct CompiledProgram {
     pub pipeline_path: String,
@@ -88,6 +143,7 @@ impl ProgramCompiler {
             types: self.compile_types().to_string(),
             pipeline: self.compile_pipeline_main(&self.name, &self.program.get_hash()),
             wasm_fns: self.compile_wasm_lib().to_string(),
+            udf_dependencies: self.program.udf_dependencies.clone().into_iter().collect(),
         };
 
         let mut client = CompilerGrpcClient::connect(endpoint)
@@ -195,6 +251,7 @@ arroyo-worker = {{ path = "{}/arroyo-worker"{}}}
                 ""
             }
         );
+        let pipeline_toml = add_udf_dependencies(&pipeline_toml, &self.program.udf_dependencies)?;
         Self::create_subproject(&dir, "pipeline", &pipeline_toml, "main.rs", main).await?;
 
         let wasmfns_toml = format!(
@@ -223,11 +280,20 @@ wasm-opt = false
         );
         Self::create_subproject(&dir, "wasm-fns", &wasmfns_toml, "lib.rs", wasm).await?;
 
+        let mut build_args = vec!["build", "--release"];
+        let cargo_config = dir.join(".cargo/config.toml");
+        if let Some(registry_config) = udf_crate_registry_config() {
+            fs::create_dir_all(dir.join(".cargo"))?;
+            fs::write(&cargo_config, registry_config)?;
+            build_args.push("--offline");
+        } else if cargo_config.exists() {
+            fs::remove_file(&cargo_config)?;
+        }
+
         let result = Command::new("cargo")
             .current_dir(&dir)
             .env("RUSTFLAGS", "-C target-cpu=native")
-            .arg("build")
-            .arg("--release")
+            .args(&build_args)
             .output()
             .await
             .map_err(|e| {
@@ -357,7 +423,8 @@ wasm-opt = false
             let node = program.graph.node_weight(idx).unwrap();
             let description = format!("{:?}", node);
             let input = program.graph.edges_directed(idx, Direction::Incoming).next();
-            let output = program.graph.edges_directed(idx, Direction::Outgoing).next();
+            let output = program.graph.edges_directed(idx, Direction::Outgoing)
+                .find(|edge| edge.weight().typ != EdgeType::LateData);
             let body = match &node.operator {
                 Operator::FileSource { dir, delay } => {
                     let dir = dir.to_string_lossy();
@@ -395,12 +462,23 @@ wasm-opt = false
                         ))
                     }
                 }
-                Operator::KafkaSource { topic, bootstrap_servers, offset_mode, kafka_input_format, messages_per_second, client_configs } => {
+                Operator::KafkaSource { topic, bootstrap_servers, offset_mode, kafka_input_format, messages_per_second, client_configs, partition_watermarks } => {
                     let offset_mode = format!("{:?}", offset_mode);
                     let offset_mode = format_ident!("{}", offset_mode);
                     let out_t = parse_type(&output.unwrap().weight().value);
                     let bootstrap_servers = bootstrap_servers.join(",");
                     let client_configs: Vec<_> = client_configs.iter().map(|(key, val)| quote!((#key, #val))).collect();
+                    let partition_watermarks = partition_watermarks.as_ref().map(|watermarks| {
+                        let max_lateness = duration_to_syn_expr(watermarks.max_lateness);
+                        let idle_time = match watermarks.idle_time {
+                            Some(idle_time) => {
+                                let idle_time = duration_to_syn_expr(idle_time);
+                                quote!(Some(#idle_time))
+                            }
+                            None => quote!(None),
+                        };
+                        quote!(.with_partition_watermarks(#max_lateness, #idle_time))
+                    });
 
                     quote! {
                         Box::new(sources::kafka::KafkaSourceFunc::<#out_t>::new(
@@ -409,7 +487,7 @@ wasm-opt = false
                             sources::kafka::OffsetMode::#offset_mode,
                             #kafka_input_format,
                             #messages_per_second,
-                            vec![#(#client_configs),*]))
+                            vec![#(#client_configs),*])#partition_watermarks)
                     }
                 }
                 Operator::EventSourceSource { url, headers, events, serialization_mode } => {
@@ -436,10 +514,11 @@ wasm-opt = false
                         Box::new(WasmOperator::<#in_k, #in_t, #out_k, #out_t>::new(#name).unwrap())
                     }
                 }
-                Operator::Window { typ, agg, flatten } => {
+                Operator::Window { typ, agg, flatten, allowed_lateness } => {
                     let in_k = parse_type(&input.unwrap().weight().key);
                     let in_t = parse_type(&input.unwrap().weight().value);
                     let out_t = parse_type(&output.unwrap().weight().value);
+                    let allowed_lateness = duration_to_syn_expr(*allowed_lateness);
 
                     let agg = match agg {
                         None => quote!{ WindowOperation::Aggregate(aggregators::vec_aggregator) },
@@ -472,7 +551,7 @@ wasm-opt = false
 
                             quote! {
                                 Box::new(KeyedWindowFunc::<#in_k, #in_t, #out_t, TumblingWindowAssigner>::
-                                    tumbling_window(#width, #agg))
+                                    tumbling_window(#width, #agg).with_allowed_lateness(#allowed_lateness))
                             }
                         }
                         WindowType::Sliding { width, slide } => {
@@ -481,13 +560,13 @@ wasm-opt = false
 
                             quote! {
                                 Box::new(KeyedWindowFunc::<#in_k, #in_t, #out_t, SlidingWindowAssigner>::
-                                    sliding_window(#width, #slide, #agg))
+                                    sliding_window(#width, #slide, #agg).with_allowed_lateness(#allowed_lateness))
                             }
                         }
                         WindowType::Instant => {
                             quote! {
                                 Box::new(KeyedWindowFunc::<#in_k, #in_t, #out_t, InstantWindowAssigner>::
-                                    instant_window(#agg))
+                                    instant_window(#agg).with_allowed_lateness(#allowed_lateness))
                             }
                         }
                     }
@@ -525,25 +604,34 @@ wasm-opt = false
                 Operator::Watermark(watermark) => {
                     let in_k = parse_type(&input.unwrap().weight().key);
                     let in_t = parse_type(&input.unwrap().weight().value);
+                    let idle_time = match watermark.idle_time() {
+                        Some(idle_time) => {
+                            let idle_time = duration_to_syn_expr(idle_time);
+                            quote!(Some(#idle_time))
+                        }
+                        None => quote!(None),
+                    };
 
                     match watermark {
-                        WatermarkType::FixedLateness { period, max_lateness } => {
+                        WatermarkType::FixedLateness { period, max_lateness, .. } => {
                             let period = duration_to_syn_expr(*period);
                             let max_lateness = duration_to_syn_expr(*max_lateness);
                             quote! {
                                 Box::new(
                                     PeriodicWatermarkGenerator::<#in_k, #in_t>::
-                                    fixed_lateness(#period,#max_lateness))
+                                    fixed_lateness(#period,#max_lateness)
+                                    .with_idle_time(#idle_time))
                             }
                         }
-                        WatermarkType::Expression { period, expression } => {
+                        WatermarkType::Expression { period, expression, .. } => {
                             let expr: syn::Expr = parse_str(expression).unwrap();
                             let watermark_function : syn::ExprClosure = parse_quote!(|record| {#expr});
                             let period = duration_to_syn_expr(*period);
                             quote! {
                                 Box::new(
                                     PeriodicWatermarkGenerator::<#in_k, #in_t>::
-                                    watermark_function(#period, Box::new(#watermark_function)))
+                                    watermark_function(#period, Box::new(#watermark_function))
+                                    .with_idle_time(#idle_time))
                             }
                         }
                     }
@@ -724,9 +812,26 @@ wasm-opt = false
                         })
                     }
                 },
+                Operator::AsyncMapOperator { name, expression, max_concurrency, ordered } => {
+                    let expr : syn::Expr = parse_str(expression).expect(expression);
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_k = parse_type(&output.unwrap().weight().key);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let func: syn::ExprClosure = parse_str(&quote!(|record| {
+                        let record = record.clone();
+                        Box::pin(async move { #expr })
+                            as std::pin::Pin<Box<dyn std::future::Future<Output = _> + Send>>
+                    }).to_string()).unwrap();
+                    quote! {
+                        Box::new(arroyo_worker::operators::async_map::AsyncMapOperator::<
+                            #in_k, #in_t, #out_k, #out_t
+                        >::new(#name, Box::new(#func), #max_concurrency, #ordered))
+                    }
+                },
                 Operator::SlidingWindowAggregator(SlidingWindowAggregator{
                     width,slide,aggregator,bin_merger,
-                    in_memory_add,in_memory_remove,bin_type,mem_type}) => {
+                    in_memory_add,in_memory_remove,bin_type,mem_type,allowed_lateness}) => {
                     let in_k = parse_type(&input.unwrap().weight().key);
                     let in_t = parse_type(&input.unwrap().weight().value);
                     let out_t = parse_type(&output.unwrap().weight().value);
@@ -738,6 +843,7 @@ wasm-opt = false
                     let bin_merger: syn::ExprClosure = parse_str(bin_merger).unwrap();
                     let in_memory_add: syn::ExprClosure = parse_str(in_memory_add).unwrap();
                     let in_memory_remove: syn::ExprClosure = parse_str(in_memory_remove).unwrap();
+                    let allowed_lateness = duration_to_syn_expr(*allowed_lateness);
 
                     quote!{
                         Box::new(arroyo_worker::operators::aggregating_window::AggregatingWindowFunc::<#in_k, #in_t, #bin_t, #mem_t, #out_t>::
@@ -746,23 +852,32 @@ wasm-opt = false
                                 #aggregator,
                                 #bin_merger,
                                 #in_memory_add,
-                                #in_memory_remove))
+                                #in_memory_remove).with_allowed_lateness(#allowed_lateness))
                     }
                 },
-                Operator::TumblingWindowAggregator(TumblingWindowAggregator { width, aggregator, bin_merger, bin_type }) => {
+                Operator::TumblingWindowAggregator(TumblingWindowAggregator { width, aggregator, bin_merger, bin_type, allowed_lateness, early_trigger }) => {
                     let in_k = parse_type(&input.unwrap().weight().key);
                     let in_t = parse_type(&input.unwrap().weight().value);
                     let out_t = parse_type(&output.unwrap().weight().value);
                     let bin_t = parse_type(bin_type);
                     let width = duration_to_syn_expr(*width);
+                    let allowed_lateness = duration_to_syn_expr(*allowed_lateness);
                     let aggregator: syn::ExprClosure = parse_str(aggregator).unwrap();
                     let bin_merger: syn::ExprClosure = parse_str(bin_merger).unwrap();
+                    let early_trigger = match early_trigger {
+                        Some(EarlyTrigger::Interval(interval)) => {
+                            let interval = duration_to_syn_expr(*interval);
+                            quote!(.with_early_fire_interval(#interval))
+                        }
+                        Some(EarlyTrigger::Count(count)) => quote!(.with_early_fire_count(#count)),
+                        None => quote!(),
+                    };
                     quote!{
                         Box::new(arroyo_worker::operators::tumbling_aggregating_window::
                             TumblingAggregatingWindowFunc::<#in_k, #in_t, #bin_t, #out_t>::
                         new(#width,
                             #aggregator,
-                            #bin_merger))
+                            #bin_merger).with_allowed_lateness(#allowed_lateness)#early_trigger)
                     }
                 },
                 Operator::TumblingTopN(
@@ -839,7 +954,57 @@ wasm-opt = false
                         #max_elements))
                 }
                 }
-                Operator::JoinWithExpiration { left_expiration, right_expiration } => {
+                Operator::OverWindow(OverWindow {
+                    frame,
+                    following_rows,
+                    ttl,
+                    bin_merger,
+                    bin_type,
+                    evaluator,
+                }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let bin_t = parse_type(bin_type);
+                    let frame = match frame {
+                        OverWindowFrame::Rows(preceding) => {
+                            let preceding = match preceding {
+                                Some(rows) => quote!(Some(#rows)),
+                                None => quote!(None),
+                            };
+                            quote!(arroyo_worker::operators::over_window::OverWindowFrame::Rows(#preceding))
+                        }
+                        OverWindowFrame::Range(preceding) => {
+                            let preceding = match preceding {
+                                Some(duration) => {
+                                    let duration = duration_to_syn_expr(*duration);
+                                    quote!(Some(#duration))
+                                }
+                                None => quote!(None),
+                            };
+                            quote!(arroyo_worker::operators::over_window::OverWindowFrame::Range(#preceding))
+                        }
+                    };
+                    let bin_merger = match bin_merger {
+                        Some(bin_merger) => {
+                            let bin_merger: syn::ExprClosure = parse_str(bin_merger).expect(bin_merger);
+                            quote!(Some(#bin_merger))
+                        }
+                        None => quote!(None),
+                    };
+                    let evaluator: syn::ExprClosure = parse_str(evaluator).expect(evaluator);
+                    let ttl = duration_to_syn_expr(*ttl);
+                    quote! {
+                        Box::new(arroyo_worker::operators::over_window::
+                            OverWindowFunc::<#in_k, #in_t, #bin_t, #out_t>::
+                        new(#frame,
+                            #following_rows,
+                            #ttl,
+                            #bin_merger,
+                            #evaluator))
+                    }
+                }
+                Operator::JoinWithExpiration { left_expiration, right_expiration, allowed_lateness } => {
                     let mut inputs: Vec<_> = program.graph.edges_directed(idx, Direction::Incoming)
                         .collect();
                     inputs.sort_by_key(|e| e.weight().typ.clone());
@@ -851,10 +1016,89 @@ wasm-opt = false
                     let in_t2 = parse_type(&inputs[1].weight().value);
                     let left_expiration = duration_to_syn_expr(*left_expiration);
                     let right_expiration = duration_to_syn_expr(*right_expiration);
+                    let allowed_lateness = duration_to_syn_expr(*allowed_lateness);
                     quote!{
                         Box::new(arroyo_worker::operators::join_with_expiration::
                             JoinWithExpiration::<#in_k, #in_t1, #in_t2>::
-                        new(#left_expiration, #right_expiration))
+                        new(#left_expiration, #right_expiration).with_allowed_lateness(#allowed_lateness))
+                    }
+                },
+                Operator::IntervalJoin { lower_bound, upper_bound } => {
+                    let mut inputs: Vec<_> = program.graph.edges_directed(idx, Direction::Incoming)
+                        .collect();
+                    inputs.sort_by_key(|e| e.weight().typ.clone());
+                    assert_eq!(2, inputs.len(), "IntervalJoin should have 2 inputs, but has {}", inputs.len());
+                    assert_eq!(inputs[0].weight().key, inputs[1].weight().key, "IntervalJoin inputs must have the same key type");
+
+                    let in_k = parse_type(&inputs[0].weight().key);
+                    let in_t1 = parse_type(&inputs[0].weight().value);
+                    let in_t2 = parse_type(&inputs[1].weight().value);
+                    let lower_bound = duration_to_syn_expr(*lower_bound);
+                    let upper_bound = duration_to_syn_expr(*upper_bound);
+                    quote!{
+                        Box::new(arroyo_worker::operators::interval_join::
+                            IntervalJoin::<#in_k, #in_t1, #in_t2>::
+                        new(#lower_bound, #upper_bound))
+                    }
+                },
+                Operator::LookupJoin(LookupJoin { connector, cache_capacity, cache_ttl, max_concurrency, lookup_type, key_fn, merge_fn }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let lookup_t = parse_type(lookup_type);
+                    let connector = match connector {
+                        LookupConnector::Postgres { connection_string, table, key_column } => quote! {
+                            Box::new(arroyo_worker::operators::lookup_join::postgres::PostgresLookup::new(
+                                #connection_string, #table, #key_column))
+                        },
+                        LookupConnector::Http { url, headers } => {
+                            let headers = headers.iter().map(|(k, v)| quote!((#k, #v))).collect::<Vec<_>>();
+                            quote! {
+                                Box::new(arroyo_worker::operators::lookup_join::http::HttpLookup::new(
+                                    #url, vec![#(#headers),*]))
+                            }
+                        }
+                        LookupConnector::Redis { address, key_prefix } => quote! {
+                            Box::new(arroyo_worker::operators::lookup_join::redis::RedisLookup::new(
+                                #address, #key_prefix))
+                        },
+                    };
+                    let cache_ttl = duration_to_syn_expr(*cache_ttl);
+                    let key_fn: syn::ExprClosure = parse_str(key_fn).expect(key_fn);
+                    let merge_fn: syn::ExprClosure = parse_str(merge_fn).expect(merge_fn);
+                    quote! {
+                        Box::new(arroyo_worker::operators::lookup_join::
+                            LookupJoin::<#in_k, #in_t, #lookup_t, #out_t>::
+                        new(#connector,
+                            #cache_capacity,
+                            #cache_ttl,
+                            #max_concurrency,
+                            #key_fn,
+                            #merge_fn))
+                    }
+                },
+                Operator::UpdatingAggregate(UpdatingAggregate { ttl, emit_interval, aggregator, bin_merger, bin_type }) => {
+                    let in_k = parse_type(&input.unwrap().weight().key);
+                    let in_t = parse_type(&input.unwrap().weight().value);
+                    let out_t = parse_type(&output.unwrap().weight().value);
+                    let bin_t = parse_type(bin_type);
+                    let ttl = duration_to_syn_expr(*ttl);
+                    let emit_interval = match emit_interval {
+                        Some(interval) => {
+                            let interval = duration_to_syn_expr(*interval);
+                            quote!(Some(#interval))
+                        }
+                        None => quote!(None),
+                    };
+                    let aggregator: syn::ExprClosure = parse_str(aggregator).unwrap();
+                    let bin_merger: syn::ExprClosure = parse_str(bin_merger).unwrap();
+                    quote! {
+                        Box::new(arroyo_worker::operators::updating_aggregate::
+                            UpdatingAggregateOperator::<#in_k, #in_t, #bin_t, #out_t>::
+                        new(#ttl,
+                            #emit_interval,
+                            #aggregator,
+                            #bin_merger))
                     }
                 },
             };
@@ -906,6 +1150,9 @@ wasm-opt = false
                     EdgeType::ShuffleJoin(order) => {
                         quote! { LogicalEdge::ShuffleJoin(#order) }
                     }
+                    EdgeType::LateData => {
+                        quote! { LogicalEdge::LateData }
+                    }
                 };
 
                 quote! {
@@ -220,6 +220,7 @@ impl RunningJobModel {
         organization_id: &str,
         pool: &Pool,
         then_stop: bool,
+        unaligned: bool,
     ) -> anyhow::Result<()> {
         self.epoch += 1;
 
@@ -239,6 +240,7 @@ impl RunningJobModel {
                     timestamp: to_micros(SystemTime::now()),
                     min_epoch: self.min_epoch,
                     then_stop,
+                    unaligned,
                 }))
                 .await?;
         }
@@ -488,7 +490,12 @@ impl JobController {
 
     pub async fn checkpoint(&mut self, then_stop: bool) -> anyhow::Result<()> {
         self.model
-            .start_checkpoint(&self.config.organization_id, &self.pool, then_stop)
+            .start_checkpoint(
+                &self.config.organization_id,
+                &self.pool,
+                then_stop,
+                self.config.unaligned_checkpoints,
+            )
             .await
     }
 
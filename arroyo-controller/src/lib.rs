@@ -96,6 +96,7 @@ pub struct JobConfig {
     definition_id: i64,
     stop_mode: StopMode,
     checkpoint_interval: Duration,
+    unaligned_checkpoints: bool,
     ttl: Option<Duration>,
     parallelism_overrides: HashMap<String, usize>,
 }
@@ -547,6 +548,7 @@ impl ControllerServer {
                         checkpoint_interval: Duration::from_micros(
                             p.checkpoint_interval_micros as u64,
                         ),
+                        unaligned_checkpoints: p.unaligned_checkpoints,
                         ttl: p.ttl_micros.map(|t| Duration::from_micros(t as u64)),
                         parallelism_overrides: p
                             .parallelism_overrides
@@ -604,7 +606,7 @@ impl ControllerServer {
 
         self.start_updater();
 
-        arroyo_server_common::grpc_server()
+        arroyo_server_common::tls::with_tls(arroyo_server_common::grpc_server())?
             .accept_http1(true)
             .add_service(ControllerGrpcServer::new(self.clone()))
             .add_service(reflection)
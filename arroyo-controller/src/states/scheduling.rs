@@ -6,7 +6,9 @@ use std::{
 
 use arroyo_datastream::Program;
 use arroyo_rpc::grpc::{worker_grpc_client::WorkerGrpcClient, StartExecutionReq, TaskAssignment};
+use arroyo_server_common::tls::grpc_endpoint;
 use arroyo_types::WorkerId;
+use rand::{distributions::Alphanumeric, Rng};
 use tokio::{sync::Mutex, task::JoinHandle};
 use tonic::{transport::Channel, Request};
 use tracing::{error, info, warn};
@@ -105,7 +107,7 @@ async fn handle_worker_connect<'a>(
                 );
 
                 for i in 0..10 {
-                    match Channel::from_shared(rpc_address.clone())
+                    match grpc_endpoint(rpc_address.clone())
                         .unwrap()
                         .timeout(Duration::from_secs(10))
                         .connect()
@@ -283,11 +285,18 @@ impl State for Scheduling {
         }
 
         let assignments = compute_assignments(workers.values().collect(), ctx.program);
+        // workers only accept data connections from peers that present this run's secret
+        let data_secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
         let worker_connects = Arc::try_unwrap(worker_connects).unwrap().into_inner();
         let tasks: Vec<_> = worker_connects
             .into_iter()
             .map(|(id, mut c)| {
                 let assignments = assignments.clone();
+                let data_secret = data_secret.clone();
 
                 let job_id = ctx.config.id.clone();
                 tokio::spawn(async move {
@@ -301,6 +310,7 @@ impl State for Scheduling {
                             .start_execution(Request::new(StartExecutionReq {
                                 restore_epoch: restore_epoch.map(|(epoch, _)| epoch),
                                 tasks: assignments.clone(),
+                                data_secret: data_secret.clone(),
                             }))
                             .await
                         {
@@ -0,0 +1,269 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use petgraph::algo::toposort;
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+
+use crate::{format_duration, Operator, Program, WatermarkType, WindowType};
+
+/// A state table kept by an operator, as it will be registered by the worker.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StateTableExplanation {
+    pub name: String,
+    pub description: String,
+    pub retention_micros: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NodeExplanation {
+    pub operator_id: String,
+    pub operator: String,
+    pub parallelism: usize,
+    pub state_tables: Vec<StateTableExplanation>,
+    /// The generated Rust for each expression the operator evaluates; only set for verbose explains.
+    pub expressions: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EdgeExplanation {
+    pub src_id: String,
+    pub dest_id: String,
+    pub key_type: String,
+    pub value_type: String,
+    pub edge_type: String,
+}
+
+/// A description of the physical dataflow of a program, returned for `EXPLAIN`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProgramExplanation {
+    pub nodes: Vec<NodeExplanation>,
+    pub edges: Vec<EdgeExplanation>,
+}
+
+fn table(name: &str, description: &str, retention: Option<Duration>) -> StateTableExplanation {
+    StateTableExplanation {
+        name: name.to_string(),
+        description: description.to_string(),
+        retention_micros: retention.map(|retention| retention.as_micros() as u64),
+    }
+}
+
+fn window_width(window: &WindowType) -> Duration {
+    match window {
+        WindowType::Tumbling { width } | WindowType::Sliding { width, .. } => *width,
+        WindowType::Instant => Duration::ZERO,
+    }
+}
+
+impl Operator {
+    /// The state tables the worker operator for this will keep, with their retention.
+    pub fn state_tables(&self) -> Vec<StateTableExplanation> {
+        match self {
+            Operator::ImpulseSource { .. } => vec![table("i", "impulse source state", None)],
+            Operator::KafkaSource { .. } => vec![table("k", "kafka source state", None)],
+            Operator::NexmarkSource { .. } => vec![table("s", "nexmark source state", None)],
+            Operator::EventSourceSource { .. } => {
+                vec![table("e", "event source state", None)]
+            }
+            Operator::Watermark(_) => vec![table("s", "periodic watermark generator state", None)],
+            Operator::Window { typ, .. } => {
+                vec![table("w", "window state", Some(window_width(typ)))]
+            }
+            Operator::WindowJoin { window } => vec![
+                table("l", "join left state", Some(window_width(window))),
+                table("r", "join right state", Some(window_width(window))),
+            ],
+            Operator::JoinWithExpiration {
+                left_expiration,
+                right_expiration,
+            } => vec![
+                table("l", "join left state", Some(*left_expiration)),
+                table("r", "join right state", Some(*right_expiration)),
+            ],
+            Operator::IntervalJoin {
+                lower_bound,
+                upper_bound,
+            } => vec![
+                table("l", "join left state", Some(*upper_bound)),
+                table("r", "join right state", Some(*lower_bound)),
+            ],
+            Operator::TumblingWindowAggregator(aggregator) => {
+                vec![table("a", "window state", Some(aggregator.width))]
+            }
+            Operator::SlidingWindowAggregator(aggregator) => {
+                vec![table("a", "window state", Some(aggregator.width))]
+            }
+            Operator::SlidingAggregatingTopN(aggregator) => {
+                vec![table("a", "window state", Some(aggregator.width))]
+            }
+            Operator::TumblingTopN(top_n) => {
+                vec![table("w", "window state", Some(top_n.width))]
+            }
+            Operator::OverWindow(_) => vec![
+                table("b", "buffered rows", Some(Duration::ZERO)),
+                table("p", "partition state", Some(Duration::ZERO)),
+            ],
+            Operator::UpdatingAggregate(aggregate) => {
+                vec![table("a", "aggregate state", Some(aggregate.ttl))]
+            }
+            Operator::FileSource { .. }
+            | Operator::FusedWasmUDFs { .. }
+            | Operator::Count
+            | Operator::Aggregate(_)
+            | Operator::GlobalKey
+            | Operator::ConsoleSink
+            | Operator::GrpcSink
+            | Operator::NullSink
+            | Operator::FileSink { .. }
+            | Operator::KafkaSink { .. }
+            | Operator::ExpressionOperator { .. }
+            | Operator::FlattenOperator { .. }
+            | Operator::FlatMapOperator { .. }
+            | Operator::AsyncMapOperator { .. }
+            | Operator::LookupJoin(_) => vec![],
+        }
+    }
+
+    /// The generated expressions evaluated by this operator, keyed by their role.
+    pub fn expressions(&self) -> Vec<(String, String)> {
+        let expressions: Vec<(&str, &str)> = match self {
+            Operator::ExpressionOperator { expression, .. }
+            | Operator::FlatMapOperator { expression, .. }
+            | Operator::AsyncMapOperator { expression, .. } => {
+                vec![("expression", expression.as_str())]
+            }
+            Operator::Watermark(WatermarkType::Expression { expression, .. }) => {
+                vec![("watermark", expression.as_str())]
+            }
+            Operator::FusedWasmUDFs { udfs, .. } => udfs
+                .iter()
+                .map(|udf| (udf.def.name.as_str(), udf.def.body.as_str()))
+                .collect(),
+            Operator::TumblingWindowAggregator(aggregator) => vec![
+                ("bin_merger", aggregator.bin_merger.as_str()),
+                ("aggregator", aggregator.aggregator.as_str()),
+            ],
+            Operator::SlidingWindowAggregator(aggregator) => vec![
+                ("bin_merger", aggregator.bin_merger.as_str()),
+                ("in_memory_add", aggregator.in_memory_add.as_str()),
+                ("in_memory_remove", aggregator.in_memory_remove.as_str()),
+                ("aggregator", aggregator.aggregator.as_str()),
+            ],
+            Operator::SlidingAggregatingTopN(aggregator) => vec![
+                ("bin_merger", aggregator.bin_merger.as_str()),
+                ("in_memory_add", aggregator.in_memory_add.as_str()),
+                ("in_memory_remove", aggregator.in_memory_remove.as_str()),
+                ("partitioning_func", aggregator.partitioning_func.as_str()),
+                ("extractor", aggregator.extractor.as_str()),
+                ("aggregator", aggregator.aggregator.as_str()),
+            ],
+            Operator::TumblingTopN(top_n) => vec![
+                ("extractor", top_n.extractor.as_str()),
+                ("converter", top_n.converter.as_str()),
+            ],
+            Operator::OverWindow(over_window) => over_window
+                .bin_merger
+                .iter()
+                .map(|bin_merger| ("bin_merger", bin_merger.as_str()))
+                .chain(std::iter::once((
+                    "evaluator",
+                    over_window.evaluator.as_str(),
+                )))
+                .collect(),
+            Operator::LookupJoin(lookup_join) => vec![
+                ("key_fn", lookup_join.key_fn.as_str()),
+                ("merge_fn", lookup_join.merge_fn.as_str()),
+            ],
+            Operator::UpdatingAggregate(aggregate) => vec![
+                ("bin_merger", aggregate.bin_merger.as_str()),
+                ("aggregator", aggregate.aggregator.as_str()),
+            ],
+            _ => vec![],
+        };
+        expressions
+            .into_iter()
+            .map(|(role, expression)| (role.to_string(), expression.to_string()))
+            .collect()
+    }
+}
+
+impl Program {
+    /// Describes the operators and edges of this program, in topological order. Verbose
+    /// explanations also include the generated code for each operator.
+    pub fn explain(&self, verbose: bool) -> ProgramExplanation {
+        let order =
+            toposort(&self.graph, None).unwrap_or_else(|_| self.graph.node_indices().collect());
+
+        let nodes = order
+            .iter()
+            .map(|index| {
+                let node = &self.graph[*index];
+                NodeExplanation {
+                    operator_id: node.operator_id.clone(),
+                    operator: format!("{:?}", node.operator),
+                    parallelism: node.parallelism,
+                    state_tables: node.operator.state_tables(),
+                    expressions: if verbose {
+                        node.operator.expressions()
+                    } else {
+                        vec![]
+                    },
+                }
+            })
+            .collect();
+
+        let edges = order
+            .iter()
+            .flat_map(|index| self.graph.edges(*index))
+            .map(|edge| EdgeExplanation {
+                src_id: self.graph[edge.source()].operator_id.clone(),
+                dest_id: self.graph[edge.target()].operator_id.clone(),
+                key_type: edge.weight().key.clone(),
+                value_type: edge.weight().value.clone(),
+                edge_type: format!("{:?}", edge.weight().typ),
+            })
+            .collect();
+
+        ProgramExplanation { nodes, edges }
+    }
+}
+
+impl ProgramExplanation {
+    /// Renders the explanation as an indented listing of operators and their outputs.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.nodes {
+            writeln!(
+                text,
+                "{} {} [parallelism={}]",
+                node.operator_id, node.operator, node.parallelism
+            )
+            .unwrap();
+            for state_table in &node.state_tables {
+                let retention = match state_table.retention_micros {
+                    Some(micros) => format_duration(Duration::from_micros(micros)),
+                    None => "unbounded".to_string(),
+                };
+                writeln!(
+                    text,
+                    "  state {}: {} (retention {})",
+                    state_table.name, state_table.description, retention
+                )
+                .unwrap();
+            }
+            for (role, expression) in &node.expressions {
+                writeln!(text, "  {}: {}", role, expression).unwrap();
+            }
+            for edge in self.edges.iter().filter(|e| e.src_id == node.operator_id) {
+                writeln!(
+                    text,
+                    "  -> {} ({}, key: {}, value: {})",
+                    edge.dest_id, edge.edge_type, edge.key_type, edge.value_type
+                )
+                .unwrap();
+            }
+        }
+        text
+    }
+}
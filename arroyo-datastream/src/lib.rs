@@ -3,7 +3,7 @@
 
 use std::cell::RefCell;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::hash::Hasher;
 use std::marker::PhantomData;
@@ -45,6 +45,8 @@ use rand::distributions::Alphanumeric;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
+pub mod explain;
+
 pub trait ArroyoData {
     fn get_def() -> String;
 }
@@ -130,13 +132,34 @@ pub enum WatermarkType {
     FixedLateness {
         period: Duration,
         max_lateness: Duration,
+        idle_time: Option<Duration>,
     },
     Expression {
         period: Duration,
         expression: String,
+        idle_time: Option<Duration>,
     },
 }
 
+impl WatermarkType {
+    /// How long the source can go without records before it is marked idle, so that it no longer
+    /// holds back the watermarks of downstream operators.
+    pub fn idle_time(&self) -> Option<Duration> {
+        match self {
+            WatermarkType::FixedLateness { idle_time, .. }
+            | WatermarkType::Expression { idle_time, .. } => *idle_time,
+        }
+    }
+}
+
+/// Watermarks that a source computes from the event times of each of the partitions it reads,
+/// rather than from the interleaved stream of records it emits.
+#[derive(Copy, Clone, Debug, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PartitionWatermarks {
+    pub max_lateness: Duration,
+    pub idle_time: Option<Duration>,
+}
+
 #[derive(Copy, Clone, Encode, Decode, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum OffsetMode {
     Earliest,
@@ -193,6 +216,7 @@ pub struct SlidingWindowAggregator {
     pub in_memory_remove: String,
     pub bin_type: String,
     pub mem_type: String,
+    pub allowed_lateness: Duration,
 }
 
 #[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
@@ -203,6 +227,18 @@ pub struct TumblingWindowAggregator {
     // fn(&T, Option<&BinA>) -> BinA
     pub bin_merger: String,
     pub bin_type: String,
+    pub allowed_lateness: Duration,
+    pub early_trigger: Option<EarlyTrigger>,
+}
+
+/// Emits the partial result of a window before it closes; the final result is still emitted
+/// once the watermark passes the end of the window.
+#[derive(Copy, Clone, Debug, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub enum EarlyTrigger {
+    /// Every interval of processing time.
+    Interval(Duration),
+    /// After every `n` records for a key.
+    Count(u64),
 }
 
 #[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
@@ -236,6 +272,70 @@ pub struct SlidingAggregatingTopN {
     pub max_elements: usize,
 }
 
+#[derive(Debug, Copy, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OverWindowFrame {
+    Rows(Option<usize>),
+    Range(Option<Duration>),
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OverWindow {
+    pub frame: OverWindowFrame,
+    pub following_rows: usize,
+    pub ttl: Duration,
+    // fn(&T, Option<&BinA>) -> BinA
+    pub bin_merger: Option<String>,
+    pub bin_type: String,
+    // fn(&T, &[T], Option<&BinA>) -> OutT
+    pub evaluator: String,
+}
+
+/// The external system a lookup join fetches rows from; rows are returned as JSON.
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LookupConnector {
+    Postgres {
+        connection_string: String,
+        table: String,
+        key_column: String,
+    },
+    /// `{key}` in the url is replaced by the lookup key.
+    Http {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+    Redis {
+        address: String,
+        key_prefix: String,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LookupJoin {
+    pub connector: LookupConnector,
+    pub cache_capacity: usize,
+    pub cache_ttl: Duration,
+    pub max_concurrency: usize,
+    pub lookup_type: String,
+    // fn(&T) -> Option<String>
+    pub key_fn: String,
+    // fn(&T, Option<&LookupT>) -> Option<OutT>
+    pub merge_fn: String,
+}
+
+/// Aggregates records by key without a window, emitting the updated aggregate for a key
+/// every time it changes or, if `emit_interval` is set, for the keys that changed in each
+/// interval. Keys that aren't updated for `ttl` are dropped.
+#[derive(Debug, Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UpdatingAggregate {
+    pub ttl: Duration,
+    pub emit_interval: Option<Duration>,
+    // fn(&BinA) -> OutT
+    pub aggregator: String,
+    // fn(&T, Option<&BinA>) -> BinA
+    pub bin_merger: String,
+    pub bin_type: String,
+}
+
 #[derive(Copy, Clone, Encode, Decode, Serialize, Deserialize, PartialEq)]
 pub enum ImpulseSpec {
     Delay(Duration),
@@ -313,6 +413,7 @@ pub enum Operator {
         kafka_input_format: SerializationMode,
         messages_per_second: u32,
         client_configs: HashMap<String, String>,
+        partition_watermarks: Option<PartitionWatermarks>,
     },
     EventSourceSource {
         url: String,
@@ -328,6 +429,7 @@ pub enum Operator {
         typ: WindowType,
         agg: Option<WindowAgg>,
         flatten: bool,
+        allowed_lateness: Duration,
     },
     Count,
     Aggregate(AggregateBehavior),
@@ -364,14 +466,28 @@ pub enum Operator {
         expression: String,
         return_type: ExpressionReturnType,
     },
+    AsyncMapOperator {
+        name: String,
+        expression: String,
+        max_concurrency: usize,
+        ordered: bool,
+    },
     SlidingWindowAggregator(SlidingWindowAggregator),
     TumblingWindowAggregator(TumblingWindowAggregator),
     TumblingTopN(TumblingTopN),
     SlidingAggregatingTopN(SlidingAggregatingTopN),
+    OverWindow(OverWindow),
     JoinWithExpiration {
         left_expiration: Duration,
         right_expiration: Duration,
+        allowed_lateness: Duration,
+    },
+    IntervalJoin {
+        lower_bound: Duration,
+        upper_bound: Duration,
     },
+    LookupJoin(LookupJoin),
+    UpdatingAggregate(UpdatingAggregate),
 }
 
 #[derive(Clone, Debug)]
@@ -405,7 +521,9 @@ impl From<SourceType> for SourceConfig {
     fn from(value: SourceType) -> Self {
         match value {
             SourceType::Kafka(kafka) => {
-                let Some(connection) = kafka.connection else {panic!("require a connection on a KafkaSourceDef")};
+                let Some(connection) = kafka.connection else {
+                    panic!("require a connection on a KafkaSourceDef")
+                };
                 SourceConfig::Kafka {
                     bootstrap_servers: connection.bootstrap_servers,
                     topic: kafka.topic,
@@ -428,7 +546,9 @@ impl From<SourceType> for SourceConfig {
                 runtime: nexmark.runtime_micros.map(Duration::from_micros),
             },
             SourceType::EventSource(event) => {
-                let Some(connection) = event.connection else { panic!("eventsource requires a connection") };
+                let Some(connection) = event.connection else {
+                    panic!("eventsource requires a connection")
+                };
 
                 SourceConfig::EventSourceSource {
                     url: connection.url + &event.path,
@@ -549,6 +669,18 @@ impl Debug for Operator {
                 expression: _,
                 return_type,
             } => write!(f, "flat_map<{}:{:?}>", name, return_type),
+            Operator::AsyncMapOperator {
+                name,
+                max_concurrency,
+                ordered,
+                ..
+            } => write!(
+                f,
+                "async_map<{}, concurrency: {}{}>",
+                name,
+                max_concurrency,
+                if *ordered { "" } else { ", unordered" }
+            ),
             Operator::SlidingWindowAggregator(SlidingWindowAggregator { width, slide, .. }) => {
                 write!(
                     f,
@@ -584,6 +716,11 @@ impl Debug for Operator {
                     }
                 )
             }
+            Operator::OverWindow(OverWindow {
+                frame,
+                following_rows,
+                ..
+            }) => write!(f, "OverWindow<{:?}, following: {}>", frame, following_rows),
             Operator::JoinWithExpiration {
                 left_expiration,
                 right_expiration,
@@ -592,6 +729,33 @@ impl Debug for Operator {
                 "JoinWithExpiration<left_expire: {:?}, right_expire: {:?}>",
                 left_expiration, right_expiration
             ),
+            Operator::IntervalJoin {
+                lower_bound,
+                upper_bound,
+            } => write!(
+                f,
+                "IntervalJoin<lower: {:?}, upper: {:?}>",
+                lower_bound, upper_bound
+            ),
+            Operator::LookupJoin(LookupJoin { connector, .. }) => match connector {
+                LookupConnector::Postgres { table, .. } => {
+                    write!(f, "LookupJoin<postgres: {}>", table)
+                }
+                LookupConnector::Http { url, .. } => write!(f, "LookupJoin<http: {}>", url),
+                LookupConnector::Redis { address, .. } => {
+                    write!(f, "LookupJoin<redis: {}>", address)
+                }
+            },
+            Operator::UpdatingAggregate(UpdatingAggregate {
+                ttl, emit_interval, ..
+            }) => match emit_interval {
+                Some(interval) => write!(
+                    f,
+                    "UpdatingAggregate<ttl: {:?}, emit every: {:?}>",
+                    ttl, interval
+                ),
+                None => write!(f, "UpdatingAggregate<ttl: {:?}>", ttl),
+            },
         }
     }
 }
@@ -628,6 +792,9 @@ pub enum EdgeType {
     Forward,
     Shuffle,
     ShuffleJoin(usize),
+    /// Carries the records that a window or join received after the watermark had passed them by
+    /// more than its allowed lateness.
+    LateData,
 }
 
 #[derive(Clone, Encode, Decode, Serialize, Deserialize)]
@@ -663,6 +830,7 @@ impl Debug for StreamEdge {
             EdgeType::ShuffleJoin(0) => "-left→",
             EdgeType::ShuffleJoin(1) => "-right→",
             EdgeType::ShuffleJoin(_) => unimplemented!(),
+            EdgeType::LateData => "-late→",
         };
         write!(f, "{} {} {}", self.key, arrow, self.value)
     }
@@ -777,6 +945,7 @@ impl Source<Vec<u8>> for KafkaSource {
             kafka_input_format: SerializationMode::Json,
             messages_per_second: self.messages_per_second,
             client_configs: HashMap::default(),
+            partition_watermarks: None,
         }
     }
 }
@@ -906,6 +1075,7 @@ impl<T: Data> Stream<T> {
             graph: s.graph.clone(),
             last_node: s.last_node,
             parallelism: s.parallelism,
+            late_data: false,
         }
     }
 
@@ -926,6 +1096,7 @@ impl<T: Data> Stream<T> {
         Program {
             types: vec![],
             other_defs: vec![],
+            udf_dependencies: BTreeMap::new(),
             graph: self.graph.take(),
         }
     }
@@ -936,6 +1107,8 @@ pub struct KeyedStream<K: Key, T: Data> {
     graph: Rc<RefCell<DiGraph<StreamNode, StreamEdge>>>,
     last_node: Option<NodeIndex>,
     parallelism: usize,
+    // whether the next node reads the late-data output of the last one
+    late_data: bool,
 }
 
 pub trait KeyedWindowFun<K: Key, T: Data> {
@@ -944,6 +1117,7 @@ pub trait KeyedWindowFun<K: Key, T: Data> {
 
 pub struct TumblingWindow<K: Key, T: Data> {
     width: Duration,
+    allowed_lateness: Duration,
     _t: PhantomData<(K, T)>,
 }
 
@@ -951,9 +1125,17 @@ impl<K: Key, T: Data> TumblingWindow<K, T> {
     pub fn new(width: Duration) -> TumblingWindow<K, T> {
         TumblingWindow {
             width,
+            allowed_lateness: Duration::ZERO,
             _t: PhantomData,
         }
     }
+
+    /// Keeps each window's state for `allowed_lateness` after it fires, re-firing it with the
+    /// updated result for every record that arrives in that time.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
 }
 
 impl<K: Key, T: Data> KeyedWindowFun<K, T> for TumblingWindow<K, T> {
@@ -962,6 +1144,7 @@ impl<K: Key, T: Data> KeyedWindowFun<K, T> for TumblingWindow<K, T> {
             typ: WindowType::Tumbling { width: self.width },
             agg: None,
             flatten: false,
+            allowed_lateness: self.allowed_lateness,
         }
     }
 }
@@ -969,6 +1152,7 @@ impl<K: Key, T: Data> KeyedWindowFun<K, T> for TumblingWindow<K, T> {
 pub struct SlidingWindow<K: Key, T: Data> {
     width: Duration,
     slide: Duration,
+    allowed_lateness: Duration,
     _t: PhantomData<(K, T)>,
 }
 
@@ -977,9 +1161,17 @@ impl<K: Key, T: Data> SlidingWindow<K, T> {
         SlidingWindow {
             width,
             slide,
+            allowed_lateness: Duration::ZERO,
             _t: PhantomData,
         }
     }
+
+    /// Keeps each window's state for `allowed_lateness` after it fires, re-firing it with the
+    /// updated result for every record that arrives in that time.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
 }
 
 impl<K: Key, T: Data> KeyedWindowFun<K, T> for SlidingWindow<K, T> {
@@ -991,6 +1183,7 @@ impl<K: Key, T: Data> KeyedWindowFun<K, T> for SlidingWindow<K, T> {
             },
             agg: None,
             flatten: false,
+            allowed_lateness: self.allowed_lateness,
         }
     }
 }
@@ -1019,6 +1212,7 @@ impl<K: Key, T: Data> KeyedWindowFun<K, T> for InstantWindow<K, T> {
             typ: WindowType::Instant,
             agg: None,
             flatten: false,
+            allowed_lateness: Duration::ZERO,
         }
     }
 }
@@ -1146,6 +1340,9 @@ impl<K: Key, T: Data> KeyedStream<K, T> {
         if last_parallelism != self.parallelism {
             edge = EdgeType::Shuffle;
         }
+        if self.late_data {
+            edge = EdgeType::LateData;
+        }
 
         let edge = StreamEdge {
             key: std::any::type_name::<K>().to_string(),
@@ -1164,6 +1361,7 @@ impl<K: Key, T: Data> KeyedStream<K, T> {
             graph: self.graph.clone(),
             last_node: Some(index),
             parallelism: self.parallelism,
+            late_data: false,
         }
     }
 
@@ -1220,6 +1418,18 @@ impl<K: Key, T: Data> KeyedStream<K, T> {
         self.add_node(w.as_operator(), EdgeType::Shuffle)
     }
 
+    /// The records that the last operator, a window, dropped for arriving after the watermark had
+    /// passed them by more than its allowed lateness. `T2` is the window's input type.
+    pub fn late_data<T2: Data>(&self) -> KeyedStream<K, T2> {
+        KeyedStream {
+            _t: PhantomData,
+            graph: self.graph.clone(),
+            last_node: self.last_node,
+            parallelism: self.parallelism,
+            late_data: true,
+        }
+    }
+
     pub fn sink<S: KeyedSink<K, T>>(&mut self, s: S) -> KeyedStream<(), ()> {
         self.add_node(s.as_operator(), EdgeType::Forward)
     }
@@ -1230,6 +1440,7 @@ impl<K: Key, T: Data> KeyedStream<K, T> {
             graph: self.graph.clone(),
             last_node: self.last_node,
             parallelism,
+            late_data: self.late_data,
         }
     }
 
@@ -1291,6 +1502,7 @@ impl<K: Key, T: Data> KeyedStream<K, T> {
             graph: self.graph.clone(),
             last_node: Some(new_idx),
             parallelism: self.parallelism,
+            late_data: false,
         }
     }
 
@@ -1298,6 +1510,7 @@ impl<K: Key, T: Data> KeyedStream<K, T> {
         Program {
             types: vec![],
             other_defs: vec![],
+            udf_dependencies: BTreeMap::new(),
             graph: self.graph.take(),
         }
     }
@@ -1387,6 +1600,8 @@ impl<K: Key, T1: Data, T2: Data, F: Fn(&Option<K>, &T1) -> T2> BiFunc<K, T1, T2>
 pub struct Program {
     pub types: Vec<String>,
     pub other_defs: Vec<String>,
+    /// Crates the pipeline depends on for its UDFs, with their Cargo dependency specs.
+    pub udf_dependencies: BTreeMap<String, String>,
     #[bincode(with_serde)]
     pub graph: DiGraph<StreamNode, StreamEdge>,
 }
@@ -1396,6 +1611,7 @@ impl Program {
         Program {
             types: vec![],
             other_defs: vec![],
+            udf_dependencies: BTreeMap::new(),
             graph: s.graph.take(),
         }
     }
@@ -1499,6 +1715,7 @@ impl Program {
                 .create_pipeline(Request::new(CreatePipelineReq {
                     name: name.to_string(),
                     config: Some(Config::Program(proto_program.encode_to_vec())),
+                    validate_only: false,
                 }))
                 .await?;
 
@@ -1507,6 +1724,7 @@ impl Program {
                     pipeline_id: res.into_inner().pipeline_id,
                     checkpoint_interval_micros,
                     preview: false,
+                    unaligned_checkpoints: false,
                 }))
                 .await?;
 
@@ -1586,6 +1804,7 @@ impl TryFrom<Program> for PipelineProgram {
                         EdgeType::Shuffle => GrpcApi::EdgeType::Shuffle,
                         EdgeType::ShuffleJoin(0) => GrpcApi::EdgeType::LeftJoin,
                         EdgeType::ShuffleJoin(1) => GrpcApi::EdgeType::RightJoin,
+                        EdgeType::LateData => GrpcApi::EdgeType::LateData,
                         _ => todo!(),
                     }
                     .into(),
@@ -1597,6 +1816,7 @@ impl TryFrom<Program> for PipelineProgram {
             types: program.types,
             other_defs: program.other_defs,
             nodes,
+            udf_dependencies: program.udf_dependencies.into_iter().collect(),
             edges,
         })
     }
@@ -1625,6 +1845,7 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 kafka_input_format,
                 messages_per_second,
                 client_configs,
+                partition_watermarks,
             } => GrpcOperator::KafkaSource(GrpcApi::KafkaSource {
                 topic,
                 bootstrap_servers,
@@ -1638,6 +1859,12 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 },
                 messages_per_second,
                 client_configs,
+                partition_watermarks: partition_watermarks.map(|watermarks| {
+                    GrpcApi::PartitionWatermarks {
+                        max_lateness_micros: watermarks.max_lateness.as_micros() as u64,
+                        idle_time_micros: watermarks.idle_time.map(|t| t.as_micros() as u64),
+                    }
+                }),
             }),
             Operator::EventSourceSource {
                 url,
@@ -1654,28 +1881,32 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 name,
                 wasm_functions: udfs.into_iter().map(|udf| udf.into()).collect(),
             }),
-            Operator::Window { typ, agg, flatten } => {
-                GrpcOperator::Window(GrpcApi::WindowOperator {
-                    aggregator: match &agg {
-                        Some(WindowAgg::Count) => Some(GrpcApi::Aggregator::CountAggregate.into()),
-                        Some(WindowAgg::Max) => Some(GrpcApi::Aggregator::MaxAggregate.into()),
-                        Some(WindowAgg::Min) => Some(GrpcApi::Aggregator::MinAggregate.into()),
-                        Some(WindowAgg::Sum) => Some(GrpcApi::Aggregator::SumAggregate.into()),
-                        Some(WindowAgg::Expression { .. }) => None,
-                        None => None,
-                    },
-                    expression_aggregator: match agg {
-                        Some(WindowAgg::Expression { name, expression }) => {
-                            Some(GrpcApi::ExpressionAggregator { name, expression })
-                        }
-                        _ => None,
-                    },
-                    flatten,
-                    window: Some(GrpcApi::Window {
-                        window: Some(typ.into()),
-                    }),
-                })
-            }
+            Operator::Window {
+                typ,
+                agg,
+                flatten,
+                allowed_lateness,
+            } => GrpcOperator::Window(GrpcApi::WindowOperator {
+                aggregator: match &agg {
+                    Some(WindowAgg::Count) => Some(GrpcApi::Aggregator::CountAggregate.into()),
+                    Some(WindowAgg::Max) => Some(GrpcApi::Aggregator::MaxAggregate.into()),
+                    Some(WindowAgg::Min) => Some(GrpcApi::Aggregator::MinAggregate.into()),
+                    Some(WindowAgg::Sum) => Some(GrpcApi::Aggregator::SumAggregate.into()),
+                    Some(WindowAgg::Expression { .. }) => None,
+                    None => None,
+                },
+                expression_aggregator: match agg {
+                    Some(WindowAgg::Expression { name, expression }) => {
+                        Some(GrpcApi::ExpressionAggregator { name, expression })
+                    }
+                    _ => None,
+                },
+                flatten,
+                window: Some(GrpcApi::Window {
+                    window: Some(typ.into()),
+                }),
+                allowed_lateness_micros: allowed_lateness.as_micros() as u64,
+            }),
             Operator::Count => GrpcOperator::Aggregator(GrpcApi::Aggregator::CountAggregate.into()),
             Operator::Aggregate(AggregateBehavior::Min) => {
                 GrpcOperator::Aggregator(GrpcApi::Aggregator::MinAggregate.into())
@@ -1689,16 +1920,21 @@ impl From<Operator> for GrpcApi::operator::Operator {
             Operator::Watermark(WatermarkType::FixedLateness {
                 period,
                 max_lateness,
+                idle_time,
             }) => GrpcOperator::PeriodicWatermark(GrpcApi::PeriodicWatermark {
                 period_micros: period.as_micros() as u64,
                 max_lateness_micros: max_lateness.as_micros() as u64,
+                idle_time_micros: idle_time.map(|t| t.as_micros() as u64),
+            }),
+            Operator::Watermark(WatermarkType::Expression {
+                period,
+                expression,
+                idle_time,
+            }) => GrpcOperator::ExpressionWatermark(GrpcApi::ExpressionWatermark {
+                period_micros: period.as_micros() as u64,
+                expression,
+                idle_time_micros: idle_time.map(|t| t.as_micros() as u64),
             }),
-            Operator::Watermark(WatermarkType::Expression { period, expression }) => {
-                GrpcOperator::ExpressionWatermark(GrpcApi::ExpressionWatermark {
-                    period_micros: period.as_micros() as u64,
-                    expression,
-                })
-            }
             Operator::GlobalKey => todo!(),
             Operator::ConsoleSink => GrpcOperator::BuiltinSink(GrpcApi::BuiltinSink::Log.into()),
             Operator::GrpcSink => GrpcOperator::BuiltinSink(GrpcApi::BuiltinSink::Web.into()),
@@ -1744,6 +1980,17 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 expression,
                 return_type: return_type.into(),
             }),
+            Operator::AsyncMapOperator {
+                name,
+                expression,
+                max_concurrency,
+                ordered,
+            } => GrpcOperator::AsyncMapOperator(GrpcApi::AsyncMapOperator {
+                name,
+                expression,
+                max_concurrency: max_concurrency as u64,
+                ordered,
+            }),
             Operator::SlidingWindowAggregator(SlidingWindowAggregator {
                 width,
                 slide,
@@ -1753,6 +2000,7 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 in_memory_remove,
                 bin_type,
                 mem_type,
+                allowed_lateness,
             }) => GrpcOperator::SlidingWindowAggregator(GrpcApi::SlidingWindowAggregator {
                 width_micros: width.as_micros() as u64,
                 slide_micros: slide.as_micros() as u64,
@@ -1762,17 +2010,31 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 in_memory_remove,
                 bin_type,
                 mem_type,
+                allowed_lateness_micros: allowed_lateness.as_micros() as u64,
             }),
             Operator::TumblingWindowAggregator(TumblingWindowAggregator {
                 width,
                 aggregator,
                 bin_merger,
                 bin_type,
+                allowed_lateness,
+                early_trigger,
             }) => GrpcOperator::TumblingWindowAggregator(GrpcApi::TumblingWindowAggregator {
                 width_micros: width.as_micros() as u64,
                 aggregator,
                 bin_merger,
                 bin_type,
+                allowed_lateness_micros: allowed_lateness.as_micros() as u64,
+                early_trigger: early_trigger.map(|trigger| GrpcApi::EarlyTrigger {
+                    trigger: Some(match trigger {
+                        EarlyTrigger::Interval(interval) => {
+                            GrpcApi::early_trigger::Trigger::IntervalMicros(
+                                interval.as_micros() as u64
+                            )
+                        }
+                        EarlyTrigger::Count(count) => GrpcApi::early_trigger::Trigger::Count(count),
+                    }),
+                }),
             }),
             Operator::TumblingTopN(TumblingTopN {
                 width,
@@ -1814,12 +2076,99 @@ impl From<Operator> for GrpcApi::operator::Operator {
                 sort_key_type,
                 max_elements: max_elements as u64,
             }),
+            Operator::OverWindow(OverWindow {
+                frame,
+                following_rows,
+                ttl,
+                bin_merger,
+                bin_type,
+                evaluator,
+            }) => {
+                let (units, preceding) = match frame {
+                    OverWindowFrame::Rows(preceding) => (
+                        GrpcApi::OverWindowFrameUnits::Rows,
+                        preceding.map(|rows| rows as u64),
+                    ),
+                    OverWindowFrame::Range(preceding) => (
+                        GrpcApi::OverWindowFrameUnits::Range,
+                        preceding.map(|duration| duration.as_micros() as u64),
+                    ),
+                };
+                GrpcOperator::OverWindow(GrpcApi::OverWindow {
+                    units: units.into(),
+                    preceding,
+                    following_rows: following_rows as u64,
+                    bin_merger,
+                    bin_type,
+                    evaluator,
+                    ttl_micros: ttl.as_micros() as u64,
+                })
+            }
             Operator::JoinWithExpiration {
                 left_expiration,
                 right_expiration,
+                allowed_lateness,
             } => GrpcOperator::JoinWithExpiration(GrpcApi::JoinWithExpiration {
                 left_expiration_micros: left_expiration.as_micros() as u64,
                 right_expiration_micros: right_expiration.as_micros() as u64,
+                allowed_lateness_micros: allowed_lateness.as_micros() as u64,
+            }),
+            Operator::IntervalJoin {
+                lower_bound,
+                upper_bound,
+            } => GrpcOperator::IntervalJoin(GrpcApi::IntervalJoin {
+                lower_bound_micros: lower_bound.as_micros() as u64,
+                upper_bound_micros: upper_bound.as_micros() as u64,
+            }),
+            Operator::LookupJoin(LookupJoin {
+                connector,
+                cache_capacity,
+                cache_ttl,
+                max_concurrency,
+                lookup_type,
+                key_fn,
+                merge_fn,
+            }) => GrpcOperator::LookupJoin(GrpcApi::LookupJoin {
+                connector: Some(match connector {
+                    LookupConnector::Postgres {
+                        connection_string,
+                        table,
+                        key_column,
+                    } => GrpcApi::lookup_join::Connector::Postgres(GrpcApi::PostgresLookup {
+                        connection_string,
+                        table,
+                        key_column,
+                    }),
+                    LookupConnector::Http { url, headers } => {
+                        GrpcApi::lookup_join::Connector::Http(GrpcApi::HttpLookup { url, headers })
+                    }
+                    LookupConnector::Redis {
+                        address,
+                        key_prefix,
+                    } => GrpcApi::lookup_join::Connector::Redis(GrpcApi::RedisLookup {
+                        address,
+                        key_prefix,
+                    }),
+                }),
+                cache_capacity: cache_capacity as u64,
+                cache_ttl_micros: cache_ttl.as_micros() as u64,
+                lookup_type,
+                key_fn,
+                merge_fn,
+                max_concurrency: max_concurrency as u64,
+            }),
+            Operator::UpdatingAggregate(UpdatingAggregate {
+                ttl,
+                emit_interval,
+                aggregator,
+                bin_merger,
+                bin_type,
+            }) => GrpcOperator::UpdatingAggregate(GrpcApi::UpdatingAggregate {
+                ttl_micros: ttl.as_micros() as u64,
+                emit_interval_micros: emit_interval.map(|interval| interval.as_micros() as u64),
+                aggregator,
+                bin_merger,
+                bin_type,
             }),
         }
     }
@@ -1941,6 +2290,7 @@ impl TryFrom<PipelineProgram> for Program {
         Ok(Program {
             types,
             other_defs,
+            udf_dependencies: program.udf_dependencies.into_iter().collect(),
             graph,
         })
     }
@@ -1980,6 +2330,12 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                         kafka_input_format,
                         messages_per_second: kafka_source.messages_per_second,
                         client_configs: kafka_source.client_configs,
+                        partition_watermarks: kafka_source.partition_watermarks.map(|watermarks| {
+                            PartitionWatermarks {
+                                max_lateness: Duration::from_micros(watermarks.max_lateness_micros),
+                                idle_time: watermarks.idle_time_micros.map(Duration::from_micros),
+                            }
+                        }),
                     }
                 }
                 GrpcOperator::EventSourceSource(source) => {
@@ -2021,6 +2377,7 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                             .into(),
                         agg,
                         flatten: window.flatten,
+                        allowed_lateness: Duration::from_micros(window.allowed_lateness_micros),
                     }
                 }
                 GrpcOperator::Aggregator(agg) => {
@@ -2036,6 +2393,7 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                     Operator::Watermark(WatermarkType::FixedLateness {
                         period: Duration::from_micros(watermark.period_micros),
                         max_lateness: Duration::from_micros(watermark.max_lateness_micros),
+                        idle_time: watermark.idle_time_micros.map(Duration::from_micros),
                     })
                 }
                 GrpcOperator::BuiltinSink(sink) => {
@@ -2079,6 +2437,12 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                         return_type,
                     }
                 }
+                GrpcOperator::AsyncMapOperator(async_map) => Operator::AsyncMapOperator {
+                    name: async_map.name,
+                    expression: async_map.expression,
+                    max_concurrency: async_map.max_concurrency as usize,
+                    ordered: async_map.ordered,
+                },
                 GrpcOperator::SlidingWindowAggregator(GrpcApi::SlidingWindowAggregator {
                     width_micros,
                     slide_micros,
@@ -2088,6 +2452,7 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                     in_memory_remove,
                     bin_type,
                     mem_type,
+                    allowed_lateness_micros,
                 }) => Operator::SlidingWindowAggregator(SlidingWindowAggregator {
                     width: Duration::from_micros(width_micros),
                     slide: Duration::from_micros(slide_micros),
@@ -2097,17 +2462,31 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                     in_memory_remove,
                     bin_type,
                     mem_type,
+                    allowed_lateness: Duration::from_micros(allowed_lateness_micros),
                 }),
                 GrpcOperator::TumblingWindowAggregator(GrpcApi::TumblingWindowAggregator {
                     width_micros,
                     aggregator,
                     bin_merger,
                     bin_type,
+                    allowed_lateness_micros,
+                    early_trigger,
                 }) => Operator::TumblingWindowAggregator(TumblingWindowAggregator {
                     width: Duration::from_micros(width_micros),
                     aggregator,
                     bin_merger,
                     bin_type,
+                    allowed_lateness: Duration::from_micros(allowed_lateness_micros),
+                    early_trigger: early_trigger.and_then(|trigger| {
+                        Some(match trigger.trigger? {
+                            GrpcApi::early_trigger::Trigger::IntervalMicros(interval) => {
+                                EarlyTrigger::Interval(Duration::from_micros(interval))
+                            }
+                            GrpcApi::early_trigger::Trigger::Count(count) => {
+                                EarlyTrigger::Count(count)
+                            }
+                        })
+                    }),
                 }),
                 GrpcOperator::TumblingTopN(GrpcApi::TumblingTopN {
                     width_micros,
@@ -2149,19 +2528,94 @@ impl TryFrom<arroyo_rpc::grpc::api::Operator> for Operator {
                     sort_key_type,
                     max_elements: max_elements as usize,
                 }),
+                GrpcOperator::OverWindow(over_window) => {
+                    let frame = match over_window.units() {
+                        GrpcApi::OverWindowFrameUnits::Rows => {
+                            OverWindowFrame::Rows(over_window.preceding.map(|rows| rows as usize))
+                        }
+                        GrpcApi::OverWindowFrameUnits::Range => {
+                            OverWindowFrame::Range(over_window.preceding.map(Duration::from_micros))
+                        }
+                    };
+                    Operator::OverWindow(OverWindow {
+                        frame,
+                        following_rows: over_window.following_rows as usize,
+                        ttl: Duration::from_micros(over_window.ttl_micros),
+                        bin_merger: over_window.bin_merger,
+                        bin_type: over_window.bin_type,
+                        evaluator: over_window.evaluator,
+                    })
+                }
                 GrpcOperator::JoinWithExpiration(GrpcApi::JoinWithExpiration {
                     left_expiration_micros,
                     right_expiration_micros,
+                    allowed_lateness_micros,
                 }) => Operator::JoinWithExpiration {
                     left_expiration: Duration::from_micros(left_expiration_micros),
                     right_expiration: Duration::from_micros(right_expiration_micros),
+                    allowed_lateness: Duration::from_micros(allowed_lateness_micros),
                 },
+                GrpcOperator::IntervalJoin(GrpcApi::IntervalJoin {
+                    lower_bound_micros,
+                    upper_bound_micros,
+                }) => Operator::IntervalJoin {
+                    lower_bound: Duration::from_micros(lower_bound_micros),
+                    upper_bound: Duration::from_micros(upper_bound_micros),
+                },
+                GrpcOperator::LookupJoin(lookup_join) => {
+                    let connector = match lookup_join.connector {
+                        Some(GrpcApi::lookup_join::Connector::Postgres(postgres)) => {
+                            LookupConnector::Postgres {
+                                connection_string: postgres.connection_string,
+                                table: postgres.table,
+                                key_column: postgres.key_column,
+                            }
+                        }
+                        Some(GrpcApi::lookup_join::Connector::Http(http)) => {
+                            LookupConnector::Http {
+                                url: http.url,
+                                headers: http.headers,
+                            }
+                        }
+                        Some(GrpcApi::lookup_join::Connector::Redis(redis)) => {
+                            LookupConnector::Redis {
+                                address: redis.address,
+                                key_prefix: redis.key_prefix,
+                            }
+                        }
+                        None => bail!("lookup join is missing a connector"),
+                    };
+                    Operator::LookupJoin(LookupJoin {
+                        connector,
+                        cache_capacity: lookup_join.cache_capacity as usize,
+                        cache_ttl: Duration::from_micros(lookup_join.cache_ttl_micros),
+                        max_concurrency: lookup_join.max_concurrency as usize,
+                        lookup_type: lookup_join.lookup_type,
+                        key_fn: lookup_join.key_fn,
+                        merge_fn: lookup_join.merge_fn,
+                    })
+                }
+                GrpcOperator::UpdatingAggregate(GrpcApi::UpdatingAggregate {
+                    ttl_micros,
+                    emit_interval_micros,
+                    aggregator,
+                    bin_merger,
+                    bin_type,
+                }) => Operator::UpdatingAggregate(UpdatingAggregate {
+                    ttl: Duration::from_micros(ttl_micros),
+                    emit_interval: emit_interval_micros.map(Duration::from_micros),
+                    aggregator,
+                    bin_merger,
+                    bin_type,
+                }),
                 GrpcOperator::ExpressionWatermark(GrpcApi::ExpressionWatermark {
                     period_micros,
                     expression,
+                    idle_time_micros,
                 }) => Operator::Watermark(WatermarkType::Expression {
                     period: Duration::from_micros(period_micros),
                     expression,
+                    idle_time: idle_time_micros.map(Duration::from_micros),
                 }),
             },
             None => bail!("unset on operator {:?}", operator),
@@ -2241,6 +2695,7 @@ impl From<arroyo_rpc::grpc::api::ProgramEdge> for StreamEdge {
             arroyo_rpc::grpc::api::EdgeType::Shuffle => EdgeType::Shuffle,
             arroyo_rpc::grpc::api::EdgeType::LeftJoin => EdgeType::ShuffleJoin(0),
             arroyo_rpc::grpc::api::EdgeType::RightJoin => EdgeType::ShuffleJoin(1),
+            arroyo_rpc::grpc::api::EdgeType::LateData => EdgeType::LateData,
         };
         StreamEdge {
             key: edge.key_type,
@@ -257,11 +257,29 @@ pub fn derive_stream_node(item: proc_macro::TokenStream) -> proc_macro::TokenStr
                 restore_from: Option<arroyo_rpc::grpc::CheckpointMetadata>,
                 control_rx: tokio::sync::mpsc::Receiver<arroyo_rpc::ControlMessage>,
                 control_tx: tokio::sync::mpsc::Sender<arroyo_rpc::ControlResp>,
-                in_qs: Vec<Vec<tokio::sync::mpsc::Receiver<crate::engine::QueueItem>>>,
+                in_qs: Vec<Vec<crate::engine::InQueue>>,
                 out_qs: Vec<Vec<crate::engine::OutQueue>>) -> tokio::task::JoinHandle<()> {
 
                 self.start_fn(task_info, restore_from, control_rx, control_tx, in_qs, out_qs)
             }
+
+            fn chainable(&self) -> bool {
+                self.chainable_fn()
+            }
+
+            fn has_processing_timers(&self) -> bool {
+                self.has_processing_timers_fn()
+            }
+
+            fn chain(self: Box<Self>,
+                task_info: arroyo_types::TaskInfo,
+                restore_from: Option<arroyo_rpc::grpc::CheckpointMetadata>,
+                control_rx: tokio::sync::mpsc::Receiver<arroyo_rpc::ControlMessage>,
+                control_tx: tokio::sync::mpsc::Sender<arroyo_rpc::ControlResp>,
+                out_qs: Vec<Vec<crate::engine::OutQueue>>,
+            ) -> futures::future::BoxFuture<'static, Box<dyn std::any::Any + Send>> {
+                self.chain_fn(task_info, restore_from, control_rx, control_tx, out_qs)
+            }
        }
     };
     proc_macro::TokenStream::from(gen)
@@ -364,6 +382,12 @@ fn impl_stream_node_type(
 
     let mut input = parse_macro_input!(item as ItemImpl);
 
+    // only operators with a single input can be chained after another
+    let chain_input = match &typ {
+        StreamNodeType::ProcessFn { in_k, in_t } => Some((in_k.clone(), in_t.clone())),
+        _ => None,
+    };
+
     let handlers = match typ {
         StreamNodeType::SourceFn {} => {
             vec![]
@@ -386,6 +410,8 @@ fn impl_stream_node_type(
     };
     let handler_count = handlers.len();
     let mut handle_matchers = vec![];
+    let mut in_flight_matchers = vec![];
+    let mut replay_matchers = vec![];
 
     for (i, (in_k, in_t, handle_fn)) in handlers.into_iter().enumerate() {
         let deserialize_error = format!(
@@ -395,9 +421,9 @@ fn impl_stream_node_type(
         );
         handle_matchers.push(quote! {
             #i => {
-                let message = match item {
+                let messages: Vec<arroyo_types::Message<#in_k, #in_t>> = match item {
                     crate::engine::QueueItem::Data(datum) => {
-                        *datum.downcast().unwrap()
+                        vec![*datum.downcast().unwrap()]
                     }
                     crate::engine::QueueItem::Bytes(bs) => {
                         ctx.counters
@@ -405,47 +431,106 @@ fn impl_stream_node_type(
                             .expect("bytes received")
                             .inc_by(bs.len() as u64);
 
-                        bincode::decode_from_slice(&bs, config::standard())
+                        vec![bincode::decode_from_slice(&bs, config::standard())
                             .expect(#deserialize_error)
-                            .0
+                            .0]
+                    }
+                    crate::engine::QueueItem::DataBatch(batch) => {
+                        let records: Vec<arroyo_types::Record<#in_k, #in_t>> = *batch.downcast().unwrap();
+                        records.into_iter().map(arroyo_types::Message::Record).collect()
+                    }
+                    crate::engine::QueueItem::BytesBatch(bs) => {
+                        ctx.counters
+                            .get("arroyo_worker_bytes_recv")
+                            .expect("bytes received")
+                            .inc_by(bs.len() as u64);
+
+                        let records: Vec<arroyo_types::Record<#in_k, #in_t>> =
+                            bincode::decode_from_slice(&bs, config::standard())
+                                .expect(#deserialize_error)
+                                .0;
+                        records.into_iter().map(arroyo_types::Message::Record).collect()
                     }
                 };
 
                 let local_idx = idx - (in_partitions / #handler_count) * #i;
-                tracing::debug!("[{}] Received message {}-{}, {:?} [{:?}]", ctx.task_info.operator_name, #i, local_idx, message, stacker::remaining_stack());
+                for message in messages {
+                    // during an unaligned checkpoint, what's read is held to be stored in the
+                    // checkpoint and processed once it has been taken
+                    if unaligned.in_progress() && !matches!(message, arroyo_types::Message::Barrier(_)) {
+                        let is_end = message.is_end();
+                        unaligned.hold(crate::engine::InFlightMessage {
+                            task_index: ctx.task_info.task_index,
+                            input: #i,
+                            idx,
+                            message: bincode::encode_to_vec(&message, config::standard()).unwrap(),
+                        }, is_end);
+
+                        if is_end {
+                            match Self::finish_unaligned_checkpoint(&mut (*self), &mut unaligned, &closed, &mut ctx).await {
+                                crate::ControlOutcome::Stop => {
+                                    stopped = true;
+                                    break 'run;
+                                }
+                                _ => {}
+                            }
+                        }
+                        continue;
+                    }
 
-                if let arroyo_types::Message::Record(record) = &message {
-                    ctx.counters
-                        .get("arroyo_worker_messages_recv")
-                        .expect("msg received")
-                        .inc();
+                    tracing::debug!("[{}] Received message {}-{}, {:?} [{:?}]", ctx.task_info.operator_name, #i, local_idx, message, stacker::remaining_stack());
 
-                    Self::#handle_fn(&mut (*self), record, &mut ctx)
-                      .instrument(tracing::trace_span!("handle_fn",
-                        name, operator_id=task_info.operator_id, subtask_idx=task_info.task_index))
-                      .await;
-                } else {
-                    match Self::handle_control_message(&mut (*self), idx, &message, &mut counter, &mut closed, in_partitions, &mut ctx).await {
-                        crate::ControlOutcome::Continue => {
-                            // do nothing
+                    if let arroyo_types::Message::Record(record) = &message {
+                        ctx.watermarks.set_active(idx);
+                        ctx.counters
+                            .get("arroyo_worker_messages_recv")
+                            .expect("msg received")
+                            .inc();
+
+                        Self::#handle_fn(&mut (*self), record, &mut ctx)
+                          .instrument(tracing::trace_span!("handle_fn",
+                            name, operator_id=task_info.operator_id, subtask_idx=task_info.task_index))
+                          .await;
+                    } else {
+                        match Self::handle_control_message(&mut (*self), idx, &message, &mut counter, &mut unaligned, &mut closed, in_partitions, &mut ctx).await {
+                            crate::ControlOutcome::Continue => {
+                                // do nothing
+                            }
+                            crate::ControlOutcome::Stop => {
+                                stopped = true;
+                                break 'run;
+                            }
+                            crate::ControlOutcome::Finish => {
+                                ctx.broadcast(arroyo_types::Message::EndOfData).await;
+                                break 'run;
+                            }
                         }
+                    }
+
+                    tracing::debug!("[{}] Handled message {}-{}, {:?} [{:?}]", ctx.task_info.operator_name, #i, local_idx, message, stacker::remaining_stack());
+                }
+
+                // once an unaligned checkpoint has been taken, the messages held back for it are
+                // processed
+                let in_flight = unaligned.take_in_flight();
+                if !in_flight.is_empty() {
+                    match Self::handle_in_flight(&mut (*self), in_flight, &mut counter, &mut unaligned, &mut closed, in_partitions, &mut ctx).await {
+                        crate::ControlOutcome::Continue => {}
                         crate::ControlOutcome::Stop => {
-                            ctx.broadcast(arroyo_types::Message::Stop).await;
-                            break;
+                            stopped = true;
+                            break 'run;
                         }
                         crate::ControlOutcome::Finish => {
                             ctx.broadcast(arroyo_types::Message::EndOfData).await;
-                            break;
+                            break 'run;
                         }
                     }
                 }
 
-                tracing::debug!("[{}] Handled message {}-{}, {:?} [{:?}]", ctx.task_info.operator_name, #i, local_idx, message, stacker::remaining_stack());
-
-                if counter.is_blocked(idx) {
+                if counter.is_blocked(idx) || unaligned.reached(idx) {
                     blocked.push(s);
                 } else {
-                    if counter.all_clear() && !blocked.is_empty() {
+                    if counter.all_clear() && !unaligned.in_progress() && !blocked.is_empty() {
                         for q in blocked.drain(..) {
                             sel.push(q);
                         }
@@ -453,7 +538,53 @@ fn impl_stream_node_type(
                     sel.push(s);
                 }
             }
-        })
+        });
+
+        in_flight_matchers.push(quote! {
+            #i => {
+                let message: arroyo_types::Message<#in_k, #in_t> =
+                    bincode::decode_from_slice(&in_flight.message, bincode::config::standard())
+                        .expect(#deserialize_error)
+                        .0;
+
+                if let arroyo_types::Message::Record(record) = &message {
+                    ctx.watermarks.set_active(in_flight.idx);
+                    ctx.counters
+                        .get("arroyo_worker_messages_recv")
+                        .expect("msg received")
+                        .inc();
+
+                    Self::#handle_fn(&mut (*self), record, ctx).await;
+                } else {
+                    match Self::handle_control_message(&mut (*self), in_flight.idx, &message, counter, unaligned, closed, in_partitions, ctx).await {
+                        crate::ControlOutcome::Continue => {}
+                        outcome => return outcome,
+                    }
+                }
+            }
+        });
+
+        replay_matchers.push(quote! {
+            #i => {
+                let message: arroyo_types::Message<#in_k, #in_t> =
+                    bincode::decode_from_slice(&in_flight.message, bincode::config::standard())
+                        .expect(#deserialize_error)
+                        .0;
+
+                // watermarks are re-sent by the inputs, so only the records need replaying
+                if let arroyo_types::Message::Record(record) = &message {
+                    // after rescaling, each subtask replays the records for its own keys
+                    let owned = !rescaled || match &record.key {
+                        Some(key) => ctx.task_info.key_range.contains(&arroyo_state::hash_key(key)),
+                        None => in_flight.task_index % ctx.task_info.parallelism == ctx.task_info.task_index,
+                    };
+
+                    if owned {
+                        Self::#handle_fn(&mut (*self), record, ctx).await;
+                    }
+                }
+            }
+        });
     }
 
     let handle_body = if handler_count == 0 {
@@ -470,17 +601,23 @@ fn impl_stream_node_type(
                     ctx.broadcast(arroyo_types::Message::EndOfData).await;
                 }
             }
+
+            Self::on_close(&mut (*self), &mut ctx).await;
         }
     } else {
         quote! {
             let mut counter = crate::engine::CheckpointCounter::new(in_qs.len());
+            let mut unaligned = crate::engine::UnalignedCheckpoints::new(in_qs.len());
             let mut closed: std::collections::HashSet<usize> = std::collections::HashSet::new();
+            let mut stopped = false;
 
             let mut sel = crate::inq_reader::InQReader::new();
+            let mut barriers = futures::stream::SelectAll::new();
 
             let in_partitions = in_qs.len();
 
-            for (i, mut q) in in_qs.into_iter().enumerate() {
+            for (i, q) in in_qs.into_iter().enumerate() {
+                let crate::engine::InQueue { rx: mut q, barriers: mut barrier_q } = q;
                 let stream = async_stream::stream! {
                     while let Some(item) = q.recv().await {
                         yield (i, item);
@@ -488,26 +625,86 @@ fn impl_stream_node_type(
                     println!("FINISHED");
                 };
                 sel.push(Box::pin(stream));
+
+                let barrier_stream = async_stream::stream! {
+                    while let Some(barrier) = barrier_q.recv().await {
+                        yield (i, barrier);
+                    }
+                };
+                barriers.push(Box::pin(barrier_stream));
             }
 
             let mut blocked = vec![];
 
-            loop {
-                match sel.next().await {
-                    Some(((idx, item), s)) => {
-                        match idx / (in_partitions / #handler_count) {
-                            #(#handle_matchers
-                            )*
-                            _ => unreachable!()
+            Self::replay_in_flight(&mut (*self), &mut ctx).await;
+
+            'run: loop {
+                // operators may have work in flight (like async UDF calls) that completes
+                // independently of their inputs
+                let operator_future = self.future_to_poll();
+                let next_processing_timer = Self::next_processing_timer_int(&mut ctx).await;
+                // while an unaligned checkpoint is in progress the operator's state is left as it
+                // was snapshotted, and the inputs are read regardless of whether there's room to
+                // send the output
+                let in_progress = unaligned.in_progress();
+                let output_ready = if in_progress { None } else { ctx.collector.output_ready() };
+                let output_full = output_ready.is_some();
+                tokio::select! {
+                    Some(result) = async move {
+                        match operator_future {
+                            Some(future) => Some(future.await),
+                            None => None,
+                        }
+                    }, if !in_progress => {
+                        self.handle_future_result(result, &mut ctx).await;
+                    }
+                    Some(()) = async move {
+                        let time = next_processing_timer?;
+                        tokio::time::sleep(
+                            time.duration_since(std::time::SystemTime::now()).unwrap_or_default()
+                        ).await;
+                        Some(())
+                    }, if !in_progress => {
+                        self.handle_processing_timers(&mut ctx).await;
+                    }
+                    Some((idx, barrier)) = barriers.next() => {
+                        if unaligned.barrier(idx, barrier, false) {
+                            Self::start_unaligned_checkpoint(&mut (*self), barrier, &mut ctx).await;
+                            // inputs that have already closed have nothing left in flight
+                            if let crate::ControlOutcome::Stop = Self::finish_unaligned_checkpoint(&mut (*self), &mut unaligned, &closed, &mut ctx).await {
+                                stopped = true;
+                                break 'run;
+                            }
                         }
                     }
-                    None => {
-                        tracing::info!("[{}] Stream completed", ctx.task_info.operator_name);
-                        break;
+                    Some(()) = async move {
+                        output_ready?.await;
+                        Some(())
+                    } => {
+                        // the outputs have room again
+                    }
+                    item = sel.next(), if !output_full => {
+                        match item {
+                            Some(((idx, item), s)) => {
+                                match idx / (in_partitions / #handler_count) {
+                                    #(#handle_matchers
+                                    )*
+                                    _ => unreachable!()
+                                }
+                            }
+                            None => {
+                                tracing::info!("[{}] Stream completed", ctx.task_info.operator_name);
+                                break;
+                            }
+                        }
                     }
                 }
             }
 
+            Self::on_close(&mut (*self), &mut ctx).await;
+            if stopped {
+                ctx.broadcast(arroyo_types::Message::Stop).await;
+            }
         }
     };
 
@@ -518,7 +715,7 @@ fn impl_stream_node_type(
             restore_from: Option<arroyo_rpc::grpc::CheckpointMetadata>,
             control_rx: tokio::sync::mpsc::Receiver<arroyo_rpc::ControlMessage>,
             control_tx: tokio::sync::mpsc::Sender<arroyo_rpc::ControlResp>,
-            mut in_qs: Vec<Vec<tokio::sync::mpsc::Receiver<crate::engine::QueueItem>>>,
+            mut in_qs: Vec<Vec<crate::engine::InQueue>>,
             out_qs: Vec<Vec<crate::engine::OutQueue>>,
         ) -> tokio::task::JoinHandle<()> {
             use bincode;
@@ -539,7 +736,7 @@ fn impl_stream_node_type(
 
             let tables = self.tables();
             tokio::spawn(async move {
-                let mut ctx = crate::engine::Context::<#out_k, #out_t>::new(
+                let mut ctx = crate::engine::Context::<#out_k, #out_t, #timer_t>::new(
                     task_info,
                     restore_from,
                     control_rx,
@@ -554,8 +751,6 @@ fn impl_stream_node_type(
                 let task_info = ctx.task_info.clone();
                 let name = self.name();
                 #handle_body
-
-                Self::on_close(&mut (*self), &mut ctx).await;
                 tracing::info!("Task finished {}-{}", ctx.task_info.operator_name, ctx.task_info.task_index);
 
                 ctx.control_tx
@@ -573,9 +768,10 @@ fn impl_stream_node_type(
         async fn handle_control_message<CONTROL_K: arroyo_types::Key, CONTROL_T: arroyo_types::Data>(&mut self,
             idx: usize, message: &arroyo_types::Message<CONTROL_K, CONTROL_T>,
             counter: &mut crate::engine::CheckpointCounter,
+            unaligned: &mut crate::engine::UnalignedCheckpoints,
             closed: &mut std::collections::HashSet<usize>,
             in_partitions: usize,
-            ctx: &mut crate::engine::Context<#out_k, #out_t>) -> crate::ControlOutcome {
+            ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) -> crate::ControlOutcome {
                 use arroyo_types::*;
                 use tracing::info;
                 use tracing::trace;
@@ -592,6 +788,15 @@ fn impl_stream_node_type(
                             idx
                         );
 
+                        if t.unaligned {
+                            // the in-line barrier marks the end of the data that was in flight
+                            // on this input when the checkpoint started
+                            if unaligned.barrier(idx, *t, true) {
+                                self.start_unaligned_checkpoint(*t, ctx).await;
+                            }
+                            return self.finish_unaligned_checkpoint(unaligned, closed, ctx).await;
+                        }
+
                         if counter.all_clear() {
                             ctx.control_tx.send(arroyo_rpc::ControlResp::CheckpointEvent(arroyo_rpc::CheckpointEvent {
                                 checkpoint_epoch: t.epoch,
@@ -619,10 +824,16 @@ fn impl_stream_node_type(
                         if idx >= ctx.watermarks.len() {
                             panic!("watermark index is too big");
                         }
-                        ctx.watermarks[idx] = Some(*watermark);
+                        let was_idle = ctx.watermarks.is_idle();
+                        ctx.watermarks.set(idx, *watermark);
 
                         trace!("received watermark {:?} in {}-{}", watermark, self.name(), ctx.task_info.task_index);
-                        if let Some(watermark) = ctx.watermark() {
+                        if ctx.watermarks.is_idle() {
+                            // with all of its inputs idle, this operator is idle as well
+                            if !was_idle {
+                                ctx.broadcast(arroyo_types::Message::Watermark(arroyo_types::Watermark::Idle)).await;
+                            }
+                        } else if let Some(watermark) = ctx.watermark() {
                             ctx.state.handle_watermark(watermark);
                             self.handle_watermark_int(watermark, ctx).await;
                         }
@@ -630,13 +841,15 @@ fn impl_stream_node_type(
                     Message::Stop => {
                         closed.insert(idx);
                         if closed.len() == in_partitions {
-                            ctx.broadcast(arroyo_types::Message::Stop).await;
+                            // the Stop is sent on once the operator has closed, so that anything it
+                            // emits in on_close goes out ahead of it
                             return crate::ControlOutcome::Stop;
                         }
                     }
                     Message::EndOfData => {
                         closed.insert(idx);
                         if closed.len() == in_partitions {
+                            self.handle_end_of_data(ctx).await;
                             ctx.broadcast(arroyo_types::Message::EndOfData).await;
                             return crate::ControlOutcome::Finish;
                         }
@@ -646,6 +859,45 @@ fn impl_stream_node_type(
             }
     });
 
+    if handler_count > 0 {
+        defs.push(quote! {
+            async fn handle_in_flight(&mut self,
+                in_flight: Vec<crate::engine::InFlightMessage>,
+                counter: &mut crate::engine::CheckpointCounter,
+                unaligned: &mut crate::engine::UnalignedCheckpoints,
+                closed: &mut std::collections::HashSet<usize>,
+                in_partitions: usize,
+                ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) -> crate::ControlOutcome {
+                for in_flight in in_flight {
+                    match in_flight.input {
+                        #(#in_flight_matchers
+                        )*
+                        _ => unreachable!()
+                    }
+                }
+                crate::ControlOutcome::Continue
+            }
+        });
+
+        defs.push(quote! {
+            async fn replay_in_flight(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
+                let (in_flight, rescaled) = ctx.restore_in_flight().await;
+                if !in_flight.is_empty() {
+                    tracing::info!("[{}] Replaying {} in-flight messages",
+                        ctx.task_info.operator_name, in_flight.len());
+                }
+
+                for in_flight in in_flight {
+                    match in_flight.input {
+                        #(#replay_matchers
+                        )*
+                        _ => unreachable!()
+                    }
+                }
+            }
+        });
+    }
+
     defs.push(quote! {
         #[tracing::instrument(
             level = "trace",
@@ -659,14 +911,34 @@ fn impl_stream_node_type(
         #[must_use]
         async fn checkpoint(&mut self,
             checkpoint_barrier: arroyo_types::CheckpointBarrier,
-            ctx: &mut crate::engine::Context<#out_k, #out_t>) -> bool {
+            ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) -> bool {
+            self.snapshot(checkpoint_barrier, ctx).await;
+            if checkpoint_barrier.unaligned {
+                ctx.collector.send_priority_barrier(checkpoint_barrier).await;
+            }
+            self.finish_checkpoint(checkpoint_barrier, ctx).await
+        }
+    });
 
+    defs.push(quote! {
+        async fn snapshot(&mut self,
+            checkpoint_barrier: arroyo_types::CheckpointBarrier,
+            ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
             crate::process_fn::ProcessFnUtils::send_event(checkpoint_barrier, ctx, arroyo_rpc::grpc::TaskCheckpointEventType::StartedCheckpointing).await;
 
             self.handle_checkpoint(&checkpoint_barrier, ctx).await;
 
+            ctx.checkpoint_timers().await;
+
             crate::process_fn::ProcessFnUtils::send_event(checkpoint_barrier, ctx, arroyo_rpc::grpc::TaskCheckpointEventType::FinishedOperatorSetup).await;
+        }
+    });
 
+    defs.push(quote! {
+        #[must_use]
+        async fn finish_checkpoint(&mut self,
+            checkpoint_barrier: arroyo_types::CheckpointBarrier,
+            ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) -> bool {
             let watermark = ctx.watermark();
             ctx.state.checkpoint(checkpoint_barrier, watermark).await;
 
@@ -679,7 +951,41 @@ fn impl_stream_node_type(
     });
 
     defs.push(quote! {
-        async fn handle_watermark_int(&mut self, watermark: std::time::SystemTime, ctx: &mut crate::engine::Context<#out_k, #out_t>) {
+        /// Starts an unaligned checkpoint on the first of its barriers: the operator is snapshotted
+        /// and the barrier sent on ahead of the data queued downstream, which will be stored
+        /// in the downstream checkpoints.
+        async fn start_unaligned_checkpoint(&mut self,
+            checkpoint_barrier: arroyo_types::CheckpointBarrier,
+            ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
+            crate::process_fn::ProcessFnUtils::send_event(checkpoint_barrier, ctx, arroyo_rpc::grpc::TaskCheckpointEventType::StartedAlignment).await;
+            self.snapshot(checkpoint_barrier, ctx).await;
+            ctx.collector.send_priority_barrier(checkpoint_barrier).await;
+        }
+    });
+
+    defs.push(quote! {
+        /// Finishes the unaligned checkpoint once every input has delivered the data that was in
+        /// flight before its barrier, storing that data with the operator's state.
+        async fn finish_unaligned_checkpoint(&mut self,
+            unaligned: &mut crate::engine::UnalignedCheckpoints,
+            closed: &std::collections::HashSet<usize>,
+            ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) -> crate::ControlOutcome {
+            let Some(checkpoint_barrier) = unaligned.complete(closed) else {
+                return crate::ControlOutcome::Continue;
+            };
+
+            ctx.checkpoint_in_flight(unaligned.in_flight()).await;
+
+            if self.finish_checkpoint(checkpoint_barrier, ctx).await {
+                crate::ControlOutcome::Stop
+            } else {
+                crate::ControlOutcome::Continue
+            }
+        }
+    });
+
+    defs.push(quote! {
+        async fn handle_watermark_int(&mut self, watermark: std::time::SystemTime, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
             // process timers
             use tracing::trace;
             trace!("handling watermark {} for {}-{}", arroyo_types::to_millis(watermark), ctx.task_info.operator_name, ctx.task_info.task_index);
@@ -687,13 +993,35 @@ fn impl_stream_node_type(
             let finished = crate::process_fn::ProcessFnUtils::finished_timers(watermark, ctx).await;
 
             for (k, tv) in finished {
-                self.handle_timer(k, tv.data, ctx).await;
+                self.handle_timer_with_domain(k, tv.data, crate::engine::TimeDomain::EventTime, ctx).await;
             }
 
             self.handle_watermark(watermark, ctx).await;
         }
     });
 
+    if handler_count > 0 {
+        defs.push(quote! {
+            async fn handle_processing_timers(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
+                let finished = ctx.expire_processing_timers(std::time::SystemTime::now()).await;
+
+                for tv in finished {
+                    self.handle_timer_with_domain(tv.key, tv.data, crate::engine::TimeDomain::ProcessingTime, ctx).await;
+                }
+
+                ctx.collector.handle_chained_processing_timers().await;
+            }
+        });
+
+        defs.push(quote! {
+            async fn next_processing_timer_int(ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) -> Option<std::time::SystemTime> {
+                let next = ctx.next_processing_timer().await;
+                let chained = ctx.collector.next_chained_processing_timer().await;
+                next.into_iter().chain(chained).min()
+            }
+        });
+    }
+
     let mut methods = HashSet::new();
 
     for item in &input.items {
@@ -702,12 +1030,154 @@ fn impl_stream_node_type(
         }
     }
 
+    // operators that wait on futures of their own need a task to poll them
+    let chainable = chain_input.is_some() && !methods.contains("future_to_poll");
+
+    defs.push(quote! {
+        fn chainable_fn(&self) -> bool {
+            #chainable
+        }
+    });
+
+    let has_processing_timers = if methods.contains("uses_processing_timers") {
+        quote! { self.uses_processing_timers() }
+    } else {
+        quote! { false }
+    };
+
+    defs.push(quote! {
+        fn has_processing_timers_fn(&self) -> bool {
+            #has_processing_timers
+        }
+    });
+
+    let mut chained_impl = quote! {};
+    if let Some((in_k, in_t)) = chain_input.filter(|_| chainable) {
+        defs.push(quote! {
+            fn chain_fn(
+                mut self: Box<Self>,
+                task_info: arroyo_types::TaskInfo,
+                restore_from: Option<arroyo_rpc::grpc::CheckpointMetadata>,
+                control_rx: tokio::sync::mpsc::Receiver<arroyo_rpc::ControlMessage>,
+                control_tx: tokio::sync::mpsc::Sender<arroyo_rpc::ControlResp>,
+                out_qs: Vec<Vec<crate::engine::OutQueue>>,
+            ) -> futures::future::BoxFuture<'static, Box<dyn std::any::Any + Send>> {
+                use futures::FutureExt;
+
+                let tables = self.tables();
+                async move {
+                    let mut ctx = crate::engine::Context::<#out_k, #out_t, #timer_t>::new(
+                        task_info,
+                        restore_from,
+                        control_rx,
+                        control_tx,
+                        1,
+                        out_qs,
+                        tables,
+                    ).await;
+
+                    Self::on_start(&mut (*self), &mut ctx).await;
+
+                    let chained: Box<dyn crate::engine::ChainedOperator<#in_k, #in_t>> =
+                        Box::new(crate::engine::ChainedNode {
+                            node: self,
+                            ctx,
+                            counter: crate::engine::CheckpointCounter::new(1),
+                            unaligned: crate::engine::UnalignedCheckpoints::new(1),
+                            closed: std::collections::HashSet::new(),
+                            finished: false,
+                        });
+                    Box::new(chained) as Box<dyn std::any::Any + Send>
+                }.boxed()
+            }
+        });
+
+        let self_ty = &input.self_ty;
+        let (impl_generics, _, where_clause) = input.generics.split_for_impl();
+        chained_impl = quote! {
+            #[async_trait::async_trait]
+            impl #impl_generics crate::engine::ChainedOperator<#in_k, #in_t>
+                for crate::engine::ChainedNode<#self_ty, #out_k, #out_t, #timer_t> #where_clause {
+                async fn handle(&mut self, message: arroyo_types::Message<#in_k, #in_t>) {
+                    // the operator before this one may send a second Stop after the first
+                    if self.finished {
+                        return;
+                    }
+
+                    let node = &mut *self.node;
+                    let ctx = &mut self.ctx;
+                    let mut stopped = false;
+                    if let arroyo_types::Message::Record(record) = &message {
+                        ctx.watermarks.set_active(0);
+                        ctx.counters
+                            .get("arroyo_worker_messages_recv")
+                            .expect("msg received")
+                            .inc();
+
+                        <#self_ty>::process_element(node, record, ctx).await;
+                    } else {
+                        match <#self_ty>::handle_control_message(node, 0, &message,
+                            &mut self.counter, &mut self.unaligned, &mut self.closed, 1, ctx).await {
+                            crate::ControlOutcome::Continue => {}
+                            crate::ControlOutcome::Stop => {
+                                stopped = true;
+                                self.finished = true;
+                            }
+                            crate::ControlOutcome::Finish => {
+                                ctx.broadcast(arroyo_types::Message::EndOfData).await;
+                                self.finished = true;
+                            }
+                        }
+                    }
+
+                    if self.finished {
+                        <#self_ty>::on_close(node, ctx).await;
+                        if stopped {
+                            ctx.broadcast(arroyo_types::Message::Stop).await;
+                        }
+                        tracing::info!("Task finished {}-{}", ctx.task_info.operator_name, ctx.task_info.task_index);
+
+                        ctx.control_tx
+                            .send(arroyo_rpc::ControlResp::TaskFinished {
+                                operator_id: ctx.task_info.operator_id.clone(),
+                                task_index: ctx.task_info.task_index,
+                            })
+                            .await
+                            .expect("control response unwrap");
+                        return;
+                    }
+                }
+
+                async fn next_processing_timer(&mut self) -> Option<std::time::SystemTime> {
+                    <#self_ty>::next_processing_timer_int(&mut self.ctx).await
+                }
+
+                async fn handle_processing_timers(&mut self) {
+                    <#self_ty>::handle_processing_timers(&mut *self.node, &mut self.ctx).await;
+                }
+            }
+        };
+    } else {
+        defs.push(quote! {
+            fn chain_fn(
+                self: Box<Self>,
+                _task_info: arroyo_types::TaskInfo,
+                _restore_from: Option<arroyo_rpc::grpc::CheckpointMetadata>,
+                _control_rx: tokio::sync::mpsc::Receiver<arroyo_rpc::ControlMessage>,
+                _control_tx: tokio::sync::mpsc::Sender<arroyo_rpc::ControlResp>,
+                _out_qs: Vec<Vec<crate::engine::OutQueue>>,
+            ) -> futures::future::BoxFuture<'static, Box<dyn std::any::Any + Send>> {
+                unreachable!("{} can't be chained", self.name())
+            }
+        });
+    }
+
     if !methods.contains("handle_checkpoint") {
         defs.push(quote! {
             async fn handle_checkpoint(
                 &mut self,
                 checkpoint_barrier: &arroyo_types::CheckpointBarrier,
-                ctx: &mut crate::engine::Context<#out_k, #out_t>,
+                ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>,
             ) {
             }
         });
@@ -715,28 +1185,60 @@ fn impl_stream_node_type(
 
     if !methods.contains("on_start") {
         defs.push(quote! {
-            async fn on_start(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t>) {}
+            async fn on_start(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {}
         })
     }
 
     if !methods.contains("on_close") {
         defs.push(quote! {
-            async fn on_close(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t>) {}
+            async fn on_close(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {}
+        })
+    }
+
+    if !methods.contains("handle_end_of_data") {
+        defs.push(quote! {
+            async fn handle_end_of_data(&mut self, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {}
+        })
+    }
+
+    if handler_count > 0 && !methods.contains("future_to_poll") {
+        defs.push(quote! {
+            fn future_to_poll(&mut self) -> Option<std::pin::Pin<Box<dyn std::future::Future<
+                Output = Box<dyn std::any::Any + Send>> + Send>>> {
+                None
+            }
+        })
+    }
+
+    if handler_count > 0 && !methods.contains("handle_future_result") {
+        defs.push(quote! {
+            async fn handle_future_result(&mut self, result: Box<dyn std::any::Any + Send>,
+                ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {}
         })
     }
 
     if !methods.contains("handle_timer") {
         defs.push(quote! {
-            async fn handle_timer(&mut self, key: #out_k, tv: #timer_t, ctx: &mut crate::engine::Context<#out_k, #out_t>) {}
+            async fn handle_timer(&mut self, key: #out_k, tv: #timer_t, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {}
+        })
+    }
+
+    // operators that only use one kind of timer can implement handle_timer instead
+    if !methods.contains("handle_timer_with_domain") {
+        defs.push(quote! {
+            async fn handle_timer_with_domain(&mut self, key: #out_k, tv: #timer_t,
+                domain: crate::engine::TimeDomain, ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
+                self.handle_timer(key, tv, ctx).await;
+            }
         })
     }
 
     if !methods.contains("handle_watermark") {
         defs.push(quote! {
             async fn handle_watermark(&mut self, watermark: std::time::SystemTime,
-                ctx: &mut crate::engine::Context<#out_k, #out_t>) {
+                ctx: &mut crate::engine::Context<#out_k, #out_t, #timer_t>) {
                     // by default, just pass watermarks on down
-                    ctx.broadcast(arroyo_types::Message::Watermark(watermark)).await;
+                    ctx.broadcast(arroyo_types::Message::Watermark(arroyo_types::Watermark::EventTime(watermark))).await;
                 }
         });
     }
@@ -757,5 +1259,7 @@ fn impl_stream_node_type(
 
     proc_macro::TokenStream::from(quote! {
         #input
+
+        #chained_impl
     })
 }
@@ -12,6 +12,7 @@ use arroyo_rpc::grpc::{
     RegisterNodeReq, StartWorkerReq, StartWorkerResp, StopWorkerReq, StopWorkerResp,
     StopWorkerStatus, WorkerFinishedReq,
 };
+use arroyo_server_common::tls::grpc_endpoint;
 use arroyo_types::{
     grpc_port, ports, to_millis, NodeId, WorkerId, CONTROLLER_ADDR_ENV, JOB_ID_ENV, NODE_ID_ENV,
     RUN_ID_ENV, TASK_SLOTS_ENV, WORKER_ID_ENV,
@@ -311,7 +312,12 @@ pub async fn main() {
 
     let mut attempts = 0;
     loop {
-        match ControllerGrpcClient::connect(controller_addr.clone()).await {
+        match grpc_endpoint(controller_addr.clone())
+            .expect("invalid controller address")
+            .connect()
+            .await
+            .map(ControllerGrpcClient::new)
+        {
             Ok(mut controller) => {
                 controller
                     .register_node(Request::new(RegisterNodeReq {
@@ -38,6 +38,8 @@ use tracing_subscriber::Registry;
 
 use tracing_appender::non_blocking::WorkerGuard;
 
+pub mod tls;
+
 pub const BUILD_TIMESTAMP: &str = env!("VERGEN_BUILD_TIMESTAMP");
 pub const GIT_SHA: &str = env!("VERGEN_GIT_SHA");
 pub const GIT_DESCRIBE: &str = env!("VERGEN_GIT_DESCRIBE");
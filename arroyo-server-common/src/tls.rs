@@ -0,0 +1,153 @@
+use std::io::BufReader;
+use std::sync::Arc;
+use std::{env, fs};
+
+use anyhow::{anyhow, bail, Context};
+use arroyo_types::{TLS_CA_PATH_ENV, TLS_CERT_PATH_ENV, TLS_DOMAIN_ENV, TLS_KEY_PATH_ENV};
+use once_cell::sync::OnceCell;
+use rustls_pemfile::Item;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tonic::transport::{self, ClientTlsConfig, Endpoint, Server, ServerTlsConfig};
+
+const DEFAULT_DOMAIN: &str = "arroyo";
+
+/// Certificates for mutual TLS between the controller and workers, and between workers. Every
+/// server requires its clients to present a certificate signed by the CA, and clients verify that
+/// servers have a certificate for `domain` signed by the same CA.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    ca: Vec<u8>,
+    domain: String,
+}
+
+impl TlsConfig {
+    /// Takes the PEM-encoded certificate chain, private key and CA certificate.
+    pub fn new(cert: Vec<u8>, key: Vec<u8>, ca: Vec<u8>, domain: impl Into<String>) -> Self {
+        Self {
+            cert,
+            key,
+            ca,
+            domain: domain.into(),
+        }
+    }
+
+    /// Loads the configuration from the files named by the TLS environment variables, returning
+    /// None if TLS isn't enabled.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(cert_path) = env::var(TLS_CERT_PATH_ENV) else {
+            return Ok(None);
+        };
+
+        let read = |path: &str| {
+            fs::read(path).with_context(|| format!("failed to read TLS file {}", path))
+        };
+        let path = |var: &str| {
+            env::var(var).map_err(|_| anyhow!("{} is set, but {} is not", TLS_CERT_PATH_ENV, var))
+        };
+
+        Ok(Some(Self::new(
+            read(&cert_path)?,
+            read(&path(TLS_KEY_PATH_ENV)?)?,
+            read(&path(TLS_CA_PATH_ENV)?)?,
+            env::var(TLS_DOMAIN_ENV).unwrap_or_else(|_| DEFAULT_DOMAIN.to_string()),
+        )))
+    }
+
+    fn certs(pem: &[u8]) -> anyhow::Result<Vec<Certificate>> {
+        let certs = rustls_pemfile::certs(&mut BufReader::new(pem))?;
+        if certs.is_empty() {
+            bail!("no certificates found in PEM");
+        }
+        Ok(certs.into_iter().map(Certificate).collect())
+    }
+
+    fn private_key(&self) -> anyhow::Result<PrivateKey> {
+        for item in rustls_pemfile::read_all(&mut BufReader::new(&self.key[..]))? {
+            if let Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) = item {
+                return Ok(PrivateKey(key));
+            }
+        }
+        bail!("no private key found in PEM")
+    }
+
+    fn roots(&self) -> anyhow::Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        for cert in Self::certs(&self.ca)? {
+            roots
+                .add(&cert)
+                .map_err(|e| anyhow!("invalid CA certificate: {:?}", e))?;
+        }
+        Ok(roots)
+    }
+
+    /// Accepts TLS connections from clients with certificates signed by the CA.
+    pub fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(self.roots()?))
+            .with_single_cert(Self::certs(&self.cert)?, self.private_key()?)?;
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    /// Opens TLS connections to servers, along with the name their certificates must be issued for.
+    pub fn connector(&self) -> anyhow::Result<(TlsConnector, ServerName)> {
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(self.roots()?)
+            .with_single_cert(Self::certs(&self.cert)?, self.private_key()?)?;
+
+        let server_name = ServerName::try_from(self.domain.as_str())
+            .map_err(|_| anyhow!("invalid TLS domain {}", self.domain))?;
+
+        Ok((TlsConnector::from(Arc::new(config)), server_name))
+    }
+
+    pub fn grpc_server_config(&self) -> ServerTlsConfig {
+        ServerTlsConfig::new()
+            .identity(transport::Identity::from_pem(&self.cert, &self.key))
+            .client_ca_root(transport::Certificate::from_pem(&self.ca))
+    }
+
+    pub fn grpc_client_config(&self) -> ClientTlsConfig {
+        ClientTlsConfig::new()
+            .domain_name(&self.domain)
+            .ca_certificate(transport::Certificate::from_pem(&self.ca))
+            .identity(transport::Identity::from_pem(&self.cert, &self.key))
+    }
+}
+
+static TLS_CONFIG: OnceCell<Option<TlsConfig>> = OnceCell::new();
+
+/// The TLS configuration from the environment, loaded on first use.
+pub fn tls_config() -> Option<&'static TlsConfig> {
+    TLS_CONFIG
+        .get_or_init(|| TlsConfig::from_env().expect("invalid TLS configuration"))
+        .as_ref()
+}
+
+/// Requires TLS on a gRPC server if it's configured.
+pub fn with_tls<L>(server: Server<L>) -> anyhow::Result<Server<L>> {
+    Ok(match tls_config() {
+        Some(tls) => server.tls_config(tls.grpc_server_config())?,
+        None => server,
+    })
+}
+
+/// An endpoint for connecting to one of the gRPC servers that use `with_tls`.
+pub fn grpc_endpoint(addr: impl Into<String>) -> anyhow::Result<Endpoint> {
+    let addr = addr.into();
+    let endpoint = Endpoint::from_shared(addr.clone())
+        .with_context(|| format!("invalid gRPC address {}", addr))?;
+
+    Ok(match tls_config() {
+        Some(tls) => endpoint.tls_config(tls.grpc_client_config())?,
+        None => endpoint,
+    })
+}
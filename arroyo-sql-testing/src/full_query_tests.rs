@@ -51,3 +51,100 @@ full_pipeline_codegen! {"watermark_test",
 );
 
 SELECT id, name, email FROM person;"}
+
+full_pipeline_codegen! {"filtered_and_ordered_aggregates",
+"SELECT count(*) FILTER (WHERE bid.auction > 10) as large_bids,
+    sum(bid.price) FILTER (WHERE bid.auction % 2 = 0) as even_sum,
+    array_agg(bid.auction ORDER BY bid.datetime DESC) as ordered_auctions,
+    max_by(bid.auction, bid.price) as priciest_auction,
+    min_by(bid.datetime, bid.auction) as earliest_datetime
+FROM nexmark
+GROUP BY hop(interval '2 seconds', interval '10 seconds')"}
+
+full_pipeline_codegen! {"filtered_and_ordered_aggregates_single_phase",
+"SELECT count(distinct bid.auction) as auctions,
+    count(*) FILTER (WHERE bid.auction > 10) as large_bids,
+    sum(bid.price) FILTER (WHERE bid.auction % 2 = 0) as even_sum,
+    array_agg(bid.auction ORDER BY bid.datetime) as ordered_auctions,
+    last_value(bid.auction, bid.datetime) as last_auction
+FROM nexmark
+GROUP BY tumble(interval '10 seconds')"}
+
+full_pipeline_codegen! {"ranking_window_functions",
+"SELECT *, DENSE_RANK() OVER (
+    PARTITION BY window
+    ORDER BY count DESC) as dense_rank
+FROM (SELECT *, RANK() OVER (
+    PARTITION BY window
+    ORDER BY count DESC) as rank
+FROM (SELECT bid.auction as auction, count(*) as count,
+    tumble(interval '10 seconds') as window
+    FROM nexmark
+    GROUP BY 1, 3))"}
+
+full_pipeline_codegen! {"windowed_aggregate_window_functions",
+"SELECT *, LAG(count, 1, 0) OVER (
+    PARTITION BY window
+    ORDER BY auction) as previous_count
+FROM (SELECT *, sum(count) OVER (
+    PARTITION BY window
+    ORDER BY auction
+    ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) as recent_sum
+FROM (SELECT *, avg(count) OVER (
+    PARTITION BY window
+    ORDER BY auction) as running_avg
+FROM (SELECT bid.auction as auction, count(*) as count,
+    tumble(interval '10 seconds') as window
+    FROM nexmark
+    GROUP BY 1, 3)))"}
+
+full_pipeline_codegen! {"over_window_functions",
+"SELECT *, LEAD(price, 2) OVER (
+    PARTITION BY auction
+    ORDER BY datetime) as next_price
+FROM (SELECT *, LAG(price) OVER (
+    PARTITION BY auction
+    ORDER BY datetime) as previous_price
+FROM (SELECT *, max(price) OVER (
+    PARTITION BY auction
+    ORDER BY datetime
+    RANGE BETWEEN INTERVAL '1 minute' PRECEDING AND CURRENT ROW) as recent_max
+FROM (SELECT *, sum(price) OVER (
+    PARTITION BY auction
+    ORDER BY datetime) as running_total
+FROM (SELECT bid.auction as auction, bid.price as price, bid.datetime as datetime
+    FROM nexmark
+    WHERE bid is not null))))"}
+
+full_pipeline_codegen! {"interval_join",
+"WITH bids as (SELECT bid.auction as auction, bid.price as price, bid.datetime as datetime
+    FROM nexmark WHERE bid is not null),
+auctions as (SELECT auction.id as id, auction.datetime as datetime
+    FROM nexmark WHERE auction is not null)
+SELECT bids.auction, bids.price, auctions.datetime as auction_datetime
+FROM auctions JOIN bids ON auctions.id = bids.auction
+AND bids.datetime BETWEEN auctions.datetime - INTERVAL '5' MINUTE
+    AND auctions.datetime + INTERVAL '10' MINUTE"}
+
+full_pipeline_codegen! {"cross_join_unnest",
+"WITH bids as (SELECT bid.auction as auction, make_array(bid.price, bid.auction) as items
+    FROM nexmark WHERE bid is not null)
+SELECT auction, item FROM bids CROSS JOIN UNNEST(bids.items) AS u(item)"}
+
+full_pipeline_codegen! {"struct_construction",
+"SELECT struct(bid.auction, bid.price) as pair, struct(bid.auction, bid.price).c1 as price
+    FROM nexmark WHERE bid is not null"}
+
+full_pipeline_codegen! {"watermark_for_clause",
+"CREATE TABLE person (
+  id bigint,
+  name TEXT,
+  date_string text,
+  datetime datetime GENERATED ALWAYS AS (CAST(date_string as timestamp)),
+  WATERMARK FOR datetime AS datetime - INTERVAL '5' SECOND
+) WITH (
+  connection = 'local',
+  topic = 'person'
+);
+
+SELECT id, name FROM person;"}
@@ -950,6 +950,59 @@ mod tests {
         },
         vec![Some(1i64), Some(2i64)]
     );
+
+    // 1-based array indexing, which is null when out of bounds
+    single_test_codegen!(
+        "array_index",
+        "make_array(non_nullable_i64, non_nullable_i64 + 1)[2]",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 1,
+            ..Default::default()
+        },
+        Some(2i64)
+    );
+
+    single_test_codegen!(
+        "array_index_out_of_bounds",
+        "make_array(non_nullable_i64, non_nullable_i64 + 1)[3]",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 1,
+            ..Default::default()
+        },
+        None
+    );
+
+    single_test_codegen!(
+        "array_slice",
+        "array_slice(make_array(non_nullable_i64, non_nullable_i64 + 1, non_nullable_i64 + 2), 2, 3)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 1,
+            ..Default::default()
+        },
+        vec![2i64, 3i64]
+    );
+
+    single_test_codegen!(
+        "cardinality",
+        "cardinality(make_array(non_nullable_i32, nullable_i32))",
+        arroyo_sql::TestStruct {
+            non_nullable_i32: 1,
+            ..Default::default()
+        },
+        2u64
+    );
+
+    single_test_codegen!(
+        "array_contains",
+        "array_contains(make_array(non_nullable_i64, nullable_i64), non_nullable_i32)",
+        arroyo_sql::TestStruct {
+            non_nullable_i32: 2,
+            non_nullable_i64: 1,
+            nullable_i64: Some(2),
+            ..Default::default()
+        },
+        true
+    );
     // test get_first_json_object
     single_test_codegen!(
         "get_first_json_object",
@@ -1038,4 +1091,348 @@ mod tests {
         },
         String::from("ThoXXs")
     );
+
+    // Regex operators
+
+    single_test_codegen!(
+        "regex_match",
+        "non_nullable_string ~ '^foo'",
+        arroyo_sql::TestStruct {
+            non_nullable_string: "foobar".into(),
+            ..Default::default()
+        },
+        true
+    );
+
+    single_test_codegen!(
+        "regex_case_insensitive_match",
+        "nullable_string ~* '^FOO'",
+        arroyo_sql::TestStruct {
+            nullable_string: Some("foobar".into()),
+            ..Default::default()
+        },
+        Some(true)
+    );
+
+    single_test_codegen!(
+        "regex_not_match",
+        "non_nullable_string !~ '^FOO'",
+        arroyo_sql::TestStruct {
+            non_nullable_string: "foobar".into(),
+            ..Default::default()
+        },
+        true
+    );
+
+    single_test_codegen!(
+        "regex_not_case_insensitive_match",
+        "non_nullable_string !~* '^FOO'",
+        arroyo_sql::TestStruct {
+            non_nullable_string: "foobar".into(),
+            ..Default::default()
+        },
+        false
+    );
+
+    single_test_codegen!(
+        "regex_match_column_pattern",
+        "non_nullable_string ~ nullable_string",
+        arroyo_sql::TestStruct {
+            non_nullable_string: "foobar".into(),
+            nullable_string: Some("^foo".into()),
+            ..Default::default()
+        },
+        Some(true)
+    );
+
+    single_test_codegen!(
+        "regex_not_match_invalid_column_pattern",
+        "non_nullable_string !~ nullable_string",
+        arroyo_sql::TestStruct {
+            non_nullable_string: "foobar".into(),
+            nullable_string: Some("(foo".into()),
+            ..Default::default()
+        },
+        Some(false)
+    );
+
+    // Bitwise operators
+
+    single_test_codegen!(
+        "bitwise_and",
+        "non_nullable_i64 & 6",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 12,
+            ..Default::default()
+        },
+        4i64
+    );
+
+    single_test_codegen!(
+        "bitwise_or",
+        "nullable_i64 | 3",
+        arroyo_sql::TestStruct {
+            nullable_i64: Some(12),
+            ..Default::default()
+        },
+        Some(15i64)
+    );
+
+    single_test_codegen!(
+        "bitwise_xor",
+        "non_nullable_i64 ^ 6",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 12,
+            ..Default::default()
+        },
+        10i64
+    );
+
+    single_test_codegen!(
+        "bitwise_xor_sharp",
+        "non_nullable_i64 # 6",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 12,
+            ..Default::default()
+        },
+        10i64
+    );
+
+    single_test_codegen!(
+        "bitwise_shift_left",
+        "non_nullable_i64 << 2",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 3,
+            ..Default::default()
+        },
+        12i64
+    );
+
+    single_test_codegen!(
+        "bitwise_shift_right",
+        "nullable_i64 >> 2",
+        arroyo_sql::TestStruct {
+            nullable_i64: None,
+            ..Default::default()
+        },
+        None
+    );
+
+    // Power, atan2, gcd and lcm
+
+    single_test_codegen!(
+        "power",
+        "power(non_nullable_f64, 2)",
+        arroyo_sql::TestStruct {
+            non_nullable_f64: 3.0,
+            ..Default::default()
+        },
+        9.0f64
+    );
+
+    single_test_codegen!(
+        "atan2",
+        "atan2(nullable_f64, 1.0)",
+        arroyo_sql::TestStruct {
+            nullable_f64: Some(0.0),
+            ..Default::default()
+        },
+        Some(0.0f64)
+    );
+
+    single_test_codegen!(
+        "gcd",
+        "gcd(non_nullable_i64, 18)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 12,
+            ..Default::default()
+        },
+        6i64
+    );
+
+    single_test_codegen!(
+        "lcm",
+        "lcm(nullable_i64, 6)",
+        arroyo_sql::TestStruct {
+            nullable_i64: Some(4),
+            ..Default::default()
+        },
+        Some(12i64)
+    );
+
+    // Cbrt, degrees, radians and pi
+
+    single_test_codegen!(
+        "cbrt",
+        "cbrt(non_nullable_f64)",
+        arroyo_sql::TestStruct {
+            non_nullable_f64: 27.0,
+            ..Default::default()
+        },
+        3.0f64
+    );
+
+    single_test_codegen!(
+        "degrees",
+        "abs(degrees(non_nullable_f64) - 180) < 0.0001",
+        arroyo_sql::TestStruct {
+            non_nullable_f64: std::f64::consts::PI,
+            ..Default::default()
+        },
+        true
+    );
+
+    single_test_codegen!(
+        "radians",
+        "abs(radians(non_nullable_f64) - pi()) < 0.0001",
+        arroyo_sql::TestStruct {
+            non_nullable_f64: 180.0,
+            ..Default::default()
+        },
+        true
+    );
+
+    single_test_codegen!(
+        "pi",
+        "pi() * non_nullable_f64",
+        arroyo_sql::TestStruct {
+            non_nullable_f64: 2.0,
+            ..Default::default()
+        },
+        2.0 * std::f64::consts::PI
+    );
+
+    // Factorial and to_hex
+
+    single_test_codegen!(
+        "factorial",
+        "factorial(non_nullable_i64)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 5,
+            ..Default::default()
+        },
+        Some(120i64)
+    );
+
+    single_test_codegen!(
+        "factorial_overflow",
+        "factorial(non_nullable_i64)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 21,
+            ..Default::default()
+        },
+        None
+    );
+
+    single_test_codegen!(
+        "to_hex",
+        "to_hex(non_nullable_i64)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 255,
+            ..Default::default()
+        },
+        "ff".to_string()
+    );
+
+    // Uuid
+
+    single_test_codegen!(
+        "uuid",
+        "character_length(uuid())",
+        arroyo_sql::TestStruct::default(),
+        36
+    );
+
+    // Json
+
+    single_test_codegen!(
+        "json_get_text",
+        "(CAST(non_nullable_string AS JSON) -> 'a' ->> 'b')",
+        arroyo_sql::TestStruct {
+            non_nullable_string: r#"{"a": {"b": "c"}}"#.into(),
+            ..Default::default()
+        },
+        Some("c".to_string())
+    );
+
+    single_test_codegen!(
+        "json_get_array_index",
+        "CAST((non_nullable_string::json -> -1) AS TEXT)",
+        arroyo_sql::TestStruct {
+            non_nullable_string: "[1, 2, {\"a\": true}]".into(),
+            ..Default::default()
+        },
+        Some(r#"{"a":true}"#.to_string())
+    );
+
+    single_test_codegen!(
+        "json_get_missing",
+        "(non_nullable_string::json ->> 'b')",
+        arroyo_sql::TestStruct {
+            non_nullable_string: r#"{"a": 1}"#.into(),
+            ..Default::default()
+        },
+        None
+    );
+
+    single_test_codegen!(
+        "json_extract_int",
+        "json_extract_int(CAST(non_nullable_string AS JSON), '$.a.b')",
+        arroyo_sql::TestStruct {
+            non_nullable_string: r#"{"a": {"b": 5}}"#.into(),
+            ..Default::default()
+        },
+        Some(5i64)
+    );
+
+    single_test_codegen!(
+        "json_extract_bool",
+        "json_extract_bool(CAST(non_nullable_string AS JSON), '$.a[1]')",
+        arroyo_sql::TestStruct {
+            non_nullable_string: r#"{"a": [false, true]}"#.into(),
+            ..Default::default()
+        },
+        Some(true)
+    );
+
+    single_test_codegen!(
+        "json_array_length",
+        "json_array_length(non_nullable_string::json -> 'items')",
+        arroyo_sql::TestStruct {
+            non_nullable_string: r#"{"items": [1, 2, 3]}"#.into(),
+            ..Default::default()
+        },
+        Some(3i64)
+    );
+
+    single_test_codegen!(
+        "json_object_keys",
+        "json_object_keys(CAST(non_nullable_string AS JSON))",
+        arroyo_sql::TestStruct {
+            non_nullable_string: r#"{"a": 1, "b": 2}"#.into(),
+            ..Default::default()
+        },
+        Some(vec!["a".to_string(), "b".to_string()])
+    );
+
+    single_test_codegen!(
+        "to_json",
+        "CAST(to_json(non_nullable_i64) AS TEXT)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 5,
+            ..Default::default()
+        },
+        "5".to_string()
+    );
+
+    single_test_codegen!(
+        "json_build_object",
+        "CAST(json_build_object('a', non_nullable_i64, 'b', nullable_string) AS TEXT)",
+        arroyo_sql::TestStruct {
+            non_nullable_i64: 5,
+            nullable_string: None,
+            ..Default::default()
+        },
+        r#"{"a":5,"b":null}"#.to_string()
+    );
 }
@@ -0,0 +1,139 @@
+use std::fmt::{self, Display};
+
+use datafusion::sql::sqlparser::tokenizer::{Location, Token, TokenWithLocation};
+use datafusion_common::{DataFusionError, SchemaError};
+
+/// A position in the query text; lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqlLocation {
+    pub line: u64,
+    pub column: u64,
+}
+
+impl From<Location> for SqlLocation {
+    fn from(location: Location) -> Self {
+        SqlLocation {
+            line: location.line,
+            column: location.column,
+        }
+    }
+}
+
+/// The part of the query text an error refers to; `end` is just past its last character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqlSpan {
+    pub start: SqlLocation,
+    pub end: SqlLocation,
+}
+
+impl SqlSpan {
+    pub(crate) fn point(location: SqlLocation) -> Self {
+        SqlSpan {
+            start: location,
+            end: SqlLocation {
+                line: location.line,
+                column: location.column + 1,
+            },
+        }
+    }
+
+    /// The span from the first to the last token that isn't whitespace.
+    pub(crate) fn of_tokens(tokens: &[TokenWithLocation]) -> Option<Self> {
+        let mut significant = tokens
+            .iter()
+            .filter(|t| !matches!(t.token, Token::Whitespace(_)));
+        let first = significant.next()?;
+        let last = significant.last().unwrap_or(first);
+        Some(SqlSpan {
+            start: first.location.into(),
+            end: token_end(last),
+        })
+    }
+}
+
+fn token_end(token: &TokenWithLocation) -> SqlLocation {
+    let text = token.token.to_string();
+    match text.rfind('\n') {
+        Some(newline) => SqlLocation {
+            line: token.location.line + text.matches('\n').count() as u64,
+            column: (text.len() - newline) as u64,
+        },
+        None => SqlLocation {
+            line: token.location.line,
+            column: token.location.column + text.chars().count() as u64,
+        },
+    }
+}
+
+/// An error in a SQL query, along with where in the query it was found, if that is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlError {
+    pub message: String,
+    pub span: Option<SqlSpan>,
+}
+
+impl Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(
+                f,
+                "{} (at line {}, column {})",
+                self.message, span.start.line, span.start.column
+            ),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for SqlError {}
+
+impl SqlError {
+    /// Returns the error as a `SqlError`, pointing it at the given statement unless it already
+    /// has a position. Errors about unknown columns point at where the column is referred to.
+    pub(crate) fn in_statement(
+        err: anyhow::Error,
+        statement_tokens: &[TokenWithLocation],
+    ) -> anyhow::Error {
+        if err.chain().any(|e| e.downcast_ref::<SqlError>().is_some()) {
+            return err;
+        }
+        let span = missing_field(&err)
+            .and_then(|field| identifier_span(statement_tokens, &field))
+            .or_else(|| SqlSpan::of_tokens(statement_tokens));
+        anyhow::Error::new(SqlError {
+            message: err.root_cause().to_string(),
+            span,
+        })
+    }
+
+    /// Returns the `SqlError` in the error's chain, or one without a position.
+    pub fn from_anyhow(err: &anyhow::Error) -> SqlError {
+        err.chain()
+            .find_map(|e| e.downcast_ref::<SqlError>())
+            .cloned()
+            .unwrap_or_else(|| SqlError {
+                message: err.root_cause().to_string(),
+                span: None,
+            })
+    }
+}
+
+fn missing_field(err: &anyhow::Error) -> Option<String> {
+    err.chain()
+        .find_map(|e| match e.downcast_ref::<DataFusionError>() {
+            Some(DataFusionError::SchemaError(SchemaError::FieldNotFound { field, .. })) => {
+                Some(field.name.clone())
+            }
+            _ => None,
+        })
+}
+
+fn identifier_span(tokens: &[TokenWithLocation], identifier: &str) -> Option<SqlSpan> {
+    tokens.iter().find_map(|t| match &t.token {
+        Token::Word(word) if word.value.eq_ignore_ascii_case(identifier) => Some(SqlSpan {
+            start: t.location.into(),
+            end: token_end(t),
+        }),
+        _ => None,
+    })
+}
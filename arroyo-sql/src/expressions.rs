@@ -3,7 +3,7 @@ use std::{fmt::Debug, sync::Arc};
 use crate::{
     operators::TwoPhaseAggregation,
     pipeline::SortDirection,
-    types::{StructDef, StructField, TypeDef},
+    types::{StructDef, StructField, TypeDef, JSON_TYPE},
     ArroyoSchemaProvider,
 };
 use anyhow::{anyhow, bail, Ok, Result};
@@ -16,9 +16,8 @@ use datafusion_expr::{
     type_coercion::aggregates::{avg_return_type, sum_return_type},
     BinaryExpr, BuiltinScalarFunction, Expr, TryCast,
 };
-use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use syn::{parse_quote, parse_str, Ident, Path};
 
 #[derive(Debug, Clone)]
@@ -39,8 +38,10 @@ pub enum Expression {
     Hash(HashExpression),
     DataStructure(DataStructureFunction),
     Json(JsonExpression),
+    JsonValue(JsonValueFunction),
     RustUdf(RustUdfExpression),
     WrapType(WrapTypeExpression),
+    Filtered(FilteredExpression),
 }
 
 impl Expression {
@@ -69,8 +70,10 @@ impl Expression {
                 data_structure_expression.to_syn_expression()
             }
             Expression::Json(json_function) => json_function.to_syn_expression(),
+            Expression::JsonValue(json_function) => json_function.to_syn_expression(),
             Expression::RustUdf(t) => t.to_syn_expression(),
             Expression::WrapType(t) => t.to_syn_expression(),
+            Expression::Filtered(t) => t.to_syn_expression(),
         }
     }
 
@@ -97,8 +100,10 @@ impl Expression {
                 data_structure_expression.return_type()
             }
             Expression::Json(json_function) => json_function.return_type(),
+            Expression::JsonValue(json_function) => json_function.return_type(),
             Expression::RustUdf(t) => t.return_type(),
             Expression::WrapType(t) => t.return_type(),
+            Expression::Filtered(t) => t.return_type(),
         }
     }
 
@@ -177,9 +182,24 @@ impl Expression {
 pub struct ExpressionContext<'a> {
     pub schema_provider: &'a ArroyoSchemaProvider,
     pub input_struct: &'a StructDef,
+    // async UDFs can only be called where the expression is evaluated by an async operator
+    pub allow_async_udfs: bool,
 }
 
 impl<'a> ExpressionContext<'a> {
+    /// Compiles a WHERE or join condition, which must be boolean.
+    pub fn compile_predicate(&self, expression: &Expr) -> Result<Expression> {
+        let predicate = self.compile_expr(expression)?;
+        if !is_boolean(&predicate.return_type()) {
+            bail!(
+                "conditions must be boolean, but {} is {}",
+                expression,
+                type_name(&predicate.return_type())
+            );
+        }
+        Ok(predicate)
+    }
+
     pub fn compile_expr(&self, expression: &Expr) -> Result<Expression> {
         match expression {
             Expr::Alias(expr, _alias) => self.compile_expr(expr),
@@ -207,7 +227,16 @@ impl<'a> ExpressionContext<'a> {
                 | datafusion_expr::Operator::Minus
                 | datafusion_expr::Operator::Multiply
                 | datafusion_expr::Operator::Divide
-                | datafusion_expr::Operator::Modulo => BinaryMathExpression::new(
+                | datafusion_expr::Operator::Modulo
+                | datafusion_expr::Operator::RegexMatch
+                | datafusion_expr::Operator::RegexIMatch
+                | datafusion_expr::Operator::RegexNotMatch
+                | datafusion_expr::Operator::RegexNotIMatch
+                | datafusion_expr::Operator::BitwiseAnd
+                | datafusion_expr::Operator::BitwiseOr
+                | datafusion_expr::Operator::BitwiseXor
+                | datafusion_expr::Operator::BitwiseShiftRight
+                | datafusion_expr::Operator::BitwiseShiftLeft => BinaryMathExpression::new(
                     Box::new(self.compile_expr(left)?),
                     *op,
                     Box::new(self.compile_expr(right)?),
@@ -218,15 +247,6 @@ impl<'a> ExpressionContext<'a> {
                         self.compile_expr(right)?,
                     ])))
                 }
-                datafusion_expr::Operator::RegexMatch
-                | datafusion_expr::Operator::RegexIMatch
-                | datafusion_expr::Operator::RegexNotMatch
-                | datafusion_expr::Operator::RegexNotIMatch
-                | datafusion_expr::Operator::BitwiseAnd
-                | datafusion_expr::Operator::BitwiseOr
-                | datafusion_expr::Operator::BitwiseXor
-                | datafusion_expr::Operator::BitwiseShiftRight
-                | datafusion_expr::Operator::BitwiseShiftLeft => bail!("{:?} is unimplemented", op),
             },
             Expr::Not(_) => bail!("NOT is unimplemented"),
             Expr::IsNotNull(expr) => Ok(UnaryBooleanExpression::new(
@@ -266,33 +286,36 @@ impl<'a> ExpressionContext<'a> {
                 Box::new(self.compile_expr(expr)?),
             )),
             Expr::GetIndexedField(datafusion_expr::GetIndexedField { expr, key }) => {
-                StructFieldExpression::new(Box::new(self.compile_expr(expr)?), key)
+                let expr = Box::new(self.compile_expr(expr)?);
+                match key {
+                    ScalarValue::Int64(Some(_)) => DataStructureFunction::array_index(
+                        expr,
+                        Box::new(LiteralExpression::new(key.clone())),
+                    ),
+                    _ => StructFieldExpression::new(expr, key),
+                }
+            }
+            Expr::AggregateFunction(aggregate) => {
+                let (producing_expression, aggregator) =
+                    self.compile_aggregate_function(aggregate)?;
+                Ok(AggregationExpression::new(producing_expression, aggregator))
             }
-            Expr::AggregateFunction(datafusion_expr::expr::AggregateFunction {
+            Expr::AggregateUDF(datafusion_expr::expr::AggregateUDF {
                 fun,
                 args,
-                distinct,
                 filter,
                 order_by,
             }) => {
-                if args.len() != 1 {
-                    bail!("multiple aggregation parameters is not yet supported");
-                }
-
                 if filter.is_some() {
-                    bail!("filters in aggregations is not yet supported");
+                    bail!("FILTER is only supported for built-in aggregates");
                 }
                 if order_by.is_some() {
-                    bail!("order by in aggregations is not yet supported");
+                    bail!("order by in aggregations is only supported for array_agg");
                 }
-
-                Ok(AggregationExpression::new(
-                    Box::new(self.compile_expr(&args[0])?),
-                    fun.clone(),
-                    *distinct,
-                )?)
+                let (producing_expression, aggregator) =
+                    self.compile_aggregate_udf(&fun.name, args)?;
+                Ok(AggregationExpression::new(producing_expression, aggregator))
             }
-            Expr::AggregateUDF { .. } => bail!("aggregate UDFs not supported"),
             Expr::Case(datafusion_expr::Case {
                 expr,
                 when_then_expr,
@@ -344,14 +367,26 @@ impl<'a> ExpressionContext<'a> {
                     | BuiltinScalarFunction::Signum
                     | BuiltinScalarFunction::Trunc
                     | BuiltinScalarFunction::Log2
-                    | BuiltinScalarFunction::Exp => Ok(NumericExpression::new(
+                    | BuiltinScalarFunction::Exp
+                    | BuiltinScalarFunction::Cbrt
+                    | BuiltinScalarFunction::Degrees
+                    | BuiltinScalarFunction::Radians
+                    | BuiltinScalarFunction::Factorial
+                    | BuiltinScalarFunction::ToHex => Ok(NumericExpression::new(
                         fun.clone(),
                         Box::new(arg_expressions.remove(0)),
                     )?),
-                    BuiltinScalarFunction::Power | BuiltinScalarFunction::Atan2 => bail!(
-                        "multiple argument numeric function {:?} not implemented",
-                        fun
+                    BuiltinScalarFunction::Power
+                    | BuiltinScalarFunction::Atan2
+                    | BuiltinScalarFunction::Gcd
+                    | BuiltinScalarFunction::Lcm => BinaryMathExpression::from_function(
+                        fun.clone(),
+                        Box::new(arg_expressions.remove(0)),
+                        Box::new(arg_expressions.remove(0)),
                     ),
+                    BuiltinScalarFunction::Pi => Ok(LiteralExpression::new(ScalarValue::Float64(
+                        Some(std::f64::consts::PI),
+                    ))),
                     BuiltinScalarFunction::Ascii
                     | BuiltinScalarFunction::BitLength
                     | BuiltinScalarFunction::Btrim
@@ -379,29 +414,39 @@ impl<'a> ExpressionContext<'a> {
                     | BuiltinScalarFunction::Rpad
                     | BuiltinScalarFunction::Rtrim
                     | BuiltinScalarFunction::RegexpMatch
-                    | BuiltinScalarFunction::RegexpReplace => {
+                    | BuiltinScalarFunction::RegexpReplace
+                    | BuiltinScalarFunction::Uuid => {
                         let string_function: StringFunction =
                             (fun.clone(), arg_expressions).try_into()?;
                         Ok(Expression::String(string_function))
                     }
-                    BuiltinScalarFunction::Coalesce => Ok(Expression::DataStructure(
-                        DataStructureFunction::Coalesce(arg_expressions),
-                    )),
-                    BuiltinScalarFunction::NullIf => {
-                        Ok(Expression::DataStructure(DataStructureFunction::NullIf {
-                            left: Box::new(arg_expressions.remove(0)),
-                            right: Box::new(arg_expressions.remove(0)),
-                        }))
+                    BuiltinScalarFunction::Coalesce => {
+                        DataStructureFunction::coalesce(arg_expressions)
                     }
+                    BuiltinScalarFunction::NullIf => DataStructureFunction::null_if(
+                        Box::new(arg_expressions.remove(0)),
+                        Box::new(arg_expressions.remove(0)),
+                    ),
                     BuiltinScalarFunction::MakeArray => {
-                        if matches!(arg_expressions[0].return_type(), TypeDef::StructDef(_, _)) {
-                            bail!("make_array only supports primitive types");
-                        };
+                        let element_type = arg_expressions[0].return_type().with_nullity(false);
+                        if let Some(term) = arg_expressions
+                            .iter()
+                            .find(|term| term.return_type().with_nullity(false) != element_type)
+                        {
+                            bail!(
+                                "make_array requires all elements to have the same type, found {:?} and {:?}",
+                                element_type,
+                                term.return_type()
+                            );
+                        }
                         Ok(Expression::DataStructure(DataStructureFunction::MakeArray(
                             arg_expressions,
                         )))
                     }
-                    BuiltinScalarFunction::Struct | BuiltinScalarFunction::ArrowTypeof => {
+                    BuiltinScalarFunction::Struct => Ok(Expression::DataStructure(
+                        DataStructureFunction::Struct(arg_expressions),
+                    )),
+                    BuiltinScalarFunction::ArrowTypeof => {
                         bail!("data structure function {:?} not implemented", fun)
                     }
                     BuiltinScalarFunction::DatePart
@@ -427,15 +472,6 @@ impl<'a> ExpressionContext<'a> {
                         fun.clone(),
                         Box::new(arg_expressions.remove(0)),
                     )?),
-                    BuiltinScalarFunction::ToHex => bail!("hex not implemented"),
-                    BuiltinScalarFunction::Uuid => bail!("UUID unimplemented"),
-                    BuiltinScalarFunction::Cbrt => bail!("cube root unimplemented"),
-                    BuiltinScalarFunction::Degrees => bail!("degrees not implemented yet"),
-                    BuiltinScalarFunction::Pi => bail!("pi not implemented yet"),
-                    BuiltinScalarFunction::Radians => bail!("radians not implemented yet"),
-                    BuiltinScalarFunction::Factorial => bail!("factorial not implemented yet"),
-                    BuiltinScalarFunction::Gcd => bail!("gcd not implemented yet"),
-                    BuiltinScalarFunction::Lcm => bail!("lcm not implemented yet"),
                 }
             }
             Expr::ScalarUDF(ScalarUDF { fun, args }) => match fun.name.as_str() {
@@ -466,6 +502,53 @@ impl<'a> ExpressionContext<'a> {
                         path,
                     }))
                 }
+                "json_get" | "json_get_text" | "json_extract_int" | "json_extract_float"
+                | "json_extract_bool" => {
+                    let function = match fun.name.as_str() {
+                        "json_get" => JsonFunction::JsonGet,
+                        "json_get_text" => JsonFunction::JsonGetText,
+                        "json_extract_int" => JsonFunction::JsonExtractInt,
+                        "json_extract_float" => JsonFunction::JsonExtractFloat,
+                        _ => JsonFunction::JsonExtractBool,
+                    };
+                    let json_string = Box::new(self.compile_expr(&args[0])?);
+                    let path = Box::new(self.compile_expr(&args[1])?);
+                    Ok(Expression::Json(JsonExpression {
+                        function,
+                        json_string,
+                        path,
+                    }))
+                }
+                "parse_json" => {
+                    CastExpression::new(Box::new(self.compile_expr(&args[0])?), &JSON_TYPE)
+                }
+                "json_array_length" => Ok(Expression::JsonValue(JsonValueFunction::ArrayLength(
+                    Box::new(self.compile_expr(&args[0])?),
+                ))),
+                "json_object_keys" => Ok(Expression::JsonValue(JsonValueFunction::ObjectKeys(
+                    Box::new(self.compile_expr(&args[0])?),
+                ))),
+                "to_json" => Ok(Expression::JsonValue(JsonValueFunction::ToJson(Box::new(
+                    self.compile_expr(&args[0])?,
+                )))),
+                "json_build_object" => JsonValueFunction::build_object(
+                    args.iter()
+                        .map(|arg| self.compile_expr(arg))
+                        .collect::<Result<Vec<_>>>()?,
+                ),
+                "cardinality" => {
+                    DataStructureFunction::cardinality(Box::new(self.compile_expr(&args[0])?))
+                }
+                "array_contains" => DataStructureFunction::array_contains(
+                    Box::new(self.compile_expr(&args[0])?),
+                    Box::new(self.compile_expr(&args[1])?),
+                ),
+                "array_slice" => DataStructureFunction::array_slice(
+                    Box::new(self.compile_expr(&args[0])?),
+                    Box::new(self.compile_expr(&args[1])?),
+                    Box::new(self.compile_expr(&args[2])?),
+                ),
+                "unnest" => bail!("unnest is only supported as a top-level expression in SELECT"),
                 udf => {
                     // get udf from context
                     let def = self
@@ -474,6 +557,13 @@ impl<'a> ExpressionContext<'a> {
                         .get(udf)
                         .ok_or_else(|| anyhow!("no UDF with name '{}'", udf))?;
 
+                    if def.async_options.is_some() && !self.allow_async_udfs {
+                        bail!(
+                            "async UDF {} can only be called in the SELECT list of a query",
+                            udf
+                        );
+                    }
+
                     let inputs: Result<Vec<Expression>> =
                         args.iter().map(|e| (self.compile_expr(e))).collect();
                     let inputs = inputs?;
@@ -491,6 +581,7 @@ impl<'a> ExpressionContext<'a> {
                         name: udf.to_string(),
                         args: def.args.clone().into_iter().zip(inputs).collect(),
                         ret_type: def.ret.clone(),
+                        is_async: def.async_options.is_some(),
                     }))
                 }
             },
@@ -505,26 +596,164 @@ impl<'a> ExpressionContext<'a> {
             bail!("expected single field input");
         }
         match expr {
-            Expr::AggregateFunction(AggregateFunction {
+            Expr::AggregateFunction(
+                aggregate @ AggregateFunction {
+                    distinct: false, ..
+                },
+            ) => {
+                let (incoming_expression, aggregator) =
+                    self.compile_aggregate_function(aggregate)?;
+                Ok(TwoPhaseAggregation {
+                    incoming_expression,
+                    aggregator,
+                })
+            }
+            Expr::AggregateUDF(datafusion_expr::expr::AggregateUDF {
                 fun,
                 args,
-                distinct: false,
                 filter: None,
                 order_by: None,
             }) => {
-                if args.len() != 1 {
-                    bail!("unexpected arg length");
-                }
-                let incoming_expression = self.compile_expr(&args[0])?;
-                let aggregator = Aggregator::from_datafusion(fun.clone(), false)?;
+                let (incoming_expression, aggregator) =
+                    self.compile_aggregate_udf(&fun.name, args)?;
                 Ok(TwoPhaseAggregation {
                     incoming_expression,
                     aggregator,
                 })
             }
+            // DataFusion aliases aggregates with a FILTER clause to keep their output name
+            Expr::Alias(expr, _) => self.as_two_phase_aggregation(expr),
             _ => bail!("expected aggregate expression"),
         }
     }
+
+    fn compile_aggregate_function(
+        &self,
+        aggregate: &AggregateFunction,
+    ) -> Result<(Expression, Aggregator)> {
+        let AggregateFunction {
+            fun,
+            args,
+            distinct,
+            filter,
+            order_by,
+        } = aggregate;
+        let (expression, aggregator) = match (fun, order_by) {
+            (aggregate_function::AggregateFunction::ApproxPercentileCont, None) => {
+                if *distinct {
+                    bail!("distinct not supported for {:?}", fun);
+                }
+                let [arg, Expr::Literal(ScalarValue::Float64(Some(percentile)))] = &args[..] else {
+                    bail!("approx_percentile_cont takes an expression and a literal percentile");
+                };
+                if !(0.0..=1.0).contains(percentile) {
+                    bail!("percentile must be between 0 and 1, not {}", percentile);
+                }
+                (
+                    self.compile_expr(arg)?,
+                    Aggregator::ApproxPercentile(*percentile),
+                )
+            }
+            (aggregate_function::AggregateFunction::ArrayAgg, Some(order_by)) => {
+                if *distinct {
+                    bail!("distinct not supported for {:?}", fun);
+                }
+                if args.len() != 1 {
+                    bail!("multiple aggregation parameters is not yet supported");
+                }
+                let sort_expressions = order_by
+                    .iter()
+                    .map(|expr| match expr {
+                        Expr::Sort(sort) => SortExpression::from_expression(self, sort),
+                        _ => bail!("expected sort expression, not {}", expr),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                (
+                    self.compile_expr(&args[0])?,
+                    Aggregator::OrderedArrayAgg(sort_expressions),
+                )
+            }
+            (_, Some(_)) => bail!("order by in aggregations is only supported for array_agg"),
+            (_, None) => {
+                if args.len() != 1 {
+                    bail!("multiple aggregation parameters is not yet supported");
+                }
+                (
+                    self.compile_expr(&args[0])?,
+                    Aggregator::from_datafusion(fun.clone(), *distinct)?,
+                )
+            }
+        };
+        let Some(filter) = filter else {
+            return Ok((expression, aggregator));
+        };
+        if let Aggregator::ArrayAgg | Aggregator::OrderedArrayAgg(_) = aggregator {
+            bail!("FILTER is not yet supported for array_agg");
+        }
+        let filter = self.compile_expr(filter)?;
+        Ok((FilteredExpression::new(filter, expression)?, aggregator))
+    }
+
+    fn compile_aggregate_udf(&self, name: &str, args: &[Expr]) -> Result<(Expression, Aggregator)> {
+        match name {
+            "approx_count_distinct" => {
+                if args.len() != 1 {
+                    bail!("approx_count_distinct takes a single argument");
+                }
+                let expression = self.compile_expr(&args[0])?;
+                // values are hashed into the sketch, which generated structs don't support
+                if let TypeDef::StructDef(_, _) | TypeDef::DataType(DataType::List(_), _) =
+                    expression.return_type()
+                {
+                    bail!(
+                        "approx_count_distinct doesn't support {} arguments",
+                        type_name(&expression.return_type())
+                    );
+                }
+                return Ok((expression, Aggregator::ApproxCountDistinct));
+            }
+            "first_value" | "last_value" | "min_by" | "max_by" | "arg_min" | "arg_max" => {
+                if args.len() != 2 {
+                    bail!("{} takes a value and an ordering key", name);
+                }
+                let key = Box::new(self.compile_expr(&args[1])?);
+                match key.return_type() {
+                    TypeDef::DataType(DataType::Timestamp(_, _), _) => {}
+                    TypeDef::DataType(_, _) if !name.ends_with("_value") => {}
+                    TypeDef::DataType(_, _) => {
+                        bail!("the second argument to {} must be a timestamp", name)
+                    }
+                    TypeDef::StructDef(_, _) => {
+                        bail!("the second argument to {} can't be a struct", name)
+                    }
+                }
+                let aggregator = match name {
+                    "first_value" | "min_by" | "arg_min" => Aggregator::MinBy(key),
+                    _ => Aggregator::MaxBy(key),
+                };
+                return Ok((self.compile_expr(&args[0])?, aggregator));
+            }
+            _ => {}
+        }
+        let def = self
+            .schema_provider
+            .udaf_defs
+            .get(name)
+            .ok_or_else(|| anyhow!("no UDAF with name '{}'", name))?;
+        if args.len() != 1 {
+            bail!(
+                "wrong number of arguments for udaf {} (found {}, expected 1)",
+                name,
+                args.len()
+            );
+        }
+        let udaf = RustUdaf {
+            name: name.to_string(),
+            arg: def.arg.clone(),
+            ret: def.ret.clone(),
+        };
+        Ok((self.compile_expr(&args[0])?, Aggregator::RustUdaf(udaf)))
+    }
 }
 
 /// A named reference to a qualified field in a schema.
@@ -575,6 +804,10 @@ impl ColumnExpression {
         Ok(ColumnExpression { column_field })
     }
 
+    pub fn column_field(&self) -> &StructField {
+        &self.column_field
+    }
+
     fn to_syn_expression(&self) -> syn::Expr {
         let field_ident = self.column_field.field_ident();
         parse_quote!(arg.#field_ident.clone())
@@ -658,7 +891,12 @@ impl LiteralExpression {
     }
 
     fn return_type(&self) -> TypeDef {
-        TypeDef::DataType(self.literal.get_datatype(), self.literal.is_null())
+        let field = Field::new(
+            "literal",
+            self.literal.get_datatype(),
+            self.literal.is_null(),
+        );
+        StructField::from(&field).data_type
     }
 
     fn new(literal: ScalarValue) -> Expression {
@@ -714,11 +952,69 @@ impl BinaryComparisonExpression {
         op: datafusion_expr::Operator,
         right: Box<Expression>,
     ) -> Result<Expression> {
-        let op = op.try_into()?;
+        let sql_op = op;
+        let op: BinaryComparison = op.try_into()?;
+        let (left_type, right_type) = (left.return_type(), right.return_type());
+        let valid = match op {
+            BinaryComparison::And | BinaryComparison::Or => {
+                is_boolean(&left_type) && is_boolean(&right_type)
+            }
+            _ => same_rust_type(&left_type, &right_type),
+        };
+        if !valid {
+            bail!(
+                "can't apply {} to {} and {}",
+                sql_op,
+                type_name(&left_type),
+                type_name(&right_type)
+            );
+        }
         Ok(Expression::BinaryComparison(Self { left, op, right }))
     }
 }
 
+fn type_name(type_def: &TypeDef) -> String {
+    match type_def {
+        TypeDef::StructDef(_, _) => "STRUCT".to_string(),
+        TypeDef::DataType(data_type, _) => data_type.to_string(),
+    }
+}
+
+fn is_boolean(type_def: &TypeDef) -> bool {
+    matches!(
+        type_def.as_datatype(),
+        Some(DataType::Boolean | DataType::Null)
+    )
+}
+
+fn is_time(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64
+    )
+}
+
+fn is_duration(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Duration(_) | DataType::Interval(_))
+}
+
+/// Whether the generated code represents both types with the same Rust type, so that
+/// they can be compared. NULL literals are compatible with any type.
+fn same_rust_type(left: &TypeDef, right: &TypeDef) -> bool {
+    match (left, right) {
+        (TypeDef::DataType(DataType::Null, _), _) | (_, TypeDef::DataType(DataType::Null, _)) => {
+            true
+        }
+        (TypeDef::DataType(left, _), TypeDef::DataType(right, _)) => {
+            left == right
+                || (is_time(left) && is_time(right))
+                || (is_duration(left) && is_duration(right))
+        }
+        (TypeDef::StructDef(left, _), TypeDef::StructDef(right, _)) => left == right,
+        _ => false,
+    }
+}
+
 impl BinaryComparisonExpression {
     fn to_syn_expression(&self) -> syn::Expr {
         let left_expr = self.left.to_syn_expression();
@@ -773,16 +1069,81 @@ pub enum BinaryMathOperator {
     Multiply,
     Divide,
     Modulo,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseShiftLeft,
+    BitwiseShiftRight,
+    RegexMatch {
+        case_insensitive: bool,
+        negated: bool,
+        // the pattern, if it's a literal; it's then validated when planning and compiled once
+        literal: Option<String>,
+    },
+    Power,
+    Atan2,
+    Gcd,
+    Lcm,
 }
 
 impl BinaryMathOperator {
-    fn as_tokens(&self) -> TokenStream {
+    /// The expression combining the non-null values bound to `left` and `right`.
+    fn apply(&self) -> syn::Expr {
         match self {
-            BinaryMathOperator::Plus => quote!(+),
-            BinaryMathOperator::Minus => quote!(-),
-            BinaryMathOperator::Multiply => quote!(*),
-            BinaryMathOperator::Divide => quote!(/),
-            BinaryMathOperator::Modulo => quote!(%),
+            BinaryMathOperator::Plus => parse_quote!((left + right)),
+            BinaryMathOperator::Minus => parse_quote!((left - right)),
+            BinaryMathOperator::Multiply => parse_quote!((left * right)),
+            BinaryMathOperator::Divide => parse_quote!((left / right)),
+            BinaryMathOperator::Modulo => parse_quote!((left % right)),
+            BinaryMathOperator::BitwiseAnd => parse_quote!((left & right)),
+            BinaryMathOperator::BitwiseOr => parse_quote!((left | right)),
+            BinaryMathOperator::BitwiseXor => parse_quote!((left ^ right)),
+            BinaryMathOperator::BitwiseShiftLeft => {
+                parse_quote!(left.wrapping_shl(right as u32))
+            }
+            BinaryMathOperator::BitwiseShiftRight => {
+                parse_quote!(left.wrapping_shr(right as u32))
+            }
+            BinaryMathOperator::RegexMatch {
+                case_insensitive,
+                negated,
+                literal: Some(pattern),
+            } => {
+                let is_match = quote!({
+                    static REGEX: arroyo_worker::operators::functions::regexp::LiteralRegex =
+                        arroyo_worker::operators::functions::regexp::LiteralRegex::new(
+                            #pattern,
+                            #case_insensitive,
+                        );
+                    REGEX.is_match(&left)
+                });
+                if *negated {
+                    parse_quote!((!#is_match))
+                } else {
+                    parse_quote!(#is_match)
+                }
+            }
+            // rows with an invalid pattern match neither the operator nor its negation
+            BinaryMathOperator::RegexMatch {
+                case_insensitive,
+                negated,
+                literal: None,
+            } => parse_quote!(
+                arroyo_worker::operators::functions::regexp::regexp_is_match(
+                    &left,
+                    &right,
+                    #case_insensitive
+                )
+                .map_or(false, |is_match| is_match != #negated)
+            ),
+            BinaryMathOperator::Power => parse_quote!((left as f64).powf(right as f64)),
+            BinaryMathOperator::Atan2 => parse_quote!((left as f64).atan2(right as f64)),
+            BinaryMathOperator::Gcd => parse_quote!(
+                arroyo_worker::operators::functions::numeric::gcd(left as i64, right as i64)
+            ),
+            BinaryMathOperator::Lcm => parse_quote!(
+                arroyo_worker::operators::functions::numeric::lcm(left as i64, right as i64)
+            ),
         }
     }
 }
@@ -797,12 +1158,66 @@ impl TryFrom<datafusion_expr::Operator> for BinaryMathOperator {
             datafusion_expr::Operator::Multiply => Self::Multiply,
             datafusion_expr::Operator::Divide => Self::Divide,
             datafusion_expr::Operator::Modulo => Self::Modulo,
+            datafusion_expr::Operator::BitwiseAnd => Self::BitwiseAnd,
+            datafusion_expr::Operator::BitwiseOr => Self::BitwiseOr,
+            datafusion_expr::Operator::BitwiseXor => Self::BitwiseXor,
+            datafusion_expr::Operator::BitwiseShiftLeft => Self::BitwiseShiftLeft,
+            datafusion_expr::Operator::BitwiseShiftRight => Self::BitwiseShiftRight,
+            datafusion_expr::Operator::RegexMatch => Self::RegexMatch {
+                case_insensitive: false,
+                negated: false,
+                literal: None,
+            },
+            datafusion_expr::Operator::RegexIMatch => Self::RegexMatch {
+                case_insensitive: true,
+                negated: false,
+                literal: None,
+            },
+            datafusion_expr::Operator::RegexNotMatch => Self::RegexMatch {
+                case_insensitive: false,
+                negated: true,
+                literal: None,
+            },
+            datafusion_expr::Operator::RegexNotIMatch => Self::RegexMatch {
+                case_insensitive: true,
+                negated: true,
+                literal: None,
+            },
             _ => bail!("{:?} is not a math operator", op),
         };
         Ok(op)
     }
 }
 
+impl TryFrom<BuiltinScalarFunction> for BinaryMathOperator {
+    type Error = anyhow::Error;
+
+    fn try_from(fun: BuiltinScalarFunction) -> Result<Self> {
+        let op = match fun {
+            BuiltinScalarFunction::Power => Self::Power,
+            BuiltinScalarFunction::Atan2 => Self::Atan2,
+            BuiltinScalarFunction::Gcd => Self::Gcd,
+            BuiltinScalarFunction::Lcm => Self::Lcm,
+            _ => bail!("{:?} is not a two argument numeric function", fun),
+        };
+        Ok(op)
+    }
+}
+
+fn is_integer(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+    )
+}
+
 #[derive(Debug, Clone)]
 pub struct BinaryMathExpression {
     left: Box<Expression>,
@@ -816,7 +1231,85 @@ impl BinaryMathExpression {
         op: datafusion_expr::Operator,
         right: Box<Expression>,
     ) -> Result<Expression> {
-        let op = op.try_into()?;
+        let name = op.to_string();
+        Self::with_operator(left, op.try_into()?, right, &name)
+    }
+
+    fn from_function(
+        fun: BuiltinScalarFunction,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    ) -> Result<Expression> {
+        let name = fun.to_string();
+        Self::with_operator(left, fun.try_into()?, right, &name)
+    }
+
+    fn with_operator(
+        left: Box<Expression>,
+        mut op: BinaryMathOperator,
+        right: Box<Expression>,
+        name: &str,
+    ) -> Result<Expression> {
+        let (left_type, right_type) = (left.return_type(), right.return_type());
+        let valid = match (left_type.as_datatype(), right_type.as_datatype()) {
+            (Some(DataType::Null), Some(_)) | (Some(_), Some(DataType::Null)) => true,
+            (Some(left), Some(right)) => match op {
+                BinaryMathOperator::Plus
+                | BinaryMathOperator::Minus
+                | BinaryMathOperator::Multiply
+                | BinaryMathOperator::Divide
+                | BinaryMathOperator::Modulo => {
+                    if left.is_numeric() {
+                        same_rust_type(&left_type, &right_type)
+                    } else {
+                        // SystemTime and Duration only support adding and subtracting durations
+                        (is_time(left) || is_duration(left))
+                            && is_duration(right)
+                            && matches!(op, BinaryMathOperator::Plus | BinaryMathOperator::Minus)
+                    }
+                }
+                BinaryMathOperator::BitwiseAnd
+                | BinaryMathOperator::BitwiseOr
+                | BinaryMathOperator::BitwiseXor => {
+                    is_integer(left) && same_rust_type(&left_type, &right_type)
+                }
+                BinaryMathOperator::BitwiseShiftLeft
+                | BinaryMathOperator::BitwiseShiftRight
+                | BinaryMathOperator::Gcd
+                | BinaryMathOperator::Lcm => is_integer(left) && is_integer(right),
+                BinaryMathOperator::RegexMatch { .. } => {
+                    *left == DataType::Utf8 && *right == DataType::Utf8
+                }
+                BinaryMathOperator::Power | BinaryMathOperator::Atan2 => {
+                    left.is_numeric() && right.is_numeric()
+                }
+            },
+            _ => false,
+        };
+        if !valid {
+            bail!(
+                "can't apply {} to {} and {}",
+                name,
+                type_name(&left_type),
+                type_name(&right_type)
+            );
+        }
+        if let (
+            BinaryMathOperator::RegexMatch {
+                case_insensitive,
+                literal,
+                ..
+            },
+            Expression::Literal(LiteralExpression {
+                literal: ScalarValue::Utf8(Some(pattern)),
+            }),
+        ) = (&mut op, right.as_ref())
+        {
+            RegexBuilder::new(pattern)
+                .case_insensitive(*case_insensitive)
+                .build()?;
+            *literal = Some(pattern.clone());
+        }
         Ok(Expression::BinaryMath(Self { left, op, right }))
     }
 }
@@ -825,29 +1318,44 @@ impl BinaryMathExpression {
     fn to_syn_expression(&self) -> syn::Expr {
         let left_expr = self.left.to_syn_expression();
         let right_expr = self.right.to_syn_expression();
-        let op = self.op.as_tokens();
+        let result = self.op.apply();
         match (self.left.nullable(), self.right.nullable()) {
             (true, true) => parse_quote!({
                 let left = #left_expr;
                 let right = #right_expr;
                 match (left, right) {
-                    (Some(left), Some(right)) => Some(left #op right),
+                    (Some(left), Some(right)) => Some(#result),
                     _ => None
                 }
             }),
-            (true, false) => {
-                parse_quote!(#left_expr.map(|left| left #op #right_expr))
-            }
-            (false, true) => {
-                parse_quote!(#right_expr.map(|right| #left_expr #op right))
-            }
-            (false, false) => parse_quote!((#left_expr #op #right_expr)),
+            (true, false) => parse_quote!({
+                let right = #right_expr;
+                #left_expr.map(|left| #result)
+            }),
+            (false, true) => parse_quote!({
+                let left = #left_expr;
+                #right_expr.map(|right| #result)
+            }),
+            (false, false) => parse_quote!({
+                let left = #left_expr;
+                let right = #right_expr;
+                #result
+            }),
         }
     }
 
     fn return_type(&self) -> TypeDef {
         let nullable = self.left.nullable() || self.right.nullable();
-        self.left.return_type().with_nullity(nullable)
+        match self.op {
+            BinaryMathOperator::RegexMatch { .. } => TypeDef::DataType(DataType::Boolean, nullable),
+            BinaryMathOperator::Power | BinaryMathOperator::Atan2 => {
+                TypeDef::DataType(DataType::Float64, nullable)
+            }
+            BinaryMathOperator::Gcd | BinaryMathOperator::Lcm => {
+                TypeDef::DataType(DataType::Int64, nullable)
+            }
+            _ => self.left.return_type().with_nullity(nullable),
+        }
     }
 }
 
@@ -901,7 +1409,7 @@ impl StructFieldExpression {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub enum Aggregator {
     Count,
     Sum,
@@ -909,6 +1417,60 @@ pub enum Aggregator {
     Max,
     Avg,
     CountDistinct,
+    ApproxCountDistinct,
+    ApproxPercentile(f64),
+    Variance,
+    VariancePop,
+    Stddev,
+    StddevPop,
+    // the value at the smallest/largest key, as given by the expression. first_value and
+    // last_value are these with the event time as the key.
+    MinBy(Box<Expression>),
+    MaxBy(Box<Expression>),
+    BoolAnd,
+    BoolOr,
+    ArrayAgg,
+    OrderedArrayAgg(Vec<SortExpression>),
+    RustUdaf(RustUdaf),
+}
+
+/// An aggregate defined by the user as a module of `init`, `accumulate`, `merge` and `finish`
+/// functions. The accumulator doubles as the bin, so it can be merged across bins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustUdaf {
+    pub name: String,
+    pub arg: TypeDef,
+    pub ret: TypeDef,
+}
+
+impl RustUdaf {
+    pub(crate) fn path(&self) -> Path {
+        let name = format_ident!("{}", self.name);
+        parse_quote!(udfs::#name)
+    }
+
+    pub(crate) fn accumulator_type(&self) -> syn::Type {
+        let path = self.path();
+        parse_quote!(#path::Accumulator)
+    }
+
+    /// Folds `input` into the accumulator `acc`. Nulls are skipped unless the UDAF
+    /// takes an `Option`.
+    pub(crate) fn accumulate_syn_expr(&self, acc: syn::Expr, input: &Expression) -> syn::Expr {
+        let path = self.path();
+        let expr = input.to_syn_expression();
+        match (self.arg.is_optional(), input.nullable()) {
+            (true, true) | (false, false) => parse_quote!(#path::accumulate(#acc, #expr)),
+            (true, false) => parse_quote!(#path::accumulate(#acc, Some(#expr))),
+            (false, true) => parse_quote!({
+                let acc = #acc;
+                match #expr {
+                    Some(value) => #path::accumulate(acc, value),
+                    None => acc,
+                }
+            }),
+        }
+    }
 }
 
 impl Aggregator {
@@ -923,13 +1485,26 @@ impl Aggregator {
             (datafusion_expr::AggregateFunction::Max, false) => Ok(Self::Max),
             (datafusion_expr::AggregateFunction::Avg, false) => Ok(Self::Avg),
             (datafusion_expr::AggregateFunction::Count, true) => Ok(Self::CountDistinct),
+            (datafusion_expr::AggregateFunction::ApproxDistinct, false) => {
+                Ok(Self::ApproxCountDistinct)
+            }
+            (datafusion_expr::AggregateFunction::ApproxMedian, false) => {
+                Ok(Self::ApproxPercentile(0.5))
+            }
+            (datafusion_expr::AggregateFunction::Variance, false) => Ok(Self::Variance),
+            (datafusion_expr::AggregateFunction::VariancePop, false) => Ok(Self::VariancePop),
+            (datafusion_expr::AggregateFunction::Stddev, false) => Ok(Self::Stddev),
+            (datafusion_expr::AggregateFunction::StddevPop, false) => Ok(Self::StddevPop),
+            (datafusion_expr::AggregateFunction::BoolAnd, false) => Ok(Self::BoolAnd),
+            (datafusion_expr::AggregateFunction::BoolOr, false) => Ok(Self::BoolOr),
+            (datafusion_expr::AggregateFunction::ArrayAgg, false) => Ok(Self::ArrayAgg),
             (aggregator, true) => bail!("distinct not supported for {:?}", aggregator),
             (aggregator, false) => bail!("aggregator {:?} not supported yet", aggregator),
         }
     }
 
     pub fn return_data_type(&self, input_type: TypeDef) -> DataType {
-        let (input_type, _) = match input_type {
+        let (input_type, input_nullable) = match input_type {
             TypeDef::StructDef(_, _) => unreachable!("aggregates over structs not supported"),
             TypeDef::DataType(arg_type, nullable) => (arg_type, nullable),
         };
@@ -943,7 +1518,19 @@ impl Aggregator {
             Aggregator::Avg => {
                 avg_return_type(&input_type).expect("data fusion should've validated types")
             }
-            Aggregator::CountDistinct => DataType::Int64,
+            Aggregator::CountDistinct | Aggregator::ApproxCountDistinct => DataType::Int64,
+            Aggregator::ApproxPercentile(_) | Aggregator::MinBy(_) | Aggregator::MaxBy(_) => {
+                input_type
+            }
+            Aggregator::Variance
+            | Aggregator::VariancePop
+            | Aggregator::Stddev
+            | Aggregator::StddevPop => DataType::Float64,
+            Aggregator::BoolAnd | Aggregator::BoolOr => DataType::Boolean,
+            Aggregator::ArrayAgg | Aggregator::OrderedArrayAgg(_) => {
+                DataType::List(Arc::new(Field::new("item", input_type, input_nullable)))
+            }
+            Aggregator::RustUdaf(udaf) => udaf.ret.as_datatype().unwrap().clone(),
         }
     }
 }
@@ -973,16 +1560,11 @@ impl TryFrom<AggregationExpression> for TwoPhaseAggregation {
 }
 
 impl AggregationExpression {
-    fn new(
-        producing_expression: Box<Expression>,
-        aggregator: aggregate_function::AggregateFunction,
-        distinct: bool,
-    ) -> Result<Expression> {
-        let aggregator = Aggregator::from_datafusion(aggregator, distinct)?;
-        Ok(Expression::Aggregation(Self {
-            producing_expression,
+    fn new(producing_expression: Expression, aggregator: Aggregator) -> Expression {
+        Expression::Aggregation(Self {
+            producing_expression: Box::new(producing_expression),
             aggregator,
-        }))
+        })
     }
 
     pub(crate) fn allows_two_phase(&self) -> bool {
@@ -991,30 +1573,48 @@ impl AggregationExpression {
             | Aggregator::Sum
             | Aggregator::Min
             | Aggregator::Avg
-            | Aggregator::Max => true,
+            | Aggregator::Max
+            | Aggregator::ApproxCountDistinct
+            | Aggregator::ApproxPercentile(_)
+            | Aggregator::Variance
+            | Aggregator::VariancePop
+            | Aggregator::Stddev
+            | Aggregator::StddevPop
+            | Aggregator::MinBy(_)
+            | Aggregator::MaxBy(_)
+            | Aggregator::BoolAnd
+            | Aggregator::BoolOr
+            | Aggregator::ArrayAgg
+            | Aggregator::OrderedArrayAgg(_)
+            | Aggregator::RustUdaf(_) => true,
             Aggregator::CountDistinct => false,
         }
     }
 
     pub fn try_from_expression(ctx: &mut ExpressionContext, expr: &Expr) -> Result<Self> {
         match expr {
-            Expr::AggregateFunction(datafusion_expr::expr::AggregateFunction {
+            Expr::AggregateFunction(aggregate) => {
+                let (producing_expression, aggregator) =
+                    ctx.compile_aggregate_function(aggregate)?;
+                Ok(AggregationExpression {
+                    producing_expression: Box::new(producing_expression),
+                    aggregator,
+                })
+            }
+            Expr::AggregateUDF(datafusion_expr::expr::AggregateUDF {
                 fun,
                 args,
-                distinct,
                 filter: None,
                 order_by: None,
             }) => {
-                if args.len() != 1 {
-                    bail!("unexpected arg length");
-                }
-                let producing_expression = Box::new(ctx.compile_expr(&args[0])?);
-                let aggregator = Aggregator::from_datafusion(fun.clone(), *distinct)?;
+                let (producing_expression, aggregator) =
+                    ctx.compile_aggregate_udf(&fun.name, args)?;
                 Ok(AggregationExpression {
-                    producing_expression,
+                    producing_expression: Box::new(producing_expression),
                     aggregator,
                 })
             }
+            Expr::Alias(expr, _) => Self::try_from_expression(ctx, expr),
             _ => bail!("expected aggregate function, not {}", expr),
         }
     }
@@ -1070,6 +1670,23 @@ impl AggregationExpression {
                     .collect::<std::collections::HashSet<_>>()
                     .len() as i64
             }),
+            Aggregator::RustUdaf(ref udaf) => {
+                let path = udaf.path();
+                let accumulate =
+                    udaf.accumulate_syn_expr(parse_quote!(acc), &self.producing_expression);
+                parse_quote!({
+                    let acc = arg.iter().fold(#path::init(), |acc, arg| #accumulate);
+                    #path::finish(acc)
+                })
+            }
+            _ => self.as_two_phase().vec_aggregating_syn_expression(),
+        }
+    }
+
+    fn as_two_phase(&self) -> TwoPhaseAggregation {
+        TwoPhaseAggregation {
+            incoming_expression: (*self.producing_expression).clone(),
+            aggregator: self.aggregator.clone(),
         }
     }
 
@@ -1078,6 +1695,19 @@ impl AggregationExpression {
             Aggregator::Count | Aggregator::CountDistinct => {
                 TypeDef::DataType(DataType::Int64, false)
             }
+            Aggregator::RustUdaf(udaf) => udaf.ret.clone(),
+            Aggregator::ApproxCountDistinct
+            | Aggregator::ApproxPercentile(_)
+            | Aggregator::Variance
+            | Aggregator::VariancePop
+            | Aggregator::Stddev
+            | Aggregator::StddevPop
+            | Aggregator::MinBy(_)
+            | Aggregator::MaxBy(_)
+            | Aggregator::BoolAnd
+            | Aggregator::BoolOr
+            | Aggregator::ArrayAgg
+            | Aggregator::OrderedArrayAgg(_) => self.as_two_phase().return_type(),
             aggregator => TypeDef::DataType(
                 aggregator.return_data_type(self.producing_expression.return_type()),
                 self.producing_expression.nullable(),
@@ -1086,29 +1716,186 @@ impl AggregationExpression {
     }
 }
 
+/// The input of an aggregate with a FILTER clause. Rows that don't match the filter
+/// evaluate to null, which the aggregators already skip.
+#[derive(Debug, Clone)]
+pub struct FilteredExpression {
+    filter: Box<Expression>,
+    input: Box<Expression>,
+}
+
+impl FilteredExpression {
+    fn new(filter: Expression, input: Expression) -> Result<Expression> {
+        if !matches!(
+            filter.return_type(),
+            TypeDef::DataType(DataType::Boolean, _)
+        ) {
+            bail!("FILTER clauses must be boolean expressions");
+        }
+        Ok(Expression::Filtered(Self {
+            filter: Box::new(filter),
+            input: Box::new(input),
+        }))
+    }
+
+    fn to_syn_expression(&self) -> syn::Expr {
+        let filter = self.filter.to_syn_expression();
+        let filter: syn::Expr = if self.filter.nullable() {
+            parse_quote!(#filter.unwrap_or(false))
+        } else {
+            filter
+        };
+        let input = self.input.to_syn_expression();
+        let input: syn::Expr = if self.input.nullable() {
+            input
+        } else {
+            parse_quote!(Some(#input))
+        };
+        parse_quote!(if #filter { #input } else { None })
+    }
+
+    fn return_type(&self) -> TypeDef {
+        self.input.return_type().as_nullable()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CastExpression {
     input: Box<Expression>,
-    data_type: DataType,
+    output_type: TypeDef,
 }
 
 impl CastExpression {
-    fn new(input: Box<Expression>, data_type: &DataType) -> Result<Expression> {
-        if let TypeDef::DataType(input_type, _) = input.return_type() {
-            if Self::allowed_types(&input_type, data_type) {
-                Ok(Expression::Cast(Self {
-                    input,
-                    data_type: data_type.clone(),
-                }))
-            } else {
-                bail!(
-                    "casting from {:?} to {:?} is currently unsupported",
-                    input_type,
-                    data_type
+    pub(crate) fn new(input: Box<Expression>, data_type: &DataType) -> Result<Expression> {
+        match Self::cast_type(&input.return_type(), data_type) {
+            Some(output_type) => Ok(Expression::Cast(Self { input, output_type })),
+            None => bail!(
+                "casting from {:?} to {:?} is currently unsupported",
+                input.return_type(),
+                data_type
+            ),
+        }
+    }
+
+    // the type produced by casting input_type to data_type, which keeps the nullability of the input.
+    fn cast_type(input_type: &TypeDef, data_type: &DataType) -> Option<TypeDef> {
+        let nullable = input_type.is_optional();
+        match (input_type, data_type) {
+            (TypeDef::StructDef(input_struct, _), DataType::Struct(fields)) => {
+                let output_fields: Vec<StructField> =
+                    fields.iter().map(|f| f.as_ref().into()).collect();
+                let fields = Self::struct_field_pairs(input_struct, &output_fields)?
+                    .into_iter()
+                    .map(|(input_field, output_field)| {
+                        let data_type: Field = output_field.clone().into();
+                        Some(StructField {
+                            name: output_field.name,
+                            alias: None,
+                            data_type: Self::cast_type(
+                                &input_field.data_type,
+                                data_type.data_type(),
+                            )?,
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(TypeDef::StructDef(
+                    StructDef { name: None, fields },
+                    nullable,
+                ))
+            }
+            (TypeDef::DataType(DataType::List(_), _), DataType::List(output_field)) => {
+                let element =
+                    Self::cast_type(&input_type.list_element_type()?, output_field.data_type())?;
+                let element = StructField {
+                    name: output_field.name().clone(),
+                    alias: None,
+                    data_type: element,
+                };
+                Some(TypeDef::DataType(
+                    DataType::List(Arc::new(element.into())),
+                    nullable,
+                ))
+            }
+            (TypeDef::DataType(input_data_type, _), _)
+                if input_data_type == data_type
+                    || Self::allowed_types(input_data_type, data_type) =>
+            {
+                Some(TypeDef::DataType(data_type.clone(), nullable))
+            }
+            _ => None,
+        }
+    }
+
+    // struct fields are matched by name, or by position if the names differ.
+    fn struct_field_pairs(
+        input_struct: &StructDef,
+        output_fields: &[StructField],
+    ) -> Option<Vec<(StructField, StructField)>> {
+        let by_name = output_fields
+            .iter()
+            .map(|output_field| {
+                let input_field = input_struct
+                    .fields
+                    .iter()
+                    .find(|field| field.name == output_field.name)?;
+                Some((input_field.clone(), output_field.clone()))
+            })
+            .collect::<Option<Vec<_>>>();
+        if by_name.is_some() || input_struct.fields.len() != output_fields.len() {
+            return by_name;
+        }
+        Some(
+            input_struct
+                .fields
+                .iter()
+                .cloned()
+                .zip(output_fields.iter().cloned())
+                .collect(),
+        )
+    }
+
+    // converts sub_expr from input_type to output_type, which have the same nullability.
+    fn cast_value(input_type: &TypeDef, output_type: &TypeDef, sub_expr: syn::Expr) -> syn::Expr {
+        let cast_expr: syn::Expr = match (input_type, output_type) {
+            (TypeDef::StructDef(input_struct, _), TypeDef::StructDef(output_struct, _)) => {
+                let pairs = Self::struct_field_pairs(input_struct, &output_struct.fields).unwrap();
+                let output_type = output_struct.get_type();
+                let assignments = pairs.iter().map(|(input_field, output_field)| {
+                    let input_ident = input_field.field_ident();
+                    let output_ident = output_field.field_ident();
+                    let value = Self::cast_value(
+                        &input_field.data_type,
+                        &output_field.data_type,
+                        parse_quote!(x.#input_ident.clone()),
+                    );
+                    quote!(#output_ident: #value)
+                });
+                parse_quote!(#output_type { #(#assignments),* })
+            }
+            (TypeDef::DataType(DataType::List(_), _), TypeDef::DataType(DataType::List(_), _)) => {
+                let value = Self::cast_value(
+                    &input_type.list_element_type().unwrap(),
+                    &output_type.list_element_type().unwrap(),
+                    parse_quote!(x),
                 );
+                parse_quote!(x.into_iter().map(|x| #value).collect::<Vec<_>>())
             }
+            (TypeDef::DataType(input_data_type, _), TypeDef::DataType(output_data_type, _)) => {
+                if input_data_type == output_data_type {
+                    parse_quote!(x)
+                } else {
+                    Self::cast_expr(input_data_type, output_data_type, parse_quote!(x))
+                }
+            }
+            _ => unreachable!("invalid cast from {:?} to {:?}", input_type, output_type),
+        };
+        if input_type.is_optional() {
+            parse_quote!(#sub_expr.map(|x| #cast_expr))
         } else {
-            bail!("casting structs is currently unsupported")
+            parse_quote!({
+                let x = #sub_expr;
+                #cast_expr
+            })
         }
     }
 
@@ -1125,8 +1912,10 @@ impl CastExpression {
         // handle string to date casts.
         else if Self::is_string(input_data_type) && Self::is_date(output_data_type) {
             true
+        // handle parsing and serializing JSON.
         } else {
-            false
+            (Self::is_string(input_data_type) && *output_data_type == JSON_TYPE)
+                || (*input_data_type == JSON_TYPE && Self::is_string(output_data_type))
         }
     }
 
@@ -1152,7 +1941,7 @@ impl CastExpression {
     }
 
     fn is_string(data_type: &DataType) -> bool {
-        matches!(data_type, DataType::Utf8 | DataType::LargeUtf8)
+        matches!(data_type, DataType::Utf8)
     }
     fn cast_expr(input_type: &DataType, output_type: &DataType, sub_expr: syn::Expr) -> syn::Expr {
         if Self::is_numeric(input_type) && Self::is_numeric(output_type) {
@@ -1172,6 +1961,11 @@ impl CastExpression {
             })
         } else if Self::is_date(input_type) && Self::is_date(output_type) {
             parse_quote!(#sub_expr)
+        } else if Self::is_string(input_type) && *output_type == JSON_TYPE {
+            // text that isn't valid JSON becomes a JSON null
+            parse_quote!(arroyo_types::Json::parse(&#sub_expr).unwrap_or_default())
+        } else if *input_type == JSON_TYPE && Self::is_string(output_type) {
+            parse_quote!(#sub_expr.to_string())
         } else if Self::is_string(input_type) && Self::is_date(output_type) {
             parse_quote!({
                 let datetime = chrono::DateTime::parse_from_rfc3339(&#sub_expr).unwrap();
@@ -1184,21 +1978,15 @@ impl CastExpression {
     }
 
     fn to_syn_expression(&self) -> syn::Expr {
-        let sub_expr = self.input.to_syn_expression();
-        let TypeDef::DataType(input_type, nullable) = self.input.return_type() else {
-            unreachable!()
-        };
-        if nullable {
-            let cast_expr = Self::cast_expr(&input_type, &self.data_type, parse_quote!(x));
-            parse_quote!(#sub_expr.map(|x| #cast_expr))
-        } else {
-            let cast_expr = Self::cast_expr(&input_type, &self.data_type, sub_expr);
-            parse_quote!(#cast_expr)
-        }
+        Self::cast_value(
+            &self.input.return_type(),
+            &self.output_type,
+            self.input.to_syn_expression(),
+        )
     }
 
     fn return_type(&self) -> TypeDef {
-        TypeDef::DataType(self.data_type.clone(), self.input.nullable())
+        self.output_type.clone()
     }
 }
 
@@ -1228,6 +2016,11 @@ enum NumericFunction {
     Trunc,
     Log2,
     Exp,
+    Cbrt,
+    Degrees,
+    Radians,
+    Factorial,
+    ToHex,
 }
 
 impl NumericFunction {
@@ -1257,6 +2050,11 @@ impl NumericFunction {
             NumericFunction::Round => "round",
             NumericFunction::Trunc => "trunc",
             NumericFunction::Signum => "signum",
+            NumericFunction::Cbrt => "cbrt",
+            NumericFunction::Degrees => "to_degrees",
+            NumericFunction::Radians => "to_radians",
+            NumericFunction::Factorial => "factorial",
+            NumericFunction::ToHex => "to_hex",
         };
         format_ident!("{}", name)
     }
@@ -1291,6 +2089,11 @@ impl TryFrom<BuiltinScalarFunction> for NumericFunction {
             BuiltinScalarFunction::Trunc => Ok(Self::Trunc),
             BuiltinScalarFunction::Log2 => Ok(Self::Log2),
             BuiltinScalarFunction::Exp => Ok(Self::Exp),
+            BuiltinScalarFunction::Cbrt => Ok(Self::Cbrt),
+            BuiltinScalarFunction::Degrees => Ok(Self::Degrees),
+            BuiltinScalarFunction::Radians => Ok(Self::Radians),
+            BuiltinScalarFunction::Factorial => Ok(Self::Factorial),
+            BuiltinScalarFunction::ToHex => Ok(Self::ToHex),
             _ => bail!("{:?} is not a single argument numeric function", fun),
         }
     }
@@ -1305,20 +2108,62 @@ pub struct NumericExpression {
 impl NumericExpression {
     fn new(function: BuiltinScalarFunction, input: Box<Expression>) -> Result<Expression> {
         let function = function.try_into()?;
+        if matches!(
+            function,
+            NumericFunction::Factorial | NumericFunction::ToHex
+        ) {
+            let input_type = input.return_type();
+            if !input_type
+                .as_datatype()
+                .map(|t| is_integer(t) || *t == DataType::Null)
+                .unwrap_or_default()
+            {
+                bail!(
+                    "{} requires an integer argument, not {}",
+                    function.function_name(),
+                    type_name(&input_type)
+                );
+            }
+        }
         Ok(Expression::Numeric(NumericExpression { function, input }))
     }
     fn to_syn_expression(&self) -> syn::Expr {
         let function_name = self.function.function_name();
         let argument_expression = self.input.to_syn_expression();
-        if self.input.return_type().is_optional() {
-            parse_quote!(#argument_expression.map(|val| (val as f64).#function_name()))
-        } else {
-            parse_quote!((#argument_expression as f64).#function_name())
+        match self.function {
+            // these work on integers, and are implemented in the worker
+            NumericFunction::Factorial | NumericFunction::ToHex => {
+                let function: syn::Expr = parse_quote!(
+                    arroyo_worker::operators::functions::numeric::#function_name(val as i64)
+                );
+                match (self.input.nullable(), &self.function) {
+                    (true, NumericFunction::Factorial) => {
+                        parse_quote!(#argument_expression.and_then(|val| #function))
+                    }
+                    (true, _) => parse_quote!(#argument_expression.map(|val| #function)),
+                    (false, _) => parse_quote!({
+                        let val = #argument_expression;
+                        #function
+                    }),
+                }
+            }
+            _ => {
+                if self.input.return_type().is_optional() {
+                    parse_quote!(#argument_expression.map(|val| (val as f64).#function_name()))
+                } else {
+                    parse_quote!((#argument_expression as f64).#function_name())
+                }
+            }
         }
     }
 
     fn return_type(&self) -> TypeDef {
-        TypeDef::DataType(DataType::Float64, self.input.return_type().is_optional())
+        match self.function {
+            // factorial is null if it overflows
+            NumericFunction::Factorial => TypeDef::DataType(DataType::Int64, true),
+            NumericFunction::ToHex => TypeDef::DataType(DataType::Utf8, self.input.nullable()),
+            _ => TypeDef::DataType(DataType::Float64, self.input.return_type().is_optional()),
+        }
     }
 }
 
@@ -1330,7 +2175,7 @@ pub struct SortExpression {
 }
 
 impl SortExpression {
-    pub fn from_expression(ctx: &mut ExpressionContext, sort: &Sort) -> Result<Self> {
+    pub fn from_expression(ctx: &ExpressionContext, sort: &Sort) -> Result<Self> {
         let value = ctx.compile_expr(&sort.expr)?;
 
         let direction = if sort.asc {
@@ -1368,6 +2213,34 @@ impl SortExpression {
         }
     }
 
+    pub(crate) fn descending(&self) -> bool {
+        matches!(self.direction, SortDirection::Desc)
+    }
+
+    /// The type of the sort value itself, which unlike the tuple type can be stored in state.
+    pub(crate) fn value_type(&self) -> syn::Type {
+        self.value.return_type().return_type()
+    }
+
+    pub(crate) fn value_syn_expr(&self) -> syn::Expr {
+        self.value.to_syn_expression()
+    }
+
+    /// Compares two values of [`Self::value_type`], producing a `std::cmp::Ordering`.
+    pub(crate) fn comparison_syn_expr(&self, left: syn::Expr, right: syn::Expr) -> syn::Expr {
+        let descending = matches!(self.direction, SortDirection::Desc);
+        if self.value.nullable() {
+            let nulls_first = self.nulls_first;
+            parse_quote!(arroyo_worker::operators::aggregating_window::nullable_sort_key_cmp(
+                &#left, &#right, #descending, #nulls_first
+            ))
+        } else {
+            parse_quote!(arroyo_worker::operators::aggregating_window::sort_key_cmp(
+                &#left, &#right, #descending
+            ))
+        }
+    }
+
     pub fn sort_tuple_type(sort_expressions: &Vec<SortExpression>) -> syn::Type {
         match sort_expressions.len() {
             0 => parse_quote!(()),
@@ -1475,6 +2348,7 @@ pub enum StringFunction {
     Right(Box<Expression>, Box<Expression>),
     Rpad(Box<Expression>, Box<Expression>, Option<Box<Expression>>),
     Rtrim(Box<Expression>, Option<Box<Expression>>),
+    Uuid,
 }
 
 #[derive(Debug, Clone)]
@@ -1521,10 +2395,18 @@ pub struct HashExpression {
 
 impl HashExpression {
     pub fn new(function: BuiltinScalarFunction, input: Box<Expression>) -> Result<Expression> {
-        Ok(Expression::Hash(HashExpression {
-            function: function.try_into()?,
-            input,
-        }))
+        let function: HashFunction = function.try_into()?;
+        if !matches!(
+            input.return_type(),
+            TypeDef::DataType(DataType::Utf8 | DataType::Binary, _)
+        ) {
+            bail!(
+                "{} requires a string or binary argument, not {}",
+                function.to_string(),
+                type_name(&input.return_type())
+            );
+        }
+        Ok(Expression::Hash(HashExpression { function, input }))
     }
 
     fn to_syn_expression(&self) -> syn::Expr {
@@ -1572,6 +2454,7 @@ impl TryFrom<(BuiltinScalarFunction, Vec<Expression>)> for StringFunction {
             return Ok(StringFunction::ConcatWithSeparator(separator, args));
         }
         match (args.len(), func) {
+            (0, BuiltinScalarFunction::Uuid) => Ok(StringFunction::Uuid),
             (1, BuiltinScalarFunction::Ascii) => {
                 Ok(StringFunction::Ascii(Box::new(args.remove(0))))
             }
@@ -1779,6 +2662,7 @@ impl StringFunction {
             StringFunction::Strpos(expr1, expr2) => {
                 TypeDef::DataType(DataType::Int32, expr1.nullable() || expr2.nullable())
             }
+            StringFunction::Uuid => TypeDef::DataType(DataType::Utf8, false),
         }
     }
     fn non_null_function_invocation(&self) -> syn::Expr {
@@ -1903,6 +2787,9 @@ impl StringFunction {
             ),
             StringFunction::Concat(_) => parse_quote!(args.join("")),
             StringFunction::ConcatWithSeparator(_, _) => parse_quote!(args.join(arg)),
+            StringFunction::Uuid => {
+                parse_quote!(arroyo_worker::operators::functions::strings::uuid())
+            }
         }
     }
 
@@ -1914,6 +2801,7 @@ impl StringFunction {
             function
         };
         match self {
+            StringFunction::Uuid => function,
             // Single argument: arg
             StringFunction::Ascii(arg)
             | StringFunction::BitLength(arg)
@@ -2141,9 +3029,181 @@ pub enum DataStructureFunction {
         right: Box<Expression>,
     },
     MakeArray(Vec<Expression>),
+    Struct(Vec<Expression>),
+    // indices are 1-based, as in Postgres
+    ArrayIndex {
+        array: Box<Expression>,
+        index: Box<Expression>,
+    },
+    ArraySlice {
+        array: Box<Expression>,
+        from: Box<Expression>,
+        to: Box<Expression>,
+    },
+    Cardinality(Box<Expression>),
+    ArrayContains {
+        array: Box<Expression>,
+        element: Box<Expression>,
+    },
 }
 
 impl DataStructureFunction {
+    fn coalesce(terms: Vec<Expression>) -> Result<Expression> {
+        Self::check_same_types("coalesce", &terms)?;
+        Ok(Expression::DataStructure(Self::Coalesce(terms)))
+    }
+
+    fn null_if(left: Box<Expression>, right: Box<Expression>) -> Result<Expression> {
+        if !same_rust_type(&left.return_type(), &right.return_type()) {
+            bail!(
+                "nullif requires arguments of the same type, not {} and {}",
+                type_name(&left.return_type()),
+                type_name(&right.return_type())
+            );
+        }
+        Ok(Expression::DataStructure(Self::NullIf { left, right }))
+    }
+
+    // NULLs are compatible with every type, so each pair of terms has to be compared
+    fn check_same_types(function: &str, terms: &[Expression]) -> Result<()> {
+        let types: Vec<_> = terms.iter().map(|term| term.return_type()).collect();
+        for (i, left) in types.iter().enumerate() {
+            for right in &types[i + 1..] {
+                if !same_rust_type(left, right) {
+                    bail!(
+                        "{} requires arguments of the same type, not {} and {}",
+                        function,
+                        type_name(left),
+                        type_name(right)
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn array_index(array: Box<Expression>, index: Box<Expression>) -> Result<Expression> {
+        Self::element_type(&array)?;
+        Self::check_integer(&index)?;
+        Ok(Expression::DataStructure(Self::ArrayIndex { array, index }))
+    }
+
+    fn array_slice(
+        array: Box<Expression>,
+        from: Box<Expression>,
+        to: Box<Expression>,
+    ) -> Result<Expression> {
+        Self::element_type(&array)?;
+        Self::check_integer(&from)?;
+        Self::check_integer(&to)?;
+        Ok(Expression::DataStructure(Self::ArraySlice {
+            array,
+            from,
+            to,
+        }))
+    }
+
+    fn cardinality(array: Box<Expression>) -> Result<Expression> {
+        Self::element_type(&array)?;
+        Ok(Expression::DataStructure(Self::Cardinality(array)))
+    }
+
+    fn array_contains(array: Box<Expression>, element: Box<Expression>) -> Result<Expression> {
+        let element_type = Self::element_type(&array)?.with_nullity(false);
+        let element = if element.return_type().with_nullity(false) == element_type {
+            element
+        } else {
+            let TypeDef::DataType(data_type, _) = element_type else {
+                bail!(
+                    "can't search for {:?} in an array of structs",
+                    element.return_type()
+                );
+            };
+            Box::new(CastExpression::new(element, &data_type)?)
+        };
+        Ok(Expression::DataStructure(Self::ArrayContains {
+            array,
+            element,
+        }))
+    }
+
+    fn element_type(array: &Expression) -> Result<TypeDef> {
+        array
+            .return_type()
+            .list_element_type()
+            .ok_or_else(|| anyhow!("expected an array, found {:?}", array.return_type()))
+    }
+
+    fn check_integer(index: &Expression) -> Result<()> {
+        match index.return_type() {
+            TypeDef::DataType(
+                DataType::Int8
+                | DataType::Int16
+                | DataType::Int32
+                | DataType::Int64
+                | DataType::UInt8
+                | DataType::UInt16
+                | DataType::UInt32
+                | DataType::UInt64,
+                _,
+            ) => Ok(()),
+            other => bail!("array indices must be integers, found {:?}", other),
+        }
+    }
+
+    // binds each argument to its name, only evaluating body if none of them are null.
+    fn with_non_null_args(
+        args: &[(Ident, &Expression)],
+        body: syn::Expr,
+        body_nullable: bool,
+    ) -> syn::Expr {
+        let bindings = args.iter().map(|(name, arg)| {
+            let expr = arg.to_syn_expression();
+            quote!(let #name = #expr;)
+        });
+        if !args.iter().any(|(_, arg)| arg.nullable()) {
+            return parse_quote!({
+                #(#bindings)*
+                #body
+            });
+        }
+        let names = args.iter().map(|(name, _)| name);
+        let patterns = args.iter().map(|(name, arg)| {
+            if arg.nullable() {
+                quote!(Some(#name))
+            } else {
+                quote!(#name)
+            }
+        });
+        let body: syn::Expr = if body_nullable {
+            body
+        } else {
+            parse_quote!(Some(#body))
+        };
+        parse_quote!({
+            #(#bindings)*
+            match (#(#names,)*) {
+                (#(#patterns,)*) => #body,
+                _ => None,
+            }
+        })
+    }
+
+    fn struct_def(terms: &[Expression]) -> StructDef {
+        StructDef {
+            name: None,
+            fields: terms
+                .iter()
+                .enumerate()
+                .map(|(i, term)| StructField {
+                    name: format!("c{}", i),
+                    alias: None,
+                    data_type: term.return_type(),
+                })
+                .collect(),
+        }
+    }
+
     fn to_syn_expression(&self) -> syn::Expr {
         match self {
             DataStructureFunction::Coalesce(terms) => {
@@ -2243,6 +3303,70 @@ impl DataStructureFunction {
                     }
                 }
             }
+            DataStructureFunction::Struct(terms) => {
+                let struct_def = Self::struct_def(terms);
+                let struct_type = struct_def.get_type();
+                let assignments = struct_def.fields.iter().zip(terms).map(|(field, term)| {
+                    let ident = field.field_ident();
+                    let expr = term.to_syn_expression();
+                    quote!(#ident: #expr)
+                });
+                parse_quote!(#struct_type { #(#assignments),* })
+            }
+            DataStructureFunction::ArrayIndex { array, index } => {
+                let element_nullable = Self::element_type(array).unwrap().is_optional();
+                let body: syn::Expr = if element_nullable {
+                    parse_quote!(usize::try_from(index as i64 - 1)
+                        .ok()
+                        .and_then(|i| array.get(i).cloned())
+                        .flatten())
+                } else {
+                    parse_quote!(usize::try_from(index as i64 - 1)
+                        .ok()
+                        .and_then(|i| array.get(i).cloned()))
+                };
+                Self::with_non_null_args(
+                    &[
+                        (format_ident!("array"), array.as_ref()),
+                        (format_ident!("index"), index.as_ref()),
+                    ],
+                    body,
+                    true,
+                )
+            }
+            DataStructureFunction::ArraySlice { array, from, to } => Self::with_non_null_args(
+                &[
+                    (format_ident!("array"), array.as_ref()),
+                    (format_ident!("from"), from.as_ref()),
+                    (format_ident!("to"), to.as_ref()),
+                ],
+                parse_quote!({
+                    let to = (to as i64).clamp(0, array.len() as i64) as usize;
+                    let from = ((from as i64).max(1) as usize).min(to + 1);
+                    array[from - 1..to].to_vec()
+                }),
+                false,
+            ),
+            DataStructureFunction::Cardinality(array) => Self::with_non_null_args(
+                &[(format_ident!("array"), array.as_ref())],
+                parse_quote!(array.len() as u64),
+                false,
+            ),
+            DataStructureFunction::ArrayContains { array, element } => {
+                let body: syn::Expr = if Self::element_type(array).unwrap().is_optional() {
+                    parse_quote!(array.contains(&Some(element)))
+                } else {
+                    parse_quote!(array.contains(&element))
+                };
+                Self::with_non_null_args(
+                    &[
+                        (format_ident!("array"), array.as_ref()),
+                        (format_ident!("element"), element.as_ref()),
+                    ],
+                    body,
+                    false,
+                )
+            }
         }
     }
     fn return_type(&self) -> TypeDef {
@@ -2253,14 +3377,28 @@ impl DataStructureFunction {
             }
             DataStructureFunction::NullIf { left, right: _ } => left.return_type().as_nullable(),
             DataStructureFunction::MakeArray(terms) => {
-                let TypeDef::DataType(primitive_type, _ ) = terms[0].return_type() else {
-                    unreachable!("make_array should only be called on a primitive type")
-                };
                 let nullable = terms.iter().any(|term| term.nullable());
-                TypeDef::DataType(
-                    DataType::List(Arc::new(Field::new("items", primitive_type, nullable))),
-                    false,
-                )
+                let element = StructField {
+                    name: "items".to_string(),
+                    alias: None,
+                    data_type: terms[0].return_type().with_nullity(nullable),
+                };
+                TypeDef::DataType(DataType::List(Arc::new(element.into())), false)
+            }
+            DataStructureFunction::Struct(terms) => {
+                TypeDef::StructDef(Self::struct_def(terms), false)
+            }
+            DataStructureFunction::ArrayIndex { array, index: _ } => {
+                Self::element_type(array).unwrap().as_nullable()
+            }
+            DataStructureFunction::ArraySlice { array, from, to } => array
+                .return_type()
+                .with_nullity(array.nullable() || from.nullable() || to.nullable()),
+            DataStructureFunction::Cardinality(array) => {
+                TypeDef::DataType(DataType::UInt64, array.nullable())
+            }
+            DataStructureFunction::ArrayContains { array, element } => {
+                TypeDef::DataType(DataType::Boolean, array.nullable() || element.nullable())
             }
         }
     }
@@ -2271,6 +3409,12 @@ enum JsonFunction {
     GetFirstJsonObject,
     GetJsonObjects,
     ExtractJsonString,
+    // these take a JSON value rather than a string
+    JsonGet,
+    JsonGetText,
+    JsonExtractInt,
+    JsonExtractFloat,
+    JsonExtractBool,
 }
 
 #[derive(Debug, Clone)]
@@ -2290,6 +3434,11 @@ impl JsonExpression {
             JsonFunction::GetFirstJsonObject => quote!(get_first_json_object),
             JsonFunction::GetJsonObjects => quote!(get_json_objects),
             JsonFunction::ExtractJsonString => quote!(extract_json_string),
+            JsonFunction::JsonGet => quote!(json_get),
+            JsonFunction::JsonGetText => quote!(json_get_text),
+            JsonFunction::JsonExtractInt => quote!(json_extract_int),
+            JsonFunction::JsonExtractFloat => quote!(json_extract_float),
+            JsonFunction::JsonExtractBool => quote!(json_extract_bool),
         };
         // Handle different nullabilities.
         match (path_nullable, json_nullable) {
@@ -2332,6 +3481,88 @@ impl JsonExpression {
                 true,
             ),
             JsonFunction::ExtractJsonString => TypeDef::DataType(DataType::Utf8, true),
+            JsonFunction::JsonGet => TypeDef::DataType(JSON_TYPE, true),
+            JsonFunction::JsonGetText => TypeDef::DataType(DataType::Utf8, true),
+            JsonFunction::JsonExtractInt => TypeDef::DataType(DataType::Int64, true),
+            JsonFunction::JsonExtractFloat => TypeDef::DataType(DataType::Float64, true),
+            JsonFunction::JsonExtractBool => TypeDef::DataType(DataType::Boolean, true),
+        }
+    }
+}
+
+/// Functions producing or inspecting JSON values, other than looking up their contents.
+#[derive(Debug, Clone)]
+pub enum JsonValueFunction {
+    ArrayLength(Box<Expression>),
+    ObjectKeys(Box<Expression>),
+    ToJson(Box<Expression>),
+    BuildObject(Vec<(Expression, Expression)>),
+}
+
+impl JsonValueFunction {
+    fn build_object(args: Vec<Expression>) -> Result<Expression> {
+        if args.len() % 2 != 0 {
+            bail!("json_build_object requires an even number of arguments, alternating keys and values");
+        }
+        let mut args = args.into_iter();
+        let mut fields = vec![];
+        while let (Some(key), Some(value)) = (args.next(), args.next()) {
+            if key.return_type() != TypeDef::DataType(DataType::Utf8, false) {
+                bail!(
+                    "json_build_object keys must be non-null strings, not {}",
+                    type_name(&key.return_type())
+                );
+            }
+            fields.push((key, value));
+        }
+        Ok(Expression::JsonValue(JsonValueFunction::BuildObject(
+            fields,
+        )))
+    }
+
+    fn to_syn_expression(&self) -> syn::Expr {
+        match self {
+            JsonValueFunction::ArrayLength(json) | JsonValueFunction::ObjectKeys(json) => {
+                let function = match self {
+                    JsonValueFunction::ArrayLength(_) => quote!(json_array_length),
+                    _ => quote!(json_object_keys),
+                };
+                let json_expr = json.to_syn_expression();
+                if json.nullable() {
+                    parse_quote!(#json_expr.and_then(|json| arroyo_worker::operators::functions::json::#function(json)))
+                } else {
+                    parse_quote!(arroyo_worker::operators::functions::json::#function(#json_expr))
+                }
+            }
+            JsonValueFunction::ToJson(value) => {
+                let value_expr = value.to_syn_expression();
+                parse_quote!(arroyo_worker::operators::functions::json::to_json(&#value_expr))
+            }
+            JsonValueFunction::BuildObject(fields) => {
+                let fields = fields.iter().map(|(key, value)| {
+                    let key_expr = key.to_syn_expression();
+                    let value_expr = value.to_syn_expression();
+                    quote!((#key_expr, arroyo_worker::operators::functions::json::to_json(&#value_expr)))
+                });
+                parse_quote!(
+                    arroyo_worker::operators::functions::json::json_build_object(
+                        vec![#(#fields),*]
+                    )
+                )
+            }
+        }
+    }
+
+    fn return_type(&self) -> TypeDef {
+        match self {
+            JsonValueFunction::ArrayLength(_) => TypeDef::DataType(DataType::Int64, true),
+            JsonValueFunction::ObjectKeys(_) => TypeDef::DataType(
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, false))),
+                true,
+            ),
+            JsonValueFunction::ToJson(_) | JsonValueFunction::BuildObject(_) => {
+                TypeDef::DataType(JSON_TYPE, false)
+            }
         }
     }
 }
@@ -2341,6 +3572,7 @@ pub struct RustUdfExpression {
     name: String,
     args: Vec<(TypeDef, Expression)>,
     ret_type: TypeDef,
+    is_async: bool,
 }
 
 impl RustUdfExpression {
@@ -2364,18 +3596,31 @@ impl RustUdfExpression {
             .unzip();
 
         let mut ret = quote!(udfs::#name(#(#args, )*));
+        if self.is_async {
+            ret = quote!(#ret.await);
+        }
 
         if self.return_type().is_optional() && !self.ret_type.is_optional() {
             // we have to wrap the result in Some
             ret = quote! { Some(#ret) }
         };
 
-        parse_quote!({
-            (|| {
-                #(#defs; )*
-                #ret
-            })()
-        })
+        if self.is_async {
+            // evaluated inside the future of an async map operator
+            parse_quote!({
+                async {
+                    #(#defs; )*
+                    #ret
+                }.await
+            })
+        } else {
+            parse_quote!({
+                (|| {
+                    #(#defs; )*
+                    #ret
+                })()
+            })
+        }
     }
 
     fn return_type(&self) -> TypeDef {
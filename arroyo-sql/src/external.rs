@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Result;
 use arroyo_datastream::auth_config_to_hashmap;
+use arroyo_datastream::LookupConnector;
 use arroyo_datastream::Operator;
+use arroyo_datastream::PartitionWatermarks;
 use arroyo_datastream::SerializationMode;
 use arroyo_datastream::SinkConfig;
 use arroyo_datastream::SourceConfig;
@@ -15,7 +17,7 @@ use arroyo_rpc::grpc::api::connection::ConnectionType;
 use arroyo_rpc::grpc::api::Connection;
 use arroyo_types::string_to_map;
 
-use crate::types::StructDef;
+use crate::types::{StructDef, StructField};
 use crate::SqlConfig;
 
 #[derive(Clone, Debug)]
@@ -24,6 +26,7 @@ pub struct SqlSource {
     pub struct_def: StructDef,
     pub source_config: SourceConfig,
     pub serialization_mode: SerializationMode,
+    pub partition_watermarks: Option<PartitionWatermarks>,
 }
 
 impl SqlSource {
@@ -40,6 +43,7 @@ impl SqlSource {
                 kafka_input_format: self.serialization_mode,
                 messages_per_second: sql_config.kafka_qps.unwrap_or(10_000),
                 client_configs,
+                partition_watermarks: self.partition_watermarks,
             },
             SourceConfig::Impulse {
                 interval,
@@ -103,6 +107,7 @@ impl SqlSource {
                         client_configs: auth_config_to_hashmap(kafka.auth_config),
                     },
                     serialization_mode,
+                    partition_watermarks: None,
                 })
             }
             ConnectionType::Kinesis(_) => {
@@ -130,7 +135,8 @@ impl SqlSource {
                                 header/value pairs, like `Content-Type: applicaiton/json,User-Agent:arroyo`"))?,
                         events,
                     },
-                    serialization_mode
+                    serialization_mode,
+                    partition_watermarks: None,
                 })
             }
         }
@@ -170,6 +176,90 @@ impl SqlSink {
     }
 }
 
+/// A table whose rows are fetched by primary key from an external system as they are joined.
+#[derive(Clone, Debug)]
+pub struct SqlLookupSource {
+    pub struct_def: StructDef,
+    pub key_field: StructField,
+    pub connector: LookupConnector,
+    pub cache_capacity: usize,
+    pub cache_ttl: Duration,
+    /// The most lookups each subtask runs at once.
+    pub max_concurrency: usize,
+}
+
+impl SqlLookupSource {
+    pub fn try_new(
+        table_name: &str,
+        struct_def: StructDef,
+        key_field: StructField,
+        connection_config: &HashMap<String, String>,
+        cache_ttl: Duration,
+    ) -> Result<Self> {
+        let get = |option: &str| {
+            connection_config
+                .get(option)
+                .cloned()
+                .ok_or_else(|| anyhow!("Missing {}", option))
+        };
+        let connector = match get("connector")?.as_str() {
+            "postgres" => LookupConnector::Postgres {
+                connection_string: get("connection_string")?,
+                table: connection_config
+                    .get("table")
+                    .cloned()
+                    .unwrap_or_else(|| table_name.to_string()),
+                key_column: key_field.name.clone(),
+            },
+            "http" => {
+                let url = get("url")?;
+                if !url.contains("{key}") {
+                    bail!("url of an http lookup table must contain {{key}}");
+                }
+                let headers = match connection_config.get("headers") {
+                    Some(headers) => string_to_map(headers).ok_or_else(|| {
+                        anyhow!("Headers are invalid, expected a comma-delimited set of header/value pairs, like `Content-Type: application/json,User-Agent:arroyo`")
+                    })?,
+                    None => HashMap::new(),
+                };
+                LookupConnector::Http { url, headers }
+            }
+            "redis" => LookupConnector::Redis {
+                address: get("address")?,
+                key_prefix: connection_config
+                    .get("key_prefix")
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+            connector => bail!(
+                "unknown lookup connector '{}', expected postgres, http or redis",
+                connector
+            ),
+        };
+        let cache_capacity = match connection_config.get("cache_capacity") {
+            Some(capacity) => capacity
+                .parse()
+                .map_err(|_| anyhow!("cache_capacity must be a non-negative integer"))?,
+            None => 10_000,
+        };
+        let max_concurrency = match connection_config.get("max_concurrency") {
+            Some(max_concurrency) => match max_concurrency.parse() {
+                Ok(max_concurrency) if max_concurrency > 0 => max_concurrency,
+                _ => bail!("max_concurrency must be a positive integer"),
+            },
+            None => 16,
+        };
+        Ok(SqlLookupSource {
+            struct_def,
+            key_field,
+            connector,
+            cache_capacity,
+            cache_ttl,
+            max_concurrency,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SqlTable {
     pub struct_def: StructDef,
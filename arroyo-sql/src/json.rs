@@ -0,0 +1,201 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, DataType as SQLDataType, Expr as SqlExpr, Function, FunctionArg,
+    FunctionArgExpr, Ident, JsonOperator, ObjectName, Statement, Value,
+};
+use datafusion_expr::{ScalarUDF, Signature, Volatility};
+
+use crate::placeholder_udf;
+use crate::types::JSON_TYPE;
+
+// DataFusion can't plan the JSON operators or casts to JSON, so `a -> b` and `a ->> b` are
+// rewritten to `json_get(a, b)` and `json_get_text(a, b)`, and `CAST(a AS JSON)` to
+// `parse_json(a)`.
+pub(crate) fn rewrite_json_syntax(statement: &mut Statement) {
+    let _ = visit_expressions_mut(statement, |expr| {
+        if let SqlExpr::JsonAccess { .. } = expr {
+            let SqlExpr::JsonAccess {
+                left,
+                operator,
+                right,
+            } = std::mem::replace(expr, SqlExpr::Value(Value::Null))
+            else {
+                unreachable!()
+            };
+            *expr = reassociate(left, operator, *right);
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    let _ = visit_expressions_mut(statement, |expr| {
+        match expr {
+            SqlExpr::JsonAccess {
+                left,
+                operator: operator @ (JsonOperator::Arrow | JsonOperator::LongArrow),
+                right,
+            } => {
+                let name = match operator {
+                    JsonOperator::Arrow => "json_get",
+                    _ => "json_get_text",
+                };
+                *expr = function_call(name, vec![(**left).clone(), (**right).clone()]);
+            }
+            SqlExpr::Cast {
+                expr: inner,
+                data_type,
+            } if is_json_type(data_type) => {
+                *expr = function_call("parse_json", vec![(**inner).clone()]);
+            }
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+}
+
+// The parser reads everything after a JSON operator as its right operand, so
+// `a -> 'b' ->> 'c' = 'd'` comes out as `a -> ('b' ->> ('c' = 'd'))`. As the JSON operators bind
+// tightest, their right operand should only be the leftmost operand of that expression.
+fn reassociate(left: Box<SqlExpr>, operator: JsonOperator, right: SqlExpr) -> SqlExpr {
+    let apply = |operand: Box<SqlExpr>| Box::new(reassociate(left, operator, *operand));
+    match right {
+        SqlExpr::JsonAccess {
+            left: operand,
+            operator: right_operator,
+            right,
+        } => SqlExpr::JsonAccess {
+            left: apply(operand),
+            operator: right_operator,
+            right,
+        },
+        SqlExpr::BinaryOp {
+            left: operand,
+            op,
+            right,
+        } => SqlExpr::BinaryOp {
+            left: apply(operand),
+            op,
+            right,
+        },
+        SqlExpr::IsNull(operand) => SqlExpr::IsNull(apply(operand)),
+        SqlExpr::IsNotNull(operand) => SqlExpr::IsNotNull(apply(operand)),
+        SqlExpr::Like {
+            negated,
+            expr: operand,
+            pattern,
+            escape_char,
+        } => SqlExpr::Like {
+            negated,
+            expr: apply(operand),
+            pattern,
+            escape_char,
+        },
+        SqlExpr::ILike {
+            negated,
+            expr: operand,
+            pattern,
+            escape_char,
+        } => SqlExpr::ILike {
+            negated,
+            expr: apply(operand),
+            pattern,
+            escape_char,
+        },
+        SqlExpr::InList {
+            expr: operand,
+            list,
+            negated,
+        } => SqlExpr::InList {
+            expr: apply(operand),
+            list,
+            negated,
+        },
+        SqlExpr::Between {
+            expr: operand,
+            negated,
+            low,
+            high,
+        } => SqlExpr::Between {
+            expr: apply(operand),
+            negated,
+            low,
+            high,
+        },
+        right => SqlExpr::JsonAccess {
+            left,
+            operator,
+            right: Box::new(right),
+        },
+    }
+}
+
+fn is_json_type(data_type: &SQLDataType) -> bool {
+    match data_type {
+        SQLDataType::JSON => true,
+        SQLDataType::Custom(name, modifiers) => {
+            modifiers.is_empty() && name.to_string().eq_ignore_ascii_case("jsonb")
+        }
+        _ => false,
+    }
+}
+
+fn function_call(name: &str, args: Vec<SqlExpr>) -> SqlExpr {
+    SqlExpr::Function(Function {
+        name: ObjectName(vec![Ident::new(name)]),
+        args: args
+            .into_iter()
+            .map(|arg| FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)))
+            .collect(),
+        over: None,
+        distinct: false,
+        special: false,
+    })
+}
+
+/// The signatures of the functions on JSON values, which are compiled by `ExpressionContext`.
+pub(crate) fn function_meta(name: &str) -> Option<Arc<ScalarUDF>> {
+    let (signature, return_type) = match name {
+        "parse_json" => (
+            Signature::exact(vec![DataType::Utf8], Volatility::Volatile),
+            JSON_TYPE,
+        ),
+        "json_get" => (
+            Signature::exact(vec![JSON_TYPE, DataType::Utf8], Volatility::Volatile),
+            JSON_TYPE,
+        ),
+        "json_get_text" => (
+            Signature::exact(vec![JSON_TYPE, DataType::Utf8], Volatility::Volatile),
+            DataType::Utf8,
+        ),
+        "json_extract_int" => (
+            Signature::exact(vec![JSON_TYPE, DataType::Utf8], Volatility::Volatile),
+            DataType::Int64,
+        ),
+        "json_extract_float" => (
+            Signature::exact(vec![JSON_TYPE, DataType::Utf8], Volatility::Volatile),
+            DataType::Float64,
+        ),
+        "json_extract_bool" => (
+            Signature::exact(vec![JSON_TYPE, DataType::Utf8], Volatility::Volatile),
+            DataType::Boolean,
+        ),
+        "json_array_length" => (
+            Signature::exact(vec![JSON_TYPE], Volatility::Volatile),
+            DataType::Int64,
+        ),
+        "json_object_keys" => (
+            Signature::exact(vec![JSON_TYPE], Volatility::Volatile),
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, false))),
+        ),
+        "to_json" => (Signature::any(1, Volatility::Volatile), JSON_TYPE),
+        "json_build_object" => (Signature::variadic_any(Volatility::Volatile), JSON_TYPE),
+        _ => return None,
+    };
+    let return_type = Arc::new(return_type);
+    Some(placeholder_udf(
+        name,
+        signature,
+        Arc::new(move |_| Ok(return_type.clone())),
+    ))
+}
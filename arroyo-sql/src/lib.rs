@@ -3,52 +3,74 @@ use anyhow::{anyhow, bail, Result};
 use arrow::array::ArrayRef;
 use arrow::datatypes::{self, DataType, Field};
 use arrow_schema::TimeUnit;
-use arroyo_datastream::{Operator, Program, SerializationMode, SinkConfig, SourceConfig};
-use arroyo_rpc::grpc::api::Connection;
+use arroyo_datastream::{
+    EarlyTrigger, Operator, Program, SerializationMode, SinkConfig, SourceConfig,
+};
+use arroyo_rpc::grpc::api::{connection::ConnectionType, Connection};
 use datafusion::optimizer::analyzer::Analyzer;
 use datafusion::optimizer::optimizer::Optimizer;
 use datafusion::optimizer::OptimizerContext;
 use datafusion::physical_plan::functions::make_scalar_function;
 
+pub mod errors;
 mod expressions;
 pub mod external;
+mod json;
+mod lookup;
 mod operators;
 mod optimizations;
 mod pipeline;
 mod plan_graph;
 pub mod schemas;
 pub mod types;
+mod unnest;
+mod watermark;
 
 use datafusion::prelude::create_udf;
 
 use datafusion::sql::planner::{PlannerContext, SqlToRel};
-use datafusion::sql::sqlparser::ast::{ColumnOption, Statement, Value};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, BinaryOperator, ColumnOption, Expr as SqlExpr, ObjectName, Statement,
+    Value,
+};
 use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
 use datafusion::sql::sqlparser::parser::Parser;
+use datafusion::sql::sqlparser::tokenizer::{Token, TokenWithLocation, Tokenizer};
 use datafusion::sql::{planner::ContextProvider, TableReference};
 use datafusion_common::config::ConfigOptions;
-use datafusion_common::{DFField, DFSchema};
+use datafusion_common::tree_node::{TreeNode, VisitRecursion};
+use datafusion_common::{DFField, DFSchema, DataFusionError};
 
 use datafusion_expr::{
-    logical_plan::builder::LogicalTableSource, AggregateUDF, ScalarUDF, TableSource,
+    logical_plan::builder::LogicalTableSource, AggregateUDF, BinaryExpr, Expr, ScalarUDF,
+    TableSource,
 };
 use datafusion_expr::{
     AccumulatorFunctionImplementation, CreateMemoryTable, CreateView, DdlStatement, DmlStatement,
-    LogicalPlan, ReturnTypeFunction, Signature, StateTypeFunction, TypeSignature, Volatility,
-    WriteOp,
+    LogicalPlan, ReturnTypeFunction, ScalarFunctionImplementation, Signature, StateTypeFunction,
+    TypeSignature, Volatility, WriteOp,
 };
 use expressions::Expression;
-use external::SqlSink;
-use pipeline::{SqlOperator, SqlPipelineBuilder};
+use external::{SqlLookupSource, SqlSink};
+use pipeline::{SourceWatermark, SqlOperator, SqlPipelineBuilder};
 use plan_graph::{get_program, PlanGraph};
 use schemas::window_arrow_struct;
+use watermark::WatermarkClause;
 
+use crate::errors::SqlError;
 use crate::expressions::ExpressionContext;
 use crate::types::{convert_data_type, StructDef, StructField, TypeDef};
 use quote::ToTokens;
-use std::time::SystemTime;
-use std::{collections::HashMap, sync::Arc};
-use syn::{parse_quote, parse_str, FnArg, Item, ReturnType, VisPublic, Visibility};
+use std::ops::ControlFlow;
+use std::time::{Duration, SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use syn::{
+    parse_quote, parse_str, Attribute, FnArg, Item, ItemMod, Lit, Meta, NestedMeta, ReturnType,
+    VisPublic, Visibility,
+};
 
 #[cfg(test)]
 mod test;
@@ -58,6 +80,47 @@ pub struct UdfDef {
     args: Vec<TypeDef>,
     ret: TypeDef,
     def: String,
+    async_options: Option<AsyncUdfOptions>,
+}
+
+/// How calls to an async UDF are run, set with a `#[udf(unordered, max_concurrency = 10)]`
+/// attribute on its definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AsyncUdfOptions {
+    pub ordered: bool,
+    pub max_concurrency: usize,
+}
+
+impl Default for AsyncUdfOptions {
+    fn default() -> Self {
+        Self {
+            ordered: true,
+            max_concurrency: 100,
+        }
+    }
+}
+
+// The crates the pipeline crate already depends on, which UDFs can't declare again. These must
+// be kept in sync with the pipeline Cargo.toml in arroyo-controller's compiler.
+const PIPELINE_DEPENDENCIES: &[&str] = &[
+    "types",
+    "petgraph",
+    "chrono",
+    "bincode",
+    "bincode_derive",
+    "serde",
+    "serde_json",
+    "arroyo-types",
+    "arroyo-worker",
+];
+
+/// A Rust UDAF, defined as a module containing `init`, `accumulate`, `merge` and `finish`
+/// functions. The accumulator type is exposed as `udfs::<name>::Accumulator`.
+#[derive(Clone, Debug)]
+pub struct UdafDef {
+    arg: TypeDef,
+    ret: TypeDef,
+    def: String,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -65,8 +128,14 @@ pub struct ArroyoSchemaProvider {
     pub source_defs: HashMap<String, String>,
     tables: HashMap<String, Table>,
     pub functions: HashMap<String, Arc<ScalarUDF>>,
+    pub aggregate_functions: HashMap<String, Arc<AggregateUDF>>,
     pub connections: HashMap<String, Connection>,
     pub udf_defs: HashMap<String, UdfDef>,
+    pub udaf_defs: HashMap<String, UdafDef>,
+    /// Crates the UDFs depend on, with their Cargo dependency specs.
+    pub udf_dependencies: BTreeMap<String, String>,
+    /// CREATE VIEW and CREATE TABLE statements saved outside of the query, planned before it.
+    pub saved_definitions: Vec<String>,
     config_options: datafusion::config::ConfigOptions,
 }
 
@@ -139,9 +208,13 @@ impl ArroyoSchemaProvider {
         Self {
             tables,
             functions,
+            aggregate_functions: HashMap::new(),
             source_defs: HashMap::new(),
             connections: HashMap::new(),
             udf_defs: HashMap::new(),
+            udaf_defs: HashMap::new(),
+            udf_dependencies: BTreeMap::new(),
+            saved_definitions: Vec::new(),
             config_options: datafusion::config::ConfigOptions::new(),
         }
     }
@@ -172,6 +245,10 @@ impl ArroyoSchemaProvider {
         );
     }
 
+    pub fn add_saved_definition(&mut self, definition: impl Into<String>) {
+        self.saved_definitions.push(definition.into());
+    }
+
     fn insert_table(&mut self, table: Table) {
         if let Some(name) = table.name() {
             self.tables.insert(name, table);
@@ -186,11 +263,36 @@ impl ArroyoSchemaProvider {
     }
 
     pub fn add_rust_udf(&mut self, body: &str) -> Result<()> {
+        let dependencies = parse_udf_dependencies(body)?;
+        for (name, spec) in &dependencies {
+            if PIPELINE_DEPENDENCIES.contains(&name.as_str()) {
+                bail!(
+                    "UDFs can't declare a dependency on {}, as pipelines already depend on it",
+                    name
+                );
+            }
+            if let Some(existing) = self.udf_dependencies.get(name) {
+                if existing != spec {
+                    bail!(
+                        "UDFs depend on conflicting versions of {}: {} and {}",
+                        name,
+                        existing,
+                        spec
+                    );
+                }
+            }
+        }
+
         let file = syn::parse_file(body)?;
 
         for item in file.items {
-            let Item::Fn(mut function) = item else {
-                bail!("not a function");
+            let mut function = match item {
+                Item::Fn(function) => function,
+                Item::Mod(module) => {
+                    self.add_rust_udaf(module)?;
+                    continue;
+                }
+                _ => bail!("not a function or an aggregate function module"),
             };
 
             let mut args: Vec<TypeDef> = vec![];
@@ -212,6 +314,8 @@ impl ArroyoSchemaProvider {
                     .map_err(|_| anyhow!("Could not convert return type into a SQL data type"))?,
             };
 
+            let async_options = Self::async_udf_options(&function.sig, &mut function.attrs)?;
+
             let fn_impl = |args: &[ArrayRef]| Ok(Arc::new(args[0].clone()) as ArrayRef);
 
             if self
@@ -244,20 +348,254 @@ impl ArroyoSchemaProvider {
                     args,
                     ret,
                     def: function.to_token_stream().to_string(),
+                    async_options,
                 },
             );
         }
 
+        self.udf_dependencies.extend(dependencies);
+        Ok(())
+    }
+
+    // Async UDFs may be configured with a `#[udf(...)]` attribute, which is removed from the
+    // definition.
+    fn async_udf_options(
+        sig: &syn::Signature,
+        attrs: &mut Vec<Attribute>,
+    ) -> Result<Option<AsyncUdfOptions>> {
+        let mut options = AsyncUdfOptions::default();
+        let mut configured = false;
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("udf")) {
+            configured = true;
+            let Meta::List(list) = attr.parse_meta()? else {
+                bail!("expected #[udf(...)] on UDF {}", sig.ident);
+            };
+            for option in list.nested {
+                match option {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("ordered") => {
+                        options.ordered = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unordered") => {
+                        options.ordered = false;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(name_value))
+                        if name_value.path.is_ident("max_concurrency") =>
+                    {
+                        let Lit::Int(max_concurrency) = &name_value.lit else {
+                            bail!("max_concurrency for UDF {} must be an integer", sig.ident);
+                        };
+                        options.max_concurrency = max_concurrency.base10_parse()?;
+                        if options.max_concurrency == 0 {
+                            bail!("max_concurrency for UDF {} must be positive", sig.ident);
+                        }
+                    }
+                    option => bail!(
+                        "unknown option '{}' for UDF {}",
+                        option.to_token_stream(),
+                        sig.ident
+                    ),
+                }
+            }
+        }
+        attrs.retain(|attr| !attr.path.is_ident("udf"));
+
+        if sig.asyncness.is_some() {
+            Ok(Some(options))
+        } else if configured {
+            bail!(
+                "#[udf(...)] options only apply to async UDFs, but {} isn't async",
+                sig.ident
+            )
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn add_rust_udaf(&mut self, mut module: ItemMod) -> Result<()> {
+        let name = module.ident.to_string();
+        let Some((_, items)) = &mut module.content else {
+            bail!("UDAF module '{}' must have a body", name);
+        };
+
+        let mut functions = HashMap::new();
+        let mut has_accumulator = false;
+        for item in items.iter_mut() {
+            match item {
+                Item::Fn(function) => {
+                    if function.sig.asyncness.is_some() {
+                        bail!("the functions of UDAF '{}' can't be async", name);
+                    }
+                    function.vis = Visibility::Public(VisPublic {
+                        pub_token: Default::default(),
+                    });
+                    functions.insert(function.sig.ident.to_string(), function.sig.clone());
+                }
+                Item::Type(t) if t.ident == "Accumulator" => has_accumulator = true,
+                Item::Struct(t) if t.ident == "Accumulator" => has_accumulator = true,
+                _ => {}
+            }
+        }
+
+        let get_fn = |fn_name: &str| {
+            functions.get(fn_name).ok_or_else(|| {
+                anyhow!(
+                    "UDAF '{}' is missing the function '{}'; UDAFs must define init, accumulate, merge and finish",
+                    name,
+                    fn_name
+                )
+            })
+        };
+
+        let ReturnType::Type(_, accumulator) = &get_fn("init")?.output else {
+            bail!("init in UDAF '{}' must return the accumulator", name);
+        };
+        let accumulator = (**accumulator).clone();
+
+        let accumulate = get_fn("accumulate")?;
+        if accumulate.inputs.len() != 2 {
+            bail!(
+                "accumulate in UDAF '{}' must take the accumulator and a single argument",
+                name
+            );
+        }
+        let arg: TypeDef = match accumulate.inputs.last().unwrap() {
+            FnArg::Receiver(_) => bail!("self types are not allowed in UDAFs"),
+            FnArg::Typed(t) => (&*t.ty).try_into().map_err(|_| {
+                anyhow!(
+                    "Could not convert argument of UDAF '{}' into a SQL data type",
+                    name
+                )
+            })?,
+        };
+
+        if get_fn("merge")?.inputs.len() != 2 {
+            bail!("merge in UDAF '{}' must take two accumulators", name);
+        }
+
+        let ret: TypeDef = match &get_fn("finish")?.output {
+            ReturnType::Default => bail!("finish in UDAF '{}' must specify a return type", name),
+            ReturnType::Type(_, t) => (&**t).try_into().map_err(|_| {
+                anyhow!(
+                    "Could not convert return type of UDAF '{}' into a SQL data type",
+                    name
+                )
+            })?,
+        };
+
+        if !has_accumulator {
+            items.push(parse_quote!(pub type Accumulator = #accumulator;));
+        }
+
+        let return_data_type = Arc::new(ret.as_datatype().unwrap().clone());
+        let udaf = placeholder_aggregate(
+            &name,
+            Signature::exact(
+                vec![arg.as_datatype().unwrap().clone()],
+                Volatility::Immutable,
+            ),
+            Arc::new(move |_| Ok(return_data_type.clone())),
+        );
+
+        if self.functions.contains_key(&name)
+            || self.aggregate_functions.insert(name.clone(), udaf).is_some()
+        {
+            bail!(
+                "Could not register UDAF '{}', as there is already a function with that name",
+                name
+            );
+        }
+
+        module.vis = Visibility::Public(VisPublic {
+            pub_token: Default::default(),
+        });
+
+        self.udaf_defs.insert(
+            name,
+            UdafDef {
+                arg,
+                ret,
+                def: module.to_token_stream().to_string(),
+            },
+        );
+
         Ok(())
     }
 }
 
+// Aggregates that are planned by DataFusion but only ever executed by generated code.
+fn placeholder_aggregate(
+    name: &str,
+    signature: Signature,
+    return_type: ReturnTypeFunction,
+) -> Arc<AggregateUDF> {
+    let accumulator: AccumulatorFunctionImplementation = Arc::new(|_| todo!());
+    let state_type: StateTypeFunction = Arc::new(|_| todo!());
+    Arc::new(AggregateUDF::new(
+        name,
+        &signature,
+        &return_type,
+        &accumulator,
+        &state_type,
+    ))
+}
+
+// Scalar functions that are planned by DataFusion but only ever executed by generated code.
+// They're volatile so that DataFusion doesn't try to evaluate them on constants.
+fn placeholder_udf(
+    name: &str,
+    signature: Signature,
+    return_type: ReturnTypeFunction,
+) -> Arc<ScalarUDF> {
+    let implementation: ScalarFunctionImplementation = Arc::new(|_| todo!());
+    Arc::new(ScalarUDF::new(
+        name,
+        &signature,
+        &return_type,
+        &implementation,
+    ))
+}
+
 fn create_table_source(fields: Vec<Field>) -> Arc<dyn TableSource> {
     Arc::new(LogicalTableSource::new(Arc::new(
         datatypes::Schema::new_with_metadata(fields, HashMap::new()),
     )))
 }
 
+// The Postgres dialect parses `^` as exponentiation and `#` as xor, neither of which DataFusion
+// can plan, so both are treated as bitwise xor, as in DataFusion's own dialect.
+fn rewrite_xor_operators(statement: &mut Statement) {
+    let _ = visit_expressions_mut(statement, |expr| {
+        if let SqlExpr::BinaryOp { op, .. } = expr {
+            if matches!(op, BinaryOperator::PGExp | BinaryOperator::PGBitwiseXor) {
+                *op = BinaryOperator::BitwiseXor;
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+}
+
+// Whether a plan has an aggregate with an ORDER BY, such as `array_agg(x ORDER BY y)`.
+fn has_ordered_aggregate(plan: &LogicalPlan) -> bool {
+    let mut ordered = false;
+    plan.apply(&mut |plan| {
+        for expr in plan.expressions() {
+            expr.apply(&mut |expr| {
+                if let Expr::AggregateFunction(datafusion_expr::expr::AggregateFunction {
+                    order_by: Some(_),
+                    ..
+                }) = expr
+                {
+                    ordered = true;
+                }
+                Ok(VisitRecursion::Continue)
+            })?;
+        }
+        Ok(VisitRecursion::Continue)
+    })
+    .unwrap();
+    ordered
+}
+
 fn value_to_inner_string(value: &Value) -> Result<String> {
     match value {
         Value::SingleQuotedString(inner_string)
@@ -267,6 +605,50 @@ fn value_to_inner_string(value: &Value) -> Result<String> {
     }
 }
 
+// UDF definitions may start with a comment declaring the crates they need, in Cargo's format:
+//
+// /*
+// [dependencies]
+// regex = "1.8"
+// */
+fn parse_udf_dependencies(body: &str) -> Result<Vec<(String, String)>> {
+    let Some(comment) = body.trim_start().strip_prefix("/*") else {
+        return Ok(vec![]);
+    };
+    let Some((comment, _)) = comment.split_once("*/") else {
+        bail!("unterminated comment in UDF definition");
+    };
+
+    let mut lines = comment.lines().map(str::trim);
+    if !lines.any(|line| line == "[dependencies]") {
+        return Ok(vec![]);
+    }
+
+    let mut dependencies = vec![];
+    for line in lines.filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        if line.starts_with('[') {
+            bail!(
+                "UDF definitions may only declare [dependencies], found {}",
+                line
+            );
+        }
+        let Some((name, spec)) = line.split_once('=') else {
+            bail!("invalid UDF dependency '{}'", line);
+        };
+        let (name, spec) = (name.trim(), spec.trim());
+        if name.is_empty()
+            || spec.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("invalid UDF dependency '{}'", line);
+        }
+        dependencies.push((name.to_string(), spec.to_string()));
+    }
+    Ok(dependencies)
+}
+
 impl ContextProvider for ArroyoSchemaProvider {
     fn get_table_provider(
         &self,
@@ -285,7 +667,35 @@ impl ContextProvider for ArroyoSchemaProvider {
     }
 
     fn get_function_meta(&self, name: &str) -> Option<Arc<ScalarUDF>> {
-        self.functions.get(name).cloned()
+        match name {
+            "unnest" => Some(placeholder_udf(
+                name,
+                Signature::any(1, Volatility::Volatile),
+                Arc::new(|input_types| match &input_types[0] {
+                    DataType::List(field) => Ok(Arc::new(field.data_type().clone())),
+                    other => Err(DataFusionError::Plan(format!(
+                        "unnest requires an array, found {}",
+                        other
+                    ))),
+                }),
+            )),
+            "cardinality" => Some(placeholder_udf(
+                name,
+                Signature::any(1, Volatility::Volatile),
+                Arc::new(|_| Ok(Arc::new(DataType::UInt64))),
+            )),
+            "array_contains" => Some(placeholder_udf(
+                name,
+                Signature::any(2, Volatility::Volatile),
+                Arc::new(|_| Ok(Arc::new(DataType::Boolean))),
+            )),
+            "array_slice" => Some(placeholder_udf(
+                name,
+                Signature::any(3, Volatility::Volatile),
+                Arc::new(|input_types| Ok(Arc::new(input_types[0].clone()))),
+            )),
+            name => json::function_meta(name).or_else(|| self.functions.get(name).cloned()),
+        }
     }
 
     fn get_aggregate_meta(&self, name: &str) -> Option<Arc<AggregateUDF>> {
@@ -320,7 +730,19 @@ impl ContextProvider for ArroyoSchemaProvider {
                     &state_type,
                 )))
             }
-            _ => None,
+            "approx_count_distinct" => Some(placeholder_aggregate(
+                "approx_count_distinct",
+                Signature::any(1, Volatility::Immutable),
+                Arc::new(|_| Ok(Arc::new(DataType::Int64))),
+            )),
+            "first_value" | "last_value" | "min_by" | "max_by" | "arg_min" | "arg_max" => {
+                Some(placeholder_aggregate(
+                    name,
+                    Signature::any(2, Volatility::Immutable),
+                    Arc::new(|input_types| Ok(Arc::new(input_types[0].clone()))),
+                ))
+            }
+            name => self.aggregate_functions.get(name).cloned(),
         }
     }
 
@@ -345,6 +767,23 @@ pub struct SqlConfig {
     pub default_parallelism: usize,
     pub sink: SinkConfig,
     pub kafka_qps: Option<u32>,
+    /// How long a non-windowed GROUP BY keeps the state of a key that isn't updated.
+    pub updating_aggregate_ttl: Duration,
+    /// How often non-windowed GROUP BYs emit their changed rows; if None, they are emitted
+    /// on every change.
+    pub updating_aggregate_emit_interval: Option<Duration>,
+    /// How long window functions computed without a time window keep the state of a partition
+    /// that gets no rows.
+    pub over_window_ttl: Duration,
+    /// How long windows and joins keep accepting records after the watermark has passed them.
+    /// Windows fire again with the updated result for each record that arrives in that time.
+    pub allowed_lateness: Duration,
+    /// Makes tumbling windows emit their partial results before they close; if None, they only
+    /// emit once the watermark passes their end.
+    pub early_trigger: Option<EarlyTrigger>,
+    /// Where windows send the records that arrive after their allowed lateness; if None, they
+    /// are dropped.
+    pub late_data_sink: Option<(String, SqlSink)>,
 }
 
 impl Default for SqlConfig {
@@ -353,6 +792,12 @@ impl Default for SqlConfig {
             default_parallelism: 4,
             sink: SinkConfig::Grpc,
             kafka_qps: None,
+            updating_aggregate_ttl: Duration::from_secs(24 * 60 * 60),
+            updating_aggregate_emit_interval: None,
+            over_window_ttl: Duration::from_secs(24 * 60 * 60),
+            allowed_lateness: Duration::ZERO,
+            early_trigger: None,
+            late_data_sink: None,
         }
     }
 }
@@ -380,11 +825,29 @@ pub fn parse_and_get_program_sync(
 ) -> Result<(Program, Vec<i64>)> {
     let mut sql_program_builder = SqlProgramBuilder {
         schema_provider: &mut schema_provider,
+        config,
     };
-    let outputs = sql_program_builder.plan_query(&query)?;
+    let failed_definitions = sql_program_builder.plan_saved_definitions();
+    let outputs = sql_program_builder.plan_query(&query).map_err(|err| {
+        // a query that fails may do so because a saved definition it reads couldn't be planned
+        match failed_definitions
+            .iter()
+            .find(|(name, _)| refers_to(&query, name))
+        {
+            Some((name, definition_err)) => anyhow!(
+                "saved definition '{}' could not be planned: {}",
+                name,
+                SqlError::from_anyhow(definition_err).message
+            ),
+            None => err,
+        }
+    })?;
+    let config = sql_program_builder.config.clone();
     let mut sql_pipeline_builder = SqlPipelineBuilder::new(sql_program_builder.schema_provider);
-    for output in outputs {
-        sql_pipeline_builder.insert_table(output)?;
+    for (output, tokens) in outputs {
+        sql_pipeline_builder
+            .insert_table(output)
+            .map_err(|e| SqlError::in_statement(e, &tokens))?;
     }
     let mut plan_graph = PlanGraph::new(config.clone());
     let last_output = sql_pipeline_builder
@@ -414,25 +877,220 @@ pub fn parse_and_get_program_sync(
     get_program(plan_graph, sql_program_builder.schema_provider.clone())
 }
 
+/// Options of an `EXPLAIN` query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExplainOptions {
+    pub verbose: bool,
+}
+
+/// Returns the options of the `EXPLAIN` in the query, if there is one.
+pub fn explain_options(query: &str) -> Result<Option<ExplainOptions>> {
+    let dialect = PostgreSqlDialect {};
+    Ok(watermark::parse_sql(&dialect, query)?
+        .into_iter()
+        .find_map(|parsed| match parsed.statement {
+            Statement::Explain { verbose, .. } => Some(ExplainOptions { verbose }),
+            _ => None,
+        }))
+}
+
+/// Returns the name of the view or table created by a saved definition, which must be a single
+/// CREATE VIEW or CREATE TABLE statement.
+pub fn saved_definition_name(definition: &str) -> Result<String> {
+    let dialect = PostgreSqlDialect {};
+    let mut statements = watermark::parse_sql(&dialect, definition)?;
+    match statements.pop().map(|parsed| parsed.statement) {
+        Some(Statement::CreateView { name, .. } | Statement::CreateTable { name, .. })
+            if statements.is_empty() =>
+        {
+            Ok(name.to_string())
+        }
+        _ => bail!("a saved definition must be a single CREATE VIEW or CREATE TABLE statement"),
+    }
+}
+
+/// Fills in the `${name}` placeholders of a query template with the given parameters. Within a
+/// string literal the value is escaped; in place of a token it must be a single identifier,
+/// number or string literal, so parameters can't change the structure of the query.
+/// Placeholders anywhere else, such as in comments or quoted identifiers, are rejected.
+pub fn substitute_parameters(
+    template: &str,
+    parameters: &HashMap<String, String>,
+) -> Result<String> {
+    let dialect = PostgreSqlDialect {};
+    let tokens = Tokenizer::new(&dialect, template)
+        .tokenize_with_location()
+        .map_err(|e| anyhow!("invalid query template: {}", e.message))?;
+
+    let mut query = String::with_capacity(template.len());
+    let mut offset = 0;
+    while let Some(start) = template[offset..].find("${").map(|start| offset + start) {
+        query.push_str(&template[offset..start]);
+        let end = template[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("unterminated parameter placeholder in query"))?;
+        let name = template[start + 2..end].trim();
+        let value = parameters
+            .get(name)
+            .ok_or_else(|| anyhow!("no value provided for parameter '{}'", name))?;
+
+        // the placeholder either starts a token of its own or is within the token before it
+        let location = location_of(template, start);
+        let token = tokens
+            .iter()
+            .map(|token| (&token.token, (token.location.line, token.location.column)))
+            .take_while(|(_, token_location)| *token_location <= location)
+            .last();
+        match token {
+            Some((Token::SingleQuotedString(_), _)) => {
+                query.push_str(&value.replace('\'', "''"));
+            }
+            Some((Token::Placeholder(placeholder), token_location))
+                if placeholder == "$" && token_location == location =>
+            {
+                check_parameter_token(name, value)?;
+                query.push_str(value);
+            }
+            _ => bail!(
+                "parameter '{}' must be used in place of a token or within a string literal",
+                name
+            ),
+        }
+        offset = end + 1;
+    }
+    query.push_str(&template[offset..]);
+    Ok(query)
+}
+
+/// The line and column of a byte offset into the text, counted the way the tokenizer does.
+fn location_of(text: &str, offset: usize) -> (u64, u64) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (
+        before.matches('\n').count() as u64 + 1,
+        before[line_start..].chars().count() as u64 + 1,
+    )
+}
+
+fn check_parameter_token(name: &str, value: &str) -> Result<()> {
+    let dialect = PostgreSqlDialect {};
+    let tokens = Tokenizer::new(&dialect, value)
+        .tokenize()
+        .map_err(|e| anyhow!("invalid value for parameter '{}': {}", name, e.message))?;
+    match tokens.as_slice() {
+        [Token::Word(_) | Token::Number(..) | Token::SingleQuotedString(_)] => Ok(()),
+        _ => bail!(
+            "the value of parameter '{}' must be a single identifier, number or string literal",
+            name
+        ),
+    }
+}
+
+/// Whether the query mentions the table or view with the given name.
+fn refers_to(query: &str, name: &str) -> bool {
+    let dialect = PostgreSqlDialect {};
+    Tokenizer::new(&dialect, query)
+        .tokenize()
+        .map(|tokens| {
+            tokens.iter().any(
+                |token| matches!(token, Token::Word(word) if word.value.eq_ignore_ascii_case(name)),
+            )
+        })
+        .unwrap_or(false)
+}
+
 struct SqlProgramBuilder<'a> {
     schema_provider: &'a mut ArroyoSchemaProvider,
+    config: SqlConfig,
 }
 
 impl<'a> SqlProgramBuilder<'a> {
-    fn plan_query(&mut self, query: &str) -> Result<Vec<Table>> {
+    /// Plans the saved views and tables so the query can refer to them. Definitions may refer
+    /// to each other in any order, so this repeats until no more can be planned. The ones that
+    /// still fail, for example because a source they read was deleted, are left out and
+    /// returned by name along with the error that planning them last failed with.
+    fn plan_saved_definitions(&mut self) -> Vec<(String, anyhow::Error)> {
+        let mut remaining = self.schema_provider.saved_definitions.clone();
+        loop {
+            let count = remaining.len();
+            let mut failures = Vec::new();
+            for definition in remaining {
+                if let Err(err) = self.plan_query(&definition) {
+                    failures.push((definition, err));
+                }
+            }
+            if failures.is_empty() || failures.len() == count {
+                return failures
+                    .into_iter()
+                    .map(|(definition, err)| {
+                        (
+                            saved_definition_name(&definition).unwrap_or(definition),
+                            err,
+                        )
+                    })
+                    .collect();
+            }
+            remaining = failures
+                .into_iter()
+                .map(|(definition, _)| definition)
+                .collect();
+        }
+    }
+
+    /// Plans the statements of the query, returning the tables they output along with the
+    /// tokens of the statement that produced each one.
+    fn plan_query(&mut self, query: &str) -> Result<Vec<(Table, Vec<TokenWithLocation>)>> {
         let dialect = PostgreSqlDialect {};
         let mut outputs = Vec::new();
-        for statement in Parser::parse_sql(&dialect, query)? {
-            let table = self.process_statement(statement)?;
-            match table.name() {
-                Some(_) => self.schema_provider.insert_table(table),
-                None => outputs.push(table),
+        for parsed in watermark::parse_sql(&dialect, query)? {
+            let table = self
+                .plan_statement(parsed.statement, parsed.watermark, parsed.lookup_tables)
+                .map_err(|e| SqlError::in_statement(e, &parsed.tokens))?;
+            match table {
+                Some(table) if table.name().is_some() => self.schema_provider.insert_table(table),
+                Some(table) => outputs.push((table, parsed.tokens)),
+                None => {}
             }
         }
         Ok(outputs)
     }
 
-    fn process_statement(&mut self, statement: Statement) -> Result<Table> {
+    fn plan_statement(
+        &mut self,
+        statement: Statement,
+        watermark: Option<WatermarkClause>,
+        lookup_tables: Vec<String>,
+    ) -> Result<Option<Table>> {
+        // EXPLAIN plans the statement as usual; callers describe the resulting program.
+        let statement = match statement {
+            Statement::Explain { analyze: true, .. } => {
+                bail!("EXPLAIN ANALYZE is not supported")
+            }
+            Statement::Explain { statement, .. } => *statement,
+            statement => statement,
+        };
+        if let Statement::SetVariable {
+            variable, value, ..
+        } = &statement
+        {
+            self.set_variable(variable, value)?;
+            return Ok(None);
+        }
+        self.process_statement(statement, watermark, lookup_tables)
+            .map(Some)
+    }
+
+    fn process_statement(
+        &mut self,
+        mut statement: Statement,
+        watermark: Option<WatermarkClause>,
+        lookup_tables: Vec<String>,
+    ) -> Result<Table> {
+        lookup::check_lookup_relations(&statement, &lookup_tables, self.schema_provider)?;
+        unnest::rewrite_unnest_joins(&mut statement)?;
+        rewrite_xor_operators(&mut statement);
+        json::rewrite_json_syntax(&mut statement);
         // Handle naked create tables separately,
         // As DataFusion doesn't support the WITH clause.
         let sql_to_rel = SqlToRel::new(self.schema_provider);
@@ -515,6 +1173,7 @@ impl<'a> SqlProgramBuilder<'a> {
                 let expression_context = ExpressionContext {
                     input_struct: &physical_struct,
                     schema_provider: self.schema_provider,
+                    allow_async_udfs: false,
                 };
                 struct_field_tuple
                     .into_iter()
@@ -543,6 +1202,21 @@ impl<'a> SqlProgramBuilder<'a> {
                     .collect::<Result<Vec<_>>>()?
             };
 
+            if with_map.contains_key("connector") {
+                if watermark.is_some() {
+                    bail!("WATERMARK FOR is not supported on lookup tables");
+                }
+                let key_column = columns
+                    .iter()
+                    .find(|column| {
+                        column.options.iter().any(|option| {
+                            matches!(option.option, ColumnOption::Unique { is_primary: true })
+                        })
+                    })
+                    .map(|column| column.name.value.to_string());
+                return self.plan_lookup_table(name, fields, key_column, &with_map);
+            }
+
             let connection_name = with_map.get("connection");
             match connection_name {
                 Some(connection_name) => {
@@ -552,13 +1226,53 @@ impl<'a> SqlProgramBuilder<'a> {
                         .get(connection_name)
                         .ok_or_else(|| anyhow!("connection {} not found", connection_name))?
                         .clone();
+                    let watermark = match watermark {
+                        Some(watermark) => Some(self.plan_watermark(
+                            &sql_to_rel,
+                            &fields,
+                            &mut with_map,
+                            watermark,
+                        )?),
+                        None => None,
+                    };
+                    let watermark = match with_map.get("partition_watermarks") {
+                        Some(max_lateness) => {
+                            if !matches!(connection.connection_type, Some(ConnectionType::Kafka(_)))
+                            {
+                                bail!("partition_watermarks are only supported on Kafka tables");
+                            }
+                            if watermark.is_some()
+                                || with_map.contains_key("event_time_field")
+                                || with_map.contains_key("watermark_field")
+                            {
+                                bail!("partition_watermarks are computed from the Kafka message timestamps, and can't be combined with WATERMARK FOR, event_time_field or watermark_field");
+                            }
+                            let max_lateness = self.parse_interval(max_lateness).map_err(|_| {
+                                anyhow!("invalid partition_watermarks '{}'", max_lateness)
+                            })?;
+                            Some(SourceWatermark::PerPartition(max_lateness))
+                        }
+                        None => watermark,
+                    };
+                    let idle_time = with_map
+                        .get("idle_time")
+                        .map(|idle_time| {
+                            self.parse_interval(idle_time)
+                                .map_err(|_| anyhow!("invalid idle_time '{}'", idle_time))
+                        })
+                        .transpose()?;
                     Ok(Table::MemoryTableWithConnectionConfig {
                         name,
                         fields,
                         connection,
                         connection_config: with_map,
+                        watermark,
+                        idle_time,
                     })
                 }
+                None if watermark.is_some() => {
+                    bail!("WATERMARK FOR is only supported on tables with a connection")
+                }
                 None => {
                     let fields = fields
                         .into_iter()
@@ -570,13 +1284,29 @@ impl<'a> SqlProgramBuilder<'a> {
                 }
             }
         } else {
-            let plan = sql_to_rel.sql_statement_to_plan(statement.clone())?;
+            if watermark.is_some() {
+                bail!("WATERMARK FOR is only supported in CREATE TABLE statements without a query");
+            }
+            let plan = unnest::plan_unnests(sql_to_rel.sql_statement_to_plan(statement.clone())?)?;
 
             let optimizer_config = OptimizerContext::default();
             let analyzer = Analyzer::default();
-            let optimizer = Optimizer::new();
             let analyzed_plan =
                 analyzer.execute_and_check(&plan, &ConfigOptions::default(), |_plan, _rule| {})?;
+            // common subexpression elimination rewrites the arguments of aggregates with an
+            // ORDER BY in a way that changes their names, which causes push_down_projection
+            // to drop them from the plan
+            let optimizer = if has_ordered_aggregate(&analyzed_plan) {
+                Optimizer::with_rules(
+                    Optimizer::new()
+                        .rules
+                        .into_iter()
+                        .filter(|rule| rule.name() != "common_sub_expression_eliminate")
+                        .collect(),
+                )
+            } else {
+                Optimizer::new()
+            };
             let optimized_plan =
                 optimizer.optimize(&analyzed_plan, &optimizer_config, |_plan, _rule| {})?;
 
@@ -614,6 +1344,271 @@ impl<'a> SqlProgramBuilder<'a> {
     }
 }
 
+impl<'a> SqlProgramBuilder<'a> {
+    // Parses a duration written like the string of an interval literal, e.g. '10 seconds'.
+    fn parse_interval(&self, value: &str) -> Result<Duration> {
+        let interval = Parser::new(&PostgreSqlDialect {})
+            .try_with_sql(&format!("INTERVAL '{}'", value.replace('\'', "''")))?
+            .parse_expr()?;
+        let interval = SqlToRel::new(self.schema_provider).sql_to_expr(
+            interval,
+            &DFSchema::empty(),
+            &mut PlannerContext::default(),
+        )?;
+        SqlPipelineBuilder::get_duration(&interval)
+    }
+
+    // `SET <setting> = '<value>'` configures how the query is planned. The emit interval of
+    // non-windowed GROUP BYs may be 'on_change' to emit every update as it happens, and tumbling
+    // windows can `SET emit = 'every 10 seconds'` or 'every 1000 rows' to emit partial results
+    // before they close. `SET allowed_lateness` keeps windows open for records that arrive after
+    // the watermark, and `SET late_data_sink = '<sink>'` sends the records that arrive later
+    // still to that sink instead of dropping them. `SET over_window_ttl` bounds how long window
+    // functions without a time window keep the state of idle partitions.
+    fn set_variable(&mut self, variable: &ObjectName, value: &[SqlExpr]) -> Result<()> {
+        let [SqlExpr::Value(value)] = value else {
+            bail!("SET {} requires a single value", variable);
+        };
+        let value = value_to_inner_string(value)?;
+        match variable.to_string().to_lowercase().as_str() {
+            "updating_aggregate_ttl" => {
+                self.config.updating_aggregate_ttl = self
+                    .parse_interval(&value)
+                    .map_err(|_| anyhow!("invalid updating_aggregate_ttl '{}'", value))?;
+            }
+            "updating_aggregate_emit_interval" => {
+                self.config.updating_aggregate_emit_interval =
+                    if value.eq_ignore_ascii_case("on_change") {
+                        None
+                    } else {
+                        let interval = self.parse_interval(&value).map_err(|_| {
+                            anyhow!("invalid updating_aggregate_emit_interval '{}'", value)
+                        })?;
+                        Some(interval)
+                    };
+            }
+            "over_window_ttl" => {
+                self.config.over_window_ttl = self
+                    .parse_interval(&value)
+                    .map_err(|_| anyhow!("invalid over_window_ttl '{}'", value))?;
+            }
+            "emit" => {
+                self.config.early_trigger = self.parse_early_trigger(&value)?;
+            }
+            "allowed_lateness" => {
+                self.config.allowed_lateness = self
+                    .parse_interval(&value)
+                    .map_err(|_| anyhow!("invalid allowed_lateness '{}'", value))?;
+            }
+            "late_data_sink" => {
+                self.config.late_data_sink = Some(self.late_data_sink(&value)?);
+            }
+            _ => bail!("unknown setting {}", variable),
+        }
+        Ok(())
+    }
+
+    // The sink's struct is filled in with the type of each window's input once it's planned.
+    fn late_data_sink(&self, name: &String) -> Result<(String, SqlSink)> {
+        let struct_def = StructDef {
+            name: None,
+            fields: vec![],
+        };
+        let sink = match self.schema_provider.get_table(name) {
+            Some(Table::SavedSink {
+                id, sink_config, ..
+            }) => SqlSink {
+                id: Some(*id),
+                struct_def,
+                sink_config: sink_config.clone(),
+            },
+            Some(Table::MemoryTableWithConnectionConfig {
+                connection,
+                connection_config,
+                ..
+            }) => SqlSink::try_new(
+                None,
+                struct_def,
+                connection.clone(),
+                connection_config.clone(),
+            )?,
+            Some(_) => bail!("late_data_sink {} is not a sink", name),
+            None => bail!("unknown late_data_sink {}", name),
+        };
+        Ok((name.clone(), sink))
+    }
+
+    fn parse_early_trigger(&self, value: &str) -> Result<Option<EarlyTrigger>> {
+        if value.eq_ignore_ascii_case("on_close") {
+            return Ok(None);
+        }
+        let invalid = || {
+            anyhow!(
+                "invalid emit '{}', expected 'on_close', 'every <interval>' or 'every <n> rows'",
+                value
+            )
+        };
+        let Some(every) = value.trim().strip_prefix("every ") else {
+            return Err(invalid());
+        };
+        let every = every.trim();
+        let trigger = match every
+            .strip_suffix("rows")
+            .or_else(|| every.strip_suffix("records"))
+        {
+            Some(count) => EarlyTrigger::Count(count.trim().parse().map_err(|_| invalid())?),
+            None => EarlyTrigger::Interval(self.parse_interval(every).map_err(|_| invalid())?),
+        };
+        if matches!(trigger, EarlyTrigger::Count(0))
+            || trigger == EarlyTrigger::Interval(Duration::ZERO)
+        {
+            bail!("emit must be every at least one row or a non-zero interval");
+        }
+        Ok(Some(trigger))
+    }
+
+    // Tables created with a `connector` rather than a connection are looked up by their
+    // primary key as they are joined.
+    fn plan_lookup_table(
+        &self,
+        name: String,
+        fields: Vec<FieldSpec>,
+        key_column: Option<String>,
+        with_map: &HashMap<String, String>,
+    ) -> Result<Table> {
+        if with_map.contains_key("connection") {
+            bail!("lookup tables are configured with a connector, not a connection");
+        }
+        let fields = fields
+            .into_iter()
+            .map(|field| match field {
+                FieldSpec::StructField(struct_field) => Ok(struct_field),
+                FieldSpec::VirtualStructField(..) => {
+                    bail!("virtual fields are not supported in lookup tables")
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let Some(key_column) = key_column else {
+            bail!(
+                "lookup table {} must have a PRIMARY KEY column to look up rows by",
+                name
+            );
+        };
+        let key_field = fields
+            .iter()
+            .find(|field| field.name == key_column)
+            .unwrap()
+            .clone();
+
+        let cache_ttl = match with_map.get("cache_ttl") {
+            Some(cache_ttl) => self
+                .parse_interval(cache_ttl)
+                .map_err(|_| anyhow!("invalid cache_ttl '{}'", cache_ttl))?,
+            None => Duration::from_secs(60),
+        };
+
+        let source = SqlLookupSource::try_new(
+            &name,
+            StructDef { name: None, fields },
+            key_field,
+            with_map,
+            cache_ttl,
+        )?;
+        Ok(Table::LookupTable { name, source })
+    }
+
+    // `WATERMARK FOR ts AS ts - INTERVAL '5' SECOND` makes ts the event time, with a watermark
+    // that trails it by a fixed lateness. Any other timestamp expression is evaluated per record.
+    fn plan_watermark(
+        &self,
+        sql_to_rel: &SqlToRel<'_, ArroyoSchemaProvider>,
+        fields: &[FieldSpec],
+        with_map: &mut HashMap<String, String>,
+        watermark: WatermarkClause,
+    ) -> Result<SourceWatermark> {
+        let event_time_field = watermark.column.value;
+        match with_map.get("event_time_field") {
+            Some(field) if *field != event_time_field => bail!(
+                "event_time_field {} conflicts with WATERMARK FOR {}",
+                field,
+                event_time_field
+            ),
+            _ => {}
+        }
+        if with_map.contains_key("watermark_field") {
+            bail!("watermark_field can't be combined with a WATERMARK FOR clause");
+        }
+        // event_time_field is validated when the table is scanned
+        with_map.insert("event_time_field".to_string(), event_time_field.clone());
+
+        let table_struct = StructDef {
+            name: None,
+            fields: fields
+                .iter()
+                .map(|field| match field {
+                    FieldSpec::StructField(struct_field)
+                    | FieldSpec::VirtualStructField(struct_field, _) => struct_field.clone(),
+                })
+                .collect(),
+        };
+        let table_schema = DFSchema::new_with_metadata(
+            table_struct
+                .fields
+                .iter()
+                .map(|f| {
+                    let TypeDef::DataType(data_type, nullable) = f.data_type.clone() else {
+                        bail!("expect data type for table column")
+                    };
+                    Ok(DFField::new_unqualified(&f.name, data_type, nullable))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            HashMap::new(),
+        )?;
+        let df_expr = sql_to_rel.sql_to_expr(
+            watermark.expression,
+            &table_schema,
+            &mut PlannerContext::default(),
+        )?;
+
+        match &df_expr {
+            Expr::Column(column) if column.name == event_time_field => {
+                return Ok(SourceWatermark::FixedLateness(Duration::ZERO));
+            }
+            Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: datafusion_expr::Operator::Minus,
+                right,
+            }) => {
+                if let (Expr::Column(column), Ok(max_lateness)) =
+                    (left.as_ref(), SqlPipelineBuilder::get_duration(right))
+                {
+                    if column.name == event_time_field {
+                        return Ok(SourceWatermark::FixedLateness(max_lateness));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let expression_context = ExpressionContext {
+            input_struct: &table_struct,
+            schema_provider: self.schema_provider,
+            allow_async_udfs: false,
+        };
+        let expression = expression_context.compile_expr(&df_expr)?;
+        if !matches!(
+            expression.return_type(),
+            TypeDef::DataType(DataType::Timestamp(..), _)
+        ) {
+            bail!(
+                "watermark expression must be a timestamp, not {:?}",
+                expression.return_type()
+            );
+        }
+        Ok(SourceWatermark::Expression(expression))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum FieldSpec {
     StructField(StructField),
@@ -644,6 +1639,13 @@ pub enum Table {
         fields: Vec<FieldSpec>,
         connection: Connection,
         connection_config: HashMap<String, String>,
+        watermark: Option<SourceWatermark>,
+        /// How long the source can go without records before it no longer holds back watermarks.
+        idle_time: Option<Duration>,
+    },
+    LookupTable {
+        name: String,
+        source: SqlLookupSource,
     },
     TableFromQuery {
         name: String,
@@ -665,6 +1667,7 @@ impl Table {
             Table::SavedSink { name, .. } => Some(name.clone()),
             Table::MemoryTable { name, .. } => Some(name.clone()),
             Table::MemoryTableWithConnectionConfig { name, .. } => Some(name.clone()),
+            Table::LookupTable { name, .. } => Some(name.clone()),
             Table::TableFromQuery { name, .. } => Some(name.clone()),
             Table::InsertQuery { .. } | Table::Anonymous { .. } => None,
         }
@@ -688,6 +1691,15 @@ impl Table {
                     Ok(field)
                 })
                 .collect::<Result<Vec<_>>>(),
+            Table::LookupTable { source, .. } => source
+                .struct_def
+                .fields
+                .iter()
+                .map(|field| {
+                    let field: Field = field.clone().into();
+                    Ok(field)
+                })
+                .collect::<Result<Vec<_>>>(),
             Table::SavedSink {
                 name: _,
                 id: _,
@@ -882,14 +1894,16 @@ pub fn get_test_expression(
     );
     let mut plan = SqlProgramBuilder {
         schema_provider: &mut schema_provider,
+        config: SqlConfig::default(),
     }
     .plan_query(&format!("SELECT {} FROM test_source", calculation_string))
     .unwrap();
 
-    let Table::Anonymous{logical_plan: LogicalPlan::Projection(projection)} = plan.remove(0) else {panic!("expect projection")};
+    let Table::Anonymous{logical_plan: LogicalPlan::Projection(projection)} = plan.remove(0).0 else {panic!("expect projection")};
     let ctx = ExpressionContext {
         schema_provider: &schema_provider,
         input_struct: &struct_def,
+        allow_async_udfs: false,
     };
 
     let generating_expression = ctx.compile_expr(&projection.expr[0]).unwrap();
@@ -0,0 +1,120 @@
+use std::ops::ControlFlow;
+
+use anyhow::{bail, Result};
+use datafusion::sql::sqlparser::ast::{visit_relations, Ident, ObjectName, Statement};
+use datafusion::sql::sqlparser::tokenizer::Token;
+
+use crate::{ArroyoSchemaProvider, Table};
+
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w.quote_style.is_none() && w.value.eq_ignore_ascii_case(word))
+}
+
+fn ident(token: &Token) -> Option<Ident> {
+    match token {
+        Token::Word(w) => Some(Ident {
+            value: w.value.clone(),
+            quote_style: w.quote_style,
+        }),
+        _ => None,
+    }
+}
+
+// sqlparser doesn't support `FOR SYSTEM_TIME AS OF`, so the clauses are removed from the statement
+// before it is parsed, and the names of the tables they followed are returned. Lookups always
+// read the current row, so the time attribute itself is only checked to be a column.
+pub(crate) fn extract_system_time(mut tokens: Vec<Token>) -> Result<(Vec<Token>, Vec<String>)> {
+    let significant: Vec<usize> = (0..tokens.len())
+        .filter(|i| !matches!(tokens[*i], Token::Whitespace(_)))
+        .collect();
+    let token = |position: usize| significant.get(position).map(|index| &tokens[*index]);
+
+    let mut relations = vec![];
+    let mut removed = vec![];
+    let mut position = 0;
+    while position < significant.len() {
+        let keywords = ["for", "system_time", "as", "of"];
+        let is_clause = keywords.iter().enumerate().all(|(offset, word)| {
+            token(position + offset)
+                .map(|token| is_word(token, word))
+                .unwrap_or(false)
+        });
+        if !is_clause {
+            position += 1;
+            continue;
+        }
+
+        // the clause follows a possibly qualified table name
+        let mut name = vec![];
+        let mut start = position;
+        while let Some(part) = start.checked_sub(1).and_then(|p| token(p)).and_then(ident) {
+            name.insert(0, part);
+            start -= 1;
+            if start == 0 || token(start - 1) != Some(&Token::Period) {
+                break;
+            }
+            start -= 1;
+        }
+        if name.is_empty() {
+            bail!("FOR SYSTEM_TIME AS OF must follow a table name");
+        }
+
+        // followed by a column, or a function call like PROCTIME()
+        let mut end = position + 4;
+        if token(end).and_then(ident).is_none() {
+            bail!("FOR SYSTEM_TIME AS OF must be followed by a time attribute column");
+        }
+        while token(end + 1) == Some(&Token::Period) && token(end + 2).and_then(ident).is_some() {
+            end += 2;
+        }
+        if token(end + 1) == Some(&Token::LParen) && token(end + 2) == Some(&Token::RParen) {
+            end += 2;
+        }
+
+        relations.push(ObjectName(name).to_string());
+        removed.push(significant[position]..=significant[end]);
+        position = end + 1;
+    }
+    for range in removed.into_iter().rev() {
+        tokens.drain(range);
+    }
+    Ok((tokens, relations))
+}
+
+/// Checks that lookup tables are only read through `FOR SYSTEM_TIME AS OF`, and that the
+/// clause is only used with lookup tables.
+pub(crate) fn check_lookup_relations(
+    statement: &Statement,
+    lookup_tables: &[String],
+    schema_provider: &ArroyoSchemaProvider,
+) -> Result<()> {
+    let mut relations = vec![];
+    let _ = visit_relations(statement, |relation| {
+        relations.push(relation.to_string());
+        ControlFlow::<()>::Continue(())
+    });
+    for relation in relations {
+        let is_lookup_table = matches!(
+            schema_provider.get_table(&relation),
+            Some(Table::LookupTable { .. })
+        );
+        if is_lookup_table && !lookup_tables.contains(&relation) {
+            bail!(
+                "lookup table {} can only be joined with FOR SYSTEM_TIME AS OF",
+                relation
+            );
+        }
+    }
+    for table in lookup_tables {
+        if !matches!(
+            schema_provider.get_table(table),
+            Some(Table::LookupTable { .. })
+        ) {
+            bail!(
+                "FOR SYSTEM_TIME AS OF is only supported for lookup tables, and {} is not one",
+                table
+            );
+        }
+    }
+    Ok(())
+}
@@ -559,9 +559,9 @@ impl TwoPhaseAggregation {
 
     fn aggregate_type_def(&self) -> TypeDef {
         let incoming_type = self.incoming_expression.return_type();
-        let data_type = match incoming_type {
+        let data_type = match &incoming_type {
             TypeDef::StructDef(_, _) => unreachable!(),
-            TypeDef::DataType(data_type, _) => data_type,
+            TypeDef::DataType(data_type, _) => data_type.clone(),
         };
         let aggregate_type = match self.aggregator {
             Aggregator::Count => DataType::Int64,
@@ -570,11 +570,13 @@ impl TwoPhaseAggregation {
             }
             Aggregator::Min | Aggregator::Max => data_type,
             Aggregator::CountDistinct => unimplemented!(),
+            Aggregator::RustUdaf(ref udaf) => return udaf.ret.clone(),
+            ref aggregator => aggregator.return_data_type(incoming_type),
         };
         TypeDef::DataType(aggregate_type, false)
     }
 
-    fn bin_type(&self) -> syn::Type {
+    pub(crate) fn bin_type(&self) -> syn::Type {
         let input_nullable = self.incoming_expression.nullable();
         let aggregate_type = self.aggregate_type();
         match (&self.aggregator, input_nullable) {
@@ -588,6 +590,32 @@ impl TwoPhaseAggregation {
             (Aggregator::Avg, true) => parse_quote!(Option<(i64, #aggregate_type)>),
             (Aggregator::Avg, false) => parse_quote!((i64, #aggregate_type)),
             (Aggregator::CountDistinct, _) => unimplemented!(),
+            (Aggregator::ApproxCountDistinct, _) => {
+                parse_quote!(arroyo_worker::operators::sketches::HyperLogLog)
+            }
+            (Aggregator::ApproxPercentile(_), _) => {
+                parse_quote!(arroyo_worker::operators::sketches::TDigest)
+            }
+            (Aggregator::Variance, _)
+            | (Aggregator::VariancePop, _)
+            | (Aggregator::Stddev, _)
+            | (Aggregator::StddevPop, _) => parse_quote!((i64, f64, f64)),
+            (Aggregator::MinBy(key), _) | (Aggregator::MaxBy(key), _) => {
+                let key_type = key.return_type().with_nullity(false).return_type();
+                let value_type = self.incoming_expression.return_type().return_type();
+                parse_quote!(Option<(#key_type, #value_type)>)
+            }
+            (Aggregator::BoolAnd, _) | (Aggregator::BoolOr, _) => parse_quote!((i64, i64)),
+            (Aggregator::ArrayAgg, _) => {
+                let value_type = self.incoming_expression.return_type().return_type();
+                parse_quote!(Vec<#value_type>)
+            }
+            (Aggregator::OrderedArrayAgg(sort_expressions), _) => {
+                let key_types = sort_expressions.iter().map(|sort| sort.value_type());
+                let value_type = self.incoming_expression.return_type().return_type();
+                parse_quote!(Vec<((#(#key_types,)*), #value_type)>)
+            }
+            (Aggregator::RustUdaf(udaf), _) => udaf.accumulator_type(),
         }
     }
 
@@ -636,10 +664,48 @@ impl TwoPhaseAggregation {
                 parse_quote!({ (current_bin.0 + new_bin.0, current_bin.1 + new_bin.1) })
             }
             (Aggregator::CountDistinct, _) => unreachable!("no two phase for count distinct"),
+            (Aggregator::ApproxCountDistinct, _) | (Aggregator::ApproxPercentile(_), _) => {
+                parse_quote!({ current_bin.merge(new_bin) })
+            }
+            (Aggregator::Variance, _)
+            | (Aggregator::VariancePop, _)
+            | (Aggregator::Stddev, _)
+            | (Aggregator::StddevPop, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_add(
+                    Some(current_bin),
+                    new_bin,
+                )
+            }),
+            (Aggregator::MinBy(_), _) => parse_quote!({
+                match (current_bin, new_bin) {
+                    (Some(current), Some(new)) if new.0 < current.0 => Some(new),
+                    (current_bin, None) | (current_bin @ Some(_), Some(_)) => current_bin,
+                    (None, new_bin) => new_bin,
+                }
+            }),
+            (Aggregator::MaxBy(_), _) => parse_quote!({
+                match (current_bin, new_bin) {
+                    (Some(current), Some(new)) if new.0 < current.0 => Some(current),
+                    (current_bin, None) => current_bin,
+                    (_, new_bin) => new_bin,
+                }
+            }),
+            (Aggregator::BoolAnd, _) | (Aggregator::BoolOr, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bool_add(Some(current_bin), new_bin)
+            }),
+            (Aggregator::ArrayAgg, _) | (Aggregator::OrderedArrayAgg(_), _) => parse_quote!({
+                let mut current_bin = current_bin;
+                current_bin.extend(new_bin);
+                current_bin
+            }),
+            (Aggregator::RustUdaf(udaf), _) => {
+                let path = udaf.path();
+                parse_quote!({ #path::merge(current_bin, new_bin) })
+            }
         }
     }
 
-    fn bin_syn_expr(&self) -> syn::Expr {
+    pub(crate) fn bin_syn_expr(&self) -> syn::Expr {
         let expr = self.incoming_expression.to_syn_expression();
         let aggregate_type = self.aggregate_type();
         let input_nullable = self.incoming_expression.nullable();
@@ -707,6 +773,118 @@ impl TwoPhaseAggregation {
                 }
             }),
             (Aggregator::CountDistinct, _) => unreachable!("no two phase for count distinct"),
+            (Aggregator::ApproxCountDistinct, nullable) => {
+                // floats aren't Hash, so their bits are hashed instead
+                let to_bits = match self.incoming_expression.return_type() {
+                    TypeDef::DataType(DataType::Float32 | DataType::Float64, _) => {
+                        quote!(.to_bits())
+                    }
+                    _ => quote!(),
+                };
+                if nullable {
+                    parse_quote!({
+                        let mut bin = current_bin.unwrap_or_default();
+                        if let Some(value) = #expr {
+                            bin.add(&value #to_bits);
+                        }
+                        bin
+                    })
+                } else {
+                    parse_quote!({
+                        let mut bin = current_bin.unwrap_or_default();
+                        bin.add(&(#expr) #to_bits);
+                        bin
+                    })
+                }
+            }
+            (Aggregator::ApproxPercentile(_), true) => parse_quote!({
+                let mut bin = current_bin.unwrap_or_default();
+                if let Some(value) = #expr {
+                    bin.add(value as f64);
+                }
+                bin
+            }),
+            (Aggregator::ApproxPercentile(_), false) => parse_quote!({
+                let mut bin = current_bin.unwrap_or_default();
+                bin.add(#expr as f64);
+                bin
+            }),
+            (Aggregator::Variance, true)
+            | (Aggregator::VariancePop, true)
+            | (Aggregator::Stddev, true)
+            | (Aggregator::StddevPop, true) => parse_quote!({
+                let (count, sum, sum_of_squares) = current_bin.unwrap_or((0, 0.0, 0.0));
+                match #expr {
+                    Some(value) => {
+                        let value = value as f64;
+                        (count + 1, sum + value, sum_of_squares + value * value)
+                    }
+                    None => (count, sum, sum_of_squares),
+                }
+            }),
+            (Aggregator::Variance, false)
+            | (Aggregator::VariancePop, false)
+            | (Aggregator::Stddev, false)
+            | (Aggregator::StddevPop, false) => parse_quote!({
+                let (count, sum, sum_of_squares) = current_bin.unwrap_or((0, 0.0, 0.0));
+                let value = #expr as f64;
+                (count + 1, sum + value, sum_of_squares + value * value)
+            }),
+            (Aggregator::MinBy(key), _) | (Aggregator::MaxBy(key), _) => {
+                let key_expr = key.to_syn_expression();
+                let key_expr: syn::Expr = if key.nullable() {
+                    key_expr
+                } else {
+                    parse_quote!(Some(#key_expr))
+                };
+                // rows without a key are skipped; ties keep the first row for min_by
+                // and the last row for max_by.
+                let keep_current: syn::Expr = if matches!(self.aggregator, Aggregator::MinBy(_)) {
+                    parse_quote!(current_key <= key)
+                } else {
+                    parse_quote!(current_key > key)
+                };
+                parse_quote!({
+                    match (current_bin.flatten(), #key_expr) {
+                        (Some((current_key, value)), Some(key)) if #keep_current => {
+                            Some((current_key, value))
+                        }
+                        (_, Some(key)) => Some((key, #expr)),
+                        (current_bin, None) => current_bin,
+                    }
+                })
+            }
+            (Aggregator::BoolAnd, true) | (Aggregator::BoolOr, true) => parse_quote!({
+                let (trues, count) = current_bin.unwrap_or((0, 0));
+                match #expr {
+                    Some(value) => (trues + value as i64, count + 1),
+                    None => (trues, count),
+                }
+            }),
+            (Aggregator::BoolAnd, false) | (Aggregator::BoolOr, false) => parse_quote!({
+                let (trues, count) = current_bin.unwrap_or((0, 0));
+                (trues + #expr as i64, count + 1)
+            }),
+            (Aggregator::ArrayAgg, _) => parse_quote!({
+                let mut bin = current_bin.unwrap_or_default();
+                bin.push(#expr);
+                bin
+            }),
+            (Aggregator::OrderedArrayAgg(sort_expressions), _) => {
+                let key_exprs = sort_expressions.iter().map(|sort| sort.value_syn_expr());
+                parse_quote!({
+                    let mut bin = current_bin.unwrap_or_default();
+                    bin.push(((#(#key_exprs,)*), #expr));
+                    bin
+                })
+            }
+            (Aggregator::RustUdaf(udaf), _) => {
+                let path = udaf.path();
+                udaf.accumulate_syn_expr(
+                    parse_quote!(current_bin.unwrap_or_else(#path::init)),
+                    &self.incoming_expression,
+                )
+            }
         }
     }
 
@@ -726,6 +904,21 @@ impl TwoPhaseAggregation {
             (Aggregator::Avg, true) => parse_quote!((i64, i64, Option<(i64, #expr_type)>)),
             (Aggregator::Avg, false) => parse_quote!((i64, #expr_type)),
             (Aggregator::CountDistinct, _) => unimplemented!(),
+            (Aggregator::Variance, _)
+            | (Aggregator::VariancePop, _)
+            | (Aggregator::Stddev, _)
+            | (Aggregator::StddevPop, _) => parse_quote!((i64, f64, f64)),
+            (Aggregator::BoolAnd, _) | (Aggregator::BoolOr, _) => parse_quote!((i64, i64)),
+            (Aggregator::ApproxCountDistinct, _)
+            | (Aggregator::ApproxPercentile(_), _)
+            | (Aggregator::MinBy(_), _)
+            | (Aggregator::MaxBy(_), _)
+            | (Aggregator::ArrayAgg, _)
+            | (Aggregator::OrderedArrayAgg(_), _)
+            | (Aggregator::RustUdaf(_), _) => {
+                let bin_type = self.bin_type();
+                parse_quote!(std::collections::VecDeque<#bin_type>)
+            }
         }
     }
 
@@ -766,6 +959,24 @@ impl TwoPhaseAggregation {
             }),
             (Aggregator::CountDistinct, true) => todo!(),
             (Aggregator::CountDistinct, false) => todo!(),
+            (Aggregator::Variance, _)
+            | (Aggregator::VariancePop, _)
+            | (Aggregator::Stddev, _)
+            | (Aggregator::StddevPop, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_add(current, bin_value)
+            }),
+            (Aggregator::BoolAnd, _) | (Aggregator::BoolOr, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bool_add(current, bin_value)
+            }),
+            (Aggregator::ApproxCountDistinct, _)
+            | (Aggregator::ApproxPercentile(_), _)
+            | (Aggregator::MinBy(_), _)
+            | (Aggregator::MaxBy(_), _)
+            | (Aggregator::ArrayAgg, _)
+            | (Aggregator::OrderedArrayAgg(_), _)
+            | (Aggregator::RustUdaf(_), _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bin_queue_add(current, bin_value)
+            }),
         }
     }
 
@@ -800,10 +1011,28 @@ impl TwoPhaseAggregation {
             }),
             (Aggregator::CountDistinct, true) => todo!(),
             (Aggregator::CountDistinct, false) => todo!(),
+            (Aggregator::Variance, _)
+            | (Aggregator::VariancePop, _)
+            | (Aggregator::Stddev, _)
+            | (Aggregator::StddevPop, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_remove(current, bin_value)
+            }),
+            (Aggregator::BoolAnd, _) | (Aggregator::BoolOr, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bool_remove(current, bin_value)
+            }),
+            (Aggregator::ApproxCountDistinct, _)
+            | (Aggregator::ApproxPercentile(_), _)
+            | (Aggregator::MinBy(_), _)
+            | (Aggregator::MaxBy(_), _)
+            | (Aggregator::ArrayAgg, _)
+            | (Aggregator::OrderedArrayAgg(_), _)
+            | (Aggregator::RustUdaf(_), _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bin_queue_remove(current, bin_value)
+            }),
         }
     }
 
-    fn return_type(&self) -> TypeDef {
+    pub(crate) fn return_type(&self) -> TypeDef {
         match self.aggregator {
             Aggregator::Count => TypeDef::DataType(DataType::Int64, false),
             Aggregator::Sum => self
@@ -819,10 +1048,24 @@ impl TwoPhaseAggregation {
                 ),
             },
             Aggregator::CountDistinct => TypeDef::DataType(DataType::Int64, false),
+            Aggregator::ApproxCountDistinct => TypeDef::DataType(DataType::Int64, false),
+            Aggregator::ApproxPercentile(_)
+            | Aggregator::Variance
+            | Aggregator::VariancePop
+            | Aggregator::Stddev
+            | Aggregator::StddevPop
+            | Aggregator::BoolAnd
+            | Aggregator::BoolOr => self.aggregate_type_def().as_nullable(),
+            Aggregator::MinBy(ref key) | Aggregator::MaxBy(ref key) => self
+                .incoming_expression
+                .return_type()
+                .with_nullity(self.incoming_expression.nullable() || key.nullable()),
+            Aggregator::ArrayAgg | Aggregator::OrderedArrayAgg(_) => self.aggregate_type_def(),
+            Aggregator::RustUdaf(ref udaf) => udaf.ret.clone(),
         }
     }
 
-    fn bin_aggregating_expression(&self) -> syn::Expr {
+    pub(crate) fn bin_aggregating_expression(&self) -> syn::Expr {
         let input_nullable = self.incoming_expression.nullable();
         match (&self.aggregator, input_nullable) {
             (Aggregator::Count, _)
@@ -836,6 +1079,57 @@ impl TwoPhaseAggregation {
             (Aggregator::Avg, false) => parse_quote!({ (arg.1 as f64) / (arg.0 as f64) }),
             (Aggregator::CountDistinct, true) => todo!(),
             (Aggregator::CountDistinct, false) => todo!(),
+            (Aggregator::ApproxCountDistinct, _) => parse_quote!(arg.count()),
+            (Aggregator::ApproxPercentile(percentile), _) => {
+                let aggregate_type = self.aggregate_type();
+                parse_quote!(arg.quantile(#percentile).map(|value| value as #aggregate_type))
+            }
+            (Aggregator::Variance, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_aggregate(arg, true)
+            }),
+            (Aggregator::VariancePop, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_aggregate(arg, false)
+            }),
+            (Aggregator::Stddev, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_aggregate(arg, true)
+                    .map(f64::sqrt)
+            }),
+            (Aggregator::StddevPop, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::variance_aggregate(arg, false)
+                    .map(f64::sqrt)
+            }),
+            (Aggregator::MinBy(key), _) | (Aggregator::MaxBy(key), _) => {
+                match (input_nullable, key.nullable()) {
+                    (true, _) => parse_quote!(arg.as_ref().and_then(|(_, value)| value.clone())),
+                    (false, true) => parse_quote!(arg.as_ref().map(|(_, value)| value.clone())),
+                    (false, false) => parse_quote!(arg.as_ref().unwrap().1.clone()),
+                }
+            }
+            (Aggregator::BoolAnd, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bool_and_aggregate(arg)
+            }),
+            (Aggregator::BoolOr, _) => parse_quote!({
+                arroyo_worker::operators::aggregating_window::bool_or_aggregate(arg)
+            }),
+            (Aggregator::ArrayAgg, _) => parse_quote!(arg.clone()),
+            (Aggregator::OrderedArrayAgg(sort_expressions), _) => {
+                // sort_by is stable, so rows with equal keys stay in arrival order
+                let comparisons = sort_expressions.iter().enumerate().map(|(i, sort)| {
+                    let i = syn::Index::from(i);
+                    sort.comparison_syn_expr(parse_quote!(left.0.#i), parse_quote!(right.0.#i))
+                });
+                parse_quote!({
+                    let mut bin = arg.clone();
+                    bin.sort_by(|left, right| {
+                        std::cmp::Ordering::Equal #(.then_with(|| #comparisons))*
+                    });
+                    bin.into_iter().map(|(_, value)| value).collect::<Vec<_>>()
+                })
+            }
+            (Aggregator::RustUdaf(udaf), _) => {
+                let path = udaf.path();
+                parse_quote!(#path::finish(arg.clone()))
+            }
         }
     }
 
@@ -873,6 +1167,47 @@ impl TwoPhaseAggregation {
             (Aggregator::Avg, false) => parse_quote!({ (arg.1 as f64) / (arg.0 as f64) }),
             (Aggregator::CountDistinct, true) => unimplemented!(),
             (Aggregator::CountDistinct, false) => unimplemented!(),
+            (Aggregator::Variance, _)
+            | (Aggregator::VariancePop, _)
+            | (Aggregator::Stddev, _)
+            | (Aggregator::StddevPop, _)
+            | (Aggregator::BoolAnd, _)
+            | (Aggregator::BoolOr, _) => self.bin_aggregating_expression(),
+            (Aggregator::ApproxCountDistinct, _)
+            | (Aggregator::ApproxPercentile(_), _)
+            | (Aggregator::MinBy(_), _)
+            | (Aggregator::MaxBy(_), _)
+            | (Aggregator::ArrayAgg, _)
+            | (Aggregator::OrderedArrayAgg(_), _)
+            | (Aggregator::RustUdaf(_), _) => {
+                // merge the queue of bins, then aggregate as if it were a single bin.
+                let combine_expr = self.combine_bin_syn_expr();
+                let bin_aggregating_expr = self.bin_aggregating_expression();
+                parse_quote!({
+                    let bin = arg
+                        .iter()
+                        .cloned()
+                        .reduce(|current_bin, new_bin| #combine_expr)
+                        .unwrap();
+                    let arg = &bin;
+                    #bin_aggregating_expr
+                })
+            }
         }
     }
+
+    /// Aggregates `arg: Vec<T>` by folding every element into a single bin.
+    pub(crate) fn vec_aggregating_syn_expression(&self) -> syn::Expr {
+        let bin_expr = self.bin_syn_expr();
+        let bin_type = self.bin_type();
+        let bin_aggregating_expr = self.bin_aggregating_expression();
+        parse_quote!({
+            let bin = arg
+                .iter()
+                .fold(None::<#bin_type>, |current_bin, arg| Some(#bin_expr))
+                .unwrap();
+            let arg = &bin;
+            #bin_aggregating_expr
+        })
+    }
 }
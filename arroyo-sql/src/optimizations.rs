@@ -13,7 +13,7 @@ use petgraph::Direction::{self, Incoming, Outgoing};
 use quote::quote;
 
 use crate::operators::{AggregateProjection, GroupByKind, Projection, TwoPhaseAggregateProjection};
-use crate::pipeline::RecordTransform;
+use crate::pipeline::{RecordTransform, WindowFunction};
 use crate::plan_graph::{
     FusedRecordTransform, PlanEdge, PlanNode, PlanOperator, PlanType, WindowFunctionOperator,
 };
@@ -357,7 +357,13 @@ impl Optimizer for WindowTopNOptimization {
                 }
             }
             SearchTarget::WindowFunctionOperator => {
-                if let PlanOperator::WindowFunction(window_function_operator) = node.operator {
+                if let PlanOperator::WindowFunction(
+                    window_function_operator @ WindowFunctionOperator {
+                        window_function: WindowFunction::RowNumber,
+                        ..
+                    },
+                ) = node.operator
+                {
                     let _field_name = window_function_operator.field_name.clone();
                     self.window_function_operator = Some(window_function_operator);
                     self.nodes.push(node_index);
@@ -7,32 +7,49 @@ use std::unreachable;
 use anyhow::Result;
 use anyhow::{anyhow, bail};
 use arrow_schema::DataType;
-use arroyo_datastream::{Operator, WindowType};
+use arroyo_datastream::{Operator, SerializationMode, WindowType};
 
+use datafusion::optimizer::utils::split_conjunction;
+use datafusion_common::tree_node::{TreeNode, VisitRecursion};
 use datafusion_common::{DFField, ScalarValue};
-use datafusion_expr::expr::ScalarUDF;
-use datafusion_expr::{BuiltInWindowFunction, Expr, JoinConstraint, LogicalPlan, Window, WriteOp};
+use datafusion_expr::expr::{
+    AggregateFunction, AggregateUDF, Between, BinaryExpr, Cast, ScalarUDF,
+};
+use datafusion_expr::{
+    BuiltInWindowFunction, Expr, JoinConstraint, LogicalPlan, Window, WindowFrameBound,
+    WindowFrameUnits, WriteOp,
+};
 
 use quote::{format_ident, quote};
 use syn::{parse_quote, Type};
 
 use crate::expressions::ExpressionContext;
-use crate::external::{SqlSink, SqlSource};
+use crate::external::{SqlLookupSource, SqlSink, SqlSource};
 use crate::{
-    expressions::{AggregationExpression, Column, ColumnExpression, Expression, SortExpression},
-    operators::{AggregateProjection, GroupByKind, Projection, TwoPhaseAggregateProjection},
-    types::{interval_month_day_nanos_to_duration, StructDef, StructField, TypeDef},
+    expressions::{
+        AggregationExpression, CastExpression, Column, ColumnExpression, Expression, SortExpression,
+    },
+    operators::{
+        AggregateProjection, GroupByKind, Projection, TwoPhaseAggregateProjection,
+        TwoPhaseAggregation,
+    },
+    schemas::window_type_def,
+    types::{interval_month_day_nanos_to_duration, StructDef, StructField, TypeDef, JSON_TYPE},
     ArroyoSchemaProvider,
 };
-use crate::{FieldSpec, Table};
+use crate::{AsyncUdfOptions, FieldSpec, Table};
 
 #[derive(Debug, Clone)]
 pub enum SqlOperator {
     Source(SourceOperator),
     Aggregator(Box<SqlOperator>, AggregateOperator),
+    UpdatingAggregator(Box<SqlOperator>, UpdatingAggregateOperator),
     JoinOperator(Box<SqlOperator>, Box<SqlOperator>, JoinOperator),
     Window(Box<SqlOperator>, SqlWindowOperator),
     RecordTransform(Box<SqlOperator>, RecordTransform),
+    Unnest(Box<SqlOperator>, UnnestOperator),
+    AsyncProjection(Box<SqlOperator>, AsyncProjectionOperator),
+    LookupJoin(Box<SqlOperator>, LookupJoinOperator),
     Sink(String, SqlSink, Box<SqlOperator>),
     NamedTable(String, Box<SqlOperator>),
 }
@@ -49,16 +66,29 @@ pub enum RecordTransform {
 pub struct SourceOperator {
     pub name: String,
     pub source: SqlSource,
-    pub virtual_field_projection: Option<Projection>,
+    /// Applied to the source's records in order, to parse raw JSON and compute virtual fields.
+    pub projections: Vec<Projection>,
     pub timestamp_override: Option<Expression>,
-    pub watermark_column: Option<Expression>,
+    pub watermark: Option<SourceWatermark>,
+    pub idle_time: Option<Duration>,
+}
+
+/// How a source with a declared event time generates watermarks.
+#[derive(Debug, Clone)]
+pub enum SourceWatermark {
+    /// The watermark trails the event time by a fixed duration.
+    FixedLateness(Duration),
+    /// The watermark is computed from each record.
+    Expression(Expression),
+    /// The source computes the watermark from the message timestamps of each of its partitions,
+    /// trailing the slowest one by a fixed duration.
+    PerPartition(Duration),
 }
 impl SourceOperator {
     fn return_type(&self) -> StructDef {
-        if let Some(ref projection) = self.virtual_field_projection {
-            projection.output_struct()
-        } else {
-            self.source.struct_def.clone()
+        match self.projections.last() {
+            Some(projection) => projection.output_struct(),
+            None => self.source.struct_def.clone(),
         }
     }
 }
@@ -106,6 +136,125 @@ impl RecordTransform {
     }
 }
 
+/// Emits a record for each element of the list in `unnest_field`, with the other fields copied.
+#[derive(Debug, Clone)]
+pub struct UnnestOperator {
+    pub unnest_field: StructField,
+}
+
+impl UnnestOperator {
+    pub fn output_struct(&self, input_struct: &StructDef) -> StructDef {
+        let element_type = self.unnest_field.data_type.list_element_type().unwrap();
+        StructDef {
+            name: None,
+            fields: input_struct
+                .fields
+                .iter()
+                .map(|field| {
+                    if *field == self.unnest_field {
+                        StructField {
+                            data_type: element_type.clone(),
+                            ..field.clone()
+                        }
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_syn_expression(&self, input_struct: &StructDef) -> syn::Expr {
+        let output_struct = self.output_struct(input_struct);
+        let output_type = output_struct.get_type();
+        let list_ident = self.unnest_field.field_ident();
+        let assignments = input_struct.fields.iter().map(|field| {
+            let ident = field.field_ident();
+            if *field == self.unnest_field {
+                quote!(#ident: element.clone())
+            } else {
+                quote!(#ident: arg.#ident.clone())
+            }
+        });
+        let elements = if self.unnest_field.nullable() {
+            quote!(arg.#list_ident.iter().flatten())
+        } else {
+            quote!(arg.#list_ident.iter())
+        };
+        parse_quote!(#elements
+            .map(|element| #output_type { #(#assignments),* })
+            .collect::<Vec<_>>())
+    }
+}
+
+/// A projection that calls async UDFs, evaluated concurrently for up to `max_concurrency` rows.
+#[derive(Debug, Clone)]
+pub struct AsyncProjectionOperator {
+    pub projection: Projection,
+    pub options: AsyncUdfOptions,
+}
+
+/// Joins each input row with the row of a lookup table whose primary key equals `key`.
+#[derive(Debug, Clone)]
+pub struct LookupJoinOperator {
+    pub source: SqlLookupSource,
+    pub key: Expression,
+    pub join_type: JoinType,
+    /// The lookup table's fields as they appear in the output of the join.
+    pub lookup_struct: StructDef,
+}
+
+impl LookupJoinOperator {
+    pub fn output_struct(&self, input_struct: &StructDef) -> StructDef {
+        self.join_type
+            .output_struct(input_struct, &self.lookup_struct)
+    }
+
+    pub fn key_syn_expression(&self) -> syn::Expr {
+        let key = self.key.to_syn_expression();
+        if self.key.nullable() {
+            parse_quote!(#key.map(|key| key.to_string()))
+        } else {
+            parse_quote!(Some(#key.to_string()))
+        }
+    }
+
+    // Builds the output from `arg` and `lookup`, the matching row if there is one.
+    pub fn merge_syn_expression(&self, input_struct: &StructDef) -> syn::Expr {
+        let output_type = self.output_struct(input_struct).get_type();
+        let mut assignments: Vec<_> = input_struct
+            .fields
+            .iter()
+            .map(|field| {
+                let ident = field.field_ident();
+                quote!(#ident: arg.#ident.clone())
+            })
+            .collect();
+        for field in &self.lookup_struct.fields {
+            let ident = field.field_ident();
+            // the looked up row has the table's unqualified field names
+            let lookup_ident = StructField {
+                alias: None,
+                ..field.clone()
+            }
+            .field_ident();
+            assignments.push(match self.join_type {
+                JoinType::Left if field.data_type.is_optional() => {
+                    quote!(#ident: lookup.and_then(|lookup| lookup.#lookup_ident.clone()))
+                }
+                JoinType::Left => {
+                    quote!(#ident: lookup.map(|lookup| lookup.#lookup_ident.clone()))
+                }
+                _ => quote!(#ident: lookup.#lookup_ident.clone()),
+            });
+        }
+        match self.join_type {
+            JoinType::Left => parse_quote!(Some(#output_type { #(#assignments),* })),
+            _ => parse_quote!(lookup.map(|lookup| #output_type { #(#assignments),* })),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AggregateOperator {
     pub key: Projection,
@@ -121,6 +270,21 @@ impl AggregateOperator {
     }
 }
 
+/// A GROUP BY without a window. Its rows are updated as records arrive, so each output
+/// row replaces the previous row with the same key.
+#[derive(Debug, Clone)]
+pub struct UpdatingAggregateOperator {
+    pub key: Projection,
+    pub aggregating: TwoPhaseAggregateProjection,
+}
+
+impl UpdatingAggregateOperator {
+    pub fn output_struct(&self) -> StructDef {
+        GroupByKind::Basic
+            .output_struct(&self.key.output_struct(), &self.aggregating.output_struct())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum AggregatingStrategy {
     AggregateProjection(AggregateProjection),
@@ -142,6 +306,49 @@ impl AggregatingStrategy {
 #[derive(Debug, Clone)]
 pub enum WindowFunction {
     RowNumber,
+    Rank,
+    DenseRank,
+    Lag {
+        expression: Expression,
+        offset: usize,
+        default: Option<Expression>,
+    },
+    Lead {
+        expression: Expression,
+        offset: usize,
+        default: Option<Expression>,
+    },
+    Aggregate {
+        aggregation: AggregationExpression,
+        frame: WindowFrame,
+    },
+}
+
+impl WindowFunction {
+    pub fn return_type(&self) -> TypeDef {
+        match self {
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => {
+                TypeDef::DataType(DataType::UInt64, false)
+            }
+            WindowFunction::Lag { expression, .. } | WindowFunction::Lead { expression, .. } => {
+                expression.return_type().as_nullable()
+            }
+            WindowFunction::Aggregate { aggregation, .. } => aggregation.return_type(),
+        }
+    }
+}
+
+/// The rows an aggregate window function is computed over. Frames end at the current row,
+/// or for RANGE frames at the last row with the same ordering value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFrame {
+    /// The current row and up to this many preceding rows, or all preceding rows if None.
+    Rows(Option<usize>),
+    /// Rows whose ordering value is within this duration of the current row's,
+    /// or all preceding rows if None.
+    Range(Option<Duration>),
+    /// Every row in the partition.
+    Partition,
 }
 
 #[derive(Debug, Clone)]
@@ -157,6 +364,9 @@ pub struct SqlWindowOperator {
     pub order_by: Vec<SortExpression>,
     pub field_name: String,
     pub window: WindowType,
+    /// Whether the function is computed over each partition in event time order as the
+    /// watermark advances, rather than over the rows of a single window.
+    pub incremental: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -164,6 +374,21 @@ pub struct JoinOperator {
     pub left_key: Projection,
     pub right_key: Projection,
     pub join_type: JoinType,
+    pub interval: Option<JoinInterval>,
+}
+
+/// How far apart the timestamps of joined rows may be: each left row joins the right rows
+/// whose timestamps are between `lower_bound` before and `upper_bound` after its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinInterval {
+    pub lower_bound: Duration,
+    pub upper_bound: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JoinSide {
+    Left,
+    Right,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -303,6 +528,9 @@ impl SqlOperator {
                     &aggregate_operator.aggregating.output_struct(),
                 )
             }
+            SqlOperator::UpdatingAggregator(_input, aggregate_operator) => {
+                aggregate_operator.output_struct()
+            }
             SqlOperator::JoinOperator(left, right, operator) => operator
                 .join_type
                 .output_struct(&left.return_type(), &right.return_type()),
@@ -311,13 +539,20 @@ impl SqlOperator {
                 input_struct.fields.push(StructField {
                     name: window.field_name.clone(),
                     alias: None,
-                    data_type: TypeDef::DataType(DataType::UInt64, false),
+                    data_type: window.window_fn.return_type(),
                 });
                 input_struct
             }
             SqlOperator::RecordTransform(input, record_transform) => {
                 record_transform.output_struct(input.return_type())
             }
+            SqlOperator::Unnest(input, unnest) => unnest.output_struct(&input.return_type()),
+            SqlOperator::AsyncProjection(_, async_projection) => {
+                async_projection.projection.output_struct()
+            }
+            SqlOperator::LookupJoin(input, lookup_join) => {
+                lookup_join.output_struct(&input.return_type())
+            }
             SqlOperator::Sink(_, sql_sink, _) => sql_sink.struct_def.clone(),
             SqlOperator::NamedTable(_table_name, table) => table.return_type(),
         }
@@ -353,13 +588,115 @@ impl SqlOperator {
         match self {
             SqlOperator::Source(_) => false,
             SqlOperator::Aggregator(_, _) => true,
+            SqlOperator::UpdatingAggregator(_, _) => false,
             SqlOperator::JoinOperator(left, right, _) => left.has_window() || right.has_window(),
             SqlOperator::Window(_, _) => true,
-            SqlOperator::RecordTransform(input, _) => input.has_window(),
+            SqlOperator::RecordTransform(input, _)
+            | SqlOperator::Unnest(input, _)
+            | SqlOperator::AsyncProjection(input, _)
+            | SqlOperator::LookupJoin(input, _) => input.has_window(),
             SqlOperator::Sink(_, _, input) => input.has_window(),
             SqlOperator::NamedTable(_, input) => input.has_window(),
         }
     }
+
+    /// Whether the operator's rows may be updated by later rows with the same key.
+    pub fn is_updating(&self) -> bool {
+        match self {
+            SqlOperator::Source(_) => false,
+            SqlOperator::UpdatingAggregator(_, _) => true,
+            SqlOperator::Aggregator(input, _)
+            | SqlOperator::Window(input, _)
+            | SqlOperator::RecordTransform(input, _)
+            | SqlOperator::Unnest(input, _)
+            | SqlOperator::AsyncProjection(input, _)
+            | SqlOperator::LookupJoin(input, _)
+            | SqlOperator::Sink(_, _, input)
+            | SqlOperator::NamedTable(_, input) => input.is_updating(),
+            SqlOperator::JoinOperator(left, right, _) => left.is_updating() || right.is_updating(),
+        }
+    }
+
+    /// For updating operators, a projection of the fields that identify the row an output
+    /// row replaces, or None if they aren't all part of the output.
+    pub fn upsert_key(&self) -> Option<Projection> {
+        match self {
+            SqlOperator::UpdatingAggregator(_, aggregate) => {
+                let output_struct = aggregate.output_struct();
+                let key_fields = aggregate.key.field_names.len();
+                Some(Projection {
+                    field_names: aggregate.key.field_names.clone(),
+                    field_computations: output_struct.fields[..key_fields]
+                        .iter()
+                        .map(|field| Expression::Column(ColumnExpression::new(field.clone())))
+                        .collect(),
+                })
+            }
+            SqlOperator::RecordTransform(input, RecordTransform::ValueProjection(projection)) => {
+                let key = input.upsert_key()?;
+                let output_struct = projection.output_struct();
+                // the key fields must be passed through unchanged
+                let field_computations = key
+                    .field_computations
+                    .iter()
+                    .map(|key_computation| {
+                        let Expression::Column(key_column) = key_computation else {
+                            return None;
+                        };
+                        let index =
+                            projection
+                                .field_computations
+                                .iter()
+                                .position(|computation| {
+                                    matches!(computation, Expression::Column(column)
+                                if column.column_field() == key_column.column_field())
+                                })?;
+                        Some(Expression::Column(ColumnExpression::new(
+                            output_struct.fields[index].clone(),
+                        )))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Projection {
+                    field_names: key.field_names,
+                    field_computations,
+                })
+            }
+            SqlOperator::RecordTransform(input, _) | SqlOperator::NamedTable(_, input) => {
+                input.upsert_key()
+            }
+            _ => None,
+        }
+    }
+}
+
+// Raw JSON sources read their value as text, so their JSON columns are read as strings and
+// parsed once, by the returned projection, rather than by each expression that uses them.
+fn raw_json_projection(source: &mut SqlSource) -> Result<Option<Projection>> {
+    let is_json = |field: &StructField| field.data_type.as_datatype() == Some(&JSON_TYPE);
+    if source.serialization_mode != SerializationMode::RawJson
+        || !source.struct_def.fields.iter().any(is_json)
+    {
+        return Ok(None);
+    }
+    let mut field_names = vec![];
+    let mut field_computations = vec![];
+    for field in &mut source.struct_def.fields {
+        field_names.push(Column {
+            relation: None,
+            name: field.name.clone(),
+        });
+        if is_json(field) {
+            field.data_type = TypeDef::DataType(DataType::Utf8, field.nullable());
+            let text = Expression::Column(ColumnExpression::new(field.clone()));
+            field_computations.push(CastExpression::new(Box::new(text), &JSON_TYPE)?);
+        } else {
+            field_computations.push(Expression::Column(ColumnExpression::new(field.clone())));
+        }
+    }
+    Ok(Some(Projection {
+        field_names,
+        field_computations,
+    }))
 }
 
 #[derive(Debug)]
@@ -382,6 +719,7 @@ impl<'a> SqlPipelineBuilder<'a> {
         ExpressionContext {
             schema_provider: self.schema_provider,
             input_struct,
+            allow_async_udfs: false,
         }
     }
 
@@ -433,7 +771,9 @@ impl<'a> SqlPipelineBuilder<'a> {
                 }
             },
             LogicalPlan::Values(_) => bail!("values are not currently supported"),
-            LogicalPlan::Explain(_) => bail!("explain is not currently supported"),
+            LogicalPlan::Explain(_) => {
+                bail!("EXPLAIN is only supported at the top level of a query")
+            }
             LogicalPlan::Analyze(_) => bail!("analyze is not currently supported"),
             LogicalPlan::Extension(_) => bail!("extensions are not currently supported"),
             LogicalPlan::Distinct(_) => bail!("distinct is not currently supported"),
@@ -441,7 +781,7 @@ impl<'a> SqlPipelineBuilder<'a> {
             LogicalPlan::Prepare(_) => bail!("prepare commands are not currently supported"),
             LogicalPlan::Dml(dml) => self.insert_dml(dml),
             LogicalPlan::DescribeTable(_) => bail!("describe table not currently supported"),
-            LogicalPlan::Unnest(_) => bail!("unnest not currently supported"),
+            LogicalPlan::Unnest(unnest) => self.insert_unnest(unnest),
             LogicalPlan::Statement(_) => bail!("statements not currently supported"),
         }
     }
@@ -492,7 +832,12 @@ impl<'a> SqlPipelineBuilder<'a> {
                 fields: _,
                 connection: _,
                 connection_config: _,
+                watermark: _,
+                idle_time: _,
             } => todo!(),
+            crate::Table::LookupTable { name, .. } => {
+                bail!("can't insert into lookup table {}", name)
+            }
             crate::Table::TableFromQuery {
                 name: _,
                 logical_plan: _,
@@ -512,7 +857,7 @@ impl<'a> SqlPipelineBuilder<'a> {
         let input = self.insert_sql_plan(&filter.input)?;
         let struct_def = input.return_type();
         let ctx = self.ctx(&struct_def);
-        let predicate = ctx.compile_expr(&filter.predicate)?;
+        let predicate = ctx.compile_predicate(&filter.predicate)?;
         // TODO: this should probably happen through a more principled optimization pass.
         Ok(SqlOperator::RecordTransform(
             Box::new(input),
@@ -527,7 +872,10 @@ impl<'a> SqlPipelineBuilder<'a> {
         let input = self.insert_sql_plan(&projection.input)?;
 
         let struct_def = input.return_type();
-        let ctx = self.ctx(&struct_def);
+        let ctx = ExpressionContext {
+            allow_async_udfs: true,
+            ..self.ctx(&struct_def)
+        };
 
         let functions = projection
             .expr
@@ -542,28 +890,107 @@ impl<'a> SqlPipelineBuilder<'a> {
             .map(|field| Column::convert(&field.qualified_column()))
             .collect();
 
+        let async_options = self.async_udf_options(&projection.expr);
         let projection = Projection {
             field_names: names,
             field_computations: functions,
         };
 
+        if let Some(options) = async_options {
+            if input.is_updating() {
+                bail!("async UDFs can't be called on the results of a non-windowed GROUP BY");
+            }
+            return Ok(SqlOperator::AsyncProjection(
+                Box::new(input),
+                AsyncProjectionOperator {
+                    projection,
+                    options,
+                },
+            ));
+        }
+
         Ok(SqlOperator::RecordTransform(
             Box::new(input),
             RecordTransform::ValueProjection(projection),
         ))
     }
 
+    // If the expressions call async UDFs, the options to evaluate them with: ordered if any of
+    // them are, and with the smallest concurrency.
+    fn async_udf_options(&self, exprs: &[Expr]) -> Option<AsyncUdfOptions> {
+        let mut options: Option<AsyncUdfOptions> = None;
+        for expr in exprs {
+            expr.apply(&mut |expr| {
+                if let Expr::ScalarUDF(ScalarUDF { fun, .. }) = expr {
+                    let udf_options = self
+                        .schema_provider
+                        .udf_defs
+                        .get(&fun.name)
+                        .and_then(|def| def.async_options);
+                    if let Some(udf_options) = udf_options {
+                        options = Some(match options {
+                            Some(current) => AsyncUdfOptions {
+                                ordered: current.ordered || udf_options.ordered,
+                                max_concurrency: current
+                                    .max_concurrency
+                                    .min(udf_options.max_concurrency),
+                            },
+                            None => udf_options,
+                        });
+                    }
+                }
+                Ok(VisitRecursion::Continue)
+            })
+            .unwrap();
+        }
+        options
+    }
+
+    fn insert_unnest(
+        &mut self,
+        unnest: &datafusion_expr::logical_plan::Unnest,
+    ) -> Result<SqlOperator> {
+        let input = self.insert_sql_plan(&unnest.input)?;
+        let unnest_field = input.return_type().get_field(
+            unnest
+                .column
+                .relation
+                .as_ref()
+                .map(|table| table.to_string()),
+            &unnest.column.name,
+        )?;
+        if unnest_field.data_type.list_element_type().is_none() {
+            bail!(
+                "unnest requires an array, found {:?}",
+                unnest_field.data_type
+            );
+        }
+        Ok(SqlOperator::Unnest(
+            Box::new(input),
+            UnnestOperator { unnest_field },
+        ))
+    }
+
     fn insert_aggregation(
         &mut self,
         aggregate: &datafusion_expr::logical_plan::Aggregate,
     ) -> Result<SqlOperator> {
         let source = self.insert_sql_plan(&aggregate.input)?;
+        if source.is_updating() {
+            bail!("aggregating the results of a non-windowed GROUP BY is not supported");
+        }
         let key = self.aggregation_key(
             &aggregate.group_expr,
             aggregate.schema.fields(),
             &source.return_type(),
         )?;
 
+        // without a window, aggregates are updated as records arrive. Windowed inputs keep
+        // being aggregated per window, as all the rows of a window have the same timestamp.
+        if !source.has_window() && !aggregate.group_expr.iter().any(Self::is_window) {
+            return self.insert_updating_aggregation(aggregate, source, key);
+        }
+
         let window = self.window(&aggregate.group_expr)?;
 
         let group_count = aggregate.group_expr.len();
@@ -598,6 +1025,44 @@ impl<'a> SqlPipelineBuilder<'a> {
         ))
     }
 
+    fn insert_updating_aggregation(
+        &mut self,
+        aggregate: &datafusion_expr::logical_plan::Aggregate,
+        source: SqlOperator,
+        key: Projection,
+    ) -> Result<SqlOperator> {
+        let input_struct = source.return_type();
+        let mut ctx = self.ctx(&input_struct);
+        let group_count = aggregate.group_expr.len();
+        let field_names = aggregate.schema.fields()[group_count..]
+            .iter()
+            .map(|field| Column::convert(&field.qualified_column()))
+            .collect();
+        let field_computations = aggregate
+            .aggr_expr
+            .iter()
+            .map(|expr| {
+                let aggregation = AggregationExpression::try_from_expression(&mut ctx, expr)?;
+                TwoPhaseAggregation::try_from(aggregation).map_err(|_| {
+                    anyhow!(
+                        "{} can't be computed incrementally, so it requires a window",
+                        expr
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(SqlOperator::UpdatingAggregator(
+            Box::new(source),
+            UpdatingAggregateOperator {
+                key,
+                aggregating: TwoPhaseAggregateProjection {
+                    field_names,
+                    field_computations,
+                },
+            },
+        ))
+    }
+
     fn aggregation_key(
         &mut self,
         group_expressions: &[Expr],
@@ -704,7 +1169,7 @@ impl<'a> SqlPipelineBuilder<'a> {
             _ => Ok(None),
         }
     }
-    fn get_duration(expression: &Expr) -> Result<Duration> {
+    pub(crate) fn get_duration(expression: &Expr) -> Result<Duration> {
         match expression {
             Expr::Literal(ScalarValue::IntervalDayTime(Some(val))) => {
                 Ok(Duration::from_millis(*val as u64))
@@ -720,15 +1185,24 @@ impl<'a> SqlPipelineBuilder<'a> {
     }
 
     fn insert_join(&mut self, join: &datafusion_expr::logical_plan::Join) -> Result<SqlOperator> {
+        if let Some(source) = self.lookup_source(&join.right)? {
+            return self.insert_lookup_join(join, source);
+        }
+        if self.lookup_source(&join.left)?.is_some() {
+            bail!("lookup tables must be on the right side of a join");
+        }
         let left_input = self.insert_sql_plan(&join.left)?;
         let right_input = self.insert_sql_plan(&join.right)?;
+        if left_input.is_updating() || right_input.is_updating() {
+            bail!("joining the results of a non-windowed GROUP BY is not supported");
+        }
         match join.join_constraint {
             JoinConstraint::On => {}
             JoinConstraint::Using => bail!("don't support 'using' in joins"),
         };
         let join_type = join.join_type.try_into()?;
         // check supported join types
-        match (left_input.has_window(), right_input.has_window()) {
+        let interval = match (left_input.has_window(), right_input.has_window()) {
             (true, false) | (false, true) => {
                 bail!("windowing join mismatch. both sides must either have or not have windows")
             }
@@ -736,9 +1210,10 @@ impl<'a> SqlPipelineBuilder<'a> {
                 if join_type != JoinType::Inner {
                     bail!("non-inner join over windows not supported")
                 }
+                self.join_interval(join)?
             }
-            _ => {}
-        }
+            _ => None,
+        };
 
         let join_projection_field_names: Vec<_> = join
             .on
@@ -779,19 +1254,337 @@ impl<'a> SqlPipelineBuilder<'a> {
                 left_key,
                 right_key,
                 join_type,
+                interval,
             },
         );
+        // the filter is kept for interval joins, as the bounds may be exclusive
         let Some(join_filter) = &join.filter else {
             return Ok(join_operator)
         };
         let join_filter = self
             .ctx(&join_operator.return_type())
-            .compile_expr(join_filter)?;
+            .compile_predicate(join_filter)?;
         Ok(SqlOperator::RecordTransform(
             Box::new(join_operator),
             RecordTransform::Filter(join_filter),
         ))
     }
+
+    // Returns the lookup table that a join input reads, if it is one.
+    fn lookup_source(&self, plan: &LogicalPlan) -> Result<Option<SqlLookupSource>> {
+        match plan {
+            LogicalPlan::SubqueryAlias(subquery_alias) => self.lookup_source(&subquery_alias.input),
+            LogicalPlan::TableScan(table_scan) => {
+                let table_name = table_scan.table_name.to_string();
+                let Some(Table::LookupTable { source, .. }) =
+                    self.schema_provider.get_table(&table_name)
+                else {
+                    return Ok(None);
+                };
+                if !table_scan.filters.is_empty() {
+                    bail!(
+                        "filters can't be pushed down to lookup table {}",
+                        table_name
+                    );
+                }
+                Ok(Some(source.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn insert_lookup_join(
+        &mut self,
+        join: &datafusion_expr::logical_plan::Join,
+        source: SqlLookupSource,
+    ) -> Result<SqlOperator> {
+        let input = self.insert_sql_plan(&join.left)?;
+        match join.join_constraint {
+            JoinConstraint::On => {}
+            JoinConstraint::Using => bail!("don't support 'using' in joins"),
+        };
+        let join_type: JoinType = join.join_type.try_into()?;
+        if !matches!(join_type, JoinType::Inner | JoinType::Left) {
+            bail!("lookup joins must be inner or left joins");
+        }
+        let [(input_key, Expr::Column(lookup_column))] = join.on.as_slice() else {
+            bail!(
+                "lookup joins must have a single equality condition on the primary key {}",
+                source.key_field.name
+            );
+        };
+        if lookup_column.name != source.key_field.name {
+            bail!(
+                "lookup joins must be on the primary key {}, not {}",
+                source.key_field.name,
+                lookup_column.name
+            );
+        }
+        let key = self.ctx(&input.return_type()).compile_expr(input_key)?;
+        match key.return_type() {
+            TypeDef::DataType(
+                DataType::Utf8
+                | DataType::Boolean
+                | DataType::Int8
+                | DataType::Int16
+                | DataType::Int32
+                | DataType::Int64
+                | DataType::UInt8
+                | DataType::UInt16
+                | DataType::UInt32
+                | DataType::UInt64,
+                _,
+            ) => {}
+            key_type => bail!(
+                "lookup keys must be strings, integers or booleans, not {:?}",
+                key_type
+            ),
+        }
+
+        let lookup_struct = StructDef {
+            name: None,
+            fields: join
+                .right
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| {
+                    let column = Column::convert(&field.qualified_column());
+                    StructField {
+                        alias: column.relation,
+                        ..field.field().as_ref().into()
+                    }
+                })
+                .collect(),
+        };
+        let lookup_join = SqlOperator::LookupJoin(
+            Box::new(input),
+            LookupJoinOperator {
+                source,
+                key,
+                join_type,
+                lookup_struct,
+            },
+        );
+        let Some(join_filter) = &join.filter else {
+            return Ok(lookup_join);
+        };
+        let join_filter = self
+            .ctx(&lookup_join.return_type())
+            .compile_predicate(join_filter)?;
+        Ok(SqlOperator::RecordTransform(
+            Box::new(lookup_join),
+            RecordTransform::Filter(join_filter),
+        ))
+    }
+
+    // Finds the bounds of an interval join in the join's filter, such as
+    // `b.ts BETWEEN a.ts - INTERVAL '5' MINUTE AND a.ts + INTERVAL '10' MINUTE`. The operator
+    // bounds the records' event times, so the columns have to be the event time of each side.
+    fn join_interval(
+        &self,
+        join: &datafusion_expr::logical_plan::Join,
+    ) -> Result<Option<JoinInterval>> {
+        let Some(filter) = &join.filter else {
+            return Ok(None);
+        };
+        // bounds on the right timestamp minus the left timestamp, in micros
+        let mut lower: Option<i128> = None;
+        let mut upper: Option<i128> = None;
+        for conjunct in split_conjunction(filter) {
+            // pairs of (greater, lesser) expressions
+            let comparisons = match conjunct {
+                Expr::Between(Between {
+                    expr,
+                    negated: false,
+                    low,
+                    high,
+                }) => vec![
+                    (expr.as_ref(), low.as_ref()),
+                    (high.as_ref(), expr.as_ref()),
+                ],
+                Expr::BinaryExpr(BinaryExpr {
+                    left,
+                    op: datafusion_expr::Operator::GtEq | datafusion_expr::Operator::Gt,
+                    right,
+                }) => vec![(left.as_ref(), right.as_ref())],
+                Expr::BinaryExpr(BinaryExpr {
+                    left,
+                    op: datafusion_expr::Operator::LtEq | datafusion_expr::Operator::Lt,
+                    right,
+                }) => vec![(right.as_ref(), left.as_ref())],
+                _ => vec![],
+            };
+            for (greater, lesser) in comparisons {
+                if let (
+                    Some((greater_side, greater_offset, greater_column)),
+                    Some((lesser_side, lesser_offset, lesser_column)),
+                ) = (
+                    Self::join_time_offset(join, greater),
+                    Self::join_time_offset(join, lesser),
+                ) {
+                    if greater_side != lesser_side {
+                        for (side, column) in
+                            [(greater_side, greater_column), (lesser_side, lesser_column)]
+                        {
+                            let input = match side {
+                                JoinSide::Left => &join.left,
+                                JoinSide::Right => &join.right,
+                            };
+                            if !self.is_event_time(input, column) {
+                                bail!(
+                                    "interval joins can only bound the event time of each side, and {} is not the event time of its table",
+                                    column
+                                );
+                            }
+                        }
+                    }
+                    match (greater_side, lesser_side) {
+                        (JoinSide::Right, JoinSide::Left) => {
+                            let bound = lesser_offset - greater_offset;
+                            lower = Some(lower.map_or(bound, |lower| lower.max(bound)));
+                        }
+                        (JoinSide::Left, JoinSide::Right) => {
+                            let bound = greater_offset - lesser_offset;
+                            upper = Some(upper.map_or(bound, |upper| upper.min(bound)));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => {
+                if lower > upper {
+                    bail!("interval join bounds can never be satisfied");
+                }
+                // bounds that exclude the left row's own timestamp are widened to include it;
+                // the filter still removes the extra rows.
+                Ok(Some(JoinInterval {
+                    lower_bound: Duration::from_micros((-lower).max(0) as u64),
+                    upper_bound: Duration::from_micros(upper.max(0) as u64),
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Matches a timestamp column from one side of a join, optionally offset by an interval,
+    // returning the side, the offset in micros and the column.
+    fn join_time_offset<'b>(
+        join: &datafusion_expr::logical_plan::Join,
+        expression: &'b Expr,
+    ) -> Option<(JoinSide, i128, &'b Expr)> {
+        match expression {
+            Expr::Column(column) => {
+                let (side, schema) = if join.left.schema().has_column(column) {
+                    (JoinSide::Left, join.left.schema())
+                } else if join.right.schema().has_column(column) {
+                    (JoinSide::Right, join.right.schema())
+                } else {
+                    return None;
+                };
+                match schema.field_from_column(column).ok()?.data_type() {
+                    DataType::Timestamp(_, _) => Some((side, 0, expression)),
+                    _ => None,
+                }
+            }
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (side, offset, column) = Self::join_time_offset(join, left)?;
+                let interval = Self::get_duration(right).ok()?.as_micros() as i128;
+                match op {
+                    datafusion_expr::Operator::Plus => Some((side, offset + interval, column)),
+                    datafusion_expr::Operator::Minus => Some((side, offset - interval, column)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Whether an expression over a plan's output is the event time of its records: the
+    // event_time_field (or WATERMARK FOR column) of a table, or the datetime of a Nexmark event.
+    fn is_event_time(&self, plan: &LogicalPlan, expr: &Expr) -> bool {
+        match self.table_expression(plan, expr) {
+            Some((
+                Table::MemoryTableWithConnectionConfig {
+                    connection_config, ..
+                },
+                Expr::Column(column),
+            )) => connection_config.get("event_time_field") == Some(&column.name),
+            Some((
+                Table::SavedSource {
+                    source_config: arroyo_datastream::SourceConfig::NexmarkSource { .. },
+                    ..
+                },
+                Expr::GetIndexedField(datafusion_expr::GetIndexedField {
+                    expr,
+                    key: ScalarValue::Utf8(Some(key)),
+                }),
+            )) => {
+                key == "datetime"
+                    && matches!(expr.as_ref(), Expr::Column(column)
+                        if matches!(column.name.as_str(), "person" | "auction" | "bid"))
+            }
+            _ => false,
+        }
+    }
+
+    // Follows an expression over a plan's output back to the table it reads, as long as it only
+    // passes columns and struct fields through, returning the table and the expression in terms
+    // of its columns.
+    fn table_expression(&self, plan: &LogicalPlan, expr: &Expr) -> Option<(&Table, Expr)> {
+        let column = match expr {
+            Expr::Alias(expr, _) => return self.table_expression(plan, expr),
+            Expr::GetIndexedField(datafusion_expr::GetIndexedField { expr, key }) => {
+                let (table, expr) = self.table_expression(plan, expr)?;
+                return Some((
+                    table,
+                    Expr::GetIndexedField(datafusion_expr::GetIndexedField {
+                        expr: Box::new(expr),
+                        key: key.clone(),
+                    }),
+                ));
+            }
+            Expr::Column(column) => column,
+            _ => return None,
+        };
+        let index = plan.schema().index_of_column(column).ok()?;
+        let input_column = |input: &LogicalPlan, index: usize| {
+            Expr::Column(input.schema().field(index).qualified_column())
+        };
+        match plan {
+            LogicalPlan::Projection(projection) => {
+                self.table_expression(&projection.input, &projection.expr[index])
+            }
+            LogicalPlan::Filter(filter) => {
+                self.table_expression(&filter.input, &input_column(&filter.input, index))
+            }
+            LogicalPlan::SubqueryAlias(subquery_alias) => self.table_expression(
+                &subquery_alias.input,
+                &input_column(&subquery_alias.input, index),
+            ),
+            LogicalPlan::TableScan(table_scan) => {
+                let table = self
+                    .schema_provider
+                    .get_table(&table_scan.table_name.to_string())?;
+                if let Table::TableFromQuery { logical_plan, .. } = table {
+                    let index = match &table_scan.projection {
+                        Some(projection) => projection[index],
+                        None => index,
+                    };
+                    return self.table_expression(logical_plan, &input_column(logical_plan, index));
+                }
+                let field = table_scan.projected_schema.field(index);
+                Some((
+                    table,
+                    Expr::Column(datafusion_common::Column::from_name(field.name())),
+                ))
+            }
+            _ => None,
+        }
+    }
+
     fn insert_table_scan(
         &mut self,
         table_scan: &datafusion::logical_expr::TableScan,
@@ -818,13 +1611,15 @@ impl<'a> SqlPipelineBuilder<'a> {
                     },
                     source_config: source_config.clone(),
                     serialization_mode: *serialization_mode,
+                    partition_watermarks: None,
                 };
                 SqlOperator::Source(SourceOperator {
                     name: table_name,
                     source,
-                    virtual_field_projection: None,
+                    projections: vec![],
                     timestamp_override: None,
-                    watermark_column: None,
+                    watermark: None,
+                    idle_time: None,
                 })
             }
             crate::Table::SavedSink {
@@ -846,6 +1641,8 @@ impl<'a> SqlPipelineBuilder<'a> {
                 fields,
                 connection,
                 connection_config,
+                watermark,
+                idle_time,
             } => {
                 let physical_fields = fields
                     .iter()
@@ -855,7 +1652,7 @@ impl<'a> SqlPipelineBuilder<'a> {
                     })
                     .collect::<Vec<_>>();
                 let has_virtual_fields = physical_fields.len() < fields.len();
-                let physical_source = SqlSource::try_new(
+                let mut physical_source = SqlSource::try_new(
                     None,
                     StructDef {
                         name: None,
@@ -864,6 +1661,7 @@ impl<'a> SqlPipelineBuilder<'a> {
                     connection.clone(),
                     connection_config,
                 )?;
+                let json_projection = raw_json_projection(&mut physical_source)?;
                 // check for virtual fields
                 let virtual_field_projection = if has_virtual_fields {
                     let (field_names, field_computations) = fields
@@ -912,9 +1710,9 @@ impl<'a> SqlPipelineBuilder<'a> {
                 } else {
                     None
                 };
-                let watermark_column = if let Some(field_name) =
-                    connection_config.get("watermark_field")
-                {
+                let watermark = if let Some(watermark) = watermark {
+                    Some(watermark.clone())
+                } else if let Some(field_name) = connection_config.get("watermark_field") {
                     // check that a column exists and it is a timestamp
                     let Some(event_column) = fields.iter().find_map(|f| match f {
                         FieldSpec::StructField(struct_field) |
@@ -928,18 +1726,28 @@ impl<'a> SqlPipelineBuilder<'a> {
                     }) else {
                         bail!("watermark_field {} not found or not a timestamp", field_name)
                     };
-                    Some(Expression::Column(ColumnExpression::new(event_column)))
+                    Some(SourceWatermark::Expression(Expression::Column(
+                        ColumnExpression::new(event_column),
+                    )))
                 } else {
                     None
                 };
                 SqlOperator::Source(SourceOperator {
                     name: table_name,
                     source: physical_source,
-                    virtual_field_projection,
+                    projections: json_projection
+                        .into_iter()
+                        .chain(virtual_field_projection)
+                        .collect(),
                     timestamp_override,
-                    watermark_column,
+                    watermark,
+                    idle_time: *idle_time,
                 })
             }
+            crate::Table::LookupTable { name, .. } => bail!(
+                "lookup table {} can only be used on the right side of a join",
+                name
+            ),
             crate::Table::TableFromQuery {
                 name: _,
                 logical_plan,
@@ -988,34 +1796,122 @@ impl<'a> SqlPipelineBuilder<'a> {
 
     fn insert_window(&mut self, window: &Window) -> Result<SqlOperator> {
         let input = self.insert_sql_plan(&window.input)?;
+        if input.is_updating() {
+            bail!("window functions over the results of a non-windowed GROUP BY are not supported");
+        }
+
+        if window.window_expr.len() > 1 {
+            bail!("multiple window functions in a single SELECT not yet supported");
+        }
 
         if let Some(expr) = window.window_expr.get(0) {
+            let expr = match expr {
+                Expr::Alias(expr, _) => expr.as_ref(),
+                expr => expr,
+            };
             match expr {
                 Expr::WindowFunction(w) => {
+                    let window_type = self.window(&w.partition_by)?;
+                    let input_struct = input.return_type();
+                    let mut ctx = self.ctx(&input_struct);
+
                     let window_fn = match &w.fun {
-                        datafusion_expr::WindowFunction::AggregateFunction(_) => {
-                            bail!("window aggregate functions not yet supported")
+                        datafusion_expr::WindowFunction::AggregateFunction(fun) => {
+                            WindowFunction::Aggregate {
+                                aggregation: AggregationExpression::try_from_expression(
+                                    &mut ctx,
+                                    &Expr::AggregateFunction(AggregateFunction::new(
+                                        fun.clone(),
+                                        w.args.clone(),
+                                        false,
+                                        None,
+                                        None,
+                                    )),
+                                )?,
+                                frame: Self::window_frame(&w.window_frame)?,
+                            }
+                        }
+                        datafusion_expr::WindowFunction::AggregateUDF(fun) => {
+                            WindowFunction::Aggregate {
+                                aggregation: AggregationExpression::try_from_expression(
+                                    &mut ctx,
+                                    &Expr::AggregateUDF(AggregateUDF::new(
+                                        fun.clone(),
+                                        w.args.clone(),
+                                        None,
+                                        None,
+                                    )),
+                                )?,
+                                frame: Self::window_frame(&w.window_frame)?,
+                            }
                         }
                         datafusion_expr::WindowFunction::BuiltInWindowFunction(
                             BuiltInWindowFunction::RowNumber,
                         ) => WindowFunction::RowNumber,
-                        datafusion_expr::WindowFunction::BuiltInWindowFunction(w) => {
-                            bail!("Window function {} not yet supported", w);
+                        datafusion_expr::WindowFunction::BuiltInWindowFunction(
+                            BuiltInWindowFunction::Rank,
+                        ) => WindowFunction::Rank,
+                        datafusion_expr::WindowFunction::BuiltInWindowFunction(
+                            BuiltInWindowFunction::DenseRank,
+                        ) => WindowFunction::DenseRank,
+                        datafusion_expr::WindowFunction::BuiltInWindowFunction(
+                            fun @ (BuiltInWindowFunction::Lag | BuiltInWindowFunction::Lead),
+                        ) => {
+                            let expression = ctx.compile_expr(&w.args[0])?;
+                            let offset = match w.args.get(1) {
+                                None => 1,
+                                Some(Expr::Literal(ScalarValue::Int64(Some(offset))))
+                                    if *offset >= 0 =>
+                                {
+                                    *offset as usize
+                                }
+                                Some(offset) => bail!(
+                                    "{} offsets must be non-negative integer literals, not {}",
+                                    fun,
+                                    offset
+                                ),
+                            };
+                            let default = match w.args.get(2) {
+                                None => None,
+                                Some(Expr::Literal(value)) if value.is_null() => None,
+                                Some(default) => {
+                                    let data_type = match expression.return_type() {
+                                        TypeDef::DataType(data_type, _) => data_type,
+                                        TypeDef::StructDef(_, _) => {
+                                            bail!("{} of structs not yet supported", fun)
+                                        }
+                                    };
+                                    Some(ctx.compile_expr(&Expr::Cast(Cast::new(
+                                        Box::new(default.clone()),
+                                        data_type,
+                                    )))?)
+                                }
+                            };
+                            if let BuiltInWindowFunction::Lag = fun {
+                                WindowFunction::Lag {
+                                    expression,
+                                    offset,
+                                    default,
+                                }
+                            } else {
+                                WindowFunction::Lead {
+                                    expression,
+                                    offset,
+                                    default,
+                                }
+                            }
                         }
-                        datafusion_expr::WindowFunction::AggregateUDF(_) => {
-                            bail!("Window UDAFs not yet supported");
+                        datafusion_expr::WindowFunction::BuiltInWindowFunction(w) => {
+                            bail!("window function {} not yet supported", w);
                         }
                     };
 
-                    let input_struct = input.return_type();
-                    let mut ctx = self.ctx(&input_struct);
-
                     let order_by: Vec<_> = w
                         .order_by
                         .iter()
                         .map(|expr| {
                             if let Expr::Sort(sort) = expr {
-                                SortExpression::from_expression(&mut ctx, sort)
+                                SortExpression::from_expression(&ctx, sort)
                             } else {
                                 panic!("expected sort expression, found {:?}", expr);
                             }
@@ -1040,13 +1936,20 @@ impl<'a> SqlPipelineBuilder<'a> {
                         .map(|expression| ctx.compile_expr(expression))
                         .collect::<Result<Vec<_>>>()?;
 
+                    // partitioning by the window of an upstream aggregate bounds each
+                    // partition to that window, whose rows all share a timestamp
+                    let incremental = matches!(window_type, WindowType::Instant)
+                        && !field_computations
+                            .iter()
+                            .any(|computation| computation.return_type() == window_type_def());
+                    Self::validate_window_function(&window_fn, incremental, &order_by)?;
+
                     let partition = Projection {
                         field_names,
                         field_computations,
                     }
                     .without_window();
                     let field_name = window.schema.field_names().last().cloned().unwrap();
-                    let window = self.window(&w.partition_by)?;
 
                     return Ok(SqlOperator::Window(
                         Box::new(input),
@@ -1055,7 +1958,8 @@ impl<'a> SqlPipelineBuilder<'a> {
                             partition,
                             order_by,
                             field_name,
-                            window,
+                            window: window_type,
+                            incremental,
                         },
                     ));
                 }
@@ -1068,6 +1972,84 @@ impl<'a> SqlPipelineBuilder<'a> {
         bail!("no expression for window");
     }
 
+    fn window_frame(frame: &datafusion_expr::WindowFrame) -> Result<WindowFrame> {
+        match (&frame.start_bound, &frame.end_bound) {
+            (WindowFrameBound::Preceding(start), WindowFrameBound::Following(end))
+                if start.is_null() && end.is_null() =>
+            {
+                return Ok(WindowFrame::Partition);
+            }
+            (_, WindowFrameBound::CurrentRow) => {}
+            _ => bail!("window frames must end at the current row, not {}", frame),
+        }
+        match frame.units {
+            WindowFrameUnits::Rows => {
+                let preceding = match &frame.start_bound {
+                    WindowFrameBound::CurrentRow => Some(0),
+                    WindowFrameBound::Preceding(rows) if rows.is_null() => None,
+                    WindowFrameBound::Preceding(ScalarValue::UInt64(Some(rows))) => {
+                        Some(*rows as usize)
+                    }
+                    _ => bail!("unsupported window frame {}", frame),
+                };
+                Ok(WindowFrame::Rows(preceding))
+            }
+            WindowFrameUnits::Range => {
+                let preceding = match &frame.start_bound {
+                    WindowFrameBound::CurrentRow => Some(Duration::ZERO),
+                    WindowFrameBound::Preceding(duration) if duration.is_null() => None,
+                    WindowFrameBound::Preceding(duration) => Some(
+                        Self::get_duration(&Expr::Literal(duration.clone())).map_err(|_| {
+                            anyhow!("RANGE frame offsets must be intervals, not {}", duration)
+                        })?,
+                    ),
+                    WindowFrameBound::Following(_) => {
+                        bail!("unsupported window frame {}", frame)
+                    }
+                };
+                Ok(WindowFrame::Range(preceding))
+            }
+            WindowFrameUnits::Groups => bail!("GROUPS window frames not yet supported"),
+        }
+    }
+
+    fn validate_window_function(
+        window_fn: &WindowFunction,
+        incremental: bool,
+        order_by: &[SortExpression],
+    ) -> Result<()> {
+        if !incremental {
+            // the whole window is available, so functions are computed over the sorted rows
+            if let WindowFunction::Aggregate {
+                frame: WindowFrame::Range(Some(_)),
+                ..
+            } = window_fn
+            {
+                bail!("RANGE frames with an offset are only supported without a time window");
+            }
+            return Ok(());
+        }
+        match window_fn {
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => {
+                return Ok(());
+            }
+            WindowFunction::Lag { .. } | WindowFunction::Lead { .. } => {}
+            WindowFunction::Aggregate { aggregation, frame } => match frame {
+                WindowFrame::Partition => {
+                    bail!("window aggregates without a time window require an ORDER BY")
+                }
+                WindowFrame::Rows(None) | WindowFrame::Range(None) => {
+                    let _: TwoPhaseAggregation = aggregation.clone().try_into()?;
+                }
+                WindowFrame::Rows(Some(_)) | WindowFrame::Range(Some(_)) => {}
+            },
+        }
+        if order_by.iter().any(|sort| sort.descending()) {
+            bail!("window functions without a time window must be ordered by ascending event time");
+        }
+        Ok(())
+    }
+
     fn insert_subquery_alias(
         &mut self,
         subquery_alias: &datafusion_expr::logical_plan::SubqueryAlias,
@@ -1172,7 +2154,10 @@ impl<'a> SqlPipelineBuilder<'a> {
                 fields: _,
                 connection: _,
                 connection_config: _,
+                watermark: _,
+                idle_time: _,
             } => todo!(),
+            Table::LookupTable { .. } => todo!(),
             Table::TableFromQuery {
                 name: _,
                 logical_plan: _,
@@ -1240,6 +2225,8 @@ impl<'a> SqlPipelineBuilder<'a> {
                         fields: _,
                         connection,
                         connection_config,
+                        watermark: _,
+                        idle_time: _,
                     } => {
                         let sql_operator = SqlOperator::Sink(
                             name.clone(),
@@ -1253,6 +2240,9 @@ impl<'a> SqlPipelineBuilder<'a> {
                         );
                         self.output_nodes.push(sql_operator);
                     }
+                    Table::LookupTable { name, .. } => {
+                        bail!("can't insert into lookup table {}", name)
+                    }
                     Table::TableFromQuery {
                         name: _,
                         logical_plan: _,
@@ -1277,7 +2267,7 @@ impl<'a> SqlPipelineBuilder<'a> {
 pub struct MethodCompiler {}
 
 impl MethodCompiler {
-    fn value_map_operator(name: impl ToString, map_expr: syn::Expr) -> Operator {
+    pub fn value_map_operator(name: impl ToString, map_expr: syn::Expr) -> Operator {
         let expression = quote!(
                 {
                     let arg = &record.value;
@@ -1295,6 +2285,31 @@ impl MethodCompiler {
         }
     }
 
+    pub fn async_value_map_operator(
+        name: impl ToString,
+        map_expr: syn::Expr,
+        options: AsyncUdfOptions,
+    ) -> Operator {
+        // evaluated in a future that owns the record
+        let expression = quote!(
+            {
+                let arg = &record.value;
+                let value = #map_expr;
+                arroyo_types::Record {
+                    timestamp: record.timestamp,
+                    key: None,
+                    value
+                }
+            }
+        );
+        Operator::AsyncMapOperator {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            max_concurrency: options.max_concurrency,
+            ordered: options.ordered,
+        }
+    }
+
     pub fn key_map_operator(name: impl ToString, key_expr: syn::Expr) -> Operator {
         let expression = quote!(
             {
@@ -3,29 +3,34 @@ use std::{
     time::Duration,
 };
 
-use arrow_schema::DataType;
 use arroyo_datastream::{
-    EdgeType, ExpressionReturnType, Operator, Program, SlidingAggregatingTopN,
-    SlidingWindowAggregator, StreamEdge, StreamNode, TumblingTopN, TumblingWindowAggregator,
-    WatermarkType, WindowAgg, WindowType,
+    EdgeType, ExpressionReturnType, LookupJoin, Operator, OverWindow, OverWindowFrame,
+    PartitionWatermarks, Program, SlidingAggregatingTopN, SlidingWindowAggregator, StreamEdge,
+    StreamNode, TumblingTopN, TumblingWindowAggregator, UpdatingAggregate, WatermarkType,
+    WindowAgg, WindowType,
 };
 use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::Direction;
 use quote::quote;
 use syn::{parse_quote, parse_str};
 
 use crate::{
-    expressions::SortExpression,
+    expressions::{Expression, SortExpression},
     external::{SqlSink, SqlSource},
-    operators::{AggregateProjection, GroupByKind, Projection, TwoPhaseAggregateProjection},
+    operators::{
+        AggregateProjection, GroupByKind, Projection, TwoPhaseAggregateProjection,
+        TwoPhaseAggregation,
+    },
     optimizations::optimize,
     pipeline::{
-        AggregatingStrategy, JoinType, MethodCompiler, RecordTransform, SourceOperator,
-        SqlOperator, WindowFunction,
+        AggregatingStrategy, AsyncProjectionOperator, JoinInterval, JoinType, LookupJoinOperator,
+        MethodCompiler, RecordTransform, SourceOperator, SourceWatermark, SqlOperator,
+        UnnestOperator, UpdatingAggregateOperator, WindowFrame, WindowFunction,
     },
-    types::{StructDef, StructField, StructPair, TypeDef},
+    types::{StructDef, StructField, StructPair},
     ArroyoSchemaProvider, SqlConfig,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 #[derive(Debug, Clone)]
 pub enum PlanOperator {
@@ -58,9 +63,25 @@ pub enum PlanOperator {
         right_expiration: Duration,
         join_type: JoinType,
     },
+    IntervalJoin {
+        lower_bound: Duration,
+        upper_bound: Duration,
+    },
     JoinListMerge(JoinType, StructPair),
     JoinPairMerge(JoinType, StructPair),
     Flatten,
+    Unnest {
+        input_struct: StructDef,
+        unnest: UnnestOperator,
+    },
+    AsyncProjection(AsyncProjectionOperator),
+    LookupJoin {
+        input_struct: StructDef,
+        lookup_join: LookupJoinOperator,
+    },
+    UpdatingAggregate {
+        projection: TwoPhaseAggregateProjection,
+    },
     // TODO: figure out naming of various things called 'window'
     WindowFunction(WindowFunctionOperator),
     TumblingLocalAggregator {
@@ -95,6 +116,269 @@ pub struct WindowFunctionOperator {
     pub window_type: WindowType,
     pub result_struct: StructDef,
     pub field_name: String,
+    pub incremental: bool,
+}
+
+impl WindowFunctionOperator {
+    fn to_operator(&self, sql_config: &SqlConfig) -> Operator {
+        match &self.window_function {
+            WindowFunction::Lag { .. }
+            | WindowFunction::Lead { .. }
+            | WindowFunction::Aggregate { .. }
+                if self.incremental =>
+            {
+                self.over_window_operator(sql_config.over_window_ttl)
+            }
+            _ => self.window_operator(),
+        }
+    }
+
+    fn output_expression(&self) -> syn::Expr {
+        let window_field = self.result_struct.fields.last().unwrap().field_ident();
+        let result_struct_name = self.result_struct.get_type();
+        let field_assignments: Vec<_> = self
+            .result_struct
+            .fields
+            .iter()
+            .take(self.result_struct.fields.len() - 1)
+            .map(|f| {
+                let ident = f.field_ident();
+                quote! { #ident: arg.#ident.clone() }
+            })
+            .collect();
+        parse_quote!(#result_struct_name {
+            #(#field_assignments, )*
+            #window_field: window_value,
+        })
+    }
+
+    fn nullable_syn_expr(expression: &Expression) -> syn::Expr {
+        let expr = expression.to_syn_expression();
+        if expression.nullable() {
+            expr
+        } else {
+            parse_quote!(Some(#expr))
+        }
+    }
+
+    fn default_syn_expr(default: &Option<Expression>) -> syn::Expr {
+        match default {
+            Some(default) => Self::nullable_syn_expr(default),
+            None => parse_quote!(None),
+        }
+    }
+
+    // Computes the window function over the whole sorted partition of a time window.
+    fn window_operator(&self) -> Operator {
+        let sort_key = SortExpression::sort_tuple_expression(&self.order_by);
+        let mut preamble = None;
+        let window_value: syn::Expr = match &self.window_function {
+            WindowFunction::RowNumber => parse_quote!((index + 1) as u64),
+            WindowFunction::Rank | WindowFunction::DenseRank => {
+                preamble = Some(quote! {
+                    let mut previous_sort_key = None;
+                    let mut rank = 0u64;
+                });
+                let next_rank: syn::Expr = if let WindowFunction::Rank = self.window_function {
+                    parse_quote!((index + 1) as u64)
+                } else {
+                    parse_quote!(rank + 1)
+                };
+                parse_quote!({
+                    let sort_key = #sort_key;
+                    if previous_sort_key.as_ref() != Some(&sort_key) {
+                        rank = #next_rank;
+                        previous_sort_key = Some(sort_key);
+                    }
+                    rank
+                })
+            }
+            WindowFunction::Lag {
+                expression,
+                offset,
+                default,
+            } => {
+                let expr = Self::nullable_syn_expr(expression);
+                let default = Self::default_syn_expr(default);
+                parse_quote!(
+                    match index.checked_sub(#offset).and_then(|lag_index| partition.get(lag_index)) {
+                        Some(arg) => #expr,
+                        None => #default,
+                    }
+                )
+            }
+            WindowFunction::Lead {
+                expression,
+                offset,
+                default,
+            } => {
+                let expr = Self::nullable_syn_expr(expression);
+                let default = Self::default_syn_expr(default);
+                parse_quote!(
+                    match partition.get(index + #offset) {
+                        Some(arg) => #expr,
+                        None => #default,
+                    }
+                )
+            }
+            WindowFunction::Aggregate { aggregation, frame } => {
+                let frame_rows: syn::Expr = match frame {
+                    WindowFrame::Rows(Some(preceding)) => {
+                        parse_quote!(&partition[index.saturating_sub(#preceding)..=index])
+                    }
+                    WindowFrame::Rows(None) => parse_quote!(&partition[..=index]),
+                    // the frame extends to the last row with the same sort key
+                    WindowFrame::Range(None) => parse_quote!({
+                        let sort_key = #sort_key;
+                        let peers = partition[index + 1..]
+                            .iter()
+                            .take_while(|arg| #sort_key == sort_key)
+                            .count();
+                        &partition[..=index + peers]
+                    }),
+                    WindowFrame::Range(Some(_)) => {
+                        unreachable!("RANGE offsets are only supported for incremental windows")
+                    }
+                    WindowFrame::Partition => parse_quote!(&partition[..]),
+                };
+                let aggregate_expr = aggregation.to_syn_expression();
+                parse_quote!({
+                    let arg = #frame_rows;
+                    #aggregate_expr
+                })
+            }
+        };
+        let output_expression = self.output_expression();
+
+        let sort = if !self.order_by.is_empty() {
+            Some(quote!(arg.sort_by_key(|arg| #sort_key);))
+        } else {
+            None
+        };
+        arroyo_datastream::Operator::Window {
+            typ: self.window_type.clone(),
+            agg: Some(WindowAgg::Expression {
+                name: "sql_window".to_string(),
+                expression: quote! {
+                    {
+                        #sort
+                        let partition = &arg;
+                        let mut result = vec![];
+                        #preamble
+                        for (index, arg) in partition.iter().enumerate() {
+                            let window_value = #window_value;
+                            result.push(#output_expression);
+                        }
+                        result
+                    }
+                }
+                .to_string(),
+            }),
+            flatten: true,
+            allowed_lateness: Duration::ZERO,
+        }
+    }
+
+    // Computes the window function incrementally over each partition in event time order.
+    fn over_window_operator(&self, ttl: Duration) -> Operator {
+        let output_expression = self.output_expression();
+        let (frame, following_rows, window_value): (_, _, syn::Expr) = match &self.window_function {
+            WindowFunction::Lag {
+                expression,
+                offset,
+                default,
+            } => {
+                let expr = Self::nullable_syn_expr(expression);
+                let default = Self::default_syn_expr(default);
+                (
+                    OverWindowFrame::Rows(Some(*offset)),
+                    0,
+                    parse_quote!(
+                        match (frame.len() - 1).checked_sub(#offset) {
+                            Some(lag_index) => {
+                                let arg = &frame[lag_index];
+                                #expr
+                            }
+                            None => #default,
+                        }
+                    ),
+                )
+            }
+            WindowFunction::Lead {
+                expression, offset, ..
+            } => {
+                let expr = Self::nullable_syn_expr(expression);
+                (
+                    OverWindowFrame::Rows(Some(0)),
+                    *offset,
+                    parse_quote!({
+                        let arg = &frame[#offset];
+                        #expr
+                    }),
+                )
+            }
+            WindowFunction::Aggregate { aggregation, frame } => {
+                let frame = match frame {
+                    WindowFrame::Rows(preceding) => OverWindowFrame::Rows(*preceding),
+                    WindowFrame::Range(preceding) => OverWindowFrame::Range(*preceding),
+                    WindowFrame::Partition => {
+                        unreachable!("whole partition frames require a time window")
+                    }
+                };
+                if let OverWindowFrame::Rows(None) | OverWindowFrame::Range(None) = frame {
+                    let two_phase: TwoPhaseAggregation = aggregation.clone().try_into().unwrap();
+                    let bin_merger = two_phase.bin_syn_expr();
+                    let bin_type = two_phase.bin_type();
+                    let bin_aggregating_expr = two_phase.bin_aggregating_expression();
+                    return Operator::OverWindow(OverWindow {
+                        frame,
+                        following_rows: 0,
+                        ttl,
+                        bin_merger: Some(
+                            quote!(|arg, current_bin| {
+                                let current_bin = current_bin.cloned();
+                                #bin_merger
+                            })
+                            .to_string(),
+                        ),
+                        bin_type: quote!(#bin_type).to_string(),
+                        evaluator: quote!(|arg, _frame, bin| {
+                            let window_value = {
+                                let arg = bin.unwrap();
+                                #bin_aggregating_expr
+                            };
+                            #output_expression
+                        })
+                        .to_string(),
+                    });
+                }
+                let aggregate_expr = aggregation.to_syn_expression();
+                (
+                    frame,
+                    0,
+                    parse_quote!({
+                        let arg = frame;
+                        #aggregate_expr
+                    }),
+                )
+            }
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => {
+                unreachable!("ranking functions are computed per timestamp")
+            }
+        };
+        Operator::OverWindow(OverWindow {
+            frame,
+            following_rows,
+            ttl,
+            bin_merger: None,
+            bin_type: "()".to_string(),
+            evaluator: quote!(|arg, frame, _bin| {
+                let window_value = #window_value;
+                #output_expression
+            })
+            .to_string(),
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -297,9 +581,14 @@ impl PlanNode {
             }
             PlanOperator::InstantJoin => "instant_join".to_string(),
             PlanOperator::JoinWithExpiration { .. } => "join_with_expiration".to_string(),
+            PlanOperator::IntervalJoin { .. } => "interval_join".to_string(),
             PlanOperator::JoinListMerge(_, _) => "join_list_merge".to_string(),
             PlanOperator::JoinPairMerge(_, _) => "join_pair_merge".to_string(),
             PlanOperator::Flatten => "flatten".to_string(),
+            PlanOperator::Unnest { .. } => "unnest".to_string(),
+            PlanOperator::AsyncProjection(_) => "async_projection".to_string(),
+            PlanOperator::LookupJoin { .. } => "lookup_join".to_string(),
+            PlanOperator::UpdatingAggregate { .. } => "updating_aggregate".to_string(),
             PlanOperator::WindowFunction { .. } => "window_function".to_string(),
             PlanOperator::StreamOperator(name, _) => name.to_string(),
             PlanOperator::TumblingLocalAggregator { .. } => "tumbling_local_aggregator".to_string(),
@@ -324,6 +613,7 @@ impl PlanNode {
                         expression: quote::quote! { #aggregate_expr }.to_string(),
                     }),
                     flatten: false,
+                    allowed_lateness: sql_config.allowed_lateness,
                 }
             }
             PlanOperator::WindowMerge {
@@ -366,6 +656,8 @@ impl PlanNode {
                     aggregator: quote!(|arg| {#aggregate_expr}).to_string(),
                     bin_merger: quote!(|arg, current_bin| {#bin_merger}).to_string(),
                     bin_type: quote!(#bin_type).to_string(),
+                    allowed_lateness: sql_config.allowed_lateness,
+                    early_trigger: sql_config.early_trigger,
                 })
             }
             PlanOperator::SlidingWindowTwoPhaseAggregator {
@@ -388,6 +680,19 @@ impl PlanNode {
                     in_memory_remove: quote!(|current, bin_value| {#in_memory_remove}).to_string(),
                     bin_type: quote!(#bin_type).to_string(),
                     mem_type: quote!(#mem_type).to_string(),
+                    allowed_lateness: sql_config.allowed_lateness,
+                })
+            }
+            PlanOperator::UpdatingAggregate { projection } => {
+                let aggregate_expr = projection.tumbling_aggregation_syn_expression();
+                let bin_merger = projection.bin_merger_syn_expression();
+                let bin_type = projection.bin_type();
+                Operator::UpdatingAggregate(UpdatingAggregate {
+                    ttl: sql_config.updating_aggregate_ttl,
+                    emit_interval: sql_config.updating_aggregate_emit_interval,
+                    aggregator: quote!(|arg| {#aggregate_expr}).to_string(),
+                    bin_merger: quote!(|arg, current_bin| {#bin_merger}).to_string(),
+                    bin_type: quote!(#bin_type).to_string(),
                 })
             }
             PlanOperator::InstantJoin => Operator::WindowJoin {
@@ -400,6 +705,14 @@ impl PlanNode {
             } => Operator::JoinWithExpiration {
                 left_expiration: *left_expiration,
                 right_expiration: *right_expiration,
+                allowed_lateness: sql_config.allowed_lateness,
+            },
+            PlanOperator::IntervalJoin {
+                lower_bound,
+                upper_bound,
+            } => Operator::IntervalJoin {
+                lower_bound: *lower_bound,
+                upper_bound: *upper_bound,
             },
             PlanOperator::JoinListMerge(join_type, struct_pair) => {
                 let merge_struct =
@@ -427,62 +740,8 @@ impl PlanNode {
                 )
                 .unwrap()
             }
-            PlanOperator::WindowFunction(WindowFunctionOperator {
-                window_function,
-                order_by,
-                window_type,
-                result_struct,
-                field_name: _,
-            }) => {
-                let window_field = result_struct.fields.last().unwrap().field_ident();
-                let result_struct_name = result_struct.get_type();
-                let mut field_assignments: Vec<_> = result_struct
-                    .fields
-                    .iter()
-                    .take(result_struct.fields.len() - 1)
-                    .map(|f| {
-                        let ident = f.field_ident();
-                        quote! { #ident: arg.#ident.clone() }
-                    })
-                    .collect();
-
-                match window_function {
-                    WindowFunction::RowNumber => {
-                        field_assignments.push(quote! {
-                            #window_field: i as u64
-                        });
-                    }
-                }
-
-                let output_expression = quote!(#result_struct_name {
-                    #(#field_assignments, )*
-                });
-
-                let sort = if order_by.len() > 0 {
-                    let sort_tokens = SortExpression::sort_tuple_expression(order_by);
-                    Some(quote!(arg.sort_by_key(|arg| #sort_tokens);))
-                } else {
-                    None
-                };
-                arroyo_datastream::Operator::Window {
-                    typ: window_type.clone(),
-                    agg: Some(WindowAgg::Expression {
-                        name: "sql_window".to_string(),
-                        expression: quote! {
-                            {
-                                #sort
-                                let mut result = vec![];
-                                for (index, arg) in arg.iter().enumerate() {
-                                    let i = index + 1;
-                                    result.push(#output_expression);
-                                }
-                                result
-                            }
-                        }
-                        .to_string(),
-                    }),
-                    flatten: true,
-                }
+            PlanOperator::WindowFunction(window_function) => {
+                window_function.to_operator(sql_config)
             }
             PlanOperator::StreamOperator(_, stream_operator) => stream_operator.clone(),
             PlanOperator::FusedRecordTransform(fused_record_transform) => {
@@ -508,6 +767,9 @@ impl PlanNode {
                     aggregator: quote!(|arg| { arg.clone() }).to_string(),
                     bin_merger: quote!(|arg, current_bin| {#bin_merger}).to_string(),
                     bin_type: quote!(#bin_type).to_string(),
+                    // feeds a top-N, which can't take back the bins it has already ranked
+                    allowed_lateness: Duration::ZERO,
+                    early_trigger: None,
                 })
             }
             PlanOperator::SlidingAggregatingTopN {
@@ -613,6 +875,7 @@ impl PlanNode {
                             #window_field: i as u64
                         });
                     }
+                    _ => unreachable!("only ROW_NUMBER windows are converted to top N"),
                 }
                 let output_expression = quote!(#output_struct {
                     #(#field_assignments, )*
@@ -642,6 +905,37 @@ impl PlanNode {
             PlanOperator::Flatten => arroyo_datastream::Operator::FlattenOperator {
                 name: "flatten".into(),
             },
+            PlanOperator::Unnest {
+                input_struct,
+                unnest,
+            } => {
+                MethodCompiler::value_map_operator("unnest", unnest.to_syn_expression(input_struct))
+            }
+            PlanOperator::AsyncProjection(async_projection) => {
+                MethodCompiler::async_value_map_operator(
+                    "async_projection",
+                    async_projection.projection.to_syn_expression(),
+                    async_projection.options,
+                )
+            }
+            PlanOperator::LookupJoin {
+                input_struct,
+                lookup_join,
+            } => {
+                let source = &lookup_join.source;
+                let lookup_type = source.struct_def.get_type();
+                let key_expr = lookup_join.key_syn_expression();
+                let merge_expr = lookup_join.merge_syn_expression(input_struct);
+                Operator::LookupJoin(LookupJoin {
+                    connector: source.connector.clone(),
+                    cache_capacity: source.cache_capacity,
+                    cache_ttl: source.cache_ttl,
+                    max_concurrency: source.max_concurrency,
+                    lookup_type: quote!(#lookup_type).to_string(),
+                    key_fn: quote!(|arg| { #key_expr }).to_string(),
+                    merge_fn: quote!(|arg, lookup| { #merge_expr }).to_string(),
+                })
+            }
             PlanOperator::Sink(_sink_name, sql_sink) => {
                 match &sql_sink.sink_config {
                     arroyo_datastream::SinkConfig::Kafka {
@@ -727,6 +1021,9 @@ impl PlanNode {
                 );
                 output_types.extend(merge_struct.all_structs());
             }
+            PlanOperator::LookupJoin { lookup_join, .. } => {
+                output_types.extend(lookup_join.source.struct_def.all_structs());
+            }
 
             _ => {}
         }
@@ -927,6 +1224,9 @@ impl PlanGraph {
         match operator {
             SqlOperator::Source(source_operator) => self.add_sql_source(source_operator),
             SqlOperator::Aggregator(input, projection) => self.add_aggregator(input, projection),
+            SqlOperator::UpdatingAggregator(input, aggregate) => {
+                self.add_updating_aggregator(input, aggregate)
+            }
             SqlOperator::JoinOperator(left, right, join_operator) => {
                 self.add_join(left, right, join_operator)
             }
@@ -934,6 +1234,11 @@ impl PlanGraph {
             SqlOperator::RecordTransform(input, transform) => {
                 self.add_record_transform(input, transform)
             }
+            SqlOperator::Unnest(input, unnest) => self.add_unnest(input, unnest),
+            SqlOperator::AsyncProjection(input, async_projection) => {
+                self.add_async_projection(input, async_projection)
+            }
+            SqlOperator::LookupJoin(input, lookup_join) => self.add_lookup_join(input, lookup_join),
             SqlOperator::Sink(name, sql_sink, input) => self.add_sql_sink(name, sql_sink, input),
             SqlOperator::NamedTable(name, input) => {
                 let index = self.named_tables.get(&name);
@@ -956,25 +1261,32 @@ impl PlanGraph {
         if let Some(source_id) = source_operator.source.id {
             self.saved_sources_used.push(source_id);
         }
-        let mut current_type = PlanType::Unkeyed(source_operator.source.struct_def.clone());
+        let mut source = source_operator.source.clone();
+        if let Some(SourceWatermark::PerPartition(max_lateness)) = source_operator.watermark {
+            source.partition_watermarks = Some(PartitionWatermarks {
+                max_lateness,
+                idle_time: source_operator.idle_time,
+            });
+        }
+        let mut current_type = PlanType::Unkeyed(source.struct_def.clone());
         let mut current_index = self.insert_operator(
-            PlanOperator::Source(source_operator.name.clone(), source_operator.source.clone()),
+            PlanOperator::Source(source_operator.name.clone(), source),
             current_type.clone(),
         );
-        if let Some(virtual_projection) = source_operator.virtual_field_projection {
-            let virtual_plan_type = PlanType::Unkeyed(virtual_projection.output_struct());
-            let virtual_index = self.insert_operator(
-                PlanOperator::RecordTransform(RecordTransform::ValueProjection(virtual_projection)),
-                virtual_plan_type.clone(),
+        for projection in source_operator.projections {
+            let projection_plan_type = PlanType::Unkeyed(projection.output_struct());
+            let projection_index = self.insert_operator(
+                PlanOperator::RecordTransform(RecordTransform::ValueProjection(projection)),
+                projection_plan_type.clone(),
             );
-            let virtual_edge = PlanEdge {
+            let projection_edge = PlanEdge {
                 edge_data_type: current_type.clone(),
                 edge_type: EdgeType::Forward,
             };
             self.graph
-                .add_edge(current_index, virtual_index, virtual_edge);
-            current_index = virtual_index;
-            current_type = virtual_plan_type;
+                .add_edge(current_index, projection_index, projection_edge);
+            current_index = projection_index;
+            current_type = projection_plan_type;
         }
 
         if let Some(timestamp_expression) = source_operator.timestamp_override {
@@ -992,27 +1304,42 @@ impl PlanGraph {
                 .add_edge(current_index, timestamp_index, timestamp_edge);
             current_index = timestamp_index;
         }
-        let watermark = if let Some(watermark_expression) = source_operator.watermark_column {
-            let expression = watermark_expression.to_syn_expression();
-            let null_checked_expression = if watermark_expression.nullable() {
-                parse_quote!(#expression.unwrap_or_else(|| std::time::SystemTime::now()))
-            } else {
-                expression
-            };
+        let watermark = match source_operator.watermark {
+            // the source emits its own watermarks
+            Some(SourceWatermark::PerPartition(_)) => {
+                self.sources.insert(source_operator.name, current_index);
+                return current_index;
+            }
+            Some(SourceWatermark::Expression(watermark_expression)) => {
+                let expression = watermark_expression.to_syn_expression();
+                let null_checked_expression = if watermark_expression.nullable() {
+                    parse_quote!(#expression.unwrap_or_else(|| std::time::SystemTime::now()))
+                } else {
+                    expression
+                };
 
-            arroyo_datastream::WatermarkType::Expression {
-                period: Duration::from_secs(1),
-                expression: quote!({
-                   let arg = record.value.clone();
-                   #null_checked_expression
-                })
-                .to_string(),
+                arroyo_datastream::WatermarkType::Expression {
+                    period: Duration::from_secs(1),
+                    expression: quote!({
+                       let arg = record.value.clone();
+                       #null_checked_expression
+                    })
+                    .to_string(),
+                    idle_time: source_operator.idle_time,
+                }
             }
-        } else {
-            arroyo_datastream::WatermarkType::FixedLateness {
+            Some(SourceWatermark::FixedLateness(max_lateness)) => {
+                arroyo_datastream::WatermarkType::FixedLateness {
+                    period: Duration::from_secs(1),
+                    max_lateness,
+                    idle_time: source_operator.idle_time,
+                }
+            }
+            None => arroyo_datastream::WatermarkType::FixedLateness {
                 period: Duration::from_secs(1),
                 max_lateness: Duration::from_secs(1),
-            }
+                idle_time: source_operator.idle_time,
+            },
         };
         let watermark_operator = PlanOperator::Watermark(watermark);
         let watermark_index = self.insert_operator(watermark_operator, current_type.clone());
@@ -1106,6 +1433,68 @@ impl PlanGraph {
         merge_index
     }
 
+    fn add_updating_aggregator(
+        &mut self,
+        input: Box<SqlOperator>,
+        aggregate: UpdatingAggregateOperator,
+    ) -> NodeIndex {
+        let input_type = input.return_type();
+        let output_type = aggregate.output_struct();
+        let key_struct = aggregate.key.output_struct();
+        let aggregate_struct = aggregate.aggregating.output_struct();
+        let input_index = self.add_sql_operator(*input);
+        let key_index = self.insert_operator(
+            PlanOperator::RecordTransform(RecordTransform::KeyProjection(aggregate.key)),
+            PlanType::Keyed {
+                key: key_struct.clone(),
+                value: input_type.clone(),
+            },
+        );
+        let key_edge = PlanEdge {
+            edge_data_type: PlanType::Unkeyed(input_type.clone()),
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(input_index, key_index, key_edge);
+
+        let aggregate_index = self.insert_operator(
+            PlanOperator::UpdatingAggregate {
+                projection: aggregate.aggregating,
+            },
+            PlanType::Keyed {
+                key: key_struct.clone(),
+                value: aggregate_struct.clone(),
+            },
+        );
+        let aggregate_edge = PlanEdge {
+            edge_data_type: PlanType::Keyed {
+                key: key_struct.clone(),
+                value: input_type,
+            },
+            edge_type: EdgeType::Shuffle,
+        };
+        self.graph
+            .add_edge(key_index, aggregate_index, aggregate_edge);
+
+        let merge_index = self.insert_operator(
+            PlanOperator::WindowMerge {
+                key_struct: key_struct.clone(),
+                value_struct: aggregate_struct.clone(),
+                group_by_kind: GroupByKind::Basic,
+            },
+            PlanType::Unkeyed(output_type),
+        );
+        let merge_edge = PlanEdge {
+            edge_data_type: PlanType::Keyed {
+                key: key_struct,
+                value: aggregate_struct,
+            },
+            edge_type: EdgeType::Forward,
+        };
+        self.graph
+            .add_edge(aggregate_index, merge_index, merge_edge);
+        merge_index
+    }
+
     fn add_join(
         &mut self,
         left: Box<SqlOperator>,
@@ -1117,6 +1506,7 @@ impl PlanGraph {
         // right now left and right either both have or don't have windows.
         let has_window = left.has_window();
         let join_type = join_operator.join_type;
+        let interval = join_operator.interval;
         let left_index = self.add_sql_operator(*left);
         let right_index = self.add_sql_operator(*right);
 
@@ -1172,6 +1562,7 @@ impl PlanGraph {
                 left_type,
                 right_type,
                 join_type,
+                interval,
             )
         }
     }
@@ -1251,11 +1642,21 @@ impl PlanGraph {
         left_struct: StructDef,
         right_struct: StructDef,
         join_type: JoinType,
+        interval: Option<JoinInterval>,
     ) -> NodeIndex {
-        let join_node = PlanOperator::JoinWithExpiration {
-            left_expiration: Duration::from_secs(24 * 60 * 60),
-            right_expiration: Duration::from_secs(24 * 60 * 60),
-            join_type: JoinType::Inner,
+        let join_node = match interval {
+            Some(JoinInterval {
+                lower_bound,
+                upper_bound,
+            }) => PlanOperator::IntervalJoin {
+                lower_bound,
+                upper_bound,
+            },
+            None => PlanOperator::JoinWithExpiration {
+                left_expiration: Duration::from_secs(24 * 60 * 60),
+                right_expiration: Duration::from_secs(24 * 60 * 60),
+                join_type: JoinType::Inner,
+            },
         };
         let join_node_output_type = PlanType::KeyedPair {
             key: key_struct.clone(),
@@ -1314,7 +1715,7 @@ impl PlanGraph {
         result_type.fields.push(StructField {
             name: window_operator.field_name.clone(),
             alias: None,
-            data_type: TypeDef::DataType(DataType::UInt64, false),
+            data_type: window_operator.window_fn.return_type(),
         });
         let partition_struct = window_operator.partition.output_struct();
 
@@ -1342,6 +1743,7 @@ impl PlanGraph {
             window_type: window_operator.window,
             result_struct: result_type.clone(),
             field_name: window_operator.field_name,
+            incremental: window_operator.incremental,
         });
         let window_function_index = self.insert_operator(
             window_function_node,
@@ -1396,6 +1798,79 @@ impl PlanGraph {
         plan_node_index
     }
 
+    fn add_async_projection(
+        &mut self,
+        input: Box<SqlOperator>,
+        async_projection: AsyncProjectionOperator,
+    ) -> NodeIndex {
+        let input_type = input.return_type();
+        let return_type = async_projection.projection.output_struct();
+        let input_index = self.add_sql_operator(*input);
+        let projection_index = self.insert_operator(
+            PlanOperator::AsyncProjection(async_projection),
+            PlanType::Unkeyed(return_type),
+        );
+        let edge = PlanEdge {
+            edge_data_type: PlanType::Unkeyed(input_type),
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(input_index, projection_index, edge);
+        projection_index
+    }
+
+    fn add_unnest(&mut self, input: Box<SqlOperator>, unnest: UnnestOperator) -> NodeIndex {
+        let input_type = input.return_type();
+        let return_type = unnest.output_struct(&input_type);
+        let input_index = self.add_sql_operator(*input);
+        let unnest_index = self.insert_operator(
+            PlanOperator::Unnest {
+                input_struct: input_type.clone(),
+                unnest,
+            },
+            PlanType::UnkeyedList(return_type.clone()),
+        );
+        let unnest_edge = PlanEdge {
+            edge_data_type: PlanType::Unkeyed(input_type),
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(input_index, unnest_index, unnest_edge);
+
+        let flatten_index = self.insert_operator(
+            PlanOperator::Flatten,
+            PlanType::Unkeyed(return_type.clone()),
+        );
+        let flatten_edge = PlanEdge {
+            edge_data_type: PlanType::UnkeyedList(return_type),
+            edge_type: EdgeType::Forward,
+        };
+        self.graph
+            .add_edge(unnest_index, flatten_index, flatten_edge);
+        flatten_index
+    }
+
+    fn add_lookup_join(
+        &mut self,
+        input: Box<SqlOperator>,
+        lookup_join: LookupJoinOperator,
+    ) -> NodeIndex {
+        let input_type = input.return_type();
+        let return_type = lookup_join.output_struct(&input_type);
+        let input_index = self.add_sql_operator(*input);
+        let lookup_index = self.insert_operator(
+            PlanOperator::LookupJoin {
+                input_struct: input_type.clone(),
+                lookup_join,
+            },
+            PlanType::Unkeyed(return_type),
+        );
+        let lookup_edge = PlanEdge {
+            edge_data_type: PlanType::Unkeyed(input_type),
+            edge_type: EdgeType::Forward,
+        };
+        self.graph.add_edge(input_index, lookup_index, lookup_edge);
+        lookup_index
+    }
+
     fn add_sql_sink(
         &mut self,
         name: String,
@@ -1403,17 +1878,108 @@ impl PlanGraph {
         input: Box<SqlOperator>,
     ) -> NodeIndex {
         let input_type = input.return_type();
-        let input_index = self.add_sql_operator(*input);
+        let upsert_key = input.upsert_key();
+        let mut input_index = self.add_sql_operator(*input);
+        let mut edge_data_type = PlanType::Unkeyed(input_type.clone());
+        // rows of updating queries are keyed so that sinks can write them as upserts
+        if let Some(upsert_key) = upsert_key {
+            let keyed_type = PlanType::Keyed {
+                key: upsert_key.output_struct(),
+                value: input_type.clone(),
+            };
+            let key_index = self.insert_operator(
+                PlanOperator::RecordTransform(RecordTransform::KeyProjection(upsert_key)),
+                keyed_type.clone(),
+            );
+            let key_edge = PlanEdge {
+                edge_data_type,
+                edge_type: EdgeType::Forward,
+            };
+            self.graph.add_edge(input_index, key_index, key_edge);
+            input_index = key_index;
+            edge_data_type = keyed_type;
+        }
         let plan_node = PlanOperator::Sink(name, sql_sink);
         let plan_node_index =
             self.insert_operator(plan_node, PlanType::Unkeyed(input_type.clone()));
         let edge = PlanEdge {
-            edge_data_type: PlanType::Unkeyed(input_type),
+            edge_data_type,
             edge_type: EdgeType::Forward,
         };
         self.graph.add_edge(input_index, plan_node_index, edge);
         plan_node_index
     }
+
+    // Windows send the records that arrive after their allowed lateness down a late data edge,
+    // so this runs after the optimizations have settled which operator each window becomes.
+    fn add_late_data_sinks(&mut self) {
+        let Some((name, sink)) = self.sql_config.late_data_sink.clone() else {
+            return;
+        };
+        let windows: Vec<_> = self
+            .graph
+            .node_indices()
+            .filter(|index| {
+                matches!(
+                    self.graph[*index].operator,
+                    PlanOperator::WindowAggregate { .. }
+                        | PlanOperator::TumblingWindowTwoPhaseAggregator { .. }
+                )
+            })
+            .collect();
+        for window_index in windows {
+            let input_type = self
+                .graph
+                .edges_directed(window_index, Direction::Incoming)
+                .next()
+                .expect("windows have an input")
+                .weight()
+                .edge_data_type
+                .clone();
+            let PlanType::Keyed { value, .. } = &input_type else {
+                unreachable!("windows have keyed inputs")
+            };
+            let sink_node = PlanOperator::Sink(
+                name.clone(),
+                SqlSink {
+                    struct_def: value.clone(),
+                    ..sink.clone()
+                },
+            );
+            let sink_index = self.insert_operator(sink_node, PlanType::Unkeyed(value.clone()));
+            let edge = PlanEdge {
+                edge_data_type: input_type,
+                edge_type: EdgeType::LateData,
+            };
+            self.graph.add_edge(window_index, sink_index, edge);
+        }
+    }
+
+    // Only the two-phase tumbling window aggregator can emit early, so `SET emit` is rejected
+    // for queries whose windows became anything else rather than being ignored.
+    fn check_early_trigger(&self) -> Result<()> {
+        if self.sql_config.early_trigger.is_none() {
+            return Ok(());
+        }
+        let mut tumbling_windows = 0;
+        for node in self.graph.node_weights() {
+            match node.operator {
+                PlanOperator::TumblingWindowTwoPhaseAggregator { .. } => tumbling_windows += 1,
+                PlanOperator::WindowAggregate { .. }
+                | PlanOperator::SlidingWindowTwoPhaseAggregator { .. }
+                | PlanOperator::TumblingLocalAggregator { .. }
+                | PlanOperator::SlidingAggregatingTopN { .. }
+                | PlanOperator::TumblingTopN { .. } => {
+                    bail!("SET emit is only supported for tumbling window aggregates")
+                }
+                _ => {}
+            }
+        }
+        if tumbling_windows == 0 {
+            bail!("SET emit requires a tumbling window aggregate");
+        }
+        Ok(())
+    }
 }
 
 impl From<PlanGraph> for DiGraph<StreamNode, StreamEdge> {
@@ -1430,6 +1996,8 @@ pub fn get_program(
     schema_provider: ArroyoSchemaProvider,
 ) -> Result<(Program, Vec<i64>)> {
     optimize(&mut plan_graph.graph);
+    plan_graph.add_late_data_sinks();
+    plan_graph.check_early_trigger()?;
 
     let mut key_structs = HashSet::new();
     let sources = plan_graph.saved_sources_used.clone();
@@ -1463,6 +2031,7 @@ pub fn get_program(
             .udf_defs
             .values()
             .map(|u| u.def.as_str())
+            .chain(schema_provider.udaf_defs.values().map(|u| u.def.as_str()))
             .collect::<Vec<_>>()
             .join("\n\n")
     ));
@@ -1475,6 +2044,7 @@ pub fn get_program(
             // in wasm
             types: vec![],
             other_defs,
+            udf_dependencies: schema_provider.udf_dependencies,
             graph,
         },
         sources,
@@ -1,9 +1,15 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use arrow_schema::{DataType, TimeUnit};
 use arroyo_datastream::SerializationMode;
+use arroyo_rpc::grpc::api::{
+    connection::ConnectionType, kafka_auth_config::AuthType, Connection, KafkaAuthConfig,
+    KafkaConnection, NoAuth,
+};
 
 use crate::{
+    errors::{SqlError, SqlLocation, SqlSpan},
     parse_and_get_program,
     types::{StructDef, StructField, TypeDef},
     ArroyoSchemaProvider, SqlConfig,
@@ -23,6 +29,11 @@ fn test_schema() -> Vec<StructField> {
                             alias: None,
                             data_type: TypeDef::DataType(DataType::UInt64, false),
                         },
+                        StructField {
+                            name: "price".to_string(),
+                            alias: None,
+                            data_type: TypeDef::DataType(DataType::UInt64, false),
+                        },
                         StructField {
                             name: "datetime".to_string(),
                             alias: None,
@@ -147,6 +158,195 @@ async fn test_program_compilation() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn test_allowed_lateness() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "SELECT bid.auction as auction, count(*) as bids
+    FROM nexmark GROUP BY 1, tumble(INTERVAL '1' MINUTE)";
+    let (program, _) = parse_and_get_program(
+        sql,
+        schema_provider,
+        SqlConfig {
+            allowed_lateness: Duration::from_secs(30),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert!(program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        arroyo_datastream::Operator::TumblingWindowAggregator(aggregator)
+            if aggregator.allowed_lateness == Duration::from_secs(30)
+    )));
+}
+
+#[tokio::test]
+async fn test_late_data_sink() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+    schema_provider.add_connection(Connection {
+        name: "local".to_string(),
+        sources: 0,
+        sinks: 0,
+        connection_type: Some(ConnectionType::Kafka(KafkaConnection {
+            bootstrap_servers: "localhost:9092".to_string(),
+            auth_config: Some(KafkaAuthConfig {
+                auth_type: Some(AuthType::NoAuth(NoAuth {})),
+            }),
+        })),
+    });
+
+    let sql =
+        "CREATE TABLE late_bids (auction BIGINT) WITH (connection = 'local', topic = 'late_bids');
+    SET allowed_lateness = '30 seconds';
+    SET late_data_sink = 'late_bids';
+    SELECT bid.auction as auction, count(*) as bids
+    FROM nexmark GROUP BY 1, tumble(INTERVAL '1' MINUTE)";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    assert!(program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        arroyo_datastream::Operator::TumblingWindowAggregator(aggregator)
+            if aggregator.allowed_lateness == Duration::from_secs(30)
+    )));
+    assert!(program
+        .graph
+        .edge_weights()
+        .any(|edge| edge.typ == arroyo_datastream::EdgeType::LateData));
+
+    assert!(parse_and_get_program(
+        "SET late_data_sink = 'nexmark'; SELECT * FROM nexmark",
+        schema_provider,
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_early_trigger() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let query = |emit: &str| {
+        format!(
+            "SET emit = '{}';
+            SELECT bid.auction as auction, count(*) as bids
+            FROM nexmark GROUP BY 1, tumble(INTERVAL '1' HOUR)",
+            emit
+        )
+    };
+    for (emit, trigger) in [
+        (
+            "every 10 seconds",
+            arroyo_datastream::EarlyTrigger::Interval(Duration::from_secs(10)),
+        ),
+        (
+            "every 1000 rows",
+            arroyo_datastream::EarlyTrigger::Count(1000),
+        ),
+    ] {
+        let (program, _) =
+            parse_and_get_program(&query(emit), schema_provider.clone(), SqlConfig::default())
+                .await
+                .unwrap();
+        assert!(program.graph.node_weights().any(|node| matches!(
+            &node.operator,
+            arroyo_datastream::Operator::TumblingWindowAggregator(aggregator)
+                if aggregator.early_trigger == Some(trigger)
+        )));
+    }
+
+    for emit in ["every 0 rows", "sometimes"] {
+        assert!(
+            parse_and_get_program(&query(emit), schema_provider.clone(), SqlConfig::default())
+                .await
+                .is_err()
+        );
+    }
+
+    // other windows can't emit early
+    for query in [
+        "SET emit = 'every 10 seconds';
+        SELECT bid.auction as auction, count(*) as bids
+        FROM nexmark GROUP BY 1, hop(INTERVAL '10' SECOND, INTERVAL '1' HOUR)",
+        "SET emit = 'every 1000 rows';
+        SELECT bid.auction as auction, count(*) as bids FROM nexmark GROUP BY 1",
+    ] {
+        assert!(
+            parse_and_get_program(query, schema_provider.clone(), SqlConfig::default())
+                .await
+                .is_err()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_regex_pattern() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let query = |pattern: &str| {
+        format!(
+            "SELECT bid.auction as auction FROM nexmark
+            WHERE CAST(bid.auction AS TEXT) ~ '{}'",
+            pattern
+        )
+    };
+    parse_and_get_program(&query("^1"), schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    // literal patterns are checked when the query is planned
+    assert!(
+        parse_and_get_program(&query("(1"), schema_provider, SqlConfig::default())
+            .await
+            .is_err()
+    );
+}
+
 #[tokio::test]
 async fn test_table_alias() {
     let mut schema_provider = ArroyoSchemaProvider::new();
@@ -223,3 +423,1032 @@ async fn test_udf() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn test_udaf() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+
+    schema_provider
+        .add_rust_udf(
+            "mod my_mean {
+                fn init() -> (u64, f64) { (0, 0.0) }
+                fn accumulate(acc: (u64, f64), value: u64) -> (u64, f64) { (acc.0 + 1, acc.1 + value as f64) }
+                fn merge(left: (u64, f64), right: (u64, f64)) -> (u64, f64) { (left.0 + right.0, left.1 + right.1) }
+                fn finish(acc: (u64, f64)) -> f64 { acc.1 / acc.0 as f64 }
+            }",
+        )
+        .unwrap();
+
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "SELECT my_mean(bid.price) FROM nexmark
+        GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_udaf_with_mergeable_aggregates() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+
+    schema_provider
+        .add_rust_udf(
+            "mod my_mean {
+                fn init() -> (u64, f64) { (0, 0.0) }
+                fn accumulate(acc: (u64, f64), value: u64) -> (u64, f64) { (acc.0 + 1, acc.1 + value as f64) }
+                fn merge(left: (u64, f64), right: (u64, f64)) -> (u64, f64) { (left.0 + right.0, left.1 + right.1) }
+                fn finish(acc: (u64, f64)) -> f64 { acc.1 / acc.0 as f64 }
+            }",
+        )
+        .unwrap();
+
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    // UDAFs share the two-phase aggregation with the built-in mergeable aggregates
+    let sql = "SELECT my_mean(bid.auction) as mean,
+        approx_count_distinct(bid.auction) as distinct_auctions
+    FROM nexmark
+    GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_mergeable_aggregates() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "SELECT approx_count_distinct(bid.auction) as distinct_auctions,
+        approx_percentile_cont(bid.auction, 0.9) as p90,
+        approx_median(bid.auction) as median,
+        stddev(bid.auction) as stddev,
+        var_pop(bid.auction) as variance,
+        first_value(bid.auction, bid.datetime) as first_auction,
+        last_value(bid.auction, bid.datetime) as last_auction,
+        bool_and(bid.auction > 100) as all_large,
+        bool_or(bid.auction > 100) as any_large,
+        array_agg(bid.auction) as auctions
+    FROM nexmark
+    GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_approx_count_distinct_arguments() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    // floats aren't Hash, so the sketch is fed their bits
+    let sql = "SELECT approx_count_distinct(price) as distinct_prices
+    FROM (SELECT CAST(bid.price AS DOUBLE) as price FROM nexmark)
+    GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    let bin_merger = program
+        .graph
+        .node_weights()
+        .find_map(|node| match &node.operator {
+            arroyo_datastream::Operator::SlidingWindowAggregator(aggregator) => {
+                Some(aggregator.bin_merger.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert!(bin_merger.contains("to_bits"));
+
+    let sql = "SELECT approx_count_distinct(bid) as distinct_bids
+    FROM nexmark
+    GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    assert!(
+        parse_and_get_program(sql, schema_provider, SqlConfig::default())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_filtered_and_ordered_aggregates() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "SELECT count(*) FILTER (WHERE bid.auction > 10) as large_bids,
+        sum(bid.auction) FILTER (WHERE bid.auction % 2 = 0) as even_sum,
+        array_agg(bid.auction ORDER BY bid.datetime DESC) as auctions,
+        max_by(bid.auction, bid.datetime) as latest_auction,
+        min_by(bid.datetime, bid.auction) as earliest_datetime
+    FROM nexmark
+    GROUP BY hop(interval '2 seconds', interval '10 seconds')";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    // count distinct forces the single-phase path
+    let sql = "SELECT count(distinct bid.auction) as auctions,
+        count(*) FILTER (WHERE bid.auction > 10) as large_bids,
+        array_agg(bid.auction ORDER BY bid.datetime) as ordered_auctions
+    FROM nexmark
+    GROUP BY tumble(interval '10 seconds')";
+    parse_and_get_program(sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_ranking_and_aggregate_window_functions() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "SELECT *, DENSE_RANK() OVER (
+        PARTITION BY window
+        ORDER BY count DESC) as dense_rank
+    FROM (SELECT *, RANK() OVER (
+        PARTITION BY window
+        ORDER BY count DESC) as rank
+    FROM (SELECT count(*) as count,
+        tumble(interval '10 seconds') as window
+            FROM nexmark
+            group by window))";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let sql = "SELECT auction, sum(count) OVER (
+        PARTITION BY window
+        ORDER BY auction
+        ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) as recent_sum
+    FROM (SELECT bid.auction as auction, count(*) as count,
+        tumble(interval '10 seconds') as window
+            FROM nexmark
+            group by 1, 3)";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let sql = "SELECT *, LEAD(auction, 2) OVER (ORDER BY datetime) as next_auction
+    FROM (SELECT *, LAG(auction, 1, 0) OVER (ORDER BY datetime) as previous_auction
+    FROM (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark))";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let sql = "SELECT *, count(*) OVER (
+        PARTITION BY auction
+        ORDER BY datetime
+        RANGE BETWEEN INTERVAL '1 minute' PRECEDING AND CURRENT ROW) as recent_bids
+    FROM (SELECT *, sum(auction) OVER (
+        PARTITION BY auction
+        ORDER BY datetime) as running_sum
+    FROM (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark))";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let sql = "SET over_window_ttl = '1 hour';
+    SELECT *, sum(auction) OVER (
+        PARTITION BY auction
+        ORDER BY datetime) as running_sum
+    FROM (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark)";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    let over_window = program
+        .graph
+        .node_weights()
+        .find_map(|node| match &node.operator {
+            arroyo_datastream::Operator::OverWindow(over_window) => Some(over_window.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(over_window.ttl, Duration::from_secs(60 * 60));
+
+    // rows without a time window are processed in event time order
+    let sql = "SELECT sum(auction) OVER (ORDER BY datetime DESC) as total
+    FROM (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark)";
+    assert!(
+        parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+            .await
+            .is_err()
+    );
+
+    let sql = "SELECT sum(auction) OVER (PARTITION BY auction) as total
+    FROM (SELECT bid.auction as auction FROM nexmark)";
+    assert!(
+        parse_and_get_program(sql, schema_provider, SqlConfig::default())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_interval_join() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "WITH bids as (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark),
+    auctions as (SELECT auction.auction as id, auction.datetime as datetime FROM nexmark)
+    SELECT bids.auction, bids.datetime, auctions.datetime as auction_datetime
+    FROM auctions JOIN bids ON auctions.id = bids.auction
+    AND bids.datetime BETWEEN auctions.datetime - INTERVAL '5' MINUTE
+        AND auctions.datetime + INTERVAL '10' MINUTE";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    assert!(program.graph.node_weights().any(|node| node.operator
+        == arroyo_datastream::Operator::IntervalJoin {
+            lower_bound: Duration::from_secs(5 * 60),
+            upper_bound: Duration::from_secs(10 * 60),
+        }));
+
+    let sql = "WITH bids as (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark),
+    auctions as (SELECT auction.auction as id, auction.datetime as datetime FROM nexmark)
+    SELECT bids.auction, bids.datetime
+    FROM auctions JOIN bids ON auctions.id = bids.auction
+    AND auctions.datetime > bids.datetime
+    AND auctions.datetime < bids.datetime + INTERVAL '1' HOUR";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    assert!(program.graph.node_weights().any(|node| node.operator
+        == arroyo_datastream::Operator::IntervalJoin {
+            lower_bound: Duration::from_secs(60 * 60),
+            upper_bound: Duration::ZERO,
+        }));
+
+    // the bounds apply to the records' event times, so they can't be on other timestamps
+    let sql = "WITH bids as (SELECT bid.auction as auction, bid.datetime as datetime FROM nexmark),
+    auctions as (SELECT auction.auction as id, auction.datetime + INTERVAL '1' HOUR as expires FROM nexmark)
+    SELECT bids.auction, bids.datetime
+    FROM auctions JOIN bids ON auctions.id = bids.auction
+    AND bids.datetime BETWEEN auctions.expires - INTERVAL '5' MINUTE AND auctions.expires";
+    assert!(
+        parse_and_get_program(sql, schema_provider, SqlConfig::default())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_cross_join_unnest() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "WITH bids as (SELECT bid.auction as auction, make_array(bid.auction, bid.auction + 1) as items FROM nexmark)
+    SELECT auction, item FROM bids CROSS JOIN UNNEST(bids.items) AS u(item)";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    assert!(program.graph.node_weights().any(|node| matches!(
+        node.operator,
+        arroyo_datastream::Operator::FlattenOperator { .. }
+    )));
+
+    // the comma-join form is equivalent
+    let sql = "WITH bids as (SELECT bid.auction as auction, make_array(bid.auction, bid.auction + 1) as items FROM nexmark)
+    SELECT auction, u.item FROM bids, UNNEST(bids.items) AS u(item)";
+    assert!(
+        parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+            .await
+            .is_ok()
+    );
+
+    // unnesting a scalar is rejected
+    let sql = "SELECT item FROM nexmark CROSS JOIN UNNEST(nexmark.bid.auction) AS u(item)";
+    assert!(
+        parse_and_get_program(sql, schema_provider, SqlConfig::default())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_watermark_for_clause() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_connection(Connection {
+        name: "local".to_string(),
+        sources: 0,
+        sinks: 0,
+        connection_type: Some(ConnectionType::Kafka(KafkaConnection {
+            bootstrap_servers: "localhost:9092".to_string(),
+            auth_config: Some(KafkaAuthConfig {
+                auth_type: Some(AuthType::NoAuth(NoAuth {})),
+            }),
+        })),
+    });
+
+    let watermark_for = |clause: &str| {
+        format!(
+            "CREATE TABLE person (
+              id bigint,
+              date_string text,
+              datetime timestamp GENERATED ALWAYS AS (CAST(date_string as timestamp)),
+              {}
+            ) WITH (
+              connection = 'local',
+              topic = 'person'
+            );
+            SELECT id FROM person;",
+            clause
+        )
+    };
+
+    let (program, _) = parse_and_get_program(
+        &watermark_for("WATERMARK FOR datetime AS datetime - INTERVAL '5' SECOND"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert!(program.graph.node_weights().any(|node| node.operator
+        == arroyo_datastream::Operator::Watermark(
+            arroyo_datastream::WatermarkType::FixedLateness {
+                period: Duration::from_secs(1),
+                max_lateness: Duration::from_secs(5),
+                idle_time: None,
+            }
+        )));
+
+    // other expressions are evaluated for each record
+    let (program, _) = parse_and_get_program(
+        &watermark_for("WATERMARK FOR datetime AS CAST(date_string as timestamp)"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert!(program.graph.node_weights().any(|node| matches!(
+        node.operator,
+        arroyo_datastream::Operator::Watermark(arroyo_datastream::WatermarkType::Expression { .. })
+    )));
+
+    // the event time must be a timestamp
+    assert!(parse_and_get_program(
+        &watermark_for("WATERMARK FOR date_string AS date_string"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+
+    // sources can be marked idle after going without records for a while
+    let idle_source = |idle_time: &str| {
+        format!(
+            "CREATE TABLE person (
+              id bigint
+            ) WITH (
+              connection = 'local',
+              topic = 'person',
+              idle_time = '{}'
+            );
+            SELECT id FROM person;",
+            idle_time
+        )
+    };
+    let (program, _) = parse_and_get_program(
+        &idle_source("30 seconds"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert!(program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        arroyo_datastream::Operator::Watermark(watermark)
+            if watermark.idle_time() == Some(Duration::from_secs(30))
+    )));
+
+    assert!(parse_and_get_program(
+        &idle_source("soon"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+
+    // Kafka sources can compute watermarks for each partition themselves
+    let partition_watermarks = |clause: &str| {
+        format!(
+            "CREATE TABLE person (
+              id bigint,
+              datetime timestamp{}
+            ) WITH (
+              connection = 'local',
+              topic = 'person',
+              partition_watermarks = '5 seconds',
+              idle_time = '30 seconds'
+            );
+            SELECT id FROM person;",
+            clause
+        )
+    };
+    let (program, _) = parse_and_get_program(
+        &partition_watermarks(""),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert!(program.graph.node_weights().any(|node| matches!(
+        &node.operator,
+        arroyo_datastream::Operator::KafkaSource {
+            partition_watermarks: Some(partition_watermarks),
+            ..
+        } if *partition_watermarks == arroyo_datastream::PartitionWatermarks {
+            max_lateness: Duration::from_secs(5),
+            idle_time: Some(Duration::from_secs(30)),
+        }
+    )));
+    assert!(!program
+        .graph
+        .node_weights()
+        .any(|node| matches!(node.operator, arroyo_datastream::Operator::Watermark(_))));
+
+    assert!(parse_and_get_program(
+        &partition_watermarks(
+            ",\n              WATERMARK FOR datetime AS datetime - INTERVAL '5' SECOND"
+        ),
+        schema_provider,
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_explain() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "EXPLAIN VERBOSE SELECT bid.auction as auction, count(*) as bids
+    FROM nexmark GROUP BY 1, tumble(INTERVAL '1' MINUTE)";
+    assert_eq!(
+        crate::explain_options(sql).unwrap(),
+        Some(crate::ExplainOptions { verbose: true })
+    );
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    let explanation = program.explain(true);
+    assert!(explanation.nodes.iter().any(|node| node
+        .state_tables
+        .iter()
+        .any(|table| table.retention_micros == Some(60_000_000))));
+    assert!(explanation
+        .nodes
+        .iter()
+        .any(|node| !node.expressions.is_empty()));
+    assert!(explanation
+        .edges
+        .iter()
+        .any(|edge| edge.edge_type == "Shuffle"));
+    assert!(program.explain(false).to_text().contains("retention 1m"));
+
+    assert_eq!(
+        crate::explain_options("SELECT bid.auction FROM nexmark").unwrap(),
+        None
+    );
+    assert!(parse_and_get_program(
+        "EXPLAIN ANALYZE SELECT bid.auction FROM nexmark",
+        schema_provider,
+        SqlConfig::default()
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_lookup_join() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+    let create_table = "CREATE TABLE auctions (
+      id TEXT PRIMARY KEY,
+      name TEXT
+    ) WITH (
+      connector = 'postgres',
+      connection_string = 'host=localhost user=arroyo',
+      cache_ttl = '30 seconds',
+      max_concurrency = '4'
+    );";
+
+    let sql = format!(
+        "{}
+        SELECT n.bid.auction, a.name FROM nexmark n
+        LEFT JOIN auctions FOR SYSTEM_TIME AS OF n.bid.datetime AS a
+        ON CAST(n.bid.auction AS TEXT) = a.id",
+        create_table
+    );
+    let (program, _) = parse_and_get_program(&sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    let lookup_join = program
+        .graph
+        .node_weights()
+        .find_map(|node| match &node.operator {
+            arroyo_datastream::Operator::LookupJoin(lookup_join) => Some(lookup_join.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        lookup_join.connector,
+        arroyo_datastream::LookupConnector::Postgres {
+            connection_string: "host=localhost user=arroyo".to_string(),
+            table: "auctions".to_string(),
+            key_column: "id".to_string(),
+        }
+    );
+    assert_eq!(lookup_join.cache_ttl, Duration::from_secs(30));
+    assert_eq!(lookup_join.max_concurrency, 4);
+
+    // lookup tables can only be read through FOR SYSTEM_TIME AS OF
+    assert!(parse_and_get_program(
+        &format!(
+            "{} SELECT n.bid.auction, a.name FROM nexmark n
+            JOIN auctions a ON CAST(n.bid.auction AS TEXT) = a.id",
+            create_table
+        ),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+    assert!(parse_and_get_program(
+        "SELECT bid.auction FROM nexmark FOR SYSTEM_TIME AS OF bid.datetime",
+        schema_provider,
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_updating_aggregate() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+    let sql = "SET updating_aggregate_ttl = '1 hour';
+    SET updating_aggregate_emit_interval = '10 seconds';
+    SELECT auction, bids FROM (
+      SELECT bid.auction as auction, count(*) as bids
+      FROM nexmark WHERE bid IS NOT NULL
+      GROUP BY 1)";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    let aggregate = program
+        .graph
+        .node_weights()
+        .find_map(|node| match &node.operator {
+            arroyo_datastream::Operator::UpdatingAggregate(aggregate) => Some(aggregate.clone()),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(aggregate.ttl, Duration::from_secs(60 * 60));
+    assert_eq!(aggregate.emit_interval, Some(Duration::from_secs(10)));
+
+    // the sink receives rows keyed by the GROUP BY columns, to write them as upserts
+    let sink = program
+        .graph
+        .node_indices()
+        .find(|index| {
+            matches!(
+                program.graph[*index].operator,
+                arroyo_datastream::Operator::GrpcSink
+            )
+        })
+        .unwrap();
+    let sink_edge = program
+        .graph
+        .edges_directed(sink, petgraph::Direction::Incoming)
+        .next()
+        .unwrap();
+    assert_ne!(sink_edge.weight().key, "()");
+
+    // aggregates that can't be updated incrementally still require a window
+    assert!(parse_and_get_program(
+        "SELECT bid.auction, count(distinct bid.bidder) FROM nexmark GROUP BY 1",
+        schema_provider,
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_async_udf() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider
+        .add_rust_udf(
+            "/*
+            [dependencies]
+            tokio = { version = \"1\", features = [\"time\"] }
+            */
+            #[udf(unordered, max_concurrency = 10)]
+            async fn slow_double(x: u64) -> u64 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                x * 2
+            }",
+        )
+        .unwrap();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    let sql = "SELECT slow_double(bid.auction) + 1 FROM nexmark";
+    let (program, _) = parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        program.udf_dependencies.get("tokio").map(String::as_str),
+        Some("{ version = \"1\", features = [\"time\"] }")
+    );
+    assert!(program.graph.node_weights().any(|node| matches!(
+        node.operator,
+        arroyo_datastream::Operator::AsyncMapOperator {
+            max_concurrency: 10,
+            ordered: false,
+            ..
+        }
+    )));
+
+    // async UDFs are only evaluated by projections
+    assert!(parse_and_get_program(
+        "SELECT bid.auction FROM nexmark WHERE slow_double(bid.auction) > 10",
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+
+    // UDFs can't disagree on the version of a dependency
+    assert!(schema_provider
+        .add_rust_udf(
+            "/*
+            [dependencies]
+            tokio = \"0.2\"
+            */
+            fn plus_one(x: u64) -> u64 { x + 1 }",
+        )
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_saved_views_and_multiple_inserts() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+    schema_provider.add_connection(Connection {
+        name: "local".to_string(),
+        sources: 0,
+        sinks: 0,
+        connection_type: Some(ConnectionType::Kafka(KafkaConnection {
+            bootstrap_servers: "localhost:9092".to_string(),
+            auth_config: Some(KafkaAuthConfig {
+                auth_type: Some(AuthType::NoAuth(NoAuth {})),
+            }),
+        })),
+    });
+
+    // saved definitions can refer to each other regardless of their order
+    let definitions = [
+        "CREATE VIEW big_bids AS SELECT auction FROM bids WHERE auction > 10",
+        "CREATE VIEW bids AS SELECT bid.auction as auction FROM nexmark WHERE bid IS NOT NULL",
+    ];
+    for definition in definitions {
+        schema_provider.add_saved_definition(definition);
+    }
+    assert_eq!(
+        crate::saved_definition_name(definitions[0]).unwrap(),
+        "big_bids"
+    );
+    assert!(crate::saved_definition_name("SELECT 1").is_err());
+
+    let template = "CREATE TABLE ${prefix}_all (auction bigint) WITH (connection = 'local', topic = '${prefix}_all');
+        CREATE TABLE ${prefix}_big (auction bigint) WITH (connection = 'local', topic = '${prefix}_big');
+        INSERT INTO ${prefix}_all SELECT auction FROM bids;
+        INSERT INTO ${prefix}_big SELECT auction FROM big_bids;";
+    assert!(crate::substitute_parameters(template, &HashMap::new()).is_err());
+    let sql = crate::substitute_parameters(
+        template,
+        &HashMap::from([("prefix".to_string(), "auctions".to_string())]),
+    )
+    .unwrap();
+    assert!(sql.contains("topic = 'auctions_big'"));
+
+    // values are escaped within string literals and must be single tokens elsewhere
+    let parameters = |value: &str| HashMap::from([("prefix".to_string(), value.to_string())]);
+    assert_eq!(
+        crate::substitute_parameters("SELECT '${prefix}'", &parameters("it's")).unwrap(),
+        "SELECT 'it''s'"
+    );
+    assert!(
+        crate::substitute_parameters(template, &parameters("a (auction bigint); DROP")).is_err()
+    );
+    // quotes outside of string literals don't change where a placeholder is
+    let template = "-- the bids table's prefix\nSELECT * FROM ${prefix}_bids";
+    assert!(crate::substitute_parameters(template, &parameters("a; DROP")).is_err());
+    assert_eq!(
+        crate::substitute_parameters(template, &parameters("auctions")).unwrap(),
+        "-- the bids table's prefix\nSELECT * FROM auctions_bids"
+    );
+    assert!(
+        crate::substitute_parameters("SELECT 1 -- ${prefix}", &parameters("auctions")).is_err()
+    );
+
+    // a query reading a saved view that can't be planned reports why
+    let mut broken_schema_provider = schema_provider.clone();
+    broken_schema_provider
+        .add_saved_definition("CREATE VIEW lost_bids AS SELECT auction FROM deleted_source");
+    let err = parse_and_get_program(
+        "SELECT auction FROM lost_bids",
+        broken_schema_provider,
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("saved definition 'lost_bids' could not be planned"));
+
+    let (program, sources) = parse_and_get_program(&sql, schema_provider, SqlConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(sources, vec![1]);
+
+    // both sinks read from a single scan of the source
+    let count = |f: fn(&arroyo_datastream::Operator) -> bool| {
+        program
+            .graph
+            .node_weights()
+            .filter(|node| f(&node.operator))
+            .count()
+    };
+    assert_eq!(
+        count(|op| matches!(op, arroyo_datastream::Operator::NexmarkSource { .. })),
+        1
+    );
+    assert_eq!(
+        count(|op| matches!(op, arroyo_datastream::Operator::KafkaSink { .. })),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_error_positions() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+    let error = |sql: &'static str| {
+        let schema_provider = schema_provider.clone();
+        async move {
+            let err = parse_and_get_program(sql, schema_provider, SqlConfig::default())
+                .await
+                .unwrap_err();
+            SqlError::from_anyhow(&err)
+        }
+    };
+    let location = |line, column| SqlLocation { line, column };
+
+    // unknown columns point at where they are used
+    let err = error("SELECT bid\nFROM nexmark;\nSELECT bid,\n  missing FROM nexmark").await;
+    assert_eq!(
+        err.span,
+        Some(SqlSpan {
+            start: location(4, 3),
+            end: location(4, 10),
+        })
+    );
+
+    // other errors point at their statement
+    let err = error("SELECT bid FROM nexmark;\n  SELECT FROM WHERE").await;
+    assert_eq!(err.span.unwrap().start, location(2, 3));
+
+    let err = error("SELECT 'unterminated FROM nexmark").await;
+    assert_eq!(err.span.unwrap().start.line, 1);
+
+    // generated expressions have to type check
+    let err = error("SELECT bid.auction FROM nexmark WHERE bid.auction AND true").await;
+    assert_eq!(err.span.unwrap().start, location(1, 1));
+}
+
+#[tokio::test]
+async fn test_type_checks() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_saved_source_with_type(
+        1,
+        "nexmark".to_string(),
+        test_schema(),
+        Some("arroyo_types::nexmark::NexmarkEvent".to_string()),
+        arroyo_datastream::SourceConfig::NexmarkSource {
+            event_rate: 10,
+            runtime: Some(Duration::from_secs(10)),
+        },
+        SerializationMode::Json,
+    );
+
+    for expression in [
+        // comparisons, arithmetic and predicates
+        "bid.auction = bid.datetime",
+        "bid.auction + bid.datetime",
+        "bid.auction AND true",
+        // casts
+        "CAST(bid.auction AS BOOLEAN)",
+        // function arguments
+        "cardinality(bid.auction)",
+        "array_slice(make_array(bid.auction), 'a', 2)",
+        "json_build_object(1, bid.auction)",
+        "coalesce(bid.auction, bid.datetime)",
+        "nullif(bid.datetime, bid.auction)",
+        // CASE isn't supported yet
+        "CASE WHEN bid.auction > 10 THEN 1 ELSE 0 END",
+    ] {
+        let sql = format!("SELECT {} FROM nexmark", expression);
+        assert!(
+            parse_and_get_program(&sql, schema_provider.clone(), SqlConfig::default())
+                .await
+                .is_err(),
+            "{} should not type check",
+            expression
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_json_columns() {
+    let mut schema_provider = ArroyoSchemaProvider::new();
+    schema_provider.add_connection(Connection {
+        name: "local".to_string(),
+        sources: 0,
+        sinks: 0,
+        connection_type: Some(ConnectionType::Kafka(KafkaConnection {
+            bootstrap_servers: "localhost:9092".to_string(),
+            auth_config: Some(KafkaAuthConfig {
+                auth_type: Some(AuthType::NoAuth(NoAuth {})),
+            }),
+        })),
+    });
+
+    let sql = "CREATE TABLE events (
+          value JSON,
+          name TEXT GENERATED ALWAYS AS (value ->> 'name')
+        ) WITH (
+          connection = 'local',
+          topic = 'events',
+          serialization_mode = 'raw_json'
+        );
+        SELECT name, json_extract_int(value, '$.user.id') as user_id,
+          value -> 'tags' -> 0 ->> 'label' as label
+        FROM events
+        WHERE value -> 'user' ->> 'country' = 'US' AND json_array_length(value -> 'tags') > 0;";
+    parse_and_get_program(sql, schema_provider.clone(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    // JSON values have to be extracted before they can be used in arithmetic
+    assert!(parse_and_get_program(
+        "CREATE TABLE events (value JSON) WITH (connection = 'local', topic = 'events');
+        SELECT (value -> 'a') + 1 FROM events;",
+        schema_provider,
+        SqlConfig::default(),
+    )
+    .await
+    .is_err());
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashMap},
     hash::{Hash, Hasher},
     sync::Arc,
     time::Duration,
@@ -72,11 +72,8 @@ impl StructDef {
         result.extend(
             self.fields
                 .iter()
-                .filter_map(|field| match &field.data_type {
-                    TypeDef::StructDef(details, _) => Some(details.all_structs()),
-                    TypeDef::DataType(_, _) => None,
-                })
-                .flatten()
+                .filter_map(|field| field.data_type.struct_def())
+                .flat_map(|details| details.all_structs())
                 .collect::<Vec<_>>(),
         );
         result
@@ -90,11 +87,8 @@ impl StructDef {
         result.extend(
             self.fields
                 .iter()
-                .filter_map(|field| match &field.data_type {
-                    TypeDef::StructDef(details, _) => Some(details.all_names()),
-                    TypeDef::DataType(_, _) => None,
-                })
-                .flatten()
+                .filter_map(|field| field.data_type.struct_def())
+                .flat_map(|details| details.all_names())
                 .collect::<Vec<_>>(),
         );
         result
@@ -152,27 +146,63 @@ pub fn duration_to_syn_expr(duration: Duration) -> syn::Expr {
     parse_quote!(std::time::Duration::new(#secs, #nanos))
 }
 
+// arrow fields don't carry struct names, so named structs keep theirs in the field metadata
+const STRUCT_NAME_METADATA_KEY: &str = "arroyo.struct_name";
+
 impl From<StructField> for Field {
     fn from(struct_field: StructField) -> Self {
-        let (dt, nullable) = match struct_field.data_type {
-            TypeDef::StructDef(s, nullable) => (
-                DataType::Struct(
-                    s.fields
-                        .into_iter()
-                        .map(|f| {
-                            let field: Field = f.into();
-                            Arc::new(field)
-                        })
-                        .collect(),
-                ),
-                nullable,
+        match struct_field.data_type {
+            TypeDef::StructDef(s, nullable) => {
+                let field = Field::new(
+                    &struct_field.name,
+                    DataType::Struct(
+                        s.fields
+                            .into_iter()
+                            .map(|f| {
+                                let field: Field = f.into();
+                                Arc::new(field)
+                            })
+                            .collect(),
+                    ),
+                    nullable,
+                );
+                match s.name {
+                    Some(name) => field.with_metadata(HashMap::from([(
+                        STRUCT_NAME_METADATA_KEY.to_string(),
+                        name,
+                    )])),
+                    None => field,
+                }
+            }
+            TypeDef::DataType(dt, nullable) => Field::new(&struct_field.name, dt, nullable),
+        }
+    }
+}
+
+impl From<&Field> for StructField {
+    fn from(field: &Field) -> Self {
+        let data_type = match field.data_type() {
+            DataType::Struct(fields) => TypeDef::StructDef(
+                StructDef {
+                    name: field.metadata().get(STRUCT_NAME_METADATA_KEY).cloned(),
+                    fields: fields.iter().map(|f| f.as_ref().into()).collect(),
+                },
+                field.is_nullable(),
             ),
-            TypeDef::DataType(dt, nullable) => (dt, nullable),
+            data_type => TypeDef::DataType(data_type.clone(), field.is_nullable()),
         };
-        Field::new(&struct_field.name, dt, nullable)
+        StructField {
+            name: field.name().clone(),
+            alias: None,
+            data_type,
+        }
     }
 }
 
+/// The type of SQL `JSON` values. Arrow has no JSON type, so they're planned as large strings,
+/// which aren't otherwise used, and are represented by `arroyo_types::Json` in generated code.
+pub const JSON_TYPE: DataType = DataType::LargeUtf8;
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum TypeDef {
     StructDef(StructDef, bool),
@@ -231,6 +261,25 @@ impl TryFrom<&Type> for TypeDef {
 }
 
 impl TypeDef {
+    /// The type of the elements, if this is a list.
+    pub fn list_element_type(&self) -> Option<TypeDef> {
+        match self {
+            TypeDef::DataType(DataType::List(field), _) => {
+                let element: StructField = field.as_ref().into();
+                Some(element.data_type)
+            }
+            _ => None,
+        }
+    }
+
+    /// The struct this type is built on, either directly or as the elements of a list.
+    pub fn struct_def(&self) -> Option<StructDef> {
+        match self {
+            TypeDef::StructDef(details, _) => Some(details.clone()),
+            TypeDef::DataType(_, _) => self.list_element_type()?.struct_def(),
+        }
+    }
+
     pub fn is_optional(&self) -> bool {
         match self {
             TypeDef::StructDef(_, optional) => *optional,
@@ -293,12 +342,25 @@ impl TypeDef {
             ScalarValue::UInt16(Some(value)) => parse_quote!(#value),
             ScalarValue::UInt32(Some(value)) => parse_quote!(#value),
             ScalarValue::UInt64(Some(value)) => parse_quote!(#value),
-            ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
-                parse_quote!(#value.to_string())
+            ScalarValue::Utf8(Some(value)) => parse_quote!(#value.to_string()),
+            ScalarValue::LargeUtf8(Some(value)) => {
+                parse_quote!(arroyo_types::Json::parse(#value).unwrap_or_default())
             }
             ScalarValue::Binary(Some(bin)) => parse_str(&format!("{:?}", bin)).unwrap(),
             ScalarValue::LargeBinary(_) => todo!(),
-            ScalarValue::List(_, _) => todo!(),
+            ScalarValue::List(Some(values), field) => {
+                let entries = values.iter().map(|value| -> syn::Expr {
+                    match (field.is_nullable(), value.is_null()) {
+                        (true, true) => parse_quote!(None),
+                        (true, false) => {
+                            let literal = Self::get_literal(value);
+                            parse_quote!(Some(#literal))
+                        }
+                        (false, _) => Self::get_literal(value),
+                    }
+                });
+                parse_quote!(vec![#(#entries),*])
+            }
             ScalarValue::Date32(Some(val)) => parse_str(&format!(
                 "std::time::UNIX_EPOCH + std::time::Duration::from_days({})",
                 val
@@ -329,7 +391,23 @@ impl TypeDef {
                 let nanos = duration.subsec_nanos() as u64;
                 parse_quote!((std::time::Duration::from_secs(#seconds) + std::time::Duration::from_nanos(#nanos)))
             }
-            ScalarValue::Struct(_, _) => todo!(),
+            ScalarValue::Struct(Some(values), fields) => {
+                let struct_def = StructDef {
+                    name: None,
+                    fields: fields.iter().map(|f| f.as_ref().into()).collect(),
+                };
+                let struct_type = struct_def.get_type();
+                let assignments = struct_def.fields.iter().zip(values).map(|(field, value)| {
+                    let ident = field.field_ident();
+                    let literal = Self::get_literal(value);
+                    match (field.nullable(), value.is_null()) {
+                        (true, false) => quote!(#ident: Some(#literal)),
+                        (true, true) => quote!(#ident: None),
+                        (false, _) => quote!(#ident: #literal),
+                    }
+                });
+                parse_quote!(#struct_type { #(#assignments),* })
+            }
             ScalarValue::Dictionary(_, _) => todo!(),
             _ => todo!(),
         }
@@ -418,9 +496,10 @@ impl StructField {
             DataType::FixedSizeBinary(_) => todo!(),
             DataType::LargeBinary => todo!(),
             DataType::Utf8 => "String".to_string(),
-            DataType::LargeUtf8 => todo!(),
+            DataType::LargeUtf8 => "arroyo_types::Json".to_string(),
             DataType::List(field) => {
-                let list_data_type = Self::data_type_name(field.data_type());
+                let element: StructField = field.as_ref().into();
+                let list_data_type = element.data_type.type_string();
                 if field.is_nullable() {
                     format!("Vec<Option<{}>>", list_data_type)
                 } else {
@@ -519,7 +598,12 @@ fn convert_simple_data_type(sql_type: &SQLDataType) -> Result<DataType> {
         }
         SQLDataType::Bytea => Ok(DataType::Binary),
         SQLDataType::Interval => Ok(DataType::Interval(IntervalUnit::MonthDayNano)),
-        SQLDataType::JSON => bail!("JSON data type is not supported yet".to_string()),
+        SQLDataType::JSON => Ok(JSON_TYPE),
+        SQLDataType::Custom(name, modifiers)
+            if modifiers.is_empty() && name.to_string().eq_ignore_ascii_case("jsonb") =>
+        {
+            Ok(JSON_TYPE)
+        }
         _ => bail!(format!("Unsupported SQL type {sql_type:?}")),
     }
 }
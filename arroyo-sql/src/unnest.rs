@@ -0,0 +1,270 @@
+use std::ops::ControlFlow;
+
+use anyhow::{bail, Result};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, Expr as SqlExpr, FunctionArg, FunctionArgExpr, Ident, JoinOperator,
+    Query, Select, SetExpr, Statement, TableAlias, TableFactor, TableWithJoins,
+};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+use datafusion_common::tree_node::{Transformed, TreeNode, VisitRecursion};
+use datafusion_common::{Column, DataFusionError};
+use datafusion_expr::expr::ScalarUDF;
+use datafusion_expr::{Expr, LogicalPlan, LogicalPlanBuilder, Projection};
+
+// DataFusion can't plan UNNEST in a FROM clause, so `FROM t CROSS JOIN UNNEST(t.items) AS u(item)`
+// (or `FROM t, UNNEST(t.items)`) is rewritten to `FROM (SELECT *, unnest(t.items) AS item FROM t) AS t`,
+// which is then planned by plan_unnests().
+pub(crate) fn rewrite_unnest_joins(statement: &mut Statement) -> Result<()> {
+    match statement {
+        Statement::Query(query)
+        | Statement::Insert { source: query, .. }
+        | Statement::CreateView { query, .. }
+        | Statement::CreateTable {
+            query: Some(query), ..
+        } => rewrite_query(query),
+        _ => Ok(()),
+    }
+}
+
+fn rewrite_query(query: &mut Query) -> Result<()> {
+    if let Some(with) = &mut query.with {
+        for cte in &mut with.cte_tables {
+            rewrite_query(&mut cte.query)?;
+        }
+    }
+    rewrite_set_expr(&mut query.body)
+}
+
+fn rewrite_set_expr(set_expr: &mut SetExpr) -> Result<()> {
+    match set_expr {
+        SetExpr::Select(select) => rewrite_select(select),
+        SetExpr::Query(query) => rewrite_query(query),
+        SetExpr::SetOperation { left, right, .. } => {
+            rewrite_set_expr(left)?;
+            rewrite_set_expr(right)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn rewrite_table_factor(table_factor: &mut TableFactor) -> Result<()> {
+    match table_factor {
+        TableFactor::Derived { subquery, .. } => rewrite_query(subquery),
+        TableFactor::NestedJoin {
+            table_with_joins, ..
+        } => {
+            rewrite_table_factor(&mut table_with_joins.relation)?;
+            for join in &mut table_with_joins.joins {
+                rewrite_table_factor(&mut join.relation)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// returns the array expression and alias if the table factor is an UNNEST.
+fn unnest_call(table_factor: &TableFactor) -> Result<Option<(SqlExpr, Option<TableAlias>)>> {
+    match table_factor {
+        TableFactor::Table {
+            name,
+            alias,
+            args: Some(args),
+            ..
+        } if name.to_string().eq_ignore_ascii_case("unnest") => match args.as_slice() {
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))] => {
+                Ok(Some((expr.clone(), alias.clone())))
+            }
+            _ => bail!("UNNEST takes a single array argument"),
+        },
+        TableFactor::UNNEST {
+            alias,
+            array_expr,
+            with_offset,
+            ..
+        } => {
+            if *with_offset {
+                bail!("UNNEST ... WITH OFFSET is not supported");
+            }
+            Ok(Some((*array_expr.clone(), alias.clone())))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn rewrite_select(select: &mut Select) -> Result<()> {
+    for table in &mut select.from {
+        rewrite_table_factor(&mut table.relation)?;
+        for join in &mut table.joins {
+            rewrite_table_factor(&mut join.relation)?;
+        }
+    }
+
+    // `FROM t, UNNEST(...)` is the same as a cross join
+    if select.from.len() == 2
+        && select.from[1].joins.is_empty()
+        && unnest_call(&select.from[1].relation)?.is_some()
+    {
+        let unnest = select.from.pop().unwrap();
+        select.from[0]
+            .joins
+            .push(datafusion::sql::sqlparser::ast::Join {
+                relation: unnest.relation,
+                join_operator: JoinOperator::CrossJoin,
+            });
+    }
+
+    let mut unnests = 0;
+    for table in &select.from {
+        for relation in
+            std::iter::once(&table.relation).chain(table.joins.iter().map(|j| &j.relation))
+        {
+            if unnest_call(relation)?.is_some() {
+                unnests += 1;
+            }
+        }
+    }
+    if unnests == 0 {
+        return Ok(());
+    }
+    let is_single_cross_join = select.from.len() == 1
+        && select.from[0].joins.len() == 1
+        && matches!(
+            select.from[0].joins[0].join_operator,
+            JoinOperator::CrossJoin
+        )
+        && unnests == 1
+        && unnest_call(&select.from[0].joins[0].relation)?.is_some();
+    if !is_single_cross_join {
+        bail!("UNNEST is only supported in a cross join with a single table");
+    }
+
+    let TableWithJoins {
+        relation,
+        mut joins,
+    } = select.from.remove(0);
+    let (array_expr, alias) = unnest_call(&joins.remove(0).relation)?.unwrap();
+
+    // like Postgres, the column is named after the alias if no column name is given
+    let column = match &alias {
+        None => Ident::new("unnest"),
+        Some(TableAlias { name, columns }) => match columns.as_slice() {
+            [] => name.clone(),
+            [column] => column.clone(),
+            _ => bail!(
+                "UNNEST produces a single column, but {} were named",
+                columns.len()
+            ),
+        },
+    };
+    let qualifier = match &relation {
+        TableFactor::Table {
+            alias: Some(alias), ..
+        }
+        | TableFactor::Derived {
+            alias: Some(alias), ..
+        } => Some(alias.name.clone()),
+        TableFactor::Table { name, .. } => name.0.last().cloned(),
+        _ => None,
+    };
+
+    let subquery = Parser::new(&PostgreSqlDialect {})
+        .try_with_sql(&format!(
+            "SELECT *, unnest({}) AS {} FROM {}",
+            array_expr, column, relation
+        ))?
+        .parse_query()?;
+    select.from = vec![TableWithJoins {
+        relation: TableFactor::Derived {
+            lateral: false,
+            subquery: Box::new(subquery),
+            alias: qualifier.map(|name| TableAlias {
+                name,
+                columns: vec![],
+            }),
+        },
+        joins: vec![],
+    }];
+
+    // references through the UNNEST alias now go to the subquery's column
+    if let Some(TableAlias { name, .. }) = alias {
+        let _ = visit_expressions_mut(select, |expr| {
+            if let SqlExpr::CompoundIdentifier(idents) = expr {
+                if idents.len() == 2 && idents[0].value == name.value {
+                    *expr = SqlExpr::Identifier(idents[1].clone());
+                }
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+    Ok(())
+}
+
+// Replaces each projection with an unnest() call by an Unnest of the projected array.
+pub(crate) fn plan_unnests(plan: LogicalPlan) -> Result<LogicalPlan> {
+    Ok(plan.transform_up(&|plan| match plan {
+        LogicalPlan::Projection(projection) => unnest_projection(projection),
+        plan => {
+            if plan.expressions().iter().any(contains_unnest) {
+                return Err(DataFusionError::Plan(
+                    "unnest is only supported as a top-level expression in SELECT".to_string(),
+                ));
+            }
+            Ok(Transformed::No(plan))
+        }
+    })?)
+}
+
+fn unnest_projection(
+    projection: Projection,
+) -> datafusion_common::Result<Transformed<LogicalPlan>> {
+    let mut unnest_column = None;
+    let mut exprs = vec![];
+    for expr in &projection.expr {
+        let (inner, name) = match expr {
+            Expr::Alias(inner, name) => (inner.as_ref(), name.clone()),
+            expr => (expr, expr.display_name()?),
+        };
+        match inner {
+            Expr::ScalarUDF(ScalarUDF { fun, args }) if fun.name == "unnest" => {
+                if unnest_column.is_some() {
+                    return Err(DataFusionError::Plan(
+                        "only one unnest is supported per SELECT".to_string(),
+                    ));
+                }
+                unnest_column = Some(Column::from_name(&name));
+                exprs.push(args[0].clone().alias(name));
+            }
+            inner if contains_unnest(inner) => {
+                return Err(DataFusionError::Plan(
+                    "unnest is only supported as a top-level expression in SELECT".to_string(),
+                ));
+            }
+            _ => exprs.push(expr.clone()),
+        }
+    }
+    let Some(column) = unnest_column else {
+        return Ok(Transformed::No(LogicalPlan::Projection(projection)));
+    };
+    let plan = LogicalPlanBuilder::from(projection.input.as_ref().clone())
+        .project(exprs)?
+        .unnest_column(column)?
+        .build()?;
+    Ok(Transformed::Yes(plan))
+}
+
+fn contains_unnest(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.apply(&mut |expr| {
+        if let Expr::ScalarUDF(ScalarUDF { fun, .. }) = expr {
+            if fun.name == "unnest" {
+                found = true;
+                return Ok(VisitRecursion::Stop);
+            }
+        }
+        Ok(VisitRecursion::Continue)
+    })
+    .unwrap();
+    found
+}
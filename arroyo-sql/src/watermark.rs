@@ -0,0 +1,193 @@
+use anyhow::{bail, Result};
+use datafusion::sql::sqlparser::ast::{Expr as SqlExpr, Ident, Statement};
+use datafusion::sql::sqlparser::dialect::Dialect;
+use datafusion::sql::sqlparser::keywords::Keyword;
+use datafusion::sql::sqlparser::parser::Parser;
+use datafusion::sql::sqlparser::tokenizer::{Token, TokenWithLocation, Tokenizer};
+
+use crate::errors::{SqlError, SqlLocation, SqlSpan};
+use crate::lookup;
+
+/// A `WATERMARK FOR column AS expression` clause from a CREATE TABLE.
+#[derive(Debug, Clone)]
+pub(crate) struct WatermarkClause {
+    pub column: Ident,
+    pub expression: SqlExpr,
+}
+
+/// A statement along with the clauses that were removed from it so that it could be parsed.
+pub(crate) struct ParsedStatement {
+    pub statement: Statement,
+    pub watermark: Option<WatermarkClause>,
+    /// Tables that were read with `FOR SYSTEM_TIME AS OF`.
+    pub lookup_tables: Vec<String>,
+    /// The statement's tokens as they appeared in the query, to locate errors in it.
+    pub tokens: Vec<TokenWithLocation>,
+}
+
+// sqlparser doesn't know about Flink-style watermark or system time clauses, so they're removed
+// from each statement before it is parsed and returned alongside it.
+pub(crate) fn parse_sql(dialect: &dyn Dialect, query: &str) -> Result<Vec<ParsedStatement>> {
+    let tokens = Tokenizer::new(dialect, query)
+        .tokenize_with_location()
+        .map_err(|e| SqlError {
+            message: e.message,
+            span: Some(SqlSpan::point(SqlLocation {
+                line: e.line,
+                column: e.col,
+            })),
+        })?;
+    let mut statements = vec![];
+    for located_tokens in split_statements(tokens) {
+        let statement_tokens = located_tokens.iter().map(|t| t.token.clone()).collect();
+        let parsed = parse_statement(dialect, statement_tokens)
+            .map_err(|e| SqlError::in_statement(e, &located_tokens))?;
+        if let Some((statement, watermark, lookup_tables)) = parsed {
+            statements.push(ParsedStatement {
+                statement,
+                watermark,
+                lookup_tables,
+                tokens: located_tokens,
+            });
+        }
+    }
+    Ok(statements)
+}
+
+fn parse_statement(
+    dialect: &dyn Dialect,
+    statement_tokens: Vec<Token>,
+) -> Result<Option<(Statement, Option<WatermarkClause>, Vec<String>)>> {
+    let (statement_tokens, watermark) = extract_watermark(dialect, statement_tokens)?;
+    let (statement_tokens, lookup_tables) = lookup::extract_system_time(statement_tokens)?;
+    if statement_tokens.iter().all(is_whitespace) {
+        if watermark.is_some() {
+            bail!("WATERMARK FOR must be part of a CREATE TABLE statement");
+        }
+        return Ok(None);
+    }
+    let mut parsed = Parser::new(dialect)
+        .with_tokens(statement_tokens)
+        .parse_statements()?;
+    if parsed.len() != 1 {
+        bail!("expected a single statement, found {}", parsed.len());
+    }
+    Ok(Some((parsed.remove(0), watermark, lookup_tables)))
+}
+
+fn is_whitespace(token: &Token) -> bool {
+    matches!(token, Token::Whitespace(_))
+}
+
+fn is_word(token: &Token, word: &str) -> bool {
+    matches!(token, Token::Word(w) if w.quote_style.is_none() && w.value.eq_ignore_ascii_case(word))
+}
+
+fn split_statements(tokens: Vec<TokenWithLocation>) -> Vec<Vec<TokenWithLocation>> {
+    let mut statements = vec![vec![]];
+    let mut depth = 0;
+    for token in tokens {
+        match token.token {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            Token::SemiColon if depth == 0 => {
+                statements.push(vec![]);
+                continue;
+            }
+            _ => {}
+        }
+        statements.last_mut().unwrap().push(token);
+    }
+    statements
+}
+
+fn extract_watermark(
+    dialect: &dyn Dialect,
+    mut tokens: Vec<Token>,
+) -> Result<(Vec<Token>, Option<WatermarkClause>)> {
+    let significant: Vec<usize> = (0..tokens.len())
+        .filter(|i| !is_whitespace(&tokens[*i]))
+        .collect();
+
+    // find `WATERMARK FOR` directly inside the column list
+    let mut depth = 0;
+    let mut clause_start = None;
+    for (position, index) in significant.iter().enumerate() {
+        match &tokens[*index] {
+            Token::LParen => depth += 1,
+            Token::RParen => depth -= 1,
+            token if depth == 1 && is_word(token, "watermark") => {
+                let follows_separator = position > 0
+                    && matches!(
+                        tokens[significant[position - 1]],
+                        Token::Comma | Token::LParen
+                    );
+                let precedes_for = significant
+                    .get(position + 1)
+                    .map(|next| is_word(&tokens[*next], "for"))
+                    .unwrap_or(false);
+                if follows_separator && precedes_for {
+                    if clause_start.is_some() {
+                        bail!("only one WATERMARK FOR clause is allowed per table");
+                    }
+                    clause_start = Some(position);
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(clause_start) = clause_start else {
+        return Ok((tokens, None));
+    };
+    if !significant
+        .first()
+        .map(|first| is_word(&tokens[*first], "create"))
+        .unwrap_or(false)
+    {
+        bail!("WATERMARK FOR is only supported in CREATE TABLE statements");
+    }
+
+    // the clause ends at the next comma or closing parenthesis of the column list
+    let mut depth = 0;
+    let mut clause_end = significant.len();
+    for (position, index) in significant.iter().enumerate().skip(clause_start) {
+        match &tokens[*index] {
+            Token::LParen => depth += 1,
+            Token::RParen if depth == 0 => {
+                clause_end = position;
+                break;
+            }
+            Token::RParen => depth -= 1,
+            Token::Comma if depth == 0 => {
+                clause_end = position;
+                break;
+            }
+            _ => {}
+        }
+    }
+    if clause_end == significant.len() {
+        bail!("WATERMARK FOR clause is not terminated");
+    }
+
+    // skip past WATERMARK FOR
+    let clause_tokens = tokens[significant[clause_start + 2]..significant[clause_end]].to_vec();
+    let mut parser = Parser::new(dialect).with_tokens(clause_tokens);
+    let column = parser.parse_identifier()?;
+    parser.expect_keyword(Keyword::AS)?;
+    let expression = parser.parse_expr()?;
+    if parser.peek_token().token != Token::EOF {
+        bail!("unexpected {} in WATERMARK FOR clause", parser.peek_token());
+    }
+
+    // remove the clause along with the comma that separated it from the other columns
+    let separator = if matches!(tokens[significant[clause_start - 1]], Token::Comma) {
+        clause_start - 1
+    } else if matches!(tokens[significant[clause_end]], Token::Comma) {
+        clause_end += 1;
+        clause_start
+    } else {
+        clause_start
+    };
+    tokens.drain(significant[separator]..significant[clause_end]);
+    Ok((tokens, Some(WatermarkClause { column, expression })))
+}
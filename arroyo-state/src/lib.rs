@@ -387,6 +387,7 @@ mod test {
                     min_epoch: 0,
                     timestamp: SystemTime::now(),
                     then_stop: false,
+                    unaligned: false,
                 },
                 Some(SystemTime::now()),
             )
@@ -443,6 +444,7 @@ mod test {
                     min_epoch: 0,
                     timestamp: SystemTime::now(),
                     then_stop: false,
+                    unaligned: false,
                 },
                 Some(SystemTime::now()),
             )
@@ -462,4 +464,27 @@ mod test {
             vec![(t1, &1, &2), (t2, &1, &3), (t3, &1, &4), (t4, &1, &5)]
         );
     }
+
+    #[test_case(parquet_for_test().await; "parquet store")]
+    #[tokio::test]
+    async fn test_time_key_map_remove(p: (StateStore<impl BackingStore>, Receiver<ControlResp>)) {
+        let (mut ss, _rx) = p;
+
+        let mut ks: TimeKeyMap<usize, i32, _> = ss.get_time_key_map('t', None).await;
+
+        let t1 = SystemTime::now();
+        let t2 = t1 + Duration::from_secs(1);
+
+        ks.insert(t1, 1, 1);
+        ks.insert(t1, 2, 2);
+        ks.flush().await;
+        ks.insert(t1, 2, 3);
+        ks.insert(t2, 1, 4);
+
+        assert_eq!(ks.remove(t1, &mut 1), Some(1));
+        assert_eq!(ks.remove(t1, &mut 1), None);
+        assert_eq!(ks.remove(t1, &mut 2), Some(3));
+        assert_eq!(ks.get_all_for_time(t1), vec![]);
+        assert_eq!(ks.get_min_time(), Some(t2));
+    }
 }
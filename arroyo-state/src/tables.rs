@@ -51,6 +51,26 @@ impl<'a, K: Key, V: Data + PartialEq, S: BackingStore> TimeKeyMap<'a, K, V, S> {
             .insert(key, value);
     }
 
+    // Removes the value from the cache. Values that were already flushed are still part of the
+    // checkpoint until their timestamp passes the table's retention.
+    pub fn remove(&mut self, timestamp: SystemTime, key: &mut K) -> Option<V> {
+        let mut removed = None;
+        for values in [
+            &mut self.cache.persisted_values,
+            &mut self.cache.buffered_values,
+        ] {
+            if let Some(map_for_time) = values.get_mut(&timestamp) {
+                if let Some(value) = map_for_time.remove(key) {
+                    removed = Some(value);
+                }
+                if map_for_time.is_empty() {
+                    values.remove(&timestamp);
+                }
+            }
+        }
+        removed
+    }
+
     pub fn get_all_for_time(&self, timestamp: SystemTime) -> Vec<(&K, &V)> {
         match (
             self.cache.buffered_values.get(&timestamp),
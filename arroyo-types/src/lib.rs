@@ -39,6 +39,7 @@ pub const WORKER_ID_ENV: &str = "WORKER_ID_ENV";
 pub const JOB_ID_ENV: &str = "JOB_ID_ENV";
 pub const RUN_ID_ENV: &str = "RUN_ID_ENV";
 pub const REMOTE_COMPILER_ENDPOINT_ENV: &str = "REMOTE_COMPILER_ENDPOINT";
+pub const UDF_CRATE_REGISTRY_ENV: &str = "UDF_CRATE_REGISTRY";
 pub const NOMAD_ENDPOINT_ENV: &str = "NOMAD_ENDPOINT";
 pub const NOMAD_DC_ENV: &str = "NOMAD_DC";
 
@@ -63,6 +64,23 @@ pub const S3_REGION_ENV: &str = "S3_REGION";
 pub const S3_BUCKET_ENV: &str = "S3_BUCKET";
 pub const OUTPUT_DIR_ENV: &str = "OUTPUT_DIR";
 
+// when set, every operator subtask runs in its own task rather than forward-connected operators
+// being chained together
+pub const DISABLE_OPERATOR_CHAINING_ENV: &str = "DISABLE_OPERATOR_CHAINING";
+
+// records are sent between tasks one at a time unless BATCH_SIZE sets the most sent in one batch;
+// BATCH_LINGER_MS is the longest in milliseconds a record waits for its batch to fill up
+pub const BATCH_SIZE_ENV: &str = "BATCH_SIZE";
+pub const BATCH_LINGER_MS_ENV: &str = "BATCH_LINGER_MS";
+
+// when TLS_CERT_PATH is set, the controller and worker gRPC servers and the worker data plane use
+// mutual TLS with the PEM certificate and key, trusting peers signed by the CA; peers' certificates
+// must be issued for TLS_DOMAIN
+pub const TLS_CERT_PATH_ENV: &str = "TLS_CERT_PATH";
+pub const TLS_KEY_PATH_ENV: &str = "TLS_KEY_PATH";
+pub const TLS_CA_PATH_ENV: &str = "TLS_CA_PATH";
+pub const TLS_DOMAIN_ENV: &str = "TLS_DOMAIN";
+
 // kubernetes scheduler configuration
 pub const K8S_NAMESPACE_ENV: &str = "K8S_NAMESPACE";
 pub const K8S_WORKER_NAME_ENV: &str = "K8S_WORKER_NAME";
@@ -223,11 +241,19 @@ impl<T: Debug + Clone + Encode + Decode + Send + PartialEq + 'static> Data for T
 pub enum Message<K: Key, T: Data> {
     Record(Record<K, T>),
     Barrier(CheckpointBarrier),
-    Watermark(SystemTime),
+    Watermark(Watermark),
     Stop,
     EndOfData,
 }
 
+#[derive(Debug, Copy, Clone, Encode, Decode, PartialEq, Eq)]
+pub enum Watermark {
+    EventTime(SystemTime),
+    // the sender has no data to send for now; its inputs shouldn't hold back downstream watermarks
+    // until it sends a record or an event-time watermark again
+    Idle,
+}
+
 impl<K: Key, T: Data> Message<K, T> {
     pub fn is_end(&self) -> bool {
         matches!(self, Message::Stop | Message::EndOfData)
@@ -326,6 +352,65 @@ pub struct RawJson {
     pub value: String,
 }
 
+/// A parsed JSON document, the value of a SQL `JSON` column. It's serialized as the document
+/// itself, so it nests in JSON output, and is encoded, compared and hashed by its text.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Json(pub serde_json::Value);
+
+impl Json {
+    /// Parses a JSON document, returning None if it isn't valid.
+    pub fn parse(text: &str) -> Option<Self> {
+        serde_json::from_str(text).ok().map(Json)
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Hash for Json {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_string().hash(state)
+    }
+}
+
+impl PartialOrd for Json {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Json {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.to_string().cmp(&other.0.to_string())
+    }
+}
+
+impl Encode for Json {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        self.0.to_string().encode(encoder)
+    }
+}
+
+impl Decode for Json {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let text = String::decode(decoder)?;
+        serde_json::from_str(&text)
+            .map(Json)
+            .map_err(|e| bincode::error::DecodeError::OtherString(e.to_string()))
+    }
+}
+
+bincode::impl_borrow_decode!(Json);
+
 pub mod nexmark {
     use bincode::{Decode, Encode};
 
@@ -451,8 +536,10 @@ pub static MESSAGES_RECV: &str = "arroyo_worker_messages_recv";
 pub static MESSAGES_SENT: &str = "arroyo_worker_messages_sent";
 pub static BYTES_RECV: &str = "arroyo_worker_bytes_recv";
 pub static BYTES_SENT: &str = "arroyo_worker_bytes_sent";
+pub static LATE_RECORDS_DROPPED: &str = "arroyo_worker_late_records_dropped";
 pub static TX_QUEUE_SIZE: &str = "arroyo_worker_tx_queue_size";
 pub static TX_QUEUE_REM: &str = "arroyo_worker_tx_queue_rem";
+pub static TX_QUEUE_BLOCKED_MICROS: &str = "arroyo_worker_tx_queue_blocked_micros";
 
 #[derive(Debug, Copy, Clone, Encode, Decode)]
 pub struct CheckpointBarrier {
@@ -460,4 +547,5 @@ pub struct CheckpointBarrier {
     pub min_epoch: u32,
     pub timestamp: SystemTime,
     pub then_stop: bool,
+    pub unaligned: bool,
 }
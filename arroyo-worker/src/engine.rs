@@ -1,19 +1,22 @@
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::RangeInclusive;
 
 use std::any::Any;
 use std::process::exit;
-use std::{mem, thread};
+use std::sync::Arc;
+use std::{env, mem, thread};
 
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
-use arroyo_metrics::{counter_for_task, gauge_for_task};
-use arroyo_state::tables::TimeKeyMap;
+use arroyo_metrics::{counter_for_task, gauge_for_task, histogram_for_task};
+use arroyo_state::tables::{GlobalKeyedState, TimeKeyMap};
 use bincode::{config, Decode, Encode};
 
+use async_trait::async_trait;
+use futures::future::BoxFuture;
 use tracing::{debug, error, info, warn};
 
 pub use arroyo_macro::StreamNode;
@@ -24,45 +27,106 @@ use arroyo_rpc::grpc::{
     TaskFailedReq, TaskFinishedReq, TaskStartedReq,
 };
 use arroyo_rpc::{ControlMessage, ControlResp};
+use arroyo_server_common::tls::grpc_endpoint;
 use arroyo_types::{
-    from_micros, to_micros, CheckpointBarrier, Data, Key, Message, Record, TaskInfo, WorkerId,
-    BYTES_RECV, BYTES_SENT, MESSAGES_RECV, MESSAGES_SENT,
+    from_micros, to_micros, CheckpointBarrier, Data, Key, Message, Record, TaskInfo, Watermark,
+    WorkerId, BATCH_LINGER_MS_ENV, BATCH_SIZE_ENV, BYTES_RECV, BYTES_SENT,
+    DISABLE_OPERATOR_CHAINING_ENV, LATE_RECORDS_DROPPED, MESSAGES_RECV, MESSAGES_SENT,
+    TX_QUEUE_BLOCKED_MICROS,
 };
-use petgraph::graph::DiGraph;
+use petgraph::algo::toposort;
+use petgraph::graph::{DiGraph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use petgraph::Direction;
-use prometheus::{labels, IntCounter, IntGauge};
+use prometheus::{exponential_buckets, labels, Histogram, IntCounter, IntGauge};
 use rand::Rng;
 use tokio::select;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{
+    channel, unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender,
+};
+use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 use tonic::Request;
 
 use crate::network_manager::{NetworkManager, Quad, Senders};
-use crate::TIMER_TABLE;
 use crate::{LogicalEdge, LogicalNode, METRICS_PUSH_INTERVAL, PROMETHEUS_PUSH_GATEWAY};
-use arroyo_state::{hash_key, BackingStore, StateBackend, StateStore};
+use crate::{IN_FLIGHT_TABLE, PROCESSING_TIMER_TABLE, TIMER_TABLE};
+use arroyo_state::{global_table, hash_key, BackingStore, StateBackend, StateStore};
 
 const QUEUE_SIZE: usize = 4 * 1024;
 
+const DEFAULT_BATCH_DELAY: Duration = Duration::from_millis(10);
+
 #[derive(Debug)]
 pub enum QueueItem {
     Data(Box<dyn Any + Send>),
     Bytes(Vec<u8>),
+    // a Vec<Record<K, T>>, sent as a single item
+    DataBatch(Box<dyn Any + Send>),
+    // an encoded Vec<Record<K, T>>
+    BytesBatch(Vec<u8>),
 }
 
-impl<K: Key, T: Data> From<QueueItem> for Message<K, T> {
-    fn from(value: QueueItem) -> Self {
+// batches hold several messages, so they're handed back rather than converted
+impl<K: Key, T: Data> TryFrom<QueueItem> for Message<K, T> {
+    type Error = QueueItem;
+
+    fn try_from(value: QueueItem) -> Result<Self, Self::Error> {
         match value {
-            crate::engine::QueueItem::Data(datum) => *datum.downcast().unwrap(),
+            crate::engine::QueueItem::Data(datum) => Ok(*datum.downcast().unwrap()),
             crate::engine::QueueItem::Bytes(bs) => {
-                bincode::decode_from_slice(&bs, config::standard())
+                Ok(bincode::decode_from_slice(&bs, config::standard())
                     .unwrap()
-                    .0
+                    .0)
             }
+            batch @ (crate::engine::QueueItem::DataBatch(_)
+            | crate::engine::QueueItem::BytesBatch(_)) => Err(batch),
         }
     }
 }
+
+/// How the records sent on a queue between tasks are batched.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// The most records sent in one batch.
+    pub max_records: usize,
+    /// The longest that a record waits for its batch to fill up before it's sent.
+    pub max_delay: Duration,
+}
+
+impl BatchConfig {
+    /// Reads the batch limits from the environment, returning None if batching isn't enabled, is
+    /// misconfigured or batches are limited to a single record.
+    pub fn from_env() -> Option<Self> {
+        let size = env::var(BATCH_SIZE_ENV).ok()?;
+        let Ok(max_records) = size.parse::<usize>() else {
+            warn!(
+                "invalid {} '{}', sending records unbatched",
+                BATCH_SIZE_ENV, size
+            );
+            return None;
+        };
+        let max_delay = match env::var(BATCH_LINGER_MS_ENV) {
+            Ok(ms) => {
+                let Ok(ms) = ms.parse() else {
+                    warn!(
+                        "invalid {} '{}', sending records unbatched",
+                        BATCH_LINGER_MS_ENV, ms
+                    );
+                    return None;
+                };
+                Duration::from_millis(ms)
+            }
+            Err(_) => DEFAULT_BATCH_DELAY,
+        };
+
+        (max_records > 1 && !max_delay.is_zero()).then_some(Self {
+            max_records,
+            max_delay,
+        })
+    }
+}
 fn range_for_server(i: usize, n: usize) -> RangeInclusive<u64> {
     let range_size = u64::MAX / (n as u64);
     let start = range_size * (i as u64);
@@ -82,6 +146,7 @@ fn server_for_hash(x: u64, n: usize) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::process_fn::ProcessFnUtils;
 
     #[test]
     fn test_range_for_server() {
@@ -115,6 +180,583 @@ mod tests {
             "u64::MAX is not in the correct range"
         );
     }
+
+    #[test]
+    fn test_processing_timers() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let mut timers = ProcessingTimers::<String, u32>::default();
+        for (offset, key, data) in [(2, "a", 1), (1, "b", 2), (3, "c", 3), (2, "d", 4)] {
+            timers.insert(TimerValue {
+                time: start + Duration::from_secs(offset),
+                key: key.to_string(),
+                data,
+            });
+        }
+
+        assert_eq!(timers.next_time(), Some(start + Duration::from_secs(1)));
+        assert_eq!(
+            timers.remove(start + Duration::from_secs(2), &"d".to_string()),
+            Some(4)
+        );
+        assert_eq!(timers.remove(start, &"a".to_string()), None);
+
+        let expired: Vec<_> = timers
+            .expire(start + Duration::from_secs(2))
+            .into_iter()
+            .map(|timer| timer.data)
+            .collect();
+        assert_eq!(expired, vec![2, 1]);
+        assert_eq!(timers.next_time(), Some(start + Duration::from_secs(3)));
+        assert_eq!(timers.pending().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_late_data_outputs() {
+        let (tx, mut rx) = channel(8);
+        let (late_tx, mut late_rx) = channel(8);
+        let mut collector = Collector::<u64, String> {
+            out_qs: vec![vec![OutQueue::new(tx, false)]],
+            late_qs: vec![vec![OutQueue::new(late_tx, false).late_data()]],
+            batcher: None,
+            _ts: PhantomData,
+            sent_bytes: None,
+            sent_messages: None,
+            tx_queue_rem_gauges: vec![vec![None]],
+            tx_queue_size_gauges: vec![vec![None]],
+        };
+        let record = |value| Record {
+            timestamp: SystemTime::UNIX_EPOCH,
+            key: Some(1u64),
+            value,
+        };
+
+        collector.collect(record("result".to_string())).await;
+        collector.collect_late(record(5u32)).await;
+        collector
+            .broadcast(Message::Watermark(Watermark::EventTime(
+                SystemTime::UNIX_EPOCH,
+            )))
+            .await;
+
+        // late records have the input type, and only go to the late-data outputs
+        let Message::Record(result) = Message::<u64, String>::from(rx.try_recv().unwrap()) else {
+            panic!("expected a record");
+        };
+        assert_eq!(result.value, "result");
+        let Message::Record(late) = Message::<u64, u32>::from(late_rx.try_recv().unwrap()) else {
+            panic!("expected a record");
+        };
+        assert_eq!(late.value, 5);
+
+        // while watermarks go everywhere
+        for item in [rx.try_recv().unwrap(), late_rx.try_recv().unwrap()] {
+            assert!(matches!(
+                Message::<u64, String>::from(item),
+                Message::Watermark(_)
+            ));
+        }
+        assert!(rx.try_recv().is_err());
+        assert!(late_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batched_outputs() {
+        let (tx, mut rx) = channel(8);
+        let out_qs = vec![vec![OutQueue::new(tx, true).batched(BatchConfig {
+            max_records: 2,
+            max_delay: Duration::from_secs(60),
+        })]];
+        let mut collector = Collector::<u64, String> {
+            out_qs: out_qs.clone(),
+            late_qs: vec![],
+            batcher: Some(Arc::new(Mutex::new(Batcher {
+                out_qs,
+                batches: vec![vec![vec![]]],
+                sent_bytes: None,
+                batch_size_histograms: vec![None],
+            }))),
+            _ts: PhantomData,
+            sent_bytes: None,
+            sent_messages: None,
+            tx_queue_rem_gauges: vec![vec![None]],
+            tx_queue_size_gauges: vec![vec![None]],
+        };
+        let record = |value: &str| Record {
+            timestamp: SystemTime::UNIX_EPOCH,
+            key: Some(1u64),
+            value: value.to_string(),
+        };
+        let decode = |item| {
+            let QueueItem::BytesBatch(bytes) = item else {
+                panic!("expected a batch");
+            };
+            let records: Vec<Record<u64, String>> =
+                bincode::decode_from_slice(&bytes, config::standard())
+                    .unwrap()
+                    .0;
+            records.into_iter().map(|r| r.value).collect::<Vec<_>>()
+        };
+
+        // records are held until the batch is full
+        collector.collect(record("a")).await;
+        assert!(rx.try_recv().is_err());
+        collector.collect(record("b")).await;
+        assert_eq!(decode(rx.try_recv().unwrap()), vec!["a", "b"]);
+
+        // and control messages send the partial batch ahead of them
+        collector.collect(record("c")).await;
+        collector.broadcast(Message::Stop).await;
+        assert_eq!(decode(rx.try_recv().unwrap()), vec!["c"]);
+        assert!(matches!(
+            Message::<u64, String>::from(rx.try_recv().unwrap()),
+            Message::Stop
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
+    struct RecordingOperator {
+        messages: Arc<std::sync::Mutex<Vec<Message<u64, String>>>>,
+        timer: Option<SystemTime>,
+    }
+
+    #[async_trait]
+    impl ChainedOperator<u64, String> for RecordingOperator {
+        async fn handle(&mut self, message: Message<u64, String>) {
+            self.messages.lock().unwrap().push(message);
+        }
+
+        async fn next_processing_timer(&mut self) -> Option<SystemTime> {
+            self.timer
+        }
+
+        async fn handle_processing_timers(&mut self) {
+            self.timer = None;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chained_outputs() {
+        let messages = Arc::new(std::sync::Mutex::new(vec![]));
+        let timer = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+        let operator: Box<dyn ChainedOperator<u64, String>> = Box::new(RecordingOperator {
+            messages: messages.clone(),
+            timer: Some(timer),
+        });
+        let (tx, mut rx) = channel(8);
+        let mut collector = Collector::<u64, String> {
+            out_qs: vec![
+                vec![OutQueue::chained(Box::new(operator))],
+                vec![OutQueue::new(tx, false)],
+            ],
+            late_qs: vec![],
+            batcher: None,
+            _ts: PhantomData,
+            sent_bytes: None,
+            sent_messages: None,
+            tx_queue_rem_gauges: vec![vec![None], vec![None]],
+            tx_queue_size_gauges: vec![vec![None], vec![None]],
+        };
+
+        collector
+            .collect(Record {
+                timestamp: SystemTime::UNIX_EPOCH,
+                key: Some(1u64),
+                value: "result".to_string(),
+            })
+            .await;
+        collector.broadcast(Message::Stop).await;
+
+        // the chained operator is handed the same messages as the queues
+        {
+            let messages = messages.lock().unwrap();
+            assert!(
+                matches!(&messages[..], [Message::Record(record), Message::Stop] if record.value == "result")
+            );
+        }
+        assert!(matches!(
+            Message::<u64, String>::from(rx.try_recv().unwrap()),
+            Message::Record(_)
+        ));
+        assert!(matches!(
+            Message::<u64, String>::from(rx.try_recv().unwrap()),
+            Message::Stop
+        ));
+
+        assert_eq!(collector.next_chained_processing_timer().await, Some(timer));
+        collector.handle_chained_processing_timers().await;
+        assert_eq!(collector.next_chained_processing_timer().await, None);
+    }
+
+    #[derive(StreamNode)]
+    struct TimerOperator {}
+
+    #[arroyo_macro::process_fn(in_k = u64, in_t = String, out_k = u64, out_t = String, timer_t = String)]
+    impl TimerOperator {
+        fn name(&self) -> String {
+            "timer".to_string()
+        }
+
+        fn uses_processing_timers(&self) -> bool {
+            true
+        }
+
+        // schedules a processing-time timer at the record's timestamp, which emits its value
+        async fn process_element(
+            &mut self,
+            record: &Record<u64, String>,
+            ctx: &mut Context<u64, String, String>,
+        ) {
+            let mut key = record.key.unwrap();
+            ctx.schedule_processing_timer(&mut key, record.timestamp, record.value.clone())
+                .await;
+        }
+
+        async fn handle_timer(
+            &mut self,
+            key: u64,
+            value: String,
+            ctx: &mut Context<u64, String, String>,
+        ) {
+            ctx.collect(Record {
+                timestamp: SystemTime::UNIX_EPOCH,
+                key: Some(key),
+                value,
+            })
+            .await;
+        }
+    }
+
+    async fn timer_context(
+        task_info: &TaskInfo,
+        restore_from: Option<CheckpointMetadata>,
+    ) -> (
+        Context<u64, String, String>,
+        Receiver<QueueItem>,
+        Receiver<ControlResp>,
+    ) {
+        let (_, control_rx) = channel(128);
+        let (control_tx, control_resp_rx) = channel(128);
+        let (data_tx, data_rx) = channel(128);
+        let ctx = Context::new(
+            task_info.clone(),
+            restore_from,
+            control_rx,
+            control_tx,
+            1,
+            vec![vec![OutQueue::new(data_tx, false)]],
+            TimerOperator {}.tables(),
+        )
+        .await;
+        (ctx, data_rx, control_resp_rx)
+    }
+
+    // checkpoints the operator and completes the checkpoint, returning its metadata to restore from
+    async fn complete_timer_checkpoint(
+        operator: &mut TimerOperator,
+        ctx: &mut Context<u64, String, String>,
+        control_resp_rx: &mut Receiver<ControlResp>,
+        epoch: u32,
+    ) -> CheckpointMetadata {
+        let barrier = CheckpointBarrier {
+            epoch,
+            min_epoch: 1,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+            unaligned: false,
+        };
+        assert!(!operator.checkpoint(barrier, ctx).await);
+        let completed = loop {
+            match control_resp_rx.recv().await {
+                Some(ControlResp::CheckpointCompleted(completed)) => break completed,
+                Some(_) => continue,
+                None => panic!("checkpoint didn't complete"),
+            }
+        };
+        StateBackend::complete_operator_checkpoint(arroyo_rpc::grpc::OperatorCheckpointMetadata {
+            job_id: ctx.task_info.job_id.clone(),
+            operator_id: ctx.task_info.operator_id.clone(),
+            epoch,
+            has_state: true,
+            tables: completed.subtask_metadata.tables,
+            backend_data: completed.subtask_metadata.backend_data,
+            ..Default::default()
+        })
+        .await;
+
+        CheckpointMetadata {
+            job_id: ctx.task_info.job_id.clone(),
+            epoch,
+            min_epoch: 1,
+            operator_ids: vec![ctx.task_info.operator_id.clone()],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processing_timers_in_operator() {
+        let task_info = TaskInfo::for_test(
+            &format!("test_job_{}", rand::thread_rng().gen::<u64>()),
+            &format!("test_op_{}", rand::thread_rng().gen::<u64>()),
+        );
+        let mut operator = TimerOperator {};
+        let (mut ctx, mut data_rx, mut control_resp_rx) = timer_context(&task_info, None).await;
+
+        let due = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let later = SystemTime::now() + Duration::from_secs(60 * 60);
+        for (key, timestamp, value) in [
+            (1, due, "fires"),
+            (2, due, "deleted"),
+            (3, later, "restored"),
+        ] {
+            let record = Record {
+                timestamp,
+                key: Some(key),
+                value: value.to_string(),
+            };
+            operator.process_element(&record, &mut ctx).await;
+        }
+
+        assert_eq!(
+            ctx.delete_timer(TimeDomain::ProcessingTime, &mut 2, due)
+                .await,
+            Some("deleted".to_string())
+        );
+
+        // only the timer that's due and wasn't deleted fires
+        operator.handle_processing_timers(&mut ctx).await;
+        let Message::Record(fired) =
+            Message::<u64, String>::try_from(data_rx.try_recv().unwrap()).unwrap()
+        else {
+            panic!("expected a record");
+        };
+        assert_eq!((fired.key, fired.value.as_str()), (Some(1), "fires"));
+        assert!(data_rx.try_recv().is_err());
+
+        // the pending timer is part of the checkpoint
+        let metadata =
+            complete_timer_checkpoint(&mut operator, &mut ctx, &mut control_resp_rx, 1).await;
+        let (mut restored, _, _) = timer_context(&task_info, Some(metadata)).await;
+        assert_eq!(restored.next_processing_timer().await, Some(later));
+        let expired: Vec<_> = restored
+            .expire_processing_timers(later)
+            .await
+            .into_iter()
+            .map(|timer| (timer.key, timer.data))
+            .collect();
+        assert_eq!(expired, vec![(3, "restored".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_event_time_timer_stays_deleted_after_restore() {
+        let task_info = TaskInfo::for_test(
+            &format!("test_job_{}", rand::thread_rng().gen::<u64>()),
+            &format!("test_op_{}", rand::thread_rng().gen::<u64>()),
+        );
+        let mut operator = TimerOperator {};
+        let (mut ctx, _data_rx, mut control_resp_rx) = timer_context(&task_info, None).await;
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(10);
+        ctx.schedule_timer(&mut 1, time, "deleted".to_string())
+            .await;
+        ctx.schedule_timer(&mut 2, time, "kept".to_string()).await;
+        complete_timer_checkpoint(&mut operator, &mut ctx, &mut control_resp_rx, 1).await;
+
+        // the deleted timer is still in the first checkpoint
+        assert_eq!(
+            ctx.delete_timer(TimeDomain::EventTime, &mut 1, time).await,
+            Some("deleted".to_string())
+        );
+        assert_eq!(
+            ctx.delete_timer(TimeDomain::EventTime, &mut 1, time).await,
+            None
+        );
+        let metadata =
+            complete_timer_checkpoint(&mut operator, &mut ctx, &mut control_resp_rx, 2).await;
+
+        let (mut restored, _, _) = timer_context(&task_info, Some(metadata)).await;
+        let fired: Vec<_> = ProcessFnUtils::finished_timers(time, &mut restored)
+            .await
+            .into_iter()
+            .map(|(key, timer)| (key, timer.data))
+            .collect();
+        assert_eq!(fired, vec![(2, "kept".to_string())]);
+    }
+
+    #[test]
+    fn test_idle_watermarks() {
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut watermarks = WatermarkHolder::new(vec![None; 3]);
+        assert_eq!(watermarks.watermark(), None);
+
+        watermarks.set(0, Watermark::EventTime(time(10)));
+        watermarks.set(1, Watermark::EventTime(time(5)));
+        assert_eq!(watermarks.watermark(), None);
+
+        // an idle input that never sent a watermark doesn't hold the others back
+        watermarks.set(2, Watermark::Idle);
+        assert_eq!(watermarks.watermark(), Some(time(5)));
+
+        watermarks.set(1, Watermark::Idle);
+        assert_eq!(watermarks.watermark(), Some(time(10)));
+        assert!(!watermarks.is_idle());
+
+        // the watermark doesn't move back when a lagging input becomes active again
+        watermarks.set_active(1);
+        assert_eq!(watermarks.watermark(), Some(time(10)));
+        watermarks.set(1, Watermark::EventTime(time(12)));
+        watermarks.set(0, Watermark::EventTime(time(11)));
+        assert_eq!(watermarks.watermark(), Some(time(11)));
+
+        watermarks.set(0, Watermark::Idle);
+        assert_eq!(watermarks.watermark(), Some(time(12)));
+        watermarks.set(1, Watermark::Idle);
+        assert!(watermarks.is_idle());
+        assert_eq!(watermarks.watermark(), Some(time(12)));
+    }
+
+    #[test]
+    fn test_unaligned_checkpoints() {
+        let barrier = |epoch| CheckpointBarrier {
+            epoch,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+            unaligned: true,
+        };
+        let in_flight = |idx| InFlightMessage {
+            task_index: 0,
+            input: 0,
+            idx,
+            message: vec![idx as u8],
+        };
+
+        let mut unaligned = UnalignedCheckpoints::new(3);
+        let mut closed = HashSet::new();
+
+        // the checkpoint starts with the first barrier, sent ahead of the data
+        assert!(unaligned.barrier(1, barrier(1), false));
+        assert!(unaligned.in_progress());
+        assert!(!unaligned.barrier(0, barrier(1), false));
+        assert!(!unaligned.reached(1));
+
+        // and is taken once the data before it has come through on each input
+        unaligned.hold(in_flight(1), false);
+        assert!(!unaligned.barrier(1, barrier(1), true));
+        assert!(unaligned.reached(1));
+        unaligned.hold(in_flight(0), false);
+        assert!(!unaligned.barrier(0, barrier(1), true));
+        assert!(unaligned.complete(&closed).is_none());
+
+        // an input that ends has nothing more in flight
+        unaligned.hold(in_flight(2), true);
+        assert_eq!(unaligned.complete(&closed).map(|b| b.epoch), Some(1));
+        assert!(!unaligned.in_progress());
+        assert_eq!(
+            unaligned.in_flight(),
+            &[vec![in_flight(0)], vec![in_flight(1)], vec![in_flight(2)]]
+        );
+        assert_eq!(
+            unaligned.take_in_flight(),
+            vec![in_flight(0), in_flight(1), in_flight(2)]
+        );
+        assert!(unaligned.take_in_flight().is_empty());
+
+        // barriers of a checkpoint that's been taken are ignored
+        assert!(!unaligned.barrier(2, barrier(1), false));
+        assert!(!unaligned.in_progress());
+
+        // as are closed inputs
+        closed.insert(2);
+        assert!(unaligned.barrier(0, barrier(2), true));
+        assert!(unaligned.complete(&closed).is_none());
+        assert!(!unaligned.barrier(1, barrier(2), true));
+        assert_eq!(unaligned.complete(&closed).map(|b| b.epoch), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_unaligned_checkpoint_with_full_output() {
+        let mut task_info = arroyo_types::get_test_task_info();
+        task_info.job_id = format!("unaligned-{}", rand::thread_rng().gen::<u64>());
+
+        let (_control_tx, control_rx) = channel(16);
+        let (resp_tx, mut resp_rx) = channel(128);
+
+        // the output has room for a single message and isn't read
+        let (out_tx, mut out_rx) = channel(1);
+        let (out_barrier_tx, mut out_barrier_rx) = unbounded_channel();
+        let out_qs = vec![vec![
+            OutQueue::new(out_tx, false).with_barriers(out_barrier_tx)
+        ]];
+
+        let mut inputs = vec![];
+        let mut in_qs = vec![];
+        for _ in 0..2 {
+            let (tx, rx) = channel(8);
+            let (barrier_tx, barrier_rx) = unbounded_channel();
+            inputs.push((tx, barrier_tx));
+            in_qs.push(InQueue {
+                rx,
+                barriers: barrier_rx,
+            });
+        }
+
+        let operator = Box::new(crate::operators::MapOperator::<u64, u64, u64, u64> {
+            name: "map".to_string(),
+            map_fn: Box::new(|record, _| record.clone()),
+        });
+        let _task = operator.start(task_info, None, control_rx, resp_tx, vec![in_qs], out_qs);
+
+        let barrier = CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+            unaligned: true,
+        };
+        for (tx, barrier_tx) in &inputs {
+            for value in 0..4u64 {
+                tx.send(QueueItem::Data(Box::new(Message::Record(Record {
+                    timestamp: SystemTime::UNIX_EPOCH,
+                    key: Some(value),
+                    value,
+                }))))
+                .await
+                .unwrap();
+            }
+            tx.send(QueueItem::Data(Box::new(Message::<u64, u64>::Barrier(
+                barrier,
+            ))))
+            .await
+            .unwrap();
+            barrier_tx.send(barrier).unwrap();
+        }
+
+        // the checkpoint completes with the downstream queue still full...
+        let completed = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match resp_rx.recv().await.unwrap() {
+                    ControlResp::CheckpointCompleted(c) => return c,
+                    _ => continue,
+                }
+            }
+        })
+        .await
+        .expect("checkpoint did not complete");
+        assert_eq!(completed.checkpoint_epoch, 1);
+
+        // ...with the barrier sent on ahead of the queued data
+        assert_eq!(out_barrier_rx.try_recv().unwrap().epoch, 1);
+
+        // which is followed by the barrier in line, once there's room
+        assert!(matches!(
+            Message::<u64, u64>::from(out_rx.recv().await.unwrap()),
+            Message::Record(_)
+        ));
+        assert!(matches!(
+            Message::<u64, u64>::from(out_rx.recv().await.unwrap()),
+            Message::Barrier(b) if b.epoch == 1
+        ));
+    }
 }
 
 pub trait StreamNode: Send {
@@ -125,40 +767,167 @@ pub trait StreamNode: Send {
         checkpoint_metadata: Option<CheckpointMetadata>,
         control_rx: Receiver<ControlMessage>,
         control_tx: Sender<ControlResp>,
-        in_qs: Vec<Vec<Receiver<QueueItem>>>,
+        in_qs: Vec<Vec<InQueue>>,
         out_qs: Vec<Vec<OutQueue>>,
     ) -> JoinHandle<()>;
+
+    /// Whether the operator can be chained after the operator before it, which is the case for
+    /// operators with a single input that don't have work of their own to wait on.
+    fn chainable(&self) -> bool;
+
+    /// Whether the operator schedules processing-time timers. Sources don't wait on the timers of
+    /// the operators chained after them, so these operators aren't chained into a source's task.
+    fn has_processing_timers(&self) -> bool;
+
+    /// Sets up the operator to run chained after another one. The result is a
+    /// `Box<dyn ChainedOperator<K, T>>` for the operator's input types.
+    fn chain(
+        self: Box<Self>,
+        task_info: TaskInfo,
+        checkpoint_metadata: Option<CheckpointMetadata>,
+        control_rx: Receiver<ControlMessage>,
+        control_tx: Sender<ControlResp>,
+        out_qs: Vec<Vec<OutQueue>>,
+    ) -> BoxFuture<'static, Box<dyn Any + Send>>;
 }
 
-pub struct Context<K: Key, T: Data, S: BackingStore = StateBackend> {
+/// An operator that runs in the task of the operator before it, which passes it messages directly
+/// rather than through a queue.
+#[async_trait]
+pub trait ChainedOperator<K: Key, T: Data>: Send {
+    async fn handle(&mut self, message: Message<K, T>);
+
+    /// Returns the time of the earliest processing-time timer of this operator and those chained
+    /// after it.
+    async fn next_processing_timer(&mut self) -> Option<SystemTime>;
+
+    /// Fires the due processing-time timers of this operator and those chained after it.
+    async fn handle_processing_timers(&mut self);
+}
+
+/// A chained operator along with the state that its task would otherwise hold. Its
+/// `ChainedOperator` implementation is generated by `process_fn`.
+pub struct ChainedNode<N, K: Key, T: Data, D: Data + PartialEq + Eq = ()> {
+    pub node: Box<N>,
+    pub ctx: Context<K, T, D>,
+    pub counter: CheckpointCounter,
+    pub unaligned: UnalignedCheckpoints,
+    pub closed: HashSet<usize>,
+    pub finished: bool,
+}
+
+/// The state and outputs of an operator's task. `D` is the data of the operator's timers.
+pub struct Context<K: Key, T: Data, D: Data + PartialEq + Eq = (), S: BackingStore = StateBackend> {
     pub task_info: TaskInfo,
     pub control_rx: Receiver<ControlMessage>,
     pub control_tx: Sender<ControlResp>,
-    pub watermarks: Vec<Option<SystemTime>>,
+    pub watermarks: WatermarkHolder,
     pub state: StateStore<S>,
     pub collector: Collector<K, T>,
     pub counters: HashMap<&'static str, IntCounter>,
+    // loaded from state on first use
+    processing_timers: Option<ProcessingTimers<K, D>>,
     _ts: PhantomData<(K, T)>,
 }
 
-unsafe impl<K: Key, T: Data, S: BackingStore> Sync for Context<K, T, S> {}
+unsafe impl<K: Key, T: Data, D: Data + PartialEq + Eq, S: BackingStore> Sync
+    for Context<K, T, D, S>
+{
+}
+
+/// The receiving end of a queue between two tasks.
+pub struct InQueue {
+    pub rx: Receiver<QueueItem>,
+    /// the barriers of unaligned checkpoints, which overtake the data queued on `rx`
+    pub barriers: UnboundedReceiver<CheckpointBarrier>,
+}
+
+#[derive(Clone)]
+enum QueueTarget {
+    Channel(Sender<QueueItem>),
+    // a Box<dyn ChainedOperator<K, T>> for the types of the messages sent to it
+    Chained(Arc<Mutex<Box<dyn Any + Send>>>),
+}
 
 #[derive(Clone)]
 pub struct OutQueue {
-    tx: Sender<QueueItem>,
+    target: QueueTarget,
     serialize: bool,
+    late_data: bool,
+    batch: Option<BatchConfig>,
+    // carries the barriers of unaligned checkpoints ahead of the data queued on the channel
+    barriers: Option<UnboundedSender<CheckpointBarrier>>,
+    // time spent waiting for the receiver to make room in the queue
+    blocked_micros: Option<IntCounter>,
 }
 
 impl OutQueue {
     pub fn new(tx: Sender<QueueItem>, serialize: bool) -> Self {
-        Self { tx, serialize }
+        Self {
+            target: QueueTarget::Channel(tx),
+            serialize,
+            late_data: false,
+            batch: None,
+            barriers: None,
+            blocked_micros: None,
+        }
+    }
+
+    /// Creates a queue that hands messages straight to the next operator of a chain, as returned
+    /// by `StreamNode::chain`.
+    pub fn chained(next: Box<dyn Any + Send>) -> Self {
+        Self {
+            target: QueueTarget::Chained(Arc::new(Mutex::new(next))),
+            serialize: false,
+            late_data: false,
+            batch: None,
+            barriers: None,
+            blocked_micros: None,
+        }
+    }
+
+    /// Sends the barriers of unaligned checkpoints on `barriers` as well, which the receiver reads
+    /// ahead of the data queued on the channel.
+    pub fn with_barriers(mut self, barriers: UnboundedSender<CheckpointBarrier>) -> Self {
+        self.barriers = Some(barriers);
+        self
     }
 
-    pub async fn send(
+    /// Sends the records collected for this queue in batches. Only records are batched; other
+    /// messages are sent right away, after the records collected before them.
+    pub fn batched(mut self, config: BatchConfig) -> Self {
+        self.batch = Some(config);
+        self
+    }
+
+    /// Marks this queue as part of a late-data edge, which only receives the records an operator
+    /// passes to `collect_late` (along with watermarks and barriers).
+    pub fn late_data(mut self) -> Self {
+        self.late_data = true;
+        self
+    }
+
+    fn capacity(&self) -> usize {
+        match &self.target {
+            QueueTarget::Channel(tx) => tx.capacity(),
+            QueueTarget::Chained(_) => QUEUE_SIZE,
+        }
+    }
+
+    pub async fn send<K: Key, T: Data>(
         &self,
-        message: Message<impl Key, impl Data>,
+        message: Message<K, T>,
         sent_bytes: &Option<IntCounter>,
     ) {
+        let tx = match &self.target {
+            QueueTarget::Channel(tx) => tx,
+            QueueTarget::Chained(next) => {
+                Self::chained_operator::<K, T>(&mut *next.lock().await)
+                    .handle(message)
+                    .await;
+                return;
+            }
+        };
         let is_end = message.is_end();
         let item = if self.serialize {
             let bytes = bincode::encode_to_vec(&message, config::standard()).unwrap();
@@ -169,15 +938,169 @@ impl OutQueue {
             QueueItem::Data(Box::new(message))
         };
 
-        if self.tx.send(item).await.is_err() && !is_end {
+        if !self.send_to_channel(tx, item).await && !is_end {
             panic!("Failed to send, queue closed");
         }
     }
+
+    // Sends an item to the channel, returning whether it was sent. Any time spent waiting for the
+    // receiver to make room is counted as backpressure.
+    async fn send_to_channel(&self, tx: &Sender<QueueItem>, item: QueueItem) -> bool {
+        match tx.try_send(item) {
+            Ok(()) => true,
+            Err(TrySendError::Closed(_)) => false,
+            Err(TrySendError::Full(item)) => {
+                let start = Instant::now();
+                let sent = tx.send(item).await.is_ok();
+                self.blocked_micros
+                    .iter()
+                    .for_each(|c| c.inc_by(start.elapsed().as_micros() as u64));
+                sent
+            }
+        }
+    }
+
+    // Sends the barrier of an unaligned checkpoint ahead of the queued data. A chained operator
+    // has nothing queued, so it's handed the barrier right away.
+    async fn send_priority_barrier<K: Key, T: Data>(&self, barrier: CheckpointBarrier) {
+        match &self.target {
+            QueueTarget::Channel(_) => {
+                if let Some(barriers) = &self.barriers {
+                    // the receiver may have finished, in which case it has no use for the barrier
+                    let _ = barriers.send(barrier);
+                }
+            }
+            QueueTarget::Chained(next) => {
+                Self::chained_operator::<K, T>(&mut *next.lock().await)
+                    .handle(Message::Barrier(barrier))
+                    .await;
+            }
+        }
+    }
+
+    async fn send_batch<K: Key, T: Data>(
+        &self,
+        records: Vec<Record<K, T>>,
+        sent_bytes: &Option<IntCounter>,
+    ) {
+        let QueueTarget::Channel(tx) = &self.target else {
+            unreachable!("chained operators are passed records one at a time");
+        };
+        let item = if self.serialize {
+            let bytes = bincode::encode_to_vec(&records, config::standard()).unwrap();
+            sent_bytes.iter().for_each(|c| c.inc_by(bytes.len() as u64));
+
+            QueueItem::BytesBatch(bytes)
+        } else {
+            QueueItem::DataBatch(Box::new(records))
+        };
+
+        // batches are also sent by the flusher task, which may outlive the receiver when the
+        // job is shutting down, so a closed queue drops the batch rather than failing the task
+        if !self.send_to_channel(tx, item).await {
+            warn!("Dropping batch for closed queue");
+        }
+    }
+
+    async fn next_chained_processing_timer<K: Key, T: Data>(&self) -> Option<SystemTime> {
+        match &self.target {
+            QueueTarget::Channel(_) => None,
+            QueueTarget::Chained(next) => {
+                Self::chained_operator::<K, T>(&mut *next.lock().await)
+                    .next_processing_timer()
+                    .await
+            }
+        }
+    }
+
+    async fn handle_chained_processing_timers<K: Key, T: Data>(&self) {
+        if let QueueTarget::Chained(next) = &self.target {
+            Self::chained_operator::<K, T>(&mut *next.lock().await)
+                .handle_processing_timers()
+                .await;
+        }
+    }
+
+    fn chained_operator<K: Key, T: Data>(
+        next: &mut Box<dyn Any + Send>,
+    ) -> &mut Box<dyn ChainedOperator<K, T>> {
+        next.downcast_mut()
+            .expect("chained operator has a different input type")
+    }
+}
+
+fn out_idx<K: Key>(key: &Option<K>, qs: usize) -> usize {
+    let hash = if let Some(key) = &key {
+        hash_key(key)
+    } else {
+        // TODO: do we want this be random or deterministic?
+        rand::thread_rng().gen()
+    };
+
+    server_for_hash(hash, qs)
+}
+
+// Records waiting to be sent in batches to the queues that have a `BatchConfig`, by output and
+// queue like `Collector::out_qs`. It's shared with a task that flushes the batches every
+// `max_delay`.
+struct Batcher<K: Key, T: Data> {
+    out_qs: Vec<Vec<OutQueue>>,
+    batches: Vec<Vec<Vec<Record<K, T>>>>,
+    sent_bytes: Option<IntCounter>,
+    batch_size_histograms: Vec<Option<Histogram>>,
+}
+
+impl<K: Key, T: Data> Batcher<K, T> {
+    fn start_flusher(batcher: &Arc<Mutex<Self>>, max_delay: Duration) {
+        let batcher = Arc::downgrade(batcher);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(max_delay);
+            loop {
+                interval.tick().await;
+                // stop once the collector is gone
+                let Some(batcher) = batcher.upgrade() else {
+                    break;
+                };
+                batcher.lock().await.flush_all().await;
+            }
+        });
+    }
+
+    async fn add(&mut self, i: usize, idx: usize, record: Record<K, T>) {
+        let max_records = self.out_qs[i][idx].batch.unwrap().max_records;
+        self.batches[i][idx].push(record);
+        if self.batches[i][idx].len() >= max_records {
+            self.flush(i, idx).await;
+        }
+    }
+
+    async fn flush(&mut self, i: usize, idx: usize) {
+        if self.batches[i][idx].is_empty() {
+            return;
+        }
+        let records = mem::take(&mut self.batches[i][idx]);
+        self.batch_size_histograms[i]
+            .iter()
+            .for_each(|h| h.observe(records.len() as f64));
+        self.out_qs[i][idx]
+            .send_batch(records, &self.sent_bytes)
+            .await;
+    }
+
+    async fn flush_all(&mut self) {
+        for i in 0..self.batches.len() {
+            for idx in 0..self.batches[i].len() {
+                self.flush(i, idx).await;
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Collector<K: Key, T: Data> {
     out_qs: Vec<Vec<OutQueue>>,
+    late_qs: Vec<Vec<OutQueue>>,
+    batcher: Option<Arc<Mutex<Batcher<K, T>>>>,
     _ts: PhantomData<(K, T)>,
     sent_bytes: Option<IntCounter>,
     sent_messages: Option<IntCounter>,
@@ -187,64 +1110,132 @@ pub struct Collector<K: Key, T: Data> {
 
 impl<K: Key, T: Data> Collector<K, T> {
     pub async fn collect(&mut self, record: Record<K, T>) {
-        fn out_idx<K: Key>(key: &Option<K>, qs: usize) -> usize {
-            let hash = if let Some(key) = &key {
-                hash_key(key)
-            } else {
-                // TODO: do we want this be random or deterministic?
-                rand::thread_rng().gen()
-            };
-
-            server_for_hash(hash, qs)
-        }
-
         self.sent_messages.iter().for_each(|c| c.inc());
 
         if self.out_qs.len() == 1 {
             let idx = out_idx(&record.key, self.out_qs[0].len());
-
-            self.tx_queue_rem_gauges[0][idx]
-                .iter()
-                .for_each(|g| g.set(self.out_qs[0][idx].tx.capacity() as i64));
-
-            self.tx_queue_size_gauges[0][idx]
-                .iter()
-                .for_each(|g| g.set(QUEUE_SIZE as i64));
-
-            self.out_qs[0][idx]
-                .send(Message::Record(record), &self.sent_bytes)
-                .await;
+            self.send_record(0, idx, record).await;
         } else {
-            let key = record.key.clone();
-            let message = Message::Record(record);
+            for i in 0..self.out_qs.len() {
+                let idx = out_idx(&record.key, self.out_qs[i].len());
+                self.send_record(i, idx, record.clone()).await;
+            }
+        }
+    }
 
-            for (i, out_node_qs) in self.out_qs.iter().enumerate() {
-                let idx = out_idx(&key, out_node_qs.len());
-                self.tx_queue_rem_gauges[i][idx]
-                    .iter()
-                    .for_each(|c| c.set(self.out_qs[i][idx].tx.capacity() as i64));
+    async fn send_record(&mut self, i: usize, idx: usize, record: Record<K, T>) {
+        self.tx_queue_rem_gauges[i][idx]
+            .iter()
+            .for_each(|g| g.set(self.out_qs[i][idx].capacity() as i64));
 
-                self.tx_queue_size_gauges[i][idx]
-                    .iter()
-                    .for_each(|c| c.set(QUEUE_SIZE as i64));
+        self.tx_queue_size_gauges[i][idx]
+            .iter()
+            .for_each(|g| g.set(QUEUE_SIZE as i64));
 
-                out_node_qs[idx]
-                    .send(message.clone(), &self.sent_bytes)
+        match &self.batcher {
+            Some(batcher) if self.out_qs[i][idx].batch.is_some() => {
+                batcher.lock().await.add(i, idx, record).await;
+            }
+            _ => {
+                self.out_qs[i][idx]
+                    .send(Message::Record(record), &self.sent_bytes)
                     .await;
             }
         }
     }
 
+    /// Sends a record to the late-data outputs. Its type is the operator's input type rather than
+    /// its output type, so it isn't checked against the collector's.
+    pub async fn collect_late(&mut self, record: Record<K, impl Data>) {
+        let key = record.key.clone();
+        let message = Message::Record(record);
+        for out_node_qs in &self.late_qs {
+            let idx = out_idx(&key, out_node_qs.len());
+            self.sent_messages.iter().for_each(|c| c.inc());
+            out_node_qs[idx]
+                .send(message.clone(), &self.sent_bytes)
+                .await;
+        }
+    }
+
     pub async fn broadcast(&mut self, message: Message<K, T>) {
-        for out_node in &self.out_qs {
+        // the batched records go out ahead of the message, and holding the lock keeps the flusher
+        // from sending any more until it has
+        let mut batcher = match &self.batcher {
+            Some(batcher) => Some(batcher.lock().await),
+            None => None,
+        };
+        if let Some(batcher) = &mut batcher {
+            batcher.flush_all().await;
+        }
+
+        // chained operators were handed the barriers of unaligned checkpoints ahead of the data
+        let unaligned = matches!(message, Message::Barrier(barrier) if barrier.unaligned);
+        for out_node in self.out_qs.iter().chain(&self.late_qs) {
             for q in out_node {
+                if unaligned && matches!(q.target, QueueTarget::Chained(_)) {
+                    continue;
+                }
                 q.send(message.clone(), &self.sent_bytes).await;
             }
         }
     }
+
+    /// Sends the barrier of an unaligned checkpoint to the next operators ahead of the data queued
+    /// for them. It still has to be broadcast in line with the data once the checkpoint has been
+    /// taken, which marks where the data that was in flight ends.
+    pub async fn send_priority_barrier(&mut self, barrier: CheckpointBarrier) {
+        for q in self.out_qs.iter().chain(&self.late_qs).flatten() {
+            q.send_priority_barrier::<K, T>(barrier).await;
+        }
+    }
+
+    /// Returns a future that completes once every output queue has room, if any are full. Tasks
+    /// don't read more input until then, so that they aren't stuck sending while a barrier waits
+    /// to overtake the queued data. The queues of chained operators aren't checked.
+    pub fn output_ready(&self) -> Option<BoxFuture<'static, ()>> {
+        let full: Vec<_> = self
+            .out_qs
+            .iter()
+            .chain(&self.late_qs)
+            .flatten()
+            .filter_map(|q| match &q.target {
+                QueueTarget::Channel(tx) if tx.capacity() == 0 => Some(tx.clone()),
+                _ => None,
+            })
+            .collect();
+        if full.is_empty() {
+            return None;
+        }
+
+        Some(Box::pin(async move {
+            for tx in full {
+                // the permit is dropped right away, leaving the room for the task's next send
+                let _ = tx.reserve().await;
+            }
+        }))
+    }
+
+    /// Returns the time of the earliest processing-time timer of the operators chained after this
+    /// one.
+    pub async fn next_chained_processing_timer(&mut self) -> Option<SystemTime> {
+        let mut next = None;
+        for q in self.out_qs.iter().flatten() {
+            let time = q.next_chained_processing_timer::<K, T>().await;
+            next = next.into_iter().chain(time).min();
+        }
+        next
+    }
+
+    /// Fires the due processing-time timers of the operators chained after this one.
+    pub async fn handle_chained_processing_timers(&mut self) {
+        for q in self.out_qs.iter().flatten() {
+            q.handle_chained_processing_timers::<K, T>().await;
+        }
+    }
 }
 
-impl<K: Key, T: Data> Context<K, T> {
+impl<K: Key, T: Data, D: Data + PartialEq + Eq> Context<K, T, D> {
     pub async fn new(
         task_info: TaskInfo,
         restore_from: Option<CheckpointMetadata>,
@@ -253,8 +1244,9 @@ impl<K: Key, T: Data> Context<K, T> {
         input_partitions: usize,
         out_qs: Vec<Vec<OutQueue>>,
         mut tables: Vec<TableDescriptor>,
-    ) -> Context<K, T> {
+    ) -> Context<K, T, D> {
         tables.push(TableDescriptor {
+            // deleted timers are kept as `None` until the watermark passes them
             name: TIMER_TABLE.to_string(),
             description: "timer state".to_string(),
             table_type: TableType::TimeKeyMap as i32,
@@ -262,6 +1254,14 @@ impl<K: Key, T: Data> Context<K, T> {
             write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
             retention_micros: 0,
         });
+        tables.push(global_table(
+            PROCESSING_TIMER_TABLE.to_string(),
+            "processing-time timer state",
+        ));
+        tables.push(global_table(
+            IN_FLIGHT_TABLE.to_string(),
+            "in-flight messages of unaligned checkpoints",
+        ));
 
         let (state, watermark) = if let Some(metadata) = restore_from {
             let watermark = {
@@ -330,6 +1330,33 @@ impl<K: Key, T: Data> Context<K, T> {
             counters.insert(BYTES_SENT, c);
         }
 
+        if let Some(c) = counter_for_task(
+            &task_info,
+            LATE_RECORDS_DROPPED,
+            "Count of records dropped by this subtask for arriving after the watermark had passed them",
+            HashMap::new(),
+        ) {
+            counters.insert(LATE_RECORDS_DROPPED, c);
+        }
+
+        let (late_qs, mut out_qs): (Vec<_>, Vec<_>) = out_qs
+            .into_iter()
+            .partition(|qs| qs.iter().any(|q| q.late_data));
+
+        for (i, qs) in out_qs.iter_mut().enumerate() {
+            for (j, q) in qs.iter_mut().enumerate() {
+                q.blocked_micros = counter_for_task(
+                    &task_info,
+                    TX_QUEUE_BLOCKED_MICROS,
+                    "Time spent waiting for space in a tx queue, in microseconds",
+                    labels! {
+                        "next_node".to_string() => format!("{}", i),
+                        "next_node_idx".to_string() => format!("{}", j)
+                    },
+                );
+            }
+        }
+
         let tx_queue_size_gauges = out_qs
             .iter()
             .enumerate()
@@ -372,13 +1399,50 @@ impl<K: Key, T: Data> Context<K, T> {
             })
             .collect();
 
+        let batch_delay = out_qs
+            .iter()
+            .flatten()
+            .filter_map(|q| q.batch)
+            .map(|config| config.max_delay)
+            .min();
+
+        let batcher = batch_delay.map(|max_delay| {
+            let batch_size_histograms = (0..out_qs.len())
+                .map(|i| {
+                    histogram_for_task(
+                        &task_info,
+                        "arroyo_worker_tx_batch_size",
+                        "Number of records in the batches sent to the next node",
+                        labels! {
+                            "next_node".to_string() => format!("{}", i)
+                        },
+                        exponential_buckets(1.0, 2.0, 14).unwrap(),
+                    )
+                })
+                .collect();
+
+            let batcher = Arc::new(Mutex::new(Batcher {
+                out_qs: out_qs.clone(),
+                batches: out_qs
+                    .iter()
+                    .map(|qs| qs.iter().map(|_| vec![]).collect())
+                    .collect(),
+                sent_bytes: counters.get(BYTES_SENT).cloned(),
+                batch_size_histograms,
+            }));
+            Batcher::start_flusher(&batcher, max_delay);
+            batcher
+        });
+
         Context {
             task_info,
             control_rx,
             control_tx,
-            watermarks: vec![watermark; input_partitions],
+            watermarks: WatermarkHolder::new(vec![watermark; input_partitions]),
             collector: Collector::<K, T> {
                 out_qs,
+                late_qs,
+                batcher,
                 sent_messages: counters.remove(MESSAGES_SENT),
                 sent_bytes: counters.remove(BYTES_SENT),
                 tx_queue_rem_gauges,
@@ -387,6 +1451,7 @@ impl<K: Key, T: Data> Context<K, T> {
             },
             state,
             counters,
+            processing_timers: None,
             _ts: PhantomData,
         }
     }
@@ -421,26 +1486,21 @@ impl<K: Key, T: Data> Context<K, T> {
     }
 
     pub fn watermark(&self) -> Option<SystemTime> {
-        self.watermarks
-            .iter()
-            .copied()
-            .reduce(|current, next| match next {
-                Some(next) => current.map(|current| current.min(next)),
-                None => None,
-            })
-            .flatten()
+        self.watermarks.watermark()
     }
 
-    pub async fn schedule_timer<D: Data + PartialEq + Eq>(
-        &mut self,
-        key: &mut K,
-        event_time: SystemTime,
-        data: D,
-    ) {
-        let Some(watermark) = self.watermark() else {
-            return;
-         };
-        let mut timer_state: TimeKeyMap<K, TimerValue<K, D>, _> =
+    /// Schedules an event-time timer, which fires once the watermark reaches `event_time`. Timers
+    /// scheduled at or before the current watermark fire with the next watermark.
+    pub async fn schedule_timer(&mut self, key: &mut K, event_time: SystemTime, data: D) {
+        if let Some(watermark) = self.watermark() {
+            if event_time <= watermark {
+                debug!(
+                    "[{}] timer for {:?} scheduled at or before watermark {:?}",
+                    self.task_info.task_index, event_time, watermark
+                );
+            }
+        }
+        let mut timer_state: TimeKeyMap<K, Option<TimerValue<K, D>>, _> =
             self.state.get_time_key_map(TIMER_TABLE, None).await;
         let value = TimerValue {
             time: event_time,
@@ -448,8 +1508,6 @@ impl<K: Key, T: Data> Context<K, T> {
             data,
         };
 
-        assert!(watermark < event_time, "Timer scheduled for past");
-
         debug!(
             "[{}] scheduling timer for [{}, {:?}]",
             self.task_info.task_index,
@@ -457,7 +1515,146 @@ impl<K: Key, T: Data> Context<K, T> {
             event_time
         );
 
-        timer_state.insert(event_time, key.clone(), value);
+        timer_state.insert(event_time, key.clone(), Some(value));
+    }
+
+    /// Schedules a processing-time timer, which fires once the system clock reaches `time`. Only
+    /// operators with inputs are sent processing-time timers; sources drive their own loop.
+    pub async fn schedule_processing_timer(&mut self, key: &mut K, time: SystemTime, data: D) {
+        debug!(
+            "[{}] scheduling processing-time timer for [{}, {:?}]",
+            self.task_info.task_index,
+            hash_key(key),
+            time
+        );
+
+        self.processing_timers().await.insert(TimerValue {
+            time,
+            key: key.clone(),
+            data,
+        });
+    }
+
+    /// Deletes the timer for `key` at `time`, returning its data if it hadn't fired yet.
+    pub async fn delete_timer(
+        &mut self,
+        domain: TimeDomain,
+        key: &mut K,
+        time: SystemTime,
+    ) -> Option<D> {
+        match domain {
+            TimeDomain::EventTime => {
+                let mut timer_state: TimeKeyMap<K, Option<TimerValue<K, D>>, _> =
+                    self.state.get_time_key_map(TIMER_TABLE, None).await;
+                let timer = timer_state.remove(time, key).flatten()?;
+                // the timer may be part of an earlier checkpoint, so a tombstone is written in its
+                // place; restores read checkpoints in order, so it replaces the timer
+                timer_state.insert(time, key.clone(), None);
+                Some(timer.data)
+            }
+            TimeDomain::ProcessingTime => self.processing_timers().await.remove(time, key),
+        }
+    }
+
+    pub async fn next_processing_timer(&mut self) -> Option<SystemTime> {
+        self.processing_timers().await.next_time()
+    }
+
+    /// Removes and returns the processing-time timers that are due at `now`.
+    pub async fn expire_processing_timers(&mut self, now: SystemTime) -> Vec<TimerValue<K, D>> {
+        self.processing_timers().await.expire(now)
+    }
+
+    /// Writes the pending timers of both time domains to state, so that they are part of the
+    /// next checkpoint.
+    pub async fn checkpoint_timers(&mut self) {
+        let mut timer_state: TimeKeyMap<K, Option<TimerValue<K, D>>, _> =
+            self.state.get_time_key_map(TIMER_TABLE, None).await;
+        timer_state.flush().await;
+
+        let pending = self.processing_timers().await.pending();
+        let task_index = self.task_info.task_index;
+        let mut processing_timer_state: GlobalKeyedState<usize, Vec<TimerValue<K, D>>, _> = self
+            .state
+            .get_global_keyed_state(PROCESSING_TIMER_TABLE)
+            .await;
+        processing_timer_state.insert(task_index, pending).await;
+    }
+
+    /// Writes the messages held for each input during an unaligned checkpoint to state, so that
+    /// they can be replayed on restore.
+    pub async fn checkpoint_in_flight(&mut self, in_flight: &[Vec<InFlightMessage>]) {
+        let task_index = self.task_info.task_index;
+        let parallelism = self.task_info.parallelism;
+        let mut state: GlobalKeyedState<(usize, usize), InFlightInput, _> =
+            self.state.get_global_keyed_state(IN_FLIGHT_TABLE).await;
+        for (idx, messages) in in_flight.iter().enumerate() {
+            if !messages.is_empty() {
+                state
+                    .insert(
+                        (task_index, idx),
+                        InFlightInput {
+                            parallelism,
+                            messages: messages.clone(),
+                        },
+                    )
+                    .await;
+            }
+        }
+    }
+
+    /// Returns the in-flight messages restored from an unaligned checkpoint, along with whether
+    /// the parallelism has changed since. If it has, the messages of every subtask are returned and
+    /// must be filtered by key.
+    pub async fn restore_in_flight(&mut self) -> (Vec<InFlightMessage>, bool) {
+        let task_info = &self.task_info;
+        let mut state: GlobalKeyedState<(usize, usize), InFlightInput, _> =
+            self.state.get_global_keyed_state(IN_FLIGHT_TABLE).await;
+        let inputs = state.get_all();
+        let rescaled = inputs
+            .iter()
+            .any(|input| input.parallelism != task_info.parallelism);
+        let messages = inputs
+            .into_iter()
+            .flat_map(|input| &input.messages)
+            .filter(|message| rescaled || message.task_index == task_info.task_index)
+            .cloned()
+            .collect();
+        (messages, rescaled)
+    }
+
+    async fn processing_timers(&mut self) -> &mut ProcessingTimers<K, D> {
+        if self.processing_timers.is_none() {
+            let task_info = &self.task_info;
+            let mut state: GlobalKeyedState<usize, Vec<TimerValue<K, D>>, _> = self
+                .state
+                .get_global_keyed_state(PROCESSING_TIMER_TABLE)
+                .await;
+            // if the parallelism is unchanged each subtask takes back its own timers, otherwise
+            // they are redistributed by key
+            let restored: Vec<_> = if state.get_all().len() == task_info.parallelism {
+                state
+                    .get(&task_info.task_index)
+                    .cloned()
+                    .unwrap_or_default()
+            } else {
+                state
+                    .get_all()
+                    .into_iter()
+                    .flatten()
+                    .filter(|timer| task_info.key_range.contains(&hash_key(&timer.key)))
+                    .cloned()
+                    .collect()
+            };
+
+            let mut timers = ProcessingTimers::default();
+            for timer in restored {
+                timers.insert(timer);
+            }
+            self.processing_timers = Some(timers);
+        }
+
+        self.processing_timers.as_mut().unwrap()
     }
 
     pub async fn collect(&mut self, record: Record<K, T>) {
@@ -467,6 +1664,106 @@ impl<K: Key, T: Data> Context<K, T> {
     pub async fn broadcast(&mut self, message: Message<K, T>) {
         self.collector.broadcast(message).await;
     }
+
+    /// Sends a record that arrived too late to be processed to the operator's late-data outputs,
+    /// or counts it as dropped if it has none.
+    pub async fn collect_late(&mut self, record: Record<K, impl Data>) {
+        // without a late data output to go to, the record is dropped
+        if self.collector.late_qs.is_empty() {
+            self.count_late_record();
+        }
+        self.collector.collect_late(record).await;
+    }
+
+    /// Records that a record arrived too late to be processed, for operators that drop it
+    /// without sending it on.
+    pub fn count_late_record(&self) {
+        if let Some(c) = self.counters.get(LATE_RECORDS_DROPPED) {
+            c.inc();
+        }
+    }
+}
+
+/// Combines the watermarks of an operator's inputs. Inputs that have gone idle are left out until
+/// they send a record or an event-time watermark again.
+#[derive(Debug)]
+pub struct WatermarkHolder {
+    // the last event-time watermark received from each input
+    watermarks: Vec<Option<SystemTime>>,
+    idle: Vec<bool>,
+    current: Option<SystemTime>,
+}
+
+impl WatermarkHolder {
+    pub fn new(watermarks: Vec<Option<SystemTime>>) -> Self {
+        let mut holder = Self {
+            idle: vec![false; watermarks.len()],
+            watermarks,
+            current: None,
+        };
+        holder.update();
+        holder
+    }
+
+    pub fn len(&self) -> usize {
+        self.watermarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watermarks.is_empty()
+    }
+
+    pub fn watermark(&self) -> Option<SystemTime> {
+        self.current
+    }
+
+    /// Whether all of the inputs are idle.
+    pub fn is_idle(&self) -> bool {
+        !self.idle.is_empty() && self.idle.iter().all(|idle| *idle)
+    }
+
+    pub fn set(&mut self, idx: usize, watermark: Watermark) {
+        match watermark {
+            Watermark::EventTime(watermark) => {
+                self.watermarks[idx] = Some(watermark);
+                self.idle[idx] = false;
+            }
+            Watermark::Idle => {
+                self.idle[idx] = true;
+            }
+        }
+        self.update();
+    }
+
+    /// Marks an input as active after it sent a record.
+    pub fn set_active(&mut self, idx: usize) {
+        if self.idle[idx] {
+            self.idle[idx] = false;
+            self.update();
+        }
+    }
+
+    fn update(&mut self) {
+        let mut active = self
+            .watermarks
+            .iter()
+            .zip(&self.idle)
+            .filter(|(_, idle)| !**idle)
+            .map(|(watermark, _)| *watermark)
+            .peekable();
+        // if every input is idle the watermark stays where it is
+        if active.peek().is_none() {
+            return;
+        }
+        let Some(min) = active
+            .reduce(|current, next| current.zip(next).map(|(current, next)| current.min(next)))
+            .flatten()
+        else {
+            return;
+        };
+        // an input that becomes active again may lag behind, but the watermark can't move back
+        self.current = Some(self.current.map_or(min, |current| current.max(min)));
+    }
 }
 
 #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
@@ -476,6 +1773,72 @@ pub struct TimerValue<K: Key, T: Decode + Encode + Clone + PartialEq + Eq> {
     pub data: T,
 }
 
+/// The clock a timer is scheduled against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeDomain {
+    EventTime,
+    ProcessingTime,
+}
+
+struct ProcessingTimers<K: Key, D: Data + PartialEq + Eq> {
+    timers: BTreeMap<SystemTime, HashMap<K, D>>,
+}
+
+impl<K: Key, D: Data + PartialEq + Eq> Default for ProcessingTimers<K, D> {
+    fn default() -> Self {
+        Self {
+            timers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Key, D: Data + PartialEq + Eq> ProcessingTimers<K, D> {
+    fn insert(&mut self, timer: TimerValue<K, D>) {
+        self.timers
+            .entry(timer.time)
+            .or_default()
+            .insert(timer.key, timer.data);
+    }
+
+    fn remove(&mut self, time: SystemTime, key: &K) -> Option<D> {
+        let timers = self.timers.get_mut(&time)?;
+        let data = timers.remove(key);
+        if timers.is_empty() {
+            self.timers.remove(&time);
+        }
+        data
+    }
+
+    fn next_time(&self) -> Option<SystemTime> {
+        self.timers.keys().next().copied()
+    }
+
+    fn expire(&mut self, now: SystemTime) -> Vec<TimerValue<K, D>> {
+        let pending = self.timers.split_off(&(now + Duration::from_nanos(1)));
+        mem::replace(&mut self.timers, pending)
+            .into_iter()
+            .flat_map(|(time, timers)| {
+                timers
+                    .into_iter()
+                    .map(move |(key, data)| TimerValue { time, key, data })
+            })
+            .collect()
+    }
+
+    fn pending(&self) -> Vec<TimerValue<K, D>> {
+        self.timers
+            .iter()
+            .flat_map(|(time, timers)| {
+                timers.iter().map(|(key, data)| TimerValue {
+                    time: *time,
+                    key: key.clone(),
+                    data: data.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct CheckpointCounter {
     inputs: Vec<Option<u32>>,
@@ -521,6 +1884,154 @@ impl CheckpointCounter {
     }
 }
 
+/// A message that an operator read from one of its inputs during an unaligned checkpoint, which
+/// is stored in the checkpoint and processed once the checkpoint has been taken.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct InFlightMessage {
+    pub task_index: usize,
+    /// the logical input (handler) the message was received on
+    pub input: usize,
+    pub idx: usize,
+    /// the bincode-encoded `Message`
+    pub message: Vec<u8>,
+}
+
+/// The in-flight messages of one of a subtask's inputs, as stored in the checkpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct InFlightInput {
+    /// the parallelism of the operator when the checkpoint was taken
+    pub parallelism: usize,
+    pub messages: Vec<InFlightMessage>,
+}
+
+/// Tracks the unaligned checkpoints of a task.
+///
+/// An unaligned checkpoint starts with the first of its barriers to reach the task, which is
+/// usually one sent ahead of the data on an input's barrier queue. The operator is snapshotted and
+/// the barrier sent on straight away. The barrier is also sent in line with the data, and until it
+/// has come through on every input, the messages read from the inputs are held rather than
+/// processed. They were sent before the barrier, so they're stored in the checkpoint, which is
+/// then completed, and processed afterwards. Inputs stop being read once their barrier has come
+/// through, so an input holds no more than it had queued plus what was sent on it until its
+/// sender saw the barrier.
+#[derive(Debug)]
+pub struct UnalignedCheckpoints {
+    inputs: usize,
+    current: Option<UnalignedCheckpoint>,
+    last_epoch: Option<u32>,
+    in_flight: Vec<Vec<InFlightMessage>>,
+}
+
+#[derive(Debug)]
+struct UnalignedCheckpoint {
+    barrier: CheckpointBarrier,
+    // whether the barrier has come through in line with the data of each input
+    reached: Vec<bool>,
+    held: Vec<Vec<InFlightMessage>>,
+}
+
+impl UnalignedCheckpoints {
+    pub fn new(inputs: usize) -> Self {
+        Self {
+            inputs,
+            current: None,
+            last_epoch: None,
+            in_flight: vec![],
+        }
+    }
+
+    /// Handles a barrier received on input `idx`, either ahead of the data or in line with it,
+    /// returning whether it starts a checkpoint. Barriers of checkpoints that have already been
+    /// taken, or that arrive ahead of the data while another is in progress, are ignored.
+    pub fn barrier(&mut self, idx: usize, barrier: CheckpointBarrier, in_line: bool) -> bool {
+        if self
+            .last_epoch
+            .map_or(false, |epoch| barrier.epoch <= epoch)
+        {
+            return false;
+        }
+
+        let started = match &self.current {
+            None => {
+                self.current = Some(UnalignedCheckpoint {
+                    barrier,
+                    reached: vec![false; self.inputs],
+                    held: vec![vec![]; self.inputs],
+                });
+                true
+            }
+            Some(current) => {
+                if current.barrier.epoch != barrier.epoch {
+                    assert!(
+                        !in_line,
+                        "received barrier {} before {} on input {}",
+                        barrier.epoch, current.barrier.epoch, idx
+                    );
+                    return false;
+                }
+                false
+            }
+        };
+
+        if in_line {
+            self.current.as_mut().unwrap().reached[idx] = true;
+        }
+        started
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Whether the barrier of the checkpoint in progress has come through on input `idx`, in which
+    /// case the input isn't read until the checkpoint has been taken.
+    pub fn reached(&self, idx: usize) -> bool {
+        self.current
+            .as_ref()
+            .map_or(false, |current| current.reached[idx])
+    }
+
+    /// Holds a message read during the checkpoint in progress. The input is finished with if the
+    /// message is the last one it will send.
+    pub fn hold(&mut self, message: InFlightMessage, is_end: bool) {
+        let current = self
+            .current
+            .as_mut()
+            .expect("no unaligned checkpoint in progress");
+        if is_end {
+            current.reached[message.idx] = true;
+        }
+        current.held[message.idx].push(message);
+    }
+
+    /// Returns the barrier of the checkpoint in progress if it has come through on every input
+    /// that's still open. The messages held for each input are then available from `in_flight`,
+    /// to be stored in the checkpoint, until they're taken to be processed.
+    pub fn complete(&mut self, closed: &HashSet<usize>) -> Option<CheckpointBarrier> {
+        let current = self.current.as_ref()?;
+        if !(0..self.inputs).all(|idx| current.reached[idx] || closed.contains(&idx)) {
+            return None;
+        }
+
+        let current = self.current.take().unwrap();
+        self.last_epoch = Some(current.barrier.epoch);
+        self.in_flight = current.held;
+        Some(current.barrier)
+    }
+
+    pub fn in_flight(&self) -> &[Vec<InFlightMessage>] {
+        &self.in_flight
+    }
+
+    /// Returns the messages held for the last checkpoint to be taken, which are to be processed.
+    pub fn take_in_flight(&mut self) -> Vec<InFlightMessage> {
+        mem::take(&mut self.in_flight)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
 pub struct SubtaskNode {
     pub id: String,
     pub subtask_idx: usize,
@@ -568,6 +2079,8 @@ struct PhysicalGraphEdge {
     edge: LogicalEdge,
     tx: Option<Sender<QueueItem>>,
     rx: Option<Receiver<QueueItem>>,
+    barrier_tx: Option<UnboundedSender<CheckpointBarrier>>,
+    barrier_rx: Option<UnboundedReceiver<CheckpointBarrier>>,
 }
 
 impl Debug for PhysicalGraphEdge {
@@ -622,6 +2135,20 @@ impl SubtaskOrQueueNode {
         }
     }
 
+    fn chainable(&self) -> bool {
+        match self {
+            SubtaskOrQueueNode::SubtaskNode(n) => n.node.chainable(),
+            SubtaskOrQueueNode::QueueNode(_) => false,
+        }
+    }
+
+    fn has_processing_timers(&self) -> bool {
+        match self {
+            SubtaskOrQueueNode::SubtaskNode(n) => n.node.has_processing_timers(),
+            SubtaskOrQueueNode::QueueNode(_) => false,
+        }
+    }
+
     fn unwrap_subtask(self) -> SubtaskNode {
         match self {
             SubtaskOrQueueNode::SubtaskNode(n) => n,
@@ -693,6 +2220,7 @@ impl Program {
                     }
                     for (f, t) in from_nodes.iter().zip(&to_nodes) {
                         let (tx, rx) = channel(QUEUE_SIZE);
+                        let (barrier_tx, barrier_rx) = unbounded_channel();
                         let edge = PhysicalGraphEdge {
                             edge_idx: 0,
                             in_logical_idx: logical_in_node_idx.index(),
@@ -700,14 +2228,17 @@ impl Program {
                             edge: edge.clone(),
                             tx: Some(tx),
                             rx: Some(rx),
+                            barrier_tx: Some(barrier_tx),
+                            barrier_rx: Some(barrier_rx),
                         };
                         physical.add_edge(*f, *t, edge);
                     }
                 }
-                LogicalEdge::Shuffle | LogicalEdge::ShuffleJoin(_) => {
+                LogicalEdge::Shuffle | LogicalEdge::ShuffleJoin(_) | LogicalEdge::LateData => {
                     for f in &from_nodes {
                         for (idx, t) in to_nodes.iter().enumerate() {
                             let (tx, rx) = channel(QUEUE_SIZE);
+                            let (barrier_tx, barrier_rx) = unbounded_channel();
                             let edge = PhysicalGraphEdge {
                                 edge_idx: idx,
                                 in_logical_idx: logical_in_node_idx.index(),
@@ -715,6 +2246,8 @@ impl Program {
                                 edge: edge.clone(),
                                 tx: Some(tx),
                                 rx: Some(rx),
+                                barrier_tx: Some(barrier_tx),
+                                barrier_rx: Some(barrier_rx),
                             };
                             physical.add_edge(*f, *t, edge);
                         }
@@ -836,7 +2369,54 @@ impl Engine {
         }
     }
 
-    pub async fn start(mut self, config: StreamConfig) -> RunningEngine {
+    fn is_local(&self, node: &SubtaskOrQueueNode) -> bool {
+        self.assignments
+            .get(&(node.id().to_string(), node.subtask_idx()))
+            .unwrap()
+            .worker_id
+            == self.worker_id.0
+    }
+
+    // Finds the subtasks that can run chained after the operator before them: those on this
+    // worker whose only input is a forward edge from another subtask on this worker. Chains
+    // replace a queue and a task switch per message with a direct call. Operators with
+    // processing-time timers aren't chained into the task of a source, which doesn't wait on them.
+    fn chained_subtasks(&self) -> HashSet<NodeIndex> {
+        if env::var(DISABLE_OPERATOR_CHAINING_ENV).is_ok() {
+            return HashSet::new();
+        }
+
+        let graph = &self.program.graph;
+        let mut chained = HashSet::new();
+        // whether each subtask runs in the task of a source
+        let mut in_source_task = HashMap::new();
+        for idx in toposort(graph, None).expect("program graph has a cycle") {
+            let mut inputs = graph.edges_directed(idx, Direction::Incoming);
+            let (input, other) = (inputs.next(), inputs.next());
+            let Some(input) = input else {
+                in_source_task.insert(idx, true);
+                continue;
+            };
+
+            let node = graph.node_weight(idx).unwrap();
+            let source_task = in_source_task[&input.source()];
+            if other.is_none()
+                && input.weight().edge == LogicalEdge::Forward
+                && node.chainable()
+                && !(source_task && node.has_processing_timers())
+                && self.is_local(node)
+                && self.is_local(graph.node_weight(input.source()).unwrap())
+            {
+                chained.insert(idx);
+                in_source_task.insert(idx, source_task);
+            } else {
+                in_source_task.insert(idx, false);
+            }
+        }
+        chained
+    }
+
+    pub async fn start(mut self, config: StreamConfig) -> Result<RunningEngine, std::io::Error> {
         //console_subscriber::init();
         let checkpoint_metadata = if let Some(epoch) = config.restore_epoch {
             info!("Restoring checkpoint {} for job {}", epoch, self.job_id);
@@ -890,14 +2470,25 @@ impl Engine {
 
         let (control_tx, mut control_rx) = channel(128);
 
-        let indices: Vec<_> = self.program.graph.node_indices().collect();
+        // subtasks are set up from the end of the graph, so that chained operators exist before
+        // the operators they are chained after
+        let chained = self.chained_subtasks();
+        let batching = BatchConfig::from_env();
+        let mut chained_operators: HashMap<NodeIndex, Box<dyn Any + Send>> = HashMap::new();
+        let indices: Vec<_> = toposort(&self.program.graph, None)
+            .expect("program graph has a cycle")
+            .into_iter()
+            .rev()
+            .collect();
 
         let mut senders = Senders::new();
 
         let worker_id = self.worker_id;
         let job_id = self.job_id.clone();
         let mut controller = if let Some(addr) = self.controller_addr.clone() {
-            Some(ControllerGrpcClient::connect(addr).await.unwrap())
+            Some(ControllerGrpcClient::new(
+                grpc_endpoint(addr).unwrap().connect().await.unwrap(),
+            ))
         } else {
             None
         };
@@ -922,48 +2513,50 @@ impl Engine {
 
             if assignment.worker_id == self.worker_id.0 {
                 info!(
-                    "[{:?}] Scheduling {}-{}-{} ({}/{})",
+                    "[{:?}] Scheduling {}-{}-{} ({}/{}){}",
                     self.worker_id,
                     node.node.node_name(),
                     node.id,
                     node.subtask_idx,
                     node.subtask_idx + 1,
-                    node.parallelism
-                );
-
-                let mut in_qs_map: BTreeMap<(LogicalEdge, usize), Vec<Receiver<QueueItem>>> =
-                    BTreeMap::new();
-
-                for edge in self.program.graph.edge_indices() {
-                    if self.program.graph.edge_endpoints(edge).unwrap().1 == idx {
-                        let weight = self.program.graph.edge_weight_mut(edge).unwrap();
-                        in_qs_map
-                            .entry((weight.edge.clone(), weight.in_logical_idx))
-                            .or_default()
-                            .push(weight.rx.take().unwrap());
+                    node.parallelism,
+                    if chained.contains(&idx) {
+                        " chained"
+                    } else {
+                        ""
                     }
-                }
+                );
 
                 let mut out_qs_map: BTreeMap<usize, BTreeMap<usize, OutQueue>> = BTreeMap::new();
 
                 for edge in self.program.graph.edges_directed(idx, Direction::Outgoing) {
-                    // is the target of this edge local or remote?
-                    let local = {
-                        let target = self.program.graph.node_weight(edge.target()).unwrap();
-                        self.assignments
-                            .get(&(target.id().to_string(), target.subtask_idx()))
-                            .unwrap()
-                            .worker_id
-                            == self.worker_id.0
+                    let sender = if let Some(next) = chained_operators.remove(&edge.target()) {
+                        OutQueue::chained(next)
+                    } else {
+                        // is the target of this edge local or remote?
+                        let local =
+                            self.is_local(self.program.graph.node_weight(edge.target()).unwrap());
+
+                        let tx = edge.weight().tx.as_ref().unwrap().clone();
+                        let queue = OutQueue::new(tx, !local)
+                            .with_barriers(edge.weight().barrier_tx.as_ref().unwrap().clone());
+                        if edge.weight().edge == LogicalEdge::LateData {
+                            queue.late_data()
+                        } else if let Some(config) = batching {
+                            queue.batched(config)
+                        } else {
+                            queue
+                        }
                     };
-
-                    let tx = edge.weight().tx.as_ref().unwrap().clone();
-                    let sender = OutQueue::new(tx, !local);
                     out_qs_map
                         .entry(edge.weight().out_logical_idx)
                         .or_default()
                         .insert(edge.weight().edge_idx, sender);
                 }
+                let out_qs = out_qs_map
+                    .into_values()
+                    .map(|v| v.into_values().collect())
+                    .collect();
 
                 let task_info = self
                     .program
@@ -973,32 +2566,61 @@ impl Engine {
                     .as_queue()
                     .task_info
                     .clone();
-                let operator_id = task_info.operator_id.clone();
-                let task_index = task_info.task_index;
-                let join_task = node.node.start(
-                    task_info,
-                    checkpoint_metadata.clone(),
-                    control_rx,
-                    control_tx.clone(),
-                    in_qs_map.into_values().collect(),
-                    out_qs_map
-                        .into_values()
-                        .map(|v| v.into_values().collect())
-                        .collect(),
-                );
-                let send_copy = control_tx.clone();
-                tokio::spawn(async move {
-                    if let Err(error) = join_task.await {
-                        send_copy
-                            .send(ControlResp::TaskFailed {
-                                operator_id,
-                                task_index,
-                                error: error.to_string(),
-                            })
-                            .await
-                            .ok();
-                    };
-                });
+
+                if chained.contains(&idx) {
+                    // runs in the task of the operator before it, so its input queue goes unused
+                    let operator = node
+                        .node
+                        .chain(
+                            task_info,
+                            checkpoint_metadata.clone(),
+                            control_rx,
+                            control_tx.clone(),
+                            out_qs,
+                        )
+                        .await;
+                    chained_operators.insert(idx, operator);
+                } else {
+                    let mut in_qs_map: BTreeMap<(LogicalEdge, usize), Vec<InQueue>> =
+                        BTreeMap::new();
+
+                    for edge in self.program.graph.edge_indices() {
+                        if self.program.graph.edge_endpoints(edge).unwrap().1 == idx {
+                            let weight = self.program.graph.edge_weight_mut(edge).unwrap();
+                            in_qs_map
+                                .entry((weight.edge.clone(), weight.in_logical_idx))
+                                .or_default()
+                                .push(InQueue {
+                                    rx: weight.rx.take().unwrap(),
+                                    barriers: weight.barrier_rx.take().unwrap(),
+                                });
+                        }
+                    }
+
+                    let operator_id = task_info.operator_id.clone();
+                    let task_index = task_info.task_index;
+                    let join_task = node.node.start(
+                        task_info,
+                        checkpoint_metadata.clone(),
+                        control_rx,
+                        control_tx.clone(),
+                        in_qs_map.into_values().collect(),
+                        out_qs,
+                    );
+                    let send_copy = control_tx.clone();
+                    tokio::spawn(async move {
+                        if let Err(error) = join_task.await {
+                            send_copy
+                                .send(ControlResp::TaskFailed {
+                                    operator_id,
+                                    task_index,
+                                    error: error.to_string(),
+                                })
+                                .await
+                                .ok();
+                        };
+                    });
+                }
 
                 // TODO: make async
                 if let Some(c) = controller.as_mut() {
@@ -1028,7 +2650,11 @@ impl Engine {
                         dst_idx: target.subtask_idx(),
                     };
 
-                    senders.add(quad, edge.weight().tx.as_ref().unwrap().clone());
+                    senders.add(
+                        quad,
+                        edge.weight().tx.as_ref().unwrap().clone(),
+                        edge.weight().barrier_tx.as_ref().unwrap().clone(),
+                    );
                 }
 
                 let mut connects = vec![];
@@ -1054,8 +2680,9 @@ impl Engine {
                             assignment.worker_addr.clone(),
                             quad,
                             edge.rx.take().unwrap(),
+                            edge.barrier_rx.take().unwrap(),
                         )
-                        .await;
+                        .await?;
                 }
             }
         }
@@ -1065,6 +2692,7 @@ impl Engine {
         // clear all of the TXs in the graph so that we don't leave dangling senders
         for n in self.program.graph.edge_weights_mut() {
             n.tx = None;
+            n.barrier_tx = None;
         }
 
         tokio::spawn(async move {
@@ -1169,11 +2797,11 @@ impl Engine {
             }
         });
 
-        RunningEngine {
+        Ok(RunningEngine {
             program: self.program,
             shutdown_tx,
             assignments: self.assignments,
             worker_id,
-        }
+        })
     }
 }
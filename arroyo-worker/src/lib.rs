@@ -13,6 +13,7 @@ use arroyo_rpc::grpc::{
 };
 use arroyo_rpc::ControlMessage;
 use arroyo_server_common::start_admin_server;
+use arroyo_server_common::tls::{grpc_endpoint, tls_config, with_tls};
 use arroyo_types::{
     from_millis, grpc_port, ports, CheckpointBarrier, NodeId, WorkerId, JOB_ID_ENV, RUN_ID_ENV,
 };
@@ -50,6 +51,8 @@ lazy_static! {
 }
 
 pub static TIMER_TABLE: char = '[';
+pub static PROCESSING_TIMER_TABLE: char = ']';
+pub static IN_FLIGHT_TABLE: char = '^';
 
 pub enum SourceFinishType {
     // stop messages should be propagated through the dataflow
@@ -71,6 +74,8 @@ pub enum LogicalEdge {
     Forward,
     Shuffle,
     ShuffleJoin(usize),
+    /// Carries the records that an operator received too late to process.
+    LateData,
 }
 
 impl Display for LogicalEdge {
@@ -79,6 +84,7 @@ impl Display for LogicalEdge {
             LogicalEdge::Forward => write!(f, "→"),
             LogicalEdge::Shuffle => write!(f, "⤨"),
             LogicalEdge::ShuffleJoin(order) => write!(f, "{}⤨", order),
+            LogicalEdge::LateData => write!(f, "late⤨"),
         }
     }
 }
@@ -124,7 +130,8 @@ impl LocalRunner {
             .start(StreamConfig {
                 restore_epoch: None,
             })
-            .await;
+            .await
+            .expect("failed to start local engine");
 
         loop {
             tokio::time::sleep(Duration::from_secs(1)).await;
@@ -189,9 +196,17 @@ impl WorkerServer {
         let local_addr = listener.local_addr()?;
 
         info!("Started worker-rpc for {} on {}", self.name, local_addr);
-        let mut client = ControllerGrpcClient::connect(self.controller_addr.clone()).await?;
+        let mut client = ControllerGrpcClient::new(
+            grpc_endpoint(self.controller_addr.clone())?
+                .connect()
+                .await?,
+        );
 
         let mut network = NetworkManager::new(0);
+        if let Some(tls) = tls_config() {
+            let (connector, server_name) = tls.connector()?;
+            network = network.with_tls(tls.acceptor()?, connector, server_name);
+        }
         let data_port = network.open_listener().await;
 
         (*self.network.lock().unwrap()) = Some(network);
@@ -234,7 +249,7 @@ impl WorkerServer {
                 .unwrap();
         });
 
-        arroyo_server_common::grpc_server()
+        with_tls(arroyo_server_common::grpc_server())?
             .add_service(WorkerGrpcServer::new(self))
             .serve_with_incoming(TcpListenerStream::new(listener))
             .await?;
@@ -272,6 +287,7 @@ impl WorkerGrpc for WorkerServer {
 
         let engine = {
             let network = { self.network.lock().unwrap().take().unwrap() };
+            network.set_secret(req.data_secret.clone());
 
             let engine = Engine::new(
                 program,
@@ -287,6 +303,7 @@ impl WorkerGrpc for WorkerServer {
                     restore_epoch: req.restore_epoch,
                 })
                 .await
+                .map_err(|e| Status::unavailable(format!("failed to start execution: {}", e)))?
         };
 
         let sources = engine.source_controls();
@@ -325,6 +342,7 @@ impl WorkerGrpc for WorkerServer {
             min_epoch: req.min_epoch,
             timestamp: from_millis(req.timestamp),
             then_stop: req.then_stop,
+            unaligned: req.unaligned,
         };
 
         for n in &senders {
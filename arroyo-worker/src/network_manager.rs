@@ -1,19 +1,23 @@
 #![allow(clippy::redundant_slicing)]
-use arroyo_types::Message;
+use arroyo_types::{CheckpointBarrier, Message};
 use bincode::config;
-use std::{collections::HashMap, mem::size_of, pin::Pin, sync::Arc, time::Duration};
+use lazy_static::lazy_static;
+use prometheus::{register_int_gauge, IntGauge};
+use std::{collections::HashMap, pin::Pin, sync::Arc, time::Duration};
 use tokio::{
     io::{self, BufReader, BufWriter},
     select,
-    sync::Mutex,
+    sync::{watch, Mutex, Semaphore},
 };
+use tokio_rustls::rustls::ServerName;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
 use tracing::warn;
 
 use bytes::{Buf, BufMut};
 use tokio::{
-    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     net::{TcpListener, TcpStream},
-    sync::mpsc::{Receiver, Sender},
+    sync::mpsc::{unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender},
 };
 
 use crate::engine::QueueItem;
@@ -22,66 +26,322 @@ use tokio_stream::StreamExt;
 
 use crate::inq_reader::InQReader;
 
+// the number of frames for each quad that a receiver will buffer, which is how many credits a
+// sender starts with
+const INITIAL_CREDITS: usize = 128;
+
+lazy_static! {
+    static ref NETWORK_BUFFERED_FRAMES: IntGauge = register_int_gauge!(
+        "arroyo_worker_network_buffered_frames",
+        "Frames received from other workers that are waiting for their operators to take them"
+    )
+    .unwrap();
+    static ref NETWORK_BUFFERED_BYTES: IntGauge = register_int_gauge!(
+        "arroyo_worker_network_buffered_bytes",
+        "Bytes received from other workers that are waiting for their operators to take them"
+    )
+    .unwrap();
+}
+
 #[derive(Clone)]
 pub struct Senders {
     senders: HashMap<Quad, Sender<QueueItem>>,
+    barriers: HashMap<Quad, UnboundedSender<CheckpointBarrier>>,
 }
 
 impl Senders {
     pub fn new() -> Self {
         Self {
             senders: HashMap::new(),
+            barriers: HashMap::new(),
         }
     }
 
-    pub fn add(&mut self, quad: Quad, tx: Sender<QueueItem>) {
+    pub fn add(
+        &mut self,
+        quad: Quad,
+        tx: Sender<QueueItem>,
+        barriers: UnboundedSender<CheckpointBarrier>,
+    ) {
         self.senders.insert(quad, tx);
+        self.barriers.insert(quad, barriers);
+    }
+
+    // Hands the barrier of an unaligned checkpoint to its operator, ahead of the quad's frames.
+    fn send_barrier(&self, quad: Quad, barrier: CheckpointBarrier) {
+        let barriers = self
+            .barriers
+            .get(&quad)
+            .unwrap_or_else(|| panic!("no queue for {:?}", quad));
+        if barriers.send(barrier).is_err() {
+            warn!("Operator for {:?} has finished", quad);
+        }
     }
 
-    async fn send(&mut self, header: Header, data: Vec<u8>) {
-        let tx = self.senders.get(&header.as_quad()).unwrap();
+    // Starts a task that hands the frames received for the quad to its operator, returning a credit
+    // to the sender as each one is taken.
+    fn forwarder(&self, quad: Quad, credits: UnboundedSender<Quad>) -> UnboundedSender<QueueItem> {
+        let tx = self
+            .senders
+            .get(&quad)
+            .unwrap_or_else(|| panic!("no queue for {:?}", quad))
+            .clone();
+
+        let (forward_tx, mut forward_rx) = unbounded_channel();
+        tokio::spawn(async move {
+            let mut closed = false;
+            while let Some(item) = forward_rx.recv().await {
+                let len = match &item {
+                    QueueItem::Bytes(data) | QueueItem::BytesBatch(data) => data.len(),
+                    QueueItem::Data(_) | QueueItem::DataBatch(_) => unreachable!(),
+                };
+                // once the operator's queue has closed its frames are dropped, but credits are still
+                // returned so that the sender isn't left waiting while it shuts down
+                if !closed {
+                    if let Err(e) = Self::send(&tx, item).await {
+                        warn!("Dropping frames for {:?}: {}", quad, e);
+                        closed = true;
+                    }
+                }
+
+                NETWORK_BUFFERED_FRAMES.dec();
+                NETWORK_BUFFERED_BYTES.sub(len as i64);
+                // the connection may have closed after the last frame
+                let _ = credits.send(quad);
+            }
+        });
+
+        forward_tx
+    }
 
-        if let Err(send_error) = tx.send(QueueItem::Bytes(data)).await {
+    async fn send(tx: &Sender<QueueItem>, item: QueueItem) -> Result<(), String> {
+        if let Err(send_error) = tx.send(item).await {
             match send_error.0 {
-                QueueItem::Data(_) => unreachable!(),
+                QueueItem::Data(_) | QueueItem::DataBatch(_) => unreachable!(),
+                QueueItem::BytesBatch(_) => {
+                    return Err("queue closed, batch of records not sent".to_string());
+                }
                 QueueItem::Bytes(data) => {
                     let message: Message<i64, i64> =
                         bincode::decode_from_slice(&data, config::standard())
-                            .expect("couldn't decode, probably a record.")
+                            .map_err(|_| "queue closed, record not sent".to_string())?
                             .0;
                     if !message.is_end() {
-                        panic!("{:?} not sent", message);
+                        return Err(format!("queue closed, {:?} not sent", message));
                     } else {
                         warn!("couldn't send end message");
                     }
                 }
             }
         }
+        Ok(())
+    }
+}
+
+// the longest secret a peer may present when connecting
+const MAX_SECRET_LEN: usize = 1024;
+
+// how long a peer has to complete the TLS handshake and present its secret
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+trait DataStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> DataStream for T {}
+
+#[derive(Clone)]
+struct DataTls {
+    acceptor: TlsAcceptor,
+    connector: TlsConnector,
+    server_name: ServerName,
+}
+
+// Completes the TLS handshake, if enabled, for a connection from another worker and checks that
+// it has presented the job's secret, waiting for this worker to be given the secret if necessary.
+async fn accept_link(
+    stream: TcpStream,
+    tls: Option<DataTls>,
+    mut secret: watch::Receiver<Option<String>>,
+) -> Result<Box<dyn DataStream>, io::Error> {
+    let (stream, presented) = tokio::time::timeout(HANDSHAKE_TIMEOUT, read_secret(stream, tls))
+        .await
+        .map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                "peer didn't present a secret in time",
+            )
+        })??;
+
+    let expected = secret
+        .wait_for(|s| s.is_some())
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "network manager was dropped"))?
+        .clone()
+        .unwrap();
+
+    if !secrets_match(&presented, expected.as_bytes()) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "peer presented the wrong secret",
+        ));
+    }
+
+    Ok(stream)
+}
+
+async fn read_secret(
+    stream: TcpStream,
+    tls: Option<DataTls>,
+) -> Result<(Box<dyn DataStream>, Vec<u8>), io::Error> {
+    let mut stream: Box<dyn DataStream> = match tls {
+        Some(tls) => Box::new(tls.acceptor.accept(stream).await?),
+        None => Box::new(stream),
+    };
+
+    let len = stream.read_u32_le().await? as usize;
+    if len > MAX_SECRET_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "presented secret is too long",
+        ));
     }
+    let mut presented = vec![0; len];
+    stream.read_exact(&mut presented).await?;
+    Ok((stream, presented))
+}
+
+// compares every byte, so that the time taken doesn't reveal how much of the secret was right
+fn secrets_match(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 pub struct InNetworkLink {
     _source: String,
-    stream: BufReader<TcpStream>,
+    stream: Box<dyn DataStream>,
     senders: Senders,
 }
 
+// the quad's four u32 ids and the u32 number of credits
+const CREDIT_SIZE: usize = 5 * 4;
+
+// Sent back to a sender when the receiver has made room for more frames for the quad.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Credit {
+    quad: Quad,
+    credits: u32,
+}
+
+impl Credit {
+    fn from_bytes<B: Buf>(mut bytes: B) -> Self {
+        Credit {
+            quad: Quad {
+                src_id: bytes.get_u32_le() as usize,
+                src_idx: bytes.get_u32_le() as usize,
+                dst_id: bytes.get_u32_le() as usize,
+                dst_idx: bytes.get_u32_le() as usize,
+            },
+            credits: bytes.get_u32_le(),
+        }
+    }
+
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        buf.put_u32_le(self.quad.src_id as u32);
+        buf.put_u32_le(self.quad.src_idx as u32);
+        buf.put_u32_le(self.quad.dst_id as u32);
+        buf.put_u32_le(self.quad.dst_idx as u32);
+        buf.put_u32_le(self.credits);
+    }
+}
+
+// Returns credits to the sender as its frames are taken by their operators, combining the
+// credits that have built up for each quad.
+async fn write_credits<W: AsyncWrite + Unpin>(mut writer: W, mut rx: UnboundedReceiver<Quad>) {
+    let mut buf = vec![];
+    while let Some(quad) = rx.recv().await {
+        let mut credits = HashMap::from([(quad, 1)]);
+        while let Ok(quad) = rx.try_recv() {
+            *credits.entry(quad).or_default() += 1;
+        }
+
+        buf.clear();
+        for (quad, credits) in credits {
+            Credit { quad, credits }.write_to(&mut buf);
+        }
+
+        if let Err(e) = async {
+            writer.write_all(&buf).await?;
+            writer.flush().await
+        }
+        .await
+        {
+            warn!("Failed to return credits: {:?}", e);
+            break;
+        }
+    }
+}
+
+async fn read_credits<R: AsyncRead + Unpin>(mut reader: R, credits: HashMap<Quad, Arc<Semaphore>>) {
+    let mut buf = [0u8; CREDIT_SIZE];
+    while reader.read_exact(&mut buf).await.is_ok() {
+        let credit = Credit::from_bytes(&buf[..]);
+        match credits.get(&credit.quad) {
+            Some(semaphore) => semaphore.add_permits(credit.credits as usize),
+            None => warn!("Received credits for unknown quad {:?}", credit.quad),
+        }
+    }
+}
+
+// four u32 ids, the u32 frame kind, and the u64 length
+const HEADER_SIZE: usize = 5 * 4 + 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FrameKind {
+    Message,
+    Batch,
+    // the barrier of an unaligned checkpoint, which is sent ahead of the quad's data frames and
+    // doesn't take a credit
+    Barrier,
+}
+
+impl FrameKind {
+    fn from_u32(kind: u32) -> Result<Self, io::Error> {
+        match kind {
+            0 => Ok(FrameKind::Message),
+            1 => Ok(FrameKind::Batch),
+            2 => Ok(FrameKind::Barrier),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown frame kind {}", kind),
+            )),
+        }
+    }
+
+    fn as_u32(&self) -> u32 {
+        match self {
+            FrameKind::Message => 0,
+            FrameKind::Batch => 1,
+            FrameKind::Barrier => 2,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Header {
     src_operator: u32,
     src_subtask: u32,
     dst_operator: u32,
     dst_subtask: u32,
+    kind: FrameKind,
     len: usize,
 }
 
 impl Header {
-    fn from_quad(quad: Quad, len: usize) -> Self {
+    fn from_quad(quad: Quad, kind: FrameKind, len: usize) -> Self {
         Self {
             src_operator: quad.src_id as u32,
             src_subtask: quad.src_idx as u32,
             dst_operator: quad.dst_id as u32,
             dst_subtask: quad.dst_idx as u32,
+            kind,
             len,
         }
     }
@@ -95,23 +355,25 @@ impl Header {
         }
     }
 
-    fn from_bytes<B: Buf>(mut bytes: B) -> Header {
-        Header {
+    fn from_bytes<B: Buf>(mut bytes: B) -> Result<Header, io::Error> {
+        Ok(Header {
             src_operator: bytes.get_u32_le(),
             src_subtask: bytes.get_u32_le(),
             dst_operator: bytes.get_u32_le(),
             dst_subtask: bytes.get_u32_le(),
+            kind: FrameKind::from_u32(bytes.get_u32_le())?,
             len: bytes.get_u64_le() as usize,
-        }
+        })
     }
 
     async fn write<W: AsyncWrite + AsyncWriteExt>(&self, mut writer: Pin<&mut W>) {
-        let mut bytes = [0u8; size_of::<Header>()];
+        let mut bytes = [0u8; HEADER_SIZE];
         let mut buf = &mut bytes[..];
         buf.put_u32_le(self.src_operator);
         buf.put_u32_le(self.src_subtask);
         buf.put_u32_le(self.dst_operator);
         buf.put_u32_le(self.dst_subtask);
+        buf.put_u32_le(self.kind.as_u32());
         buf.put_u64_le(self.len as u64);
 
         writer.write_all(&bytes).await.unwrap();
@@ -119,33 +381,81 @@ impl Header {
 }
 
 impl InNetworkLink {
-    pub fn new(source: String, stream: TcpStream, senders: Senders) -> Self {
+    fn new(source: String, stream: Box<dyn DataStream>, senders: Senders) -> Self {
         InNetworkLink {
             _source: source,
-            stream: BufReader::new(stream),
+            stream,
             senders,
         }
     }
 
-    async fn next(&mut self, header_buf: &mut [u8]) -> Result<(), io::Error> {
-        self.stream.read_exact(header_buf).await?;
-        let header = Header::from_bytes(&header_buf[..]);
+    async fn next<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        header_buf: &mut [u8],
+    ) -> Result<(Header, Vec<u8>), io::Error> {
+        reader.read_exact(header_buf).await?;
+        let header = Header::from_bytes(&header_buf[..])?;
 
         let mut buf = vec![0; header.len];
-        self.stream.read_exact(&mut buf).await?;
+        reader.read_exact(&mut buf).await?;
 
-        self.senders.send(header, buf).await;
-        Ok(())
+        Ok((header, buf))
     }
 
-    pub fn start(mut self) {
+    pub fn start(self) {
+        let (reader, writer) = io::split(self.stream);
+        let (credit_tx, credit_rx) = unbounded_channel();
+        tokio::spawn(write_credits(writer, credit_rx));
+
+        let senders = self.senders;
         tokio::spawn(async move {
-            let mut header_buf = vec![0u8; size_of::<Header>()];
+            let mut reader = BufReader::new(reader);
+            let mut header_buf = vec![0u8; HEADER_SIZE];
+            // each quad's frames are handed off to a task of its own, so that an operator that's
+            // slow to take them only holds up its own quad; the sender's credits bound how many
+            // are buffered
+            let mut forwarders: HashMap<Quad, UnboundedSender<QueueItem>> = HashMap::new();
             loop {
-                if let Err(e) = self.next(&mut header_buf).await {
-                    warn!("Socket hung up: {:?}", e);
-                    break;
+                let (header, buf) = match Self::next(&mut reader, &mut header_buf).await {
+                    Ok(frame) => frame,
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        warn!("Received an invalid frame, closing link: {:?}", e);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Socket hung up: {:?}", e);
+                        break;
+                    }
+                };
+
+                let quad = header.as_quad();
+                let len = header.len;
+                let item = match header.kind {
+                    FrameKind::Message => QueueItem::Bytes(buf),
+                    FrameKind::Batch => QueueItem::BytesBatch(buf),
+                    FrameKind::Barrier => {
+                        match bincode::decode_from_slice(&buf, config::standard()) {
+                            Ok((barrier, _)) => senders.send_barrier(quad, barrier),
+                            Err(e) => {
+                                warn!(
+                                    "Couldn't decode barrier for {:?}, closing link: {:?}",
+                                    quad, e
+                                );
+                                break;
+                            }
+                        }
+                        continue;
+                    }
                 };
+
+                NETWORK_BUFFERED_FRAMES.inc();
+                NETWORK_BUFFERED_BYTES.add(len as i64);
+                let forwarder = forwarders
+                    .entry(quad)
+                    .or_insert_with(|| senders.forwarder(quad, credit_tx.clone()));
+                if forwarder.send(item).is_err() {
+                    warn!("Forwarder for {:?} has exited", quad);
+                }
             }
         });
     }
@@ -161,51 +471,122 @@ pub struct Quad {
 
 struct OutNetworkLink {
     _dest: String,
-    stream: BufWriter<TcpStream>,
-    receivers: Vec<(Quad, Receiver<QueueItem>)>,
+    stream: Box<dyn DataStream>,
+    receivers: Vec<(
+        Quad,
+        Receiver<QueueItem>,
+        UnboundedReceiver<CheckpointBarrier>,
+    )>,
 }
 
 impl OutNetworkLink {
-    pub async fn connect(dest: String) -> Self {
-        let stream = TcpStream::connect(&dest).await.unwrap();
+    async fn connect(dest: String, tls: Option<&DataTls>, secret: &str) -> Result<Self, io::Error> {
+        let stream = TcpStream::connect(&dest).await.map_err(|e| {
+            io::Error::new(e.kind(), format!("failed to connect to {}: {}", dest, e))
+        })?;
+        let mut stream: Box<dyn DataStream> = match tls {
+            Some(tls) => Box::new(
+                tls.connector
+                    .connect(tls.server_name.clone(), stream)
+                    .await
+                    .map_err(|e| {
+                        io::Error::new(
+                            e.kind(),
+                            format!("TLS handshake with {} failed: {}", dest, e),
+                        )
+                    })?,
+            ),
+            None => {
+                warn!(
+                    "Sending the job secret to {} in plaintext, as TLS isn't configured for data connections",
+                    dest
+                );
+                Box::new(stream)
+            }
+        };
 
-        Self {
+        let mut buf = Vec::with_capacity(4 + secret.len());
+        buf.put_u32_le(secret.len() as u32);
+        buf.put_slice(secret.as_bytes());
+        stream.write_all(&buf).await.map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("failed to send secret to {}: {}", dest, e),
+            )
+        })?;
+
+        Ok(Self {
             _dest: dest,
-            stream: BufWriter::new(stream),
+            stream,
             receivers: vec![],
-        }
+        })
     }
 
-    pub async fn add_receiver(&mut self, quad: Quad, rx: Receiver<QueueItem>) {
-        self.receivers.push((quad, rx));
+    pub async fn add_receiver(
+        &mut self,
+        quad: Quad,
+        rx: Receiver<QueueItem>,
+        barriers: UnboundedReceiver<CheckpointBarrier>,
+    ) {
+        self.receivers.push((quad, rx, barriers));
     }
 
-    pub fn start(mut self) {
+    pub fn start(self) {
         tokio::spawn(async move {
+            let (reader, writer) = io::split(self.stream);
+            let mut writer = BufWriter::new(writer);
+
+            let mut credits = HashMap::new();
             let mut sel = InQReader::new();
-            for (quad, mut rx) in self.receivers {
+            let mut barrier_sel = InQReader::new();
+            for (quad, mut rx, mut barriers) in self.receivers {
+                let barrier_stream = async_stream::stream! {
+                    while let Some(barrier) = barriers.recv().await {
+                        yield (quad, barrier);
+                    }
+                };
+                barrier_sel.push(Box::pin(barrier_stream));
+
+                let quad_credits = Arc::new(Semaphore::new(INITIAL_CREDITS));
+                credits.insert(quad, quad_credits.clone());
                 let stream = async_stream::stream! {
                     while let Some(item) = rx.recv().await {
+                        // wait for the receiver to have room for another of this quad's frames
+                        quad_credits.acquire().await.expect("credits are never closed").forget();
                         yield (quad, item);
                     }
                 };
                 sel.push(Box::pin(stream));
             }
+            tokio::spawn(read_credits(reader, credits));
+
             let mut flush_interval: Interval = interval(Duration::from_millis(100));
 
             loop {
                 select! {
+                    // barriers go out ahead of the data frames waiting for credits
+                    biased;
+                    Some(((quad, barrier), s)) = barrier_sel.next() => {
+                        let data = bincode::encode_to_vec(barrier, config::standard()).unwrap();
+                        let frame = Header::from_quad(quad, FrameKind::Barrier, data.len());
+                        frame.write(Pin::new(&mut writer)).await;
+                        writer.write_all(&data).await.unwrap();
+                        writer.flush().await.unwrap();
+                        barrier_sel.push(s);
+                    }
                     Some(((quad, msg), s)) = sel.next() => {
-                        let QueueItem::Bytes(data) = msg else {
-                            panic!("non-byte data in network queue")
+                        let (kind, data) = match msg {
+                            QueueItem::Bytes(data) => (FrameKind::Message, data),
+                            QueueItem::BytesBatch(data) => (FrameKind::Batch, data),
+                            _ => panic!("non-byte data in network queue"),
                         };
-                        let frame = Header::from_quad(quad, data.len());
-                        frame.write(Pin::new(&mut self.stream)).await;
-                        self.stream.write_all(&data).await.unwrap();
+                        let frame = Header::from_quad(quad, kind, data.len());
+                        frame.write(Pin::new(&mut writer)).await;
+                        writer.write_all(&data).await.unwrap();
                         sel.push(s);
                     }
                     _ = flush_interval.tick() => {
-                        self.stream.flush().await.unwrap();
+                        writer.flush().await.unwrap();
                     }
                 }
             }
@@ -214,7 +595,7 @@ impl OutNetworkLink {
 }
 
 enum InStreamsOrSenders {
-    InStreams(Vec<TcpStream>),
+    InStreams(Vec<(String, Box<dyn DataStream>)>),
     Senders(Senders),
 }
 
@@ -222,6 +603,8 @@ pub struct NetworkManager {
     port: u16,
     in_streams: Arc<Mutex<InStreamsOrSenders>>,
     out_streams: Arc<Mutex<HashMap<Quad, OutNetworkLink>>>,
+    tls: Option<DataTls>,
+    secret: watch::Sender<Option<String>>,
 }
 
 impl NetworkManager {
@@ -230,9 +613,32 @@ impl NetworkManager {
             port,
             in_streams: Arc::new(Mutex::new(InStreamsOrSenders::InStreams(vec![]))),
             out_streams: Arc::new(Mutex::new(HashMap::new())),
+            tls: None,
+            secret: watch::channel(None).0,
         }
     }
 
+    /// Uses mutual TLS for the connections to and from other workers.
+    pub fn with_tls(
+        mut self,
+        acceptor: TlsAcceptor,
+        connector: TlsConnector,
+        server_name: ServerName,
+    ) -> Self {
+        self.tls = Some(DataTls {
+            acceptor,
+            connector,
+            server_name,
+        });
+        self
+    }
+
+    /// Sets the job's secret, which other workers must present when connecting to this one and
+    /// which this one presents to them. Incoming connections wait for it to be set.
+    pub fn set_secret(&self, secret: String) {
+        self.secret.send_replace(Some(secret));
+    }
+
     pub async fn open_listener(&mut self) -> u16 {
         let port = self.port;
         let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
@@ -241,26 +647,34 @@ impl NetworkManager {
         let port = listener.local_addr().unwrap().port();
 
         let streams = Arc::clone(&self.in_streams);
+        let tls = self.tls.clone();
+        let secret = self.secret.subscribe();
         tokio::spawn(async move {
             loop {
-                let (stream, _) = listener.accept().await.unwrap();
-
-                let mut s = streams.lock().await;
-
-                match &mut *s {
-                    InStreamsOrSenders::InStreams(streams) => streams.push(stream),
-                    InStreamsOrSenders::Senders(ref senders) => {
-                        let senders = senders.clone();
-                        tokio::spawn(async move {
-                            InNetworkLink::new(
-                                stream.local_addr().unwrap().to_string(),
-                                stream,
-                                senders,
-                            )
-                            .start();
-                        });
+                let (stream, peer) = listener.accept().await.unwrap();
+                let source = stream.local_addr().unwrap().to_string();
+
+                let streams = Arc::clone(&streams);
+                let tls = tls.clone();
+                let secret = secret.clone();
+                tokio::spawn(async move {
+                    let stream = match accept_link(stream, tls, secret).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            warn!("Rejected data connection from {}: {:?}", peer, e);
+                            return;
+                        }
+                    };
+
+                    let mut s = streams.lock().await;
+
+                    match &mut *s {
+                        InStreamsOrSenders::InStreams(streams) => streams.push((source, stream)),
+                        InStreamsOrSenders::Senders(ref senders) => {
+                            InNetworkLink::new(source, stream, senders.clone()).start();
+                        }
                     }
-                }
+                });
             }
         });
 
@@ -272,12 +686,8 @@ impl NetworkManager {
 
         match &mut *sockets {
             InStreamsOrSenders::InStreams(ref mut in_streams) => {
-                for s in in_streams.drain(..) {
-                    let senders = senders.clone();
-                    tokio::spawn(async move {
-                        InNetworkLink::new(s.local_addr().unwrap().to_string(), s, senders.clone())
-                            .start();
-                    });
+                for (source, s) in in_streams.drain(..) {
+                    InNetworkLink::new(source, s, senders.clone()).start();
                 }
             }
             InStreamsOrSenders::Senders(_) => {
@@ -293,30 +703,55 @@ impl NetworkManager {
         }
     }
 
-    pub async fn connect(&mut self, addr: String, quad: Quad, rx: Receiver<QueueItem>) {
+    pub async fn connect(
+        &mut self,
+        addr: String,
+        quad: Quad,
+        rx: Receiver<QueueItem>,
+        barriers: UnboundedReceiver<CheckpointBarrier>,
+    ) -> Result<(), io::Error> {
         let mut ins = self.out_streams.lock().await;
         if let std::collections::hash_map::Entry::Vacant(e) = ins.entry(quad) {
-            e.insert(OutNetworkLink::connect(addr.clone()).await);
+            let secret = self
+                .secret
+                .borrow()
+                .clone()
+                .expect("secret must be set before connecting to other workers");
+            e.insert(OutNetworkLink::connect(addr.clone(), self.tls.as_ref(), &secret).await?);
         }
 
         ins.get_mut(&quad)
             .as_mut()
             .unwrap()
-            .add_receiver(quad, rx)
+            .add_receiver(quad, rx, barriers)
             .await;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::{pin::Pin, time::Duration};
+    use std::{
+        pin::Pin,
+        time::{Duration, SystemTime},
+    };
 
     use crate::engine::QueueItem;
-    use tokio::{io::AsyncWriteExt, net::TcpStream, sync::mpsc::channel, time::timeout};
+    use arroyo_server_common::tls::TlsConfig;
+    use arroyo_types::CheckpointBarrier;
+    use tokio::{
+        io::{self, AsyncWriteExt},
+        net::TcpStream,
+        sync::mpsc::{channel, unbounded_channel},
+        time::timeout,
+    };
 
     use crate::network_manager::Quad;
 
-    use super::{Header, NetworkManager, Senders};
+    use super::{
+        Credit, FrameKind, Header, NetworkManager, OutNetworkLink, Senders, HEADER_SIZE,
+        INITIAL_CREDITS,
+    };
 
     #[tokio::test]
     async fn test_header_serdes() {
@@ -327,14 +762,21 @@ mod test {
             src_subtask: 3,
             dst_operator: 9098,
             dst_subtask: 100,
+            kind: FrameKind::Batch,
             len: 30,
         };
 
         header.write(Pin::new(&mut buffer)).await;
+        assert_eq!(buffer.len(), HEADER_SIZE);
 
-        let h2 = Header::from_bytes(&buffer[..]);
+        let h2 = Header::from_bytes(&buffer[..]).unwrap();
 
         assert_eq!(header, h2);
+
+        // an unknown frame kind is an error rather than a panic
+        buffer[16..20].copy_from_slice(&7u32.to_le_bytes());
+        let err = Header::from_bytes(&buffer[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
 
     #[tokio::test]
@@ -349,7 +791,7 @@ mod test {
             dst_idx: 3,
         };
 
-        senders.add(quad, tx);
+        senders.add(quad, tx, unbounded_channel().0);
 
         let mut nm = NetworkManager::new(0);
         let port = nm.open_listener().await;
@@ -357,10 +799,13 @@ mod test {
         println!("port: {}", port);
 
         nm.start(senders).await;
+        nm.set_secret("secret".to_string());
 
         let mut client = TcpStream::connect(format!("localhost:{}", port))
             .await
             .unwrap();
+        client.write_u32_le(6).await.unwrap();
+        client.write_all(b"secret").await.unwrap();
 
         let message = b"Hello World!";
 
@@ -369,6 +814,7 @@ mod test {
             src_subtask: 1,
             dst_operator: 2,
             dst_subtask: 3,
+            kind: FrameKind::Message,
             len: message.len(),
         };
 
@@ -396,14 +842,21 @@ mod test {
             dst_idx: 3,
         };
 
-        senders.add(quad, server_tx);
+        senders.add(quad, server_tx, unbounded_channel().0);
 
         let mut nm = NetworkManager::new(0);
         let port = nm.open_listener().await;
 
+        nm.set_secret("secret".to_string());
+
         let (client_tx, client_rx) = channel(10);
-        nm.connect(format!("localhost:{}", port), quad, client_rx)
-            .await;
+        nm.connect(
+            format!("localhost:{}", port),
+            quad,
+            client_rx,
+            unbounded_channel().1,
+        )
+        .await;
 
         nm.start(senders).await;
 
@@ -423,5 +876,302 @@ mod test {
             panic!("expected bytes");
         };
         assert_eq!(&data[..], &bytes);
+
+        // batches keep their framing across the network
+        client_tx
+            .send(QueueItem::BytesBatch(data.to_vec()))
+            .await
+            .unwrap();
+
+        let result = timeout(Duration::from_secs(1), server_rx.recv())
+            .await
+            .unwrap()
+            .expect("timed out");
+
+        let QueueItem::BytesBatch(bytes) = result else {
+            panic!("expected a batch");
+        };
+        assert_eq!(&data[..], &bytes);
+    }
+
+    #[test]
+    fn test_credit_serdes() {
+        let credit = Credit {
+            quad: Quad {
+                src_id: 12412,
+                src_idx: 3,
+                dst_id: 9098,
+                dst_idx: 100,
+            },
+            credits: 17,
+        };
+
+        let mut buffer = vec![];
+        credit.write_to(&mut buffer);
+
+        assert_eq!(Credit::from_bytes(&buffer[..]), credit);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_quad_does_not_block_others() {
+        let blocked = Quad {
+            src_id: 0,
+            src_idx: 0,
+            dst_id: 1,
+            dst_idx: 0,
+        };
+        let open = Quad {
+            src_id: 0,
+            src_idx: 0,
+            dst_id: 1,
+            dst_idx: 1,
+        };
+
+        // the operator for the blocked quad never takes anything from its queue
+        let (blocked_tx, _blocked_rx) = channel(1);
+        let (blocked_barrier_tx, mut blocked_barrier_rx) = unbounded_channel();
+        let (open_tx, mut open_rx) = channel(10);
+        let mut senders = Senders::new();
+        senders.add(blocked, blocked_tx, blocked_barrier_tx);
+        senders.add(open, open_tx, unbounded_channel().0);
+
+        let mut nm = NetworkManager::new(0);
+        let port = nm.open_listener().await;
+        nm.start(senders).await;
+        nm.set_secret("secret".to_string());
+
+        let mut link = OutNetworkLink::connect(format!("localhost:{}", port), None, "secret")
+            .await
+            .unwrap();
+        let (client_blocked_tx, client_blocked_rx) = channel(INITIAL_CREDITS * 2);
+        let (client_open_tx, client_open_rx) = channel(10);
+        let (client_barrier_tx, client_barrier_rx) = unbounded_channel();
+        link.add_receiver(blocked, client_blocked_rx, client_barrier_rx)
+            .await;
+        link.add_receiver(open, client_open_rx, unbounded_channel().1)
+            .await;
+        link.start();
+
+        for _ in 0..INITIAL_CREDITS + 10 {
+            client_blocked_tx
+                .send(QueueItem::Bytes(b"stuck".to_vec()))
+                .await
+                .unwrap();
+        }
+        client_open_tx
+            .send(QueueItem::Bytes(b"flowing".to_vec()))
+            .await
+            .unwrap();
+
+        let result = timeout(Duration::from_secs(1), open_rx.recv())
+            .await
+            .expect("timed out")
+            .unwrap();
+        let QueueItem::Bytes(bytes) = result else {
+            panic!("expected bytes");
+        };
+        assert_eq!(&bytes, b"flowing");
+
+        // the barriers of unaligned checkpoints overtake the data stuck waiting for credits
+        let barrier = CheckpointBarrier {
+            epoch: 3,
+            min_epoch: 1,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+            unaligned: true,
+        };
+        client_barrier_tx.send(barrier).unwrap();
+        let received = timeout(Duration::from_secs(1), blocked_barrier_rx.recv())
+            .await
+            .expect("timed out")
+            .unwrap();
+        assert_eq!(received.epoch, 3);
+        assert!(received.unaligned);
+    }
+
+    #[tokio::test]
+    async fn test_closed_queue_drops_frames() {
+        let closed = Quad {
+            src_id: 0,
+            src_idx: 0,
+            dst_id: 1,
+            dst_idx: 0,
+        };
+        let open = Quad {
+            src_id: 0,
+            src_idx: 0,
+            dst_id: 1,
+            dst_idx: 1,
+        };
+
+        // the operator for the closed quad has already shut down
+        let (closed_tx, closed_rx) = channel(1);
+        drop(closed_rx);
+        let (open_tx, mut open_rx) = channel(10);
+        let mut senders = Senders::new();
+        senders.add(closed, closed_tx, unbounded_channel().0);
+        senders.add(open, open_tx, unbounded_channel().0);
+
+        let mut nm = NetworkManager::new(0);
+        let port = nm.open_listener().await;
+        nm.start(senders).await;
+        nm.set_secret("secret".to_string());
+
+        let mut link = OutNetworkLink::connect(format!("localhost:{}", port), None, "secret")
+            .await
+            .unwrap();
+        let (client_closed_tx, client_closed_rx) = channel(INITIAL_CREDITS * 2);
+        let (client_open_tx, client_open_rx) = channel(10);
+        link.add_receiver(closed, client_closed_rx, unbounded_channel().1)
+            .await;
+        link.add_receiver(open, client_open_rx, unbounded_channel().1)
+            .await;
+        link.start();
+
+        for _ in 0..INITIAL_CREDITS * 2 {
+            client_closed_tx
+                .send(QueueItem::Bytes(b"dropped".to_vec()))
+                .await
+                .unwrap();
+        }
+
+        // credits keep coming back for the dropped frames, so the sender drains its queue
+        timeout(Duration::from_secs(1), async {
+            while client_closed_tx.capacity() < INITIAL_CREDITS * 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timed out");
+
+        client_open_tx
+            .send(QueueItem::Bytes(b"flowing".to_vec()))
+            .await
+            .unwrap();
+        let result = timeout(Duration::from_secs(1), open_rx.recv())
+            .await
+            .expect("timed out")
+            .unwrap();
+        let QueueItem::Bytes(bytes) = result else {
+            panic!("expected bytes");
+        };
+        assert_eq!(&bytes, b"flowing");
+    }
+
+    #[tokio::test]
+    async fn test_wrong_secret() {
+        let (tx, mut rx) = channel(10);
+
+        let mut senders = Senders::new();
+        senders.add(
+            Quad {
+                src_id: 0,
+                src_idx: 1,
+                dst_id: 2,
+                dst_idx: 3,
+            },
+            tx,
+            unbounded_channel().0,
+        );
+
+        let mut nm = NetworkManager::new(0);
+        let port = nm.open_listener().await;
+        nm.start(senders).await;
+        nm.set_secret("secret".to_string());
+
+        let mut client = TcpStream::connect(format!("localhost:{}", port))
+            .await
+            .unwrap();
+        client.write_u32_le(6).await.unwrap();
+        client.write_all(b"wrong!").await.unwrap();
+
+        let message = b"Hello World!";
+        let header = Header {
+            src_operator: 0,
+            src_subtask: 1,
+            dst_operator: 2,
+            dst_subtask: 3,
+            kind: FrameKind::Message,
+            len: message.len(),
+        };
+        header.write(Pin::new(&mut client)).await;
+        // the connection may already have been closed
+        let _ = client.write_all(message).await;
+
+        assert!(timeout(Duration::from_millis(500), rx.recv())
+            .await
+            .map_or(true, |item| item.is_none()));
+    }
+
+    fn test_tls_config() -> TlsConfig {
+        let mut ca_params = rcgen::CertificateParams::new(vec![]);
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "arroyo test ca");
+        let ca = rcgen::Certificate::from_params(ca_params).unwrap();
+
+        let cert = rcgen::Certificate::from_params(rcgen::CertificateParams::new(vec![
+            "arroyo".to_string()
+        ]))
+        .unwrap();
+
+        TlsConfig::new(
+            cert.serialize_pem_with_signer(&ca).unwrap().into_bytes(),
+            cert.serialize_private_key_pem().into_bytes(),
+            ca.serialize_pem().unwrap().into_bytes(),
+            "arroyo",
+        )
+    }
+
+    #[tokio::test]
+    async fn test_tls_client_server() {
+        let (server_tx, mut server_rx) = channel(10);
+
+        let mut senders = Senders::new();
+
+        let quad = Quad {
+            src_id: 1,
+            src_idx: 0,
+            dst_id: 2,
+            dst_idx: 0,
+        };
+
+        senders.add(quad, server_tx, unbounded_channel().0);
+
+        let tls = test_tls_config();
+        let (connector, server_name) = tls.connector().unwrap();
+        let mut nm =
+            NetworkManager::new(0).with_tls(tls.acceptor().unwrap(), connector, server_name);
+        let port = nm.open_listener().await;
+        nm.set_secret("secret".to_string());
+
+        let (client_tx, client_rx) = channel(10);
+        nm.connect(
+            format!("localhost:{}", port),
+            quad,
+            client_rx,
+            unbounded_channel().1,
+        )
+        .await;
+
+        nm.start(senders).await;
+
+        let data = b"this is some encrypted data";
+
+        client_tx
+            .send(QueueItem::Bytes(data.to_vec()))
+            .await
+            .unwrap();
+
+        let result = timeout(Duration::from_secs(1), server_rx.recv())
+            .await
+            .unwrap()
+            .expect("timed out");
+
+        let QueueItem::Bytes(bytes) = result else {
+            panic!("expected bytes");
+        };
+        assert_eq!(&data[..], &bytes);
     }
 }
@@ -1,5 +1,6 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, VecDeque},
     ops::{Add, Sub},
     time::SystemTime,
 };
@@ -15,6 +16,7 @@ use tracing::warn;
 pub struct AggregatingWindowFunc<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data> {
     width: Duration,
     slide: Duration,
+    allowed_lateness: Duration,
     aggregator: fn(&MemA) -> OutT,
     bin_merger: fn(&T, Option<&BinA>) -> BinA,
     in_memory_add: fn(Option<MemA>, BinA) -> MemA,
@@ -52,6 +54,7 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         AggregatingWindowFunc {
             width,
             slide,
+            allowed_lateness: Duration::ZERO,
             aggregator,
             bin_merger,
             in_memory_add,
@@ -61,29 +64,62 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         }
     }
 
+    /// Keeps the bins that have left the window for `allowed_lateness`, re-firing the windows
+    /// that a record arriving in that time belongs to.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
+
     fn bin_start(&self, timestamp: SystemTime) -> SystemTime {
         let mut nanos = to_nanos(timestamp);
         nanos -= nanos % self.slide.as_nanos();
         from_nanos(nanos)
     }
 
+    // The earliest bin that can still take records, once the watermark has reached `watermark`.
+    fn lateness_bin(&self, watermark: SystemTime) -> SystemTime {
+        self.bin_start(
+            watermark
+                .checked_sub(self.allowed_lateness)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        )
+    }
+
     fn tables(&self) -> Vec<TableDescriptor> {
-        vec![TableDescriptor {
-            name: "a".to_string(),
-            description: "window state".to_string(),
-            table_type: TableType::TimeKeyMap as i32,
-            delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
-            write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
-            retention_micros: self.width.as_micros() as u64,
-        }]
+        vec![
+            TableDescriptor {
+                name: "a".to_string(),
+                description: "window state".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.width.as_micros() as u64,
+            },
+            TableDescriptor {
+                name: "l".to_string(),
+                description: "expired bin state".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: (self.width + self.allowed_lateness).as_micros() as u64,
+            },
+        ]
     }
 
     async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
         let bin_start = self.bin_start(record.timestamp);
 
         let watermark = ctx.watermark();
-        if watermark.is_some() && bin_start < self.bin_start(watermark.unwrap()) {
-            return;
+        if let Some(watermark) = watermark {
+            if bin_start < self.bin_start(watermark) {
+                if bin_start < self.lateness_bin(watermark) {
+                    ctx.collect_late(record.clone()).await;
+                } else {
+                    self.add_late(bin_start, watermark, record, ctx).await;
+                }
+                return;
+            }
         }
         self.state = match self.state {
             SlidingWindowState::NoData => SlidingWindowState::OnlyBufferedData {
@@ -105,17 +141,109 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         aggregating_map.insert(bin_start, key, new_value);
     }
 
+    // Adds a record whose bin the watermark has passed, and re-fires the closed windows that
+    // contain it.
+    async fn add_late(
+        &mut self,
+        bin_start: SystemTime,
+        watermark: SystemTime,
+        record: &Record<K, T>,
+        ctx: &mut Context<K, OutT>,
+    ) {
+        let watermark_bin = self.bin_start(watermark);
+        let memory_end = match self.state {
+            SlidingWindowState::InMemoryData { next_window_start } => next_window_start,
+            SlidingWindowState::NoData | SlidingWindowState::OnlyBufferedData { .. } => {
+                watermark_bin
+            }
+        };
+        let memory_start = memory_end
+            .checked_sub(self.width)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut key = record.key.clone().unwrap();
+
+        // bins that are part of the in-memory window are in 'a', older ones have moved to 'l'
+        let table = if bin_start >= memory_start { 'a' } else { 'l' };
+        {
+            let mut map: TimeKeyMap<K, BinA, _> =
+                ctx.state.get_time_key_map(table, Some(watermark)).await;
+            let bin_aggregate = (self.bin_merger)(&record.value, map.get(bin_start, &mut key));
+            map.insert(bin_start, key.clone(), bin_aggregate);
+        }
+
+        if table == 'a' {
+            // the memory can't remove arbitrary bins, so rebuild it from this key's bins
+            let bins = self
+                .bins_for_key(&mut key, memory_start, memory_end, ctx)
+                .await;
+            self.memory_view.remove(&key);
+            for bin in bins {
+                self.add_data(&key, bin);
+            }
+            self.state = SlidingWindowState::InMemoryData {
+                next_window_start: memory_end,
+            };
+        }
+
+        let mut window_end = bin_start + self.slide;
+        while window_end <= watermark_bin && window_end <= bin_start + self.width {
+            let window_start = window_end
+                .checked_sub(self.width)
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let bins = self
+                .bins_for_key(&mut key, window_start, window_end, ctx)
+                .await;
+            let memory = bins
+                .into_iter()
+                .fold(None, |memory, bin| Some((self.in_memory_add)(memory, bin)));
+            if let Some(memory) = memory {
+                ctx.collect(Record {
+                    timestamp: window_end - Duration::from_nanos(1),
+                    key: Some(key.clone()),
+                    value: (self.aggregator)(&memory),
+                })
+                .await;
+            }
+            window_end += self.slide;
+        }
+    }
+
+    // The key's bins between start and end, from either the window state or the expired bins.
+    async fn bins_for_key(
+        &self,
+        key: &mut K,
+        start: SystemTime,
+        end: SystemTime,
+        ctx: &mut Context<K, OutT>,
+    ) -> Vec<BinA> {
+        let watermark = ctx.watermark();
+        let mut bins = BTreeMap::new();
+        for table in ['l', 'a'] {
+            let map: TimeKeyMap<K, BinA, _> = ctx.state.get_time_key_map(table, watermark).await;
+            let mut bin = start;
+            while bin < end {
+                if let Some(value) = map.get(bin, key) {
+                    bins.insert(bin, value.clone());
+                }
+                bin += self.slide;
+            }
+        }
+        bins.into_values().collect()
+    }
+
     async fn on_start(&mut self, ctx: &mut Context<K, OutT>) {
         let watermark = ctx.watermark();
         let map = ctx.state.get_time_key_map::<K, BinA>('a', watermark).await;
 
-        let Some(map_min_time) = map.get_min_time()  else {
+        let Some(map_min_time) = map.get_min_time() else {
             self.state = SlidingWindowState::NoData;
             return;
         };
         let map_min_bin = self.bin_start(map_min_time);
         let Some(watermark) = watermark else {
-            self.state = SlidingWindowState::OnlyBufferedData { earliest_bin_time: map_min_bin };
+            self.state = SlidingWindowState::OnlyBufferedData {
+                earliest_bin_time: map_min_bin,
+            };
             return;
         };
         let watermark_bin = self.bin_start(watermark);
@@ -185,7 +313,12 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
             self.add_data(key, bin.clone());
         }
         // remove the leaving bin data from memory
-        for (key, bin) in aggregating_map.evict_for_timestamp(bin_start - self.width) {
+        let leaving_bin = bin_start - self.width;
+        let mut expired = vec![];
+        for (key, bin) in aggregating_map.evict_for_timestamp(leaving_bin) {
+            if !self.allowed_lateness.is_zero() {
+                expired.push((key.clone(), bin.clone()));
+            }
             self.remove_data(&key, bin);
         }
         let window_end = bin_end - Duration::from_nanos(1);
@@ -210,6 +343,14 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
                 next_window_start: bin_end,
             }
         };
+        if !expired.is_empty() {
+            // keep the bin around for records that arrive within the allowed lateness
+            let mut expired_map: TimeKeyMap<K, BinA, _> =
+                ctx.state.get_time_key_map('l', ctx.watermark()).await;
+            for (key, bin) in expired {
+                expired_map.insert(leaving_bin, key, bin);
+            }
+        }
         for record in records {
             ctx.collect(record).await;
         }
@@ -220,12 +361,24 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         _watermark: std::time::SystemTime,
         ctx: &mut Context<K, OutT>,
     ) {
-        let Some(watermark) = ctx.watermark() else {return};
+        let Some(watermark) = ctx.watermark() else {
+            return;
+        };
         while self.should_advance(watermark) {
             self.advance(ctx).await;
         }
-        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
-            .await;
+        if !self.allowed_lateness.is_zero() {
+            // the windows with bins this old have closed for good
+            if let Some(expired_bin) = self.lateness_bin(watermark).checked_sub(self.width) {
+                let mut expired_map: TimeKeyMap<K, BinA, _> =
+                    ctx.state.get_time_key_map('l', Some(watermark)).await;
+                expired_map.evict_all_before_watermark(expired_bin);
+            }
+        }
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
     }
 
     async fn handle_checkpoint(
@@ -236,6 +389,9 @@ impl<K: Key, T: Data, BinA: Data, MemA: Data, OutT: Data>
         let mut aggregating_map: TimeKeyMap<K, BinA, _> =
             ctx.state.get_time_key_map('a', ctx.watermark()).await;
         aggregating_map.flush().await;
+        let mut expired_map: TimeKeyMap<K, BinA, _> =
+            ctx.state.get_time_key_map('l', ctx.watermark()).await;
+        expired_map.flush().await;
     }
 }
 
@@ -495,3 +651,102 @@ pub fn non_nullable_average_remove<T: Sub<Output = T>>(
     }
     Some((current_count - bin_count, current_sum - bin_sum))
 }
+
+pub fn variance_add(
+    current: Option<(i64, f64, f64)>,
+    bin_value: (i64, f64, f64),
+) -> (i64, f64, f64) {
+    match current {
+        Some((count, sum, sum_of_squares)) => (
+            count + bin_value.0,
+            sum + bin_value.1,
+            sum_of_squares + bin_value.2,
+        ),
+        None => bin_value,
+    }
+}
+
+pub fn variance_remove(
+    current: (i64, f64, f64),
+    bin_value: (i64, f64, f64),
+) -> Option<(i64, f64, f64)> {
+    Some((
+        current.0 - bin_value.0,
+        current.1 - bin_value.1,
+        current.2 - bin_value.2,
+    ))
+}
+
+pub fn variance_aggregate(memory: &(i64, f64, f64), sample: bool) -> Option<f64> {
+    let (count, sum, sum_of_squares) = *memory;
+    let denominator = if sample { count - 1 } else { count };
+    if denominator <= 0 {
+        return None;
+    }
+    let count = count as f64;
+    Some(((sum_of_squares - sum * sum / count) / denominator as f64).max(0.0))
+}
+
+pub fn bool_add(current: Option<(i64, i64)>, bin_value: (i64, i64)) -> (i64, i64) {
+    match current {
+        Some((trues, count)) => (trues + bin_value.0, count + bin_value.1),
+        None => bin_value,
+    }
+}
+
+pub fn bool_remove(current: (i64, i64), bin_value: (i64, i64)) -> Option<(i64, i64)> {
+    Some((current.0 - bin_value.0, current.1 - bin_value.1))
+}
+
+pub fn bool_and_aggregate(memory: &(i64, i64)) -> Option<bool> {
+    (memory.1 > 0).then_some(memory.0 == memory.1)
+}
+
+pub fn bool_or_aggregate(memory: &(i64, i64)) -> Option<bool> {
+    (memory.1 > 0).then_some(memory.0 > 0)
+}
+
+// For aggregates whose bins can be merged but not subtracted, the memory keeps every bin
+// in the window. Bins are added and evicted in time order, so this is a queue.
+pub fn bin_queue_add<T>(current: Option<VecDeque<T>>, bin_value: T) -> VecDeque<T> {
+    let mut bins = current.unwrap_or_default();
+    bins.push_back(bin_value);
+    bins
+}
+
+pub fn bin_queue_remove<T>(current: VecDeque<T>, _bin_value: T) -> Option<VecDeque<T>> {
+    let mut bins = current;
+    bins.pop_front();
+    if bins.is_empty() {
+        None
+    } else {
+        Some(bins)
+    }
+}
+
+// Comparators for aggregates with an ORDER BY. Sort values are kept as-is in the bins, so floats
+// are compared with partial_cmp rather than being wrapped in OrderedFloat.
+pub fn sort_key_cmp<T: PartialOrd>(left: &T, right: &T, descending: bool) -> Ordering {
+    let ordering = left.partial_cmp(right).unwrap_or(Ordering::Equal);
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+pub fn nullable_sort_key_cmp<T: PartialOrd>(
+    left: &Option<T>,
+    right: &Option<T>,
+    descending: bool,
+    nulls_first: bool,
+) -> Ordering {
+    match (left, right) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) if nulls_first => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) if nulls_first => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(left), Some(right)) => sort_key_cmp(left, right, descending),
+    }
+}
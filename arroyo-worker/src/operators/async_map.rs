@@ -0,0 +1,202 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::engine::{Context, StreamNode};
+use arroyo_macro::process_fn;
+use arroyo_types::*;
+use futures::stream::{FuturesOrdered, FuturesUnordered};
+use futures::StreamExt;
+use tokio::sync::Mutex;
+
+pub type AsyncMapFn<InKey, InT, OutKey, OutT> = Box<
+    dyn Fn(&Record<InKey, InT>) -> Pin<Box<dyn Future<Output = Record<OutKey, OutT>> + Send>>
+        + Send,
+>;
+
+type OutputFuture<K, T> = Pin<Box<dyn Future<Output = AsyncOutput<K, T>> + Send>>;
+
+enum AsyncOutput<K: Key, T: Data> {
+    Record(Record<K, T>),
+    Watermark(SystemTime),
+}
+
+enum InFlight<K: Key, T: Data> {
+    Ordered(FuturesOrdered<OutputFuture<K, T>>),
+    // records may finish in any order, but not overtake a watermark, so each watermark
+    // starts a new batch that is only polled once the ones before it are done
+    Unordered(VecDeque<FuturesUnordered<OutputFuture<K, T>>>),
+}
+
+impl<K: Key, T: Data> InFlight<K, T> {
+    fn push(&mut self, future: OutputFuture<K, T>) {
+        match self {
+            InFlight::Ordered(futures) => futures.push_back(future),
+            InFlight::Unordered(batches) => {
+                if batches.is_empty() {
+                    batches.push_back(FuturesUnordered::new());
+                }
+                batches.back_mut().unwrap().push(future);
+            }
+        }
+    }
+
+    fn push_watermark(&mut self, watermark: SystemTime) {
+        let future = Box::pin(futures::future::ready(AsyncOutput::Watermark(watermark)));
+        match self {
+            InFlight::Ordered(futures) => futures.push_back(future),
+            InFlight::Unordered(batches) => {
+                batches.push_back(FuturesUnordered::from_iter([future as OutputFuture<K, T>]));
+                batches.push_back(FuturesUnordered::new());
+            }
+        }
+    }
+
+    async fn next(&mut self) -> Option<AsyncOutput<K, T>> {
+        match self {
+            InFlight::Ordered(futures) => futures.next().await,
+            InFlight::Unordered(batches) => loop {
+                if let Some(output) = batches.front_mut()?.next().await {
+                    return Some(output);
+                }
+                batches.pop_front();
+            },
+        }
+    }
+}
+
+/// Maps records with an async function, such as an async UDF, running up to `max_concurrency`
+/// calls at once. If `ordered` is false, records are emitted as soon as their call finishes,
+/// which may be out of order, though never after a later watermark.
+#[derive(StreamNode)]
+pub struct AsyncMapOperator<InKey: Key, InT: Data, OutKey: Key, OutT: Data> {
+    name: String,
+    map_fn: AsyncMapFn<InKey, InT, OutKey, OutT>,
+    max_concurrency: usize,
+    in_flight: Arc<Mutex<InFlight<OutKey, OutT>>>,
+    // records and watermarks that haven't been emitted yet
+    pending: usize,
+    pending_records: usize,
+}
+
+#[process_fn(in_k = InKey, in_t = InT, out_k = OutKey, out_t = OutT)]
+impl<InKey: Key, InT: Data, OutKey: Key, OutT: Data> AsyncMapOperator<InKey, InT, OutKey, OutT> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn new(
+        name: impl Into<String>,
+        map_fn: AsyncMapFn<InKey, InT, OutKey, OutT>,
+        max_concurrency: usize,
+        ordered: bool,
+    ) -> Self {
+        let in_flight = if ordered {
+            InFlight::Ordered(FuturesOrdered::new())
+        } else {
+            InFlight::Unordered(VecDeque::new())
+        };
+        AsyncMapOperator {
+            name: name.into(),
+            map_fn,
+            max_concurrency: max_concurrency.max(1),
+            in_flight: Arc::new(Mutex::new(in_flight)),
+            pending: 0,
+            pending_records: 0,
+        }
+    }
+
+    async fn process_element(
+        &mut self,
+        record: &Record<InKey, InT>,
+        ctx: &mut Context<OutKey, OutT>,
+    ) {
+        while self.pending_records >= self.max_concurrency {
+            let output = self.in_flight.lock().await.next().await;
+            self.emit(output.unwrap(), ctx).await;
+        }
+
+        let future = (self.map_fn)(record);
+        self.in_flight
+            .lock()
+            .await
+            .push(Box::pin(async move { AsyncOutput::Record(future.await) }));
+        self.pending += 1;
+        self.pending_records += 1;
+    }
+
+    async fn emit(&mut self, output: AsyncOutput<OutKey, OutT>, ctx: &mut Context<OutKey, OutT>) {
+        self.pending -= 1;
+        match output {
+            AsyncOutput::Record(record) => {
+                self.pending_records -= 1;
+                ctx.collect(record).await;
+            }
+            AsyncOutput::Watermark(watermark) => {
+                ctx.broadcast(Message::Watermark(Watermark::EventTime(watermark)))
+                    .await;
+            }
+        }
+    }
+
+    async fn drain(&mut self, ctx: &mut Context<OutKey, OutT>) {
+        while self.pending > 0 {
+            let output = self.in_flight.lock().await.next().await;
+            self.emit(output.unwrap(), ctx).await;
+        }
+    }
+
+    fn future_to_poll(
+        &mut self,
+    ) -> Option<Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>> {
+        let in_flight = self.in_flight.clone();
+        Some(Box::pin(async move {
+            match in_flight.lock().await.next().await {
+                Some(output) => Box::new(output) as Box<dyn Any + Send>,
+                None => futures::future::pending().await,
+            }
+        }))
+    }
+
+    async fn handle_future_result(
+        &mut self,
+        result: Box<dyn Any + Send>,
+        ctx: &mut Context<OutKey, OutT>,
+    ) {
+        let output = result
+            .downcast::<AsyncOutput<OutKey, OutT>>()
+            .expect("unexpected result from async map");
+        self.emit(*output, ctx).await;
+    }
+
+    async fn handle_watermark(&mut self, watermark: SystemTime, ctx: &mut Context<OutKey, OutT>) {
+        if self.pending == 0 {
+            ctx.broadcast(Message::Watermark(Watermark::EventTime(watermark)))
+                .await;
+        } else {
+            self.in_flight.lock().await.push_watermark(watermark);
+            self.pending += 1;
+        }
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &CheckpointBarrier,
+        ctx: &mut Context<OutKey, OutT>,
+    ) {
+        // calls in flight aren't part of the checkpoint, so they have to finish first
+        self.drain(ctx).await;
+    }
+
+    async fn handle_end_of_data(&mut self, ctx: &mut Context<OutKey, OutT>) {
+        self.drain(ctx).await;
+    }
+
+    // when the job is stopped, the calls in flight finish before the Stop is sent on
+    async fn on_close(&mut self, ctx: &mut Context<OutKey, OutT>) {
+        self.drain(ctx).await;
+    }
+}
@@ -1,3 +1,5 @@
+use arroyo_types::Json;
+use serde::Serialize;
 use serde_json::Value;
 use serde_json_path::JsonPath;
 
@@ -27,3 +29,137 @@ pub fn extract_json_string(json_str: String, path: String) -> Option<String> {
         _ => None,
     }
 }
+
+// Looks up an object's field, or an array's element if the key is an integer. Negative
+// indices count from the end of the array.
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(fields) => fields.get(key),
+        Value::Array(elements) => {
+            let index: i64 = key.parse().ok()?;
+            let index = if index < 0 {
+                elements.len() as i64 + index
+            } else {
+                index
+            };
+            elements.get(usize::try_from(index).ok()?)
+        }
+        _ => None,
+    }
+}
+
+fn query<'a>(json: &'a Json, path: &str) -> Option<&'a Value> {
+    let path = JsonPath::parse(path).ok()?;
+    path.query(&json.0).first()
+}
+
+// strings are unquoted, and JSON nulls become SQL nulls
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// The `->` operator.
+pub fn json_get(json: Json, key: String) -> Option<Json> {
+    lookup(&json.0, &key).cloned().map(Json)
+}
+
+/// The `->>` operator.
+pub fn json_get_text(json: Json, key: String) -> Option<String> {
+    lookup(&json.0, &key).and_then(as_text)
+}
+
+pub fn json_extract_int(json: Json, path: String) -> Option<i64> {
+    query(&json, &path)?.as_i64()
+}
+
+pub fn json_extract_float(json: Json, path: String) -> Option<f64> {
+    query(&json, &path)?.as_f64()
+}
+
+pub fn json_extract_bool(json: Json, path: String) -> Option<bool> {
+    query(&json, &path)?.as_bool()
+}
+
+pub fn json_array_length(json: Json) -> Option<i64> {
+    json.0.as_array().map(|elements| elements.len() as i64)
+}
+
+pub fn json_object_keys(json: Json) -> Option<Vec<String>> {
+    json.0
+        .as_object()
+        .map(|fields| fields.keys().cloned().collect())
+}
+
+pub fn to_json<T: Serialize>(value: &T) -> Json {
+    Json(serde_json::to_value(value).unwrap_or(Value::Null))
+}
+
+pub fn json_build_object(fields: Vec<(String, Json)>) -> Json {
+    Json(Value::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_get() {
+        let json = Json::parse(r#"{"a": {"b": "c"}, "items": [1, 2, 3], "n": null}"#).unwrap();
+        assert_eq!(
+            json_get(json.clone(), "a".to_string()),
+            Json::parse(r#"{"b": "c"}"#)
+        );
+        assert_eq!(json_get_text(json.clone(), "n".to_string()), None);
+        assert_eq!(json_get_text(json.clone(), "missing".to_string()), None);
+
+        let items = json_get(json, "items".to_string()).unwrap();
+        assert_eq!(
+            json_get_text(items.clone(), "0".to_string()),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            json_get_text(items.clone(), "-1".to_string()),
+            Some("3".to_string())
+        );
+        assert_eq!(json_get_text(items.clone(), "3".to_string()), None);
+        assert_eq!(json_array_length(items), Some(3));
+    }
+
+    #[test]
+    fn test_json_extract() {
+        let json = Json::parse(r#"{"a": {"int": 5, "float": 1.5, "bool": true}}"#).unwrap();
+        assert_eq!(
+            json_extract_int(json.clone(), "$.a.int".to_string()),
+            Some(5)
+        );
+        assert_eq!(
+            json_extract_float(json.clone(), "$.a.float".to_string()),
+            Some(1.5)
+        );
+        assert_eq!(
+            json_extract_bool(json.clone(), "$.a.bool".to_string()),
+            Some(true)
+        );
+        assert_eq!(json_extract_int(json.clone(), "$.a.bool".to_string()), None);
+        assert_eq!(json_object_keys(json), Some(vec!["a".to_string()]));
+    }
+
+    #[test]
+    fn test_json_build_object() {
+        let json = json_build_object(vec![
+            ("a".to_string(), to_json(&1)),
+            ("b".to_string(), to_json(&Some("x"))),
+            ("c".to_string(), to_json::<Option<i64>>(&None)),
+        ]);
+        assert_eq!(json.to_string(), r#"{"a":1,"b":"x","c":null}"#);
+    }
+}
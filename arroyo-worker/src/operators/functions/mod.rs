@@ -1,4 +1,5 @@
 pub mod hash;
 pub mod json;
+pub mod numeric;
 pub mod regexp;
 pub mod strings;
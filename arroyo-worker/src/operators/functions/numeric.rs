@@ -0,0 +1,56 @@
+pub fn gcd(left: i64, right: i64) -> i64 {
+    let (mut a, mut b) = (left.unsigned_abs(), right.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a as i64
+}
+
+pub fn lcm(left: i64, right: i64) -> i64 {
+    if left == 0 || right == 0 {
+        return 0;
+    }
+    (left / gcd(left, right)).wrapping_mul(right).wrapping_abs()
+}
+
+/// Returns None if the factorial overflows an i64 or the argument is negative.
+pub fn factorial(argument: i64) -> Option<i64> {
+    if argument < 0 {
+        return None;
+    }
+    (1..=argument).try_fold(1i64, |acc, i| acc.checked_mul(i))
+}
+
+pub fn to_hex(argument: i64) -> String {
+    format!("{:x}", argument)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gcd_and_lcm() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(-12, 18), 6);
+        assert_eq!(gcd(0, 5), 5);
+        assert_eq!(lcm(4, 6), 12);
+        assert_eq!(lcm(-4, 6), 12);
+        assert_eq!(lcm(0, 6), 0);
+    }
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(factorial(0), Some(1));
+        assert_eq!(factorial(5), Some(120));
+        assert_eq!(factorial(20), Some(2432902008176640000));
+        assert_eq!(factorial(21), None);
+        assert_eq!(factorial(-1), None);
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(255), "ff");
+        assert_eq!(to_hex(-1), "ffffffffffffffff");
+    }
+}
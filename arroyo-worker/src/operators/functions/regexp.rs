@@ -1,4 +1,5 @@
-use regex::Regex;
+use once_cell::sync::OnceCell;
+use regex::{Regex, RegexBuilder};
 
 pub fn regexp_match(argument: String, regex: String) -> Vec<String> {
     let re = Regex::new(&regex).unwrap();
@@ -24,6 +25,44 @@ pub fn regexp_replace(argument: String, regex: String, replacement: String) -> S
     result.into_owned()
 }
 
+/// Matches `argument` against a pattern computed per row; returns None if the pattern is invalid.
+pub fn regexp_is_match(argument: &str, regex: &str, case_insensitive: bool) -> Option<bool> {
+    let re = RegexBuilder::new(regex)
+        .case_insensitive(case_insensitive)
+        .build()
+        .ok()?;
+    Some(re.is_match(argument))
+}
+
+/// A pattern that's a literal in the query, compiled on first use and shared by every row. The
+/// pattern has already been validated when the query was planned.
+pub struct LiteralRegex {
+    pattern: &'static str,
+    case_insensitive: bool,
+    regex: OnceCell<Regex>,
+}
+
+impl LiteralRegex {
+    pub const fn new(pattern: &'static str, case_insensitive: bool) -> Self {
+        Self {
+            pattern,
+            case_insensitive,
+            regex: OnceCell::new(),
+        }
+    }
+
+    pub fn is_match(&self, argument: &str) -> bool {
+        self.regex
+            .get_or_init(|| {
+                RegexBuilder::new(self.pattern)
+                    .case_insensitive(self.case_insensitive)
+                    .build()
+                    .unwrap()
+            })
+            .is_match(argument)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -45,4 +84,20 @@ mod tests {
         );
         assert_eq!(result.as_str(), "ThoXXs");
     }
+
+    #[test]
+    pub fn test_regexp_is_match_is_correct() {
+        assert_eq!(regexp_is_match("Thomas", "^Th", false), Some(true));
+        assert_eq!(regexp_is_match("thomas", "^Th", false), Some(false));
+        assert_eq!(regexp_is_match("thomas", "^Th", true), Some(true));
+        assert_eq!(regexp_is_match("thomas", "(Th", false), None);
+    }
+
+    #[test]
+    pub fn test_literal_regex_is_correct() {
+        static REGEX: LiteralRegex = LiteralRegex::new("^Th", true);
+        assert!(REGEX.is_match("Thomas"));
+        assert!(REGEX.is_match("thomas"));
+        assert!(!REGEX.is_match("Tom"));
+    }
 }
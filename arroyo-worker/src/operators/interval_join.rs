@@ -0,0 +1,155 @@
+use std::{
+    marker::PhantomData,
+    time::{Duration, SystemTime},
+};
+
+use arroyo_macro::{co_process_fn, StreamNode};
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
+use arroyo_state::tables::KeyTimeMultiMap;
+use arroyo_types::*;
+
+use crate::engine::Context;
+
+/// Joins each left row with the right rows of the same key whose timestamps are between
+/// `lower_bound` before and `upper_bound` after its own. Rows are expired as soon as the
+/// watermark guarantees that nothing else can match them.
+#[derive(StreamNode)]
+pub struct IntervalJoin<K: Key, T1: Data, T2: Data> {
+    lower_bound: Duration,
+    upper_bound: Duration,
+    _t: PhantomData<(K, T1, T2)>,
+}
+
+#[co_process_fn(in_k1=K, in_t1=T1, in_k2=K, in_t2=T2, out_k=K, out_t=(T1,T2))]
+impl<K: Key, T1: Data, T2: Data> IntervalJoin<K, T1, T2> {
+    fn name(&self) -> String {
+        "IntervalJoin".to_string()
+    }
+
+    pub fn new(lower_bound: Duration, upper_bound: Duration) -> Self {
+        Self {
+            lower_bound,
+            upper_bound,
+            _t: PhantomData,
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![
+            TableDescriptor {
+                name: "l".to_string(),
+                description: "join left state".to_string(),
+                table_type: TableType::KeyTimeMultiMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.upper_bound.as_micros() as u64,
+            },
+            TableDescriptor {
+                name: "r".to_string(),
+                description: "join right state".to_string(),
+                table_type: TableType::KeyTimeMultiMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.lower_bound.as_micros() as u64,
+            },
+        ]
+    }
+
+    async fn process_left(&mut self, record: &Record<K, T1>, ctx: &mut Context<K, (T1, T2)>) {
+        if let Some(watermark) = ctx.watermark() {
+            if record.timestamp < watermark {
+                return;
+            }
+        };
+        let mut right_state: KeyTimeMultiMap<K, T2, _> =
+            ctx.state.get_key_time_multi_map('r').await;
+        let mut key = record.key.clone().unwrap();
+        let value = record.value.clone();
+        let start = record
+            .timestamp
+            .checked_sub(self.lower_bound)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let end = record.timestamp + self.upper_bound;
+        let records = {
+            let mut records = vec![];
+            if let Some(right_rows) = right_state.get_all_values_with_timestamps(&mut key).await {
+                for (timestamp, right_value) in right_rows {
+                    if timestamp < start || timestamp > end {
+                        continue;
+                    }
+                    records.push(Record {
+                        timestamp: record.timestamp.max(timestamp),
+                        key: Some(key.clone()),
+                        value: (value.clone(), right_value.clone()),
+                    });
+                }
+            }
+            records
+        };
+        for record in records {
+            ctx.collect(record).await;
+        }
+        let mut left_state = ctx.state.get_key_time_multi_map('l').await;
+        left_state.insert(record.timestamp, key, value).await;
+    }
+
+    async fn process_right(&mut self, record: &Record<K, T2>, ctx: &mut Context<K, (T1, T2)>) {
+        if let Some(watermark) = ctx.watermark() {
+            if record.timestamp < watermark {
+                return;
+            }
+        };
+
+        let mut left_state: KeyTimeMultiMap<K, T1, _> = ctx.state.get_key_time_multi_map('l').await;
+        let mut key = record.key.clone().unwrap();
+        let value = record.value.clone();
+        let start = record
+            .timestamp
+            .checked_sub(self.upper_bound)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let end = record.timestamp + self.lower_bound;
+        let records = {
+            let mut records = vec![];
+            if let Some(left_rows) = left_state.get_all_values_with_timestamps(&mut key).await {
+                for (timestamp, left_value) in left_rows {
+                    if timestamp < start || timestamp > end {
+                        continue;
+                    }
+                    records.push(Record {
+                        timestamp: record.timestamp.max(timestamp),
+                        key: Some(key.clone()),
+                        value: (left_value.clone(), value.clone()),
+                    });
+                }
+            }
+            records
+        };
+        for record in records {
+            ctx.collect(record).await;
+        }
+        let mut right_state = ctx.state.get_key_time_multi_map('r').await;
+        right_state.insert(record.timestamp, key, value).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        _watermark: std::time::SystemTime,
+        ctx: &mut Context<K, (T1, T2)>,
+    ) {
+        let Some(watermark) = ctx.watermark() else {return};
+        // right rows at or after the watermark can only match left rows within upper_bound of it
+        let mut left_state: KeyTimeMultiMap<K, T1, _> = ctx.state.get_key_time_multi_map('l').await;
+        if let Some(expiration) = watermark.checked_sub(self.upper_bound) {
+            left_state.expire_entries_before(expiration);
+        }
+        let mut right_state: KeyTimeMultiMap<K, T2, _> =
+            ctx.state.get_key_time_multi_map('r').await;
+        if let Some(expiration) = watermark.checked_sub(self.lower_bound) {
+            right_state.expire_entries_before(expiration);
+        }
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
+    }
+}
@@ -1,4 +1,7 @@
-use std::{marker::PhantomData, time::Duration};
+use std::{
+    marker::PhantomData,
+    time::{Duration, SystemTime},
+};
 
 use arroyo_macro::{co_process_fn, StreamNode};
 use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableType, TableWriteBehavior};
@@ -11,6 +14,7 @@ use crate::engine::Context;
 pub struct JoinWithExpiration<K: Key, T1: Data, T2: Data> {
     left_expiration: Duration,
     right_expiration: Duration,
+    allowed_lateness: Duration,
     _t: PhantomData<(K, T1, T2)>,
 }
 
@@ -24,10 +28,24 @@ impl<K: Key, T1: Data, T2: Data> JoinWithExpiration<K, T1, T2> {
         Self {
             left_expiration,
             right_expiration,
+            allowed_lateness: Duration::ZERO,
             _t: PhantomData,
         }
     }
 
+    /// Joins records that are at most `allowed_lateness` behind the watermark, rather than
+    /// dropping everything the watermark has passed.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
+
+    fn is_late(&self, timestamp: SystemTime, ctx: &Context<K, (T1, T2)>) -> bool {
+        ctx.watermark()
+            .map(|watermark| timestamp + self.allowed_lateness < watermark)
+            .unwrap_or(false)
+    }
+
     fn tables(&self) -> Vec<TableDescriptor> {
         vec![
             TableDescriptor {
@@ -50,11 +68,10 @@ impl<K: Key, T1: Data, T2: Data> JoinWithExpiration<K, T1, T2> {
     }
 
     async fn process_left(&mut self, record: &Record<K, T1>, ctx: &mut Context<K, (T1, T2)>) {
-        if let Some(watermark) = ctx.watermark() {
-            if record.timestamp < watermark {
-                return;
-            }
-        };
+        if self.is_late(record.timestamp, ctx) {
+            ctx.collect_late(record.clone()).await;
+            return;
+        }
         let mut right_state: KeyTimeMultiMap<K, T2, _> =
             ctx.state.get_key_time_multi_map('r').await;
         let mut key = record.key.clone().unwrap();
@@ -80,11 +97,11 @@ impl<K: Key, T1: Data, T2: Data> JoinWithExpiration<K, T1, T2> {
     }
 
     async fn process_right(&mut self, record: &Record<K, T2>, ctx: &mut Context<K, (T1, T2)>) {
-        if let Some(watermark) = ctx.watermark() {
-            if record.timestamp < watermark {
-                return;
-            }
-        };
+        // the late-data output carries the left side's type, so late right records are only counted
+        if self.is_late(record.timestamp, ctx) {
+            ctx.count_late_record();
+            return;
+        }
 
         let mut left_state: KeyTimeMultiMap<K, T1, _> = ctx.state.get_key_time_multi_map('l').await;
         let mut key = record.key.clone().unwrap();
@@ -120,7 +137,9 @@ impl<K: Key, T1: Data, T2: Data> JoinWithExpiration<K, T1, T2> {
         let mut right_state: KeyTimeMultiMap<K, T2, _> =
             ctx.state.get_key_time_multi_map('r').await;
         right_state.expire_entries_before(watermark - self.right_expiration);
-        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
-            .await;
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
     }
 }
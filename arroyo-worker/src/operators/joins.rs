@@ -95,7 +95,7 @@ impl<K: Key, T1: Data, T2: Data, W1: TimeWindowAssigner<K, T1>, W2: TimeWindowAs
         record: &Record<K, T>,
         assigner: W,
         table: char,
-        ctx: &mut Context<K, (Vec<T1>, Vec<T2>)>,
+        ctx: &mut Context<K, (Vec<T1>, Vec<T2>), Window>,
     ) {
         let windows = assigner.windows(record.timestamp);
         let watermark = ctx.watermark().unwrap_or(SystemTime::UNIX_EPOCH);
@@ -123,7 +123,7 @@ impl<K: Key, T1: Data, T2: Data, W1: TimeWindowAssigner<K, T1>, W2: TimeWindowAs
         &mut self,
         mut key: K,
         window: Window,
-        ctx: &mut Context<K, (Vec<T1>, Vec<T2>)>,
+        ctx: &mut Context<K, (Vec<T1>, Vec<T2>), Window>,
     ) {
         let record = {
             let mut left_state = ctx.state.get_key_time_multi_map('l').await;
@@ -164,7 +164,7 @@ impl<K: Key, T1: Data, T2: Data, W1: TimeWindowAssigner<K, T1>, W2: TimeWindowAs
     async fn process_left(
         &mut self,
         record: &Record<K, T1>,
-        ctx: &mut Context<K, (Vec<T1>, Vec<T2>)>,
+        ctx: &mut Context<K, (Vec<T1>, Vec<T2>), Window>,
     ) {
         Self::store(record, self.assigner1, 'l', ctx).await;
     }
@@ -172,7 +172,7 @@ impl<K: Key, T1: Data, T2: Data, W1: TimeWindowAssigner<K, T1>, W2: TimeWindowAs
     async fn process_right(
         &mut self,
         record: &Record<K, T2>,
-        ctx: &mut Context<K, (Vec<T1>, Vec<T2>)>,
+        ctx: &mut Context<K, (Vec<T1>, Vec<T2>), Window>,
     ) {
         Self::store(record, self.assigner2, 'r', ctx).await;
     }
@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, StatusCode};
+
+use super::LookupConnector;
+
+/// Looks up rows with a GET request to a url in which `{key}` is replaced by the key.
+/// A 404 response means there is no row for the key.
+pub struct HttpLookup {
+    url: String,
+    client: Client,
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl HttpLookup {
+    pub fn new(url: &str, headers: Vec<(&str, &str)>) -> Self {
+        let headers: HeaderMap = headers
+            .into_iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::try_from(name).expect("invalid header name"),
+                    HeaderValue::try_from(value).expect("invalid header value"),
+                )
+            })
+            .collect();
+        Self {
+            url: url.to_string(),
+            client: Client::builder()
+                .default_headers(headers)
+                .build()
+                .expect("failed to build http client"),
+        }
+    }
+}
+
+#[async_trait]
+impl LookupConnector for HttpLookup {
+    async fn lookup(&self, key: &str) -> Result<Option<String>, String> {
+        let url = self.url.replace("{key}", &percent_encode(key));
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("request to {} failed: {}", url, e))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|e| format!("request to {} failed: {}", url, e))?;
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("failed to read response from {}: {}", url, e))?;
+        Ok(Some(body))
+    }
+}
@@ -0,0 +1,378 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use arroyo_macro::{process_fn, StreamNode};
+use arroyo_types::*;
+use async_trait::async_trait;
+use futures::stream::FuturesOrdered;
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::engine::Context;
+
+pub mod http;
+pub mod postgres;
+pub mod redis;
+
+/// An external system that rows can be looked up in by key. Lookups of several keys may run
+/// at once.
+#[async_trait]
+pub trait LookupConnector: Send + Sync {
+    /// Fetches the row for `key` as a JSON object, or None if there is no such row.
+    async fn lookup(&self, key: &str) -> Result<Option<String>, String>;
+}
+
+struct CacheEntry<V> {
+    value: Option<V>,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+/// A least-recently-used cache of lookup results, including misses, that expires
+/// entries `ttl` after they were fetched.
+pub struct LookupCache<V> {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<String, CacheEntry<V>>,
+    // keys ordered by when they were last used
+    recency: BTreeMap<u64, String>,
+    counter: u64,
+}
+
+impl<V: Clone> LookupCache<V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            counter: 0,
+        }
+    }
+
+    /// Returns the cached result for `key`, or None if it isn't cached or has expired.
+    pub fn get(&mut self, key: &str, now: Instant) -> Option<Option<V>> {
+        let entry = self.entries.get_mut(key)?;
+        if now.duration_since(entry.inserted_at) >= self.ttl {
+            self.recency.remove(&entry.last_used);
+            self.entries.remove(key);
+            return None;
+        }
+        self.counter += 1;
+        let key = self.recency.remove(&entry.last_used).unwrap();
+        entry.last_used = self.counter;
+        self.recency.insert(self.counter, key);
+        Some(entry.value.clone())
+    }
+
+    pub fn insert(&mut self, key: String, value: Option<V>, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(entry) = self.entries.remove(&key) {
+            self.recency.remove(&entry.last_used);
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&evicted);
+        }
+        self.counter += 1;
+        self.recency.insert(self.counter, key.clone());
+        self.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                inserted_at: now,
+                last_used: self.counter,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+type LookupFuture<K, T, V> = Pin<Box<dyn Future<Output = LookupOutput<K, T, V>> + Send>>;
+
+enum LookupOutput<K: Key, T: Data, V> {
+    // the record with the row it matched, and the key if the row was fetched rather than cached
+    Record(Record<K, T>, Option<V>, Option<String>),
+    Watermark(SystemTime),
+}
+
+const MAX_LOOKUP_ATTEMPTS: u32 = 5;
+const INITIAL_LOOKUP_BACKOFF: Duration = Duration::from_millis(100);
+
+// Looks up a key, retrying failures with exponential backoff. Records can't be joined correctly
+// without their rows, so once the retries are exhausted the task fails and is restarted.
+async fn fetch<V: DeserializeOwned>(connector: Arc<dyn LookupConnector>, key: &str) -> Option<V> {
+    let mut backoff = INITIAL_LOOKUP_BACKOFF;
+    let mut attempt = 1;
+    let row = loop {
+        match connector.lookup(key).await {
+            Ok(row) => break row,
+            Err(e) if attempt < MAX_LOOKUP_ATTEMPTS => {
+                warn!(
+                    "lookup of key '{}' failed, retrying in {:?}: {}",
+                    key, backoff, e
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => panic!(
+                "lookup of key '{}' failed after {} attempts: {}",
+                key, attempt, e
+            ),
+        }
+    };
+    let row = row?;
+    match serde_json::from_str(&row) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("failed to deserialize lookup row '{}': {}", row, e);
+            None
+        }
+    }
+}
+
+/// Enriches each record with the row of a lookup table that matches its key, as of the
+/// time the record is processed. Up to `max_concurrency` lookups run at once, and records are
+/// emitted in the order they arrived. Lookups are cached per subtask; nothing is checkpointed.
+#[derive(StreamNode)]
+pub struct LookupJoin<K: Key, T: Data, V: Data + DeserializeOwned, OutT: Data> {
+    connector: Arc<dyn LookupConnector>,
+    cache: LookupCache<V>,
+    max_concurrency: usize,
+    key_fn: fn(&T) -> Option<String>,
+    merge_fn: fn(&T, Option<&V>) -> Option<OutT>,
+    in_flight: Arc<Mutex<FuturesOrdered<LookupFuture<K, T, V>>>>,
+    // records and watermarks that haven't been emitted yet
+    pending: usize,
+    pending_records: usize,
+}
+
+#[process_fn(in_k = K, in_t = T, out_t = OutT)]
+impl<K: Key, T: Data, V: Data + DeserializeOwned, OutT: Data> LookupJoin<K, T, V, OutT> {
+    fn name(&self) -> String {
+        "LookupJoin".to_string()
+    }
+
+    pub fn new(
+        connector: Box<dyn LookupConnector>,
+        cache_capacity: usize,
+        cache_ttl: Duration,
+        max_concurrency: usize,
+        key_fn: fn(&T) -> Option<String>,
+        merge_fn: fn(&T, Option<&V>) -> Option<OutT>,
+    ) -> Self {
+        Self {
+            connector: Arc::from(connector),
+            cache: LookupCache::new(cache_capacity, cache_ttl),
+            max_concurrency: max_concurrency.max(1),
+            key_fn,
+            merge_fn,
+            in_flight: Arc::new(Mutex::new(FuturesOrdered::new())),
+            pending: 0,
+            pending_records: 0,
+        }
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), OutT>) {
+        while self.pending_records >= self.max_concurrency {
+            let output = self.in_flight.lock().await.next().await;
+            self.emit(output.unwrap(), ctx).await;
+        }
+
+        let record = record.clone();
+        let future: LookupFuture<K, T, V> = match (self.key_fn)(&record.value) {
+            Some(key) => match self.cache.get(&key, Instant::now()) {
+                Some(value) => Box::pin(futures::future::ready(LookupOutput::Record(
+                    record, value, None,
+                ))),
+                None => {
+                    let connector = self.connector.clone();
+                    Box::pin(async move {
+                        let value = fetch(connector, &key).await;
+                        LookupOutput::Record(record, value, Some(key))
+                    })
+                }
+            },
+            None => Box::pin(futures::future::ready(LookupOutput::Record(
+                record, None, None,
+            ))),
+        };
+        self.in_flight.lock().await.push_back(future);
+        self.pending += 1;
+        self.pending_records += 1;
+    }
+
+    async fn emit(&mut self, output: LookupOutput<K, T, V>, ctx: &mut Context<(), OutT>) {
+        self.pending -= 1;
+        match output {
+            LookupOutput::Record(record, value, fetched_key) => {
+                self.pending_records -= 1;
+                if let Some(key) = fetched_key {
+                    self.cache.insert(key, value.clone(), Instant::now());
+                }
+                if let Some(value) = (self.merge_fn)(&record.value, value.as_ref()) {
+                    ctx.collect(Record {
+                        timestamp: record.timestamp,
+                        key: None,
+                        value,
+                    })
+                    .await;
+                }
+            }
+            LookupOutput::Watermark(watermark) => {
+                ctx.broadcast(Message::Watermark(Watermark::EventTime(watermark)))
+                    .await;
+            }
+        }
+    }
+
+    async fn drain(&mut self, ctx: &mut Context<(), OutT>) {
+        while self.pending > 0 {
+            let output = self.in_flight.lock().await.next().await;
+            self.emit(output.unwrap(), ctx).await;
+        }
+    }
+
+    fn future_to_poll(
+        &mut self,
+    ) -> Option<Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>> {
+        let in_flight = self.in_flight.clone();
+        Some(Box::pin(async move {
+            match in_flight.lock().await.next().await {
+                Some(output) => Box::new(output) as Box<dyn Any + Send>,
+                None => futures::future::pending().await,
+            }
+        }))
+    }
+
+    async fn handle_future_result(
+        &mut self,
+        result: Box<dyn Any + Send>,
+        ctx: &mut Context<(), OutT>,
+    ) {
+        let output = result
+            .downcast::<LookupOutput<K, T, V>>()
+            .expect("unexpected result from lookup join");
+        self.emit(*output, ctx).await;
+    }
+
+    async fn handle_watermark(&mut self, watermark: SystemTime, ctx: &mut Context<(), OutT>) {
+        if self.pending == 0 {
+            ctx.broadcast(Message::Watermark(Watermark::EventTime(watermark)))
+                .await;
+        } else {
+            self.in_flight
+                .lock()
+                .await
+                .push_back(Box::pin(futures::future::ready(LookupOutput::Watermark(
+                    watermark,
+                ))));
+            self.pending += 1;
+        }
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &CheckpointBarrier,
+        ctx: &mut Context<(), OutT>,
+    ) {
+        // lookups in flight aren't part of the checkpoint, so they have to finish first
+        self.drain(ctx).await;
+    }
+
+    async fn handle_end_of_data(&mut self, ctx: &mut Context<(), OutT>) {
+        self.drain(ctx).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+
+    use super::{fetch, LookupCache, LookupConnector, MAX_LOOKUP_ATTEMPTS};
+
+    // fails the first `failures` lookups
+    struct FlakyLookup {
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl LookupConnector for FlakyLookup {
+        async fn lookup(&self, _key: &str) -> Result<Option<String>, String> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err("unavailable".to_string())
+            } else {
+                Ok(Some("1".to_string()))
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let now = Instant::now();
+        let mut cache = LookupCache::new(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), Some(1), now);
+        cache.insert("b".to_string(), None, now);
+        assert_eq!(cache.get("a", now), Some(Some(1)));
+        cache.insert("c".to_string(), Some(3), now);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b", now), None);
+        assert_eq!(cache.get("a", now), Some(Some(1)));
+        assert_eq!(cache.get("c", now), Some(Some(3)));
+    }
+
+    #[test]
+    fn test_cache_expires_entries() {
+        let now = Instant::now();
+        let mut cache = LookupCache::new(10, Duration::from_secs(60));
+        cache.insert("a".to_string(), Some(1), now);
+        assert_eq!(cache.get("a", now + Duration::from_secs(59)), Some(Some(1)));
+        assert_eq!(cache.get("a", now + Duration::from_secs(60)), None);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_failed_lookups() {
+        let connector = Arc::new(FlakyLookup {
+            failures: 2,
+            attempts: AtomicU32::new(0),
+        });
+        let value: Option<u64> = fetch(connector.clone(), "a").await;
+        assert_eq!(value, Some(1));
+        assert_eq!(connector.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "lookup of key 'a' failed")]
+    async fn test_fetch_fails_after_retries() {
+        let connector = Arc::new(FlakyLookup {
+            failures: MAX_LOOKUP_ATTEMPTS,
+            attempts: AtomicU32::new(0),
+        });
+        let _: Option<u64> = fetch(connector, "a").await;
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tokio_postgres::{Client, NoTls, Statement};
+use tracing::warn;
+
+use super::LookupConnector;
+
+/// Looks up rows in a Postgres table by a key column. Concurrent lookups are pipelined on a
+/// single connection.
+pub struct PostgresLookup {
+    connection_string: String,
+    table: String,
+    key_column: String,
+    client: Mutex<Option<(Arc<Client>, Statement)>>,
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    identifier
+        .split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+impl PostgresLookup {
+    pub fn new(connection_string: &str, table: &str, key_column: &str) -> Self {
+        Self {
+            connection_string: connection_string.to_string(),
+            table: table.to_string(),
+            key_column: key_column.to_string(),
+            client: Mutex::new(None),
+        }
+    }
+
+    async fn client(&self) -> Result<(Arc<Client>, Statement), String> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().map(|(c, _)| c.is_closed()).unwrap_or(true) {
+            let (new_client, connection) = tokio_postgres::connect(&self.connection_string, NoTls)
+                .await
+                .map_err(|e| format!("failed to connect to postgres: {}", e))?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    warn!("postgres lookup connection failed: {}", e);
+                }
+            });
+            let statement = self.prepare(&new_client).await?;
+            *client = Some((Arc::new(new_client), statement));
+        }
+        Ok(client.as_ref().unwrap().clone())
+    }
+
+    /// Prepares the lookup query. The key is cast to the column's own type rather than the
+    /// column to text, so that lookups can use an index on the key column.
+    async fn prepare(&self, client: &Client) -> Result<Statement, String> {
+        let table = quote_identifier(&self.table);
+        let column_type: String = client
+            .query_opt(
+                "SELECT format_type(atttypid, atttypmod) FROM pg_attribute
+                WHERE attrelid = $1::text::regclass AND attname = $2 AND NOT attisdropped",
+                &[&table, &self.key_column],
+            )
+            .await
+            .map_err(|e| format!("failed to look up type of key column: {}", e))?
+            .ok_or_else(|| {
+                format!(
+                    "key column '{}' does not exist in table '{}'",
+                    self.key_column, self.table
+                )
+            })?
+            .get(0);
+
+        let query = format!(
+            "SELECT row_to_json(t)::text FROM (SELECT * FROM {} WHERE {} = $1::text::{} LIMIT 1) t",
+            table,
+            quote_identifier(&self.key_column),
+            column_type
+        );
+        client
+            .prepare(&query)
+            .await
+            .map_err(|e| format!("failed to prepare lookup query: {}", e))
+    }
+}
+
+#[async_trait]
+impl LookupConnector for PostgresLookup {
+    async fn lookup(&self, key: &str) -> Result<Option<String>, String> {
+        let (client, statement) = self.client().await?;
+        let row = client
+            .query_opt(&statement, &[&key])
+            .await
+            .map_err(|e| format!("postgres lookup failed: {}", e))?;
+        Ok(row.map(|row| row.get(0)))
+    }
+}
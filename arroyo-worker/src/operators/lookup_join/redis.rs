@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::LookupConnector;
+
+/// Looks up rows stored as JSON strings under `key_prefix` + key, with the Redis `GET` command.
+/// Each lookup in flight uses its own connection, and idle connections are kept for reuse.
+pub struct RedisLookup {
+    address: String,
+    key_prefix: String,
+    idle_connections: Mutex<Vec<BufReader<TcpStream>>>,
+}
+
+impl RedisLookup {
+    pub fn new(address: &str, key_prefix: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            key_prefix: key_prefix.to_string(),
+            idle_connections: Mutex::new(vec![]),
+        }
+    }
+
+    async fn connection(&self) -> Result<BufReader<TcpStream>, String> {
+        if let Some(connection) = self.idle_connections.lock().await.pop() {
+            return Ok(connection);
+        }
+        let stream = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| format!("failed to connect to redis at {}: {}", self.address, e))?;
+        Ok(BufReader::new(stream))
+    }
+}
+
+async fn get(connection: &mut BufReader<TcpStream>, key: &str) -> Result<Option<String>, String> {
+    let command = format!("*2\r\n$3\r\nGET\r\n${}\r\n{}\r\n", key.len(), key);
+    connection
+        .get_mut()
+        .write_all(command.as_bytes())
+        .await
+        .map_err(|e| format!("failed to send redis command: {}", e))?;
+    read_bulk_string(connection).await
+}
+
+// Reads the reply to a GET: a bulk string, a null bulk string, or an error.
+async fn read_bulk_string<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<String>, String> {
+    let mut header = String::new();
+    reader
+        .read_line(&mut header)
+        .await
+        .map_err(|e| format!("failed to read redis reply: {}", e))?;
+    let header = header.trim_end();
+    if let Some(error) = header.strip_prefix('-') {
+        return Err(format!("redis returned an error: {}", error));
+    }
+    let Some(length) = header.strip_prefix('$') else {
+        return Err(format!("unexpected redis reply '{}'", header));
+    };
+    let length: i64 = length
+        .parse()
+        .map_err(|_| format!("invalid redis bulk string length '{}'", length))?;
+    if length < 0 {
+        return Ok(None);
+    }
+    // the value is followed by \r\n
+    let mut value = vec![0; length as usize + 2];
+    reader
+        .read_exact(&mut value)
+        .await
+        .map_err(|e| format!("failed to read redis reply: {}", e))?;
+    value.truncate(length as usize);
+    String::from_utf8(value)
+        .map(Some)
+        .map_err(|_| "redis value is not valid utf-8".to_string())
+}
+
+#[async_trait]
+impl LookupConnector for RedisLookup {
+    async fn lookup(&self, key: &str) -> Result<Option<String>, String> {
+        let key = format!("{}{}", self.key_prefix, key);
+        let mut connection = self.connection().await?;
+        let result = get(&mut connection, &key).await;
+        // after an error the connection may be in an unknown state, so it's dropped
+        if result.is_ok() {
+            self.idle_connections.lock().await.push(connection);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_bulk_string;
+
+    #[tokio::test]
+    async fn test_read_bulk_string() {
+        let mut reply: &[u8] = b"$12\r\n{\"name\":\"a\"}\r\n$-1\r\n-ERR wrong type\r\n";
+        assert_eq!(
+            read_bulk_string(&mut reply).await,
+            Ok(Some("{\"name\":\"a\"}".to_string()))
+        );
+        assert_eq!(read_bulk_string(&mut reply).await, Ok(None));
+        assert!(read_bulk_string(&mut reply).await.is_err());
+    }
+}
@@ -2,34 +2,44 @@ use std::fs;
 use std::str::FromStr;
 use std::{fmt::Debug, path::PathBuf};
 
+use std::any::Any;
+use std::future::Future;
 use std::marker::PhantomData;
 use std::ops::Add;
+use std::pin::Pin;
 
 use crate::engine::{Collector, Context, StreamNode};
 use arroyo_macro::process_fn;
 use arroyo_rpc::grpc::TableDescriptor;
 use arroyo_types::{
     from_millis, to_millis, CheckpointBarrier, Data, GlobalKey, Key, Message, Record, TaskInfo,
-    Window,
+    Watermark, Window,
 };
 use bincode::{config, Decode, Encode};
 use serde::de::DeserializeOwned;
 use serde_json::json;
 use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
 use tracing::debug;
 use wasmtime::{
     Caller, Engine, InstanceAllocationStrategy, InstanceLimits, Linker, Module,
     PoolingAllocationStrategy, Store, TypedFunc,
 };
 pub mod aggregating_window;
+pub mod async_map;
 pub mod functions;
+pub mod interval_join;
 pub mod join_with_expiration;
 pub mod joins;
+pub mod lookup_join;
+pub mod over_window;
 pub mod sinks;
+pub mod sketches;
 pub mod sliding_top_n_aggregating_window;
 pub mod sources;
 pub mod tumbling_aggregating_window;
 pub mod tumbling_top_n_window;
+pub mod updating_aggregate;
 pub mod windows;
 
 #[derive(Clone, Copy)]
@@ -125,7 +135,7 @@ mod test {
         operator.process_element(&record, &mut ctx).await;
 
         let item = data_rx.try_recv().unwrap();
-        let result: Message<String, u64> = item.into();
+        let result: Message<String, u64> = item.try_into().unwrap();
 
         match result {
             Message::Record(record) => {
@@ -166,6 +176,9 @@ pub struct PeriodicWatermarkGenerator<K: Key, D: Data> {
     interval: Duration,
     watermark_function: Box<dyn Fn(&Record<K, D>) -> SystemTime + Send>,
     state_cache: PeriodicWatermarkGeneratorState,
+    idle_time: Option<Duration>,
+    last_event: Instant,
+    idle: bool,
     _t: PhantomData<(K, D)>,
 }
 
@@ -182,6 +195,9 @@ impl<K: Key, D: Data> PeriodicWatermarkGenerator<K, D> {
                 last_watermark_emitted_at: SystemTime::UNIX_EPOCH,
                 max_watermark: SystemTime::UNIX_EPOCH,
             },
+            idle_time: None,
+            last_event: Instant::now(),
+            idle: false,
             _t: PhantomData,
         }
     }
@@ -197,10 +213,20 @@ impl<K: Key, D: Data> PeriodicWatermarkGenerator<K, D> {
                 last_watermark_emitted_at: SystemTime::UNIX_EPOCH,
                 max_watermark: SystemTime::UNIX_EPOCH,
             },
+            idle_time: None,
+            last_event: Instant::now(),
+            idle: false,
             _t: PhantomData,
         }
     }
 
+    /// Marks the source as idle after it hasn't produced records for `idle_time`, so that it
+    /// doesn't hold back the watermarks of downstream operators.
+    pub fn with_idle_time(mut self, idle_time: Option<Duration>) -> Self {
+        self.idle_time = idle_time;
+        self
+    }
+
     fn tables(&self) -> Vec<TableDescriptor> {
         vec![arroyo_state::global_table(
             "s",
@@ -223,17 +249,22 @@ impl<K: Key, D: Data> PeriodicWatermarkGenerator<K, D> {
                 }));
 
         self.state_cache = state;
+        self.last_event = Instant::now();
     }
 
     async fn on_close(&mut self, ctx: &mut Context<K, D>) {
         // send final watermark on close
         ctx.collector
-            .broadcast(Message::Watermark(from_millis(u64::MAX)))
+            .broadcast(Message::Watermark(Watermark::EventTime(from_millis(
+                u64::MAX,
+            ))))
             .await;
     }
 
     async fn process_element(&mut self, record: &Record<K, D>, ctx: &mut Context<K, D>) {
         ctx.collector.collect(record.clone()).await;
+        self.last_event = Instant::now();
+        self.idle = false;
 
         let watermark = (self.watermark_function)(record);
 
@@ -249,7 +280,9 @@ impl<K: Key, D: Data> PeriodicWatermarkGenerator<K, D> {
                 ctx.task_info.task_index,
                 to_millis(watermark)
             );
-            ctx.collector.broadcast(Message::Watermark(watermark)).await;
+            ctx.collector
+                .broadcast(Message::Watermark(Watermark::EventTime(watermark)))
+                .await;
             self.state_cache.last_watermark_emitted_at = record.timestamp;
         }
     }
@@ -259,6 +292,31 @@ impl<K: Key, D: Data> PeriodicWatermarkGenerator<K, D> {
 
         gs.insert(ctx.task_info.task_index, self.state_cache).await;
     }
+
+    fn future_to_poll(
+        &mut self,
+    ) -> Option<Pin<Box<dyn Future<Output = Box<dyn Any + Send>> + Send>>> {
+        let idle_time = self.idle_time?;
+        if self.idle {
+            return None;
+        }
+        let idle_at = self.last_event + idle_time;
+        Some(Box::pin(async move {
+            tokio::time::sleep_until(idle_at).await;
+            Box::new(()) as Box<dyn Any + Send>
+        }))
+    }
+
+    async fn handle_future_result(&mut self, _: Box<dyn Any + Send>, ctx: &mut Context<K, D>) {
+        debug!(
+            "[{}] No records for {:?}, marking the source idle",
+            ctx.task_info.task_index, self.idle_time
+        );
+        self.idle = true;
+        ctx.collector
+            .broadcast(Message::Watermark(Watermark::Idle))
+            .await;
+    }
 }
 
 pub trait TimeWindowAssigner<K: Key, T: Data>: Copy + Clone + Send + 'static {
@@ -0,0 +1,343 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    time::SystemTime,
+};
+
+use crate::engine::{Context, StreamNode};
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::{TableDeleteBehavior, TableDescriptor, TableWriteBehavior};
+use arroyo_state::tables::TimeKeyMap;
+
+use arroyo_types::*;
+use std::time::Duration;
+use tracing::debug;
+
+/// The rows preceding the current one that a window function is evaluated over.
+#[derive(Debug, Clone, Copy)]
+pub enum OverWindowFrame {
+    /// The current row and up to this many rows before it, or all preceding rows if None.
+    Rows(Option<usize>),
+    /// Every row whose timestamp is within this duration of the current row's, including rows
+    /// with the same timestamp, or all preceding rows if None.
+    Range(Option<Duration>),
+}
+
+/// Evaluates window functions over the rows of each key in event-time order, without
+/// a tumbling or sliding window. Rows are buffered until the watermark passes them;
+/// unbounded frames are computed incrementally with `bin_merger` rather than by retaining rows.
+/// Keys that haven't had a row for `ttl` of event time are dropped.
+#[derive(StreamNode)]
+pub struct OverWindowFunc<K: Key, T: Data, BinA: Data, OutT: Data> {
+    frame: OverWindowFrame,
+    following_rows: usize,
+    ttl: Duration,
+    bin_merger: Option<fn(&T, Option<&BinA>) -> BinA>,
+    // (current row, frame, running bin) -> output. The frame includes the current row
+    // and any following rows.
+    evaluator: fn(&T, &[T], Option<&BinA>) -> OutT,
+    buffered_rows: BTreeMap<SystemTime, HashMap<K, Vec<T>>>,
+    partitions: HashMap<K, PartitionState<T, BinA>>,
+    // keys by the timestamp of their last row, to expire idle partitions
+    last_updated: BTreeMap<SystemTime, HashSet<K>>,
+}
+
+struct PartitionState<T, BinA> {
+    timestamps: VecDeque<SystemTime>,
+    rows: VecDeque<T>,
+    bin: Option<BinA>,
+    last_updated: SystemTime,
+}
+
+impl<T, BinA> Default for PartitionState<T, BinA> {
+    fn default() -> Self {
+        Self {
+            timestamps: VecDeque::new(),
+            rows: VecDeque::new(),
+            bin: None,
+            last_updated: SystemTime::UNIX_EPOCH,
+        }
+    }
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = K, out_t = OutT)]
+impl<K: Key, T: Data, BinA: Data, OutT: Data> OverWindowFunc<K, T, BinA, OutT> {
+    fn name(&self) -> String {
+        "OverWindow".to_string()
+    }
+
+    pub fn new(
+        frame: OverWindowFrame,
+        following_rows: usize,
+        ttl: Duration,
+        bin_merger: Option<fn(&T, Option<&BinA>) -> BinA>,
+        evaluator: fn(&T, &[T], Option<&BinA>) -> OutT,
+    ) -> Self {
+        Self {
+            frame,
+            following_rows,
+            ttl,
+            bin_merger,
+            evaluator,
+            buffered_rows: BTreeMap::new(),
+            partitions: HashMap::new(),
+            last_updated: BTreeMap::new(),
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![
+            arroyo_state::timestamp_table(
+                "b",
+                "buffered rows",
+                TableDeleteBehavior::NoReadsBeforeWatermark,
+                TableWriteBehavior::NoWritesBeforeWatermark,
+                Duration::ZERO,
+            ),
+            arroyo_state::timestamp_table(
+                "p",
+                "partition state",
+                TableDeleteBehavior::NoReadsBeforeWatermark,
+                TableWriteBehavior::DefaultWrites,
+                Duration::ZERO,
+            ),
+        ]
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
+        if let Some(watermark) = ctx.watermark() {
+            if record.timestamp < watermark {
+                return;
+            }
+        }
+        self.buffered_rows
+            .entry(record.timestamp)
+            .or_default()
+            .entry(record.key.clone().unwrap())
+            .or_default()
+            .push(record.value.clone());
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        _watermark: std::time::SystemTime,
+        ctx: &mut Context<K, OutT>,
+    ) {
+        let Some(watermark) = ctx.watermark() else {
+            return;
+        };
+        while let Some(entry) = self.buffered_rows.first_entry() {
+            if *entry.key() >= watermark {
+                break;
+            }
+            let (timestamp, partitions) = entry.remove_entry();
+            for (key, rows) in partitions {
+                for (timestamp, value) in self.evaluate(&key, timestamp, rows) {
+                    let record = Record {
+                        timestamp,
+                        key: Some(key.clone()),
+                        value,
+                    };
+                    debug!("emitting {:?}", record);
+                    ctx.collect(record).await;
+                }
+            }
+        }
+        if let Some(expiration) = watermark.checked_sub(self.ttl) {
+            self.expire_partitions(expiration);
+        }
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
+    }
+
+    // Adds the rows for a key at a single timestamp to its partition, returning the results
+    // that can now be computed. Results that look ahead to following rows are emitted with the
+    // timestamp of the last of those rows, so that they aren't behind the watermark.
+    fn evaluate(
+        &mut self,
+        key: &K,
+        timestamp: SystemTime,
+        rows: Vec<T>,
+    ) -> Vec<(SystemTime, OutT)> {
+        let partition = self.partitions.entry(key.clone()).or_default();
+        if let Some(keys) = self.last_updated.get_mut(&partition.last_updated) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.last_updated.remove(&partition.last_updated);
+            }
+        }
+        partition.last_updated = timestamp;
+        self.last_updated
+            .entry(timestamp)
+            .or_default()
+            .insert(key.clone());
+        let mut results = vec![];
+        match self.frame {
+            OverWindowFrame::Rows(preceding) => {
+                for row in rows {
+                    if let Some(bin_merger) = self.bin_merger {
+                        partition.bin = Some(bin_merger(&row, partition.bin.as_ref()));
+                    }
+                    partition.timestamps.push_back(timestamp);
+                    partition.rows.push_back(row);
+
+                    let len = partition.rows.len();
+                    if len > self.following_rows {
+                        let current = len - 1 - self.following_rows;
+                        let start = preceding
+                            .map_or(current, |preceding| current.saturating_sub(preceding));
+                        let frame = &partition.rows.make_contiguous()[start..];
+                        results.push((
+                            timestamp,
+                            (self.evaluator)(
+                                &frame[current - start],
+                                frame,
+                                partition.bin.as_ref(),
+                            ),
+                        ));
+                    }
+
+                    let retained = preceding.unwrap_or(0) + self.following_rows;
+                    while partition.rows.len() > retained {
+                        partition.timestamps.pop_front();
+                        partition.rows.pop_front();
+                    }
+                }
+            }
+            OverWindowFrame::Range(preceding) => {
+                match preceding {
+                    Some(preceding) => {
+                        let start_time = timestamp
+                            .checked_sub(preceding)
+                            .unwrap_or(SystemTime::UNIX_EPOCH);
+                        while partition
+                            .timestamps
+                            .front()
+                            .map_or(false, |front| *front < start_time)
+                        {
+                            partition.timestamps.pop_front();
+                            partition.rows.pop_front();
+                        }
+                    }
+                    // the running bin covers everything before this timestamp
+                    None => {
+                        partition.timestamps.clear();
+                        partition.rows.clear();
+                    }
+                }
+                let first_new_row = partition.rows.len();
+                for row in rows {
+                    if let Some(bin_merger) = self.bin_merger {
+                        partition.bin = Some(bin_merger(&row, partition.bin.as_ref()));
+                    }
+                    partition.timestamps.push_back(timestamp);
+                    partition.rows.push_back(row);
+                }
+                let frame = partition.rows.make_contiguous();
+                for current in first_new_row..frame.len() {
+                    results.push((
+                        timestamp,
+                        (self.evaluator)(&frame[current], frame, partition.bin.as_ref()),
+                    ));
+                }
+            }
+        }
+        results
+    }
+
+    // Drops the partitions whose last row is before `expiration`.
+    fn expire_partitions(&mut self, expiration: SystemTime) {
+        while let Some(entry) = self.last_updated.first_entry() {
+            if *entry.key() >= expiration {
+                break;
+            }
+            for key in entry.remove() {
+                debug!("expiring partition for {:?}", key);
+                self.partitions.remove(&key);
+            }
+        }
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<K, OutT>) {
+        let mut buffered: TimeKeyMap<(K, usize), T, _> =
+            ctx.state.get_time_key_map('b', ctx.watermark()).await;
+        for (timestamp, (key, _index), value) in buffered.get_all().await {
+            self.buffered_rows
+                .entry(timestamp)
+                .or_default()
+                .entry(key.clone())
+                .or_default()
+                .push(value.clone());
+        }
+
+        let mut partitions: TimeKeyMap<K, (Vec<(SystemTime, T)>, Option<BinA>, SystemTime), _> =
+            ctx.state.get_time_key_map('p', ctx.watermark()).await;
+        // partitions are written at each checkpoint, so only keep the latest state for each key
+        let mut latest: HashMap<K, SystemTime> = HashMap::new();
+        for (checkpoint_time, key, (rows, bin, last_updated)) in partitions.get_all().await {
+            if latest
+                .get(key)
+                .map_or(false, |latest_time| *latest_time > checkpoint_time)
+            {
+                continue;
+            }
+            latest.insert(key.clone(), checkpoint_time);
+            let (timestamps, rows) = rows.iter().cloned().unzip();
+            self.partitions.insert(
+                key.clone(),
+                PartitionState {
+                    timestamps,
+                    rows,
+                    bin: bin.clone(),
+                    last_updated: *last_updated,
+                },
+            );
+        }
+        for (key, partition) in &self.partitions {
+            self.last_updated
+                .entry(partition.last_updated)
+                .or_default()
+                .insert(key.clone());
+        }
+        if let Some(expiration) = ctx
+            .watermark()
+            .and_then(|watermark| watermark.checked_sub(self.ttl))
+        {
+            self.expire_partitions(expiration);
+        }
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
+        ctx: &mut Context<K, OutT>,
+    ) {
+        let checkpoint_time = ctx.watermark().unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut buffered = ctx.state.get_time_key_map('b', ctx.watermark()).await;
+        for (timestamp, partitions) in &self.buffered_rows {
+            for (key, rows) in partitions {
+                for (index, row) in rows.iter().enumerate() {
+                    buffered.insert(*timestamp, (key.clone(), index), row.clone());
+                }
+            }
+        }
+        buffered.flush().await;
+
+        let mut partitions = ctx.state.get_time_key_map('p', ctx.watermark()).await;
+        for (key, partition) in &self.partitions {
+            let rows: Vec<_> = partition
+                .timestamps
+                .iter()
+                .cloned()
+                .zip(partition.rows.iter().cloned())
+                .collect();
+            partitions.insert(
+                checkpoint_time,
+                key.clone(),
+                (rows, partition.bin.clone(), partition.last_updated),
+            );
+        }
+        partitions.flush().await;
+    }
+}
@@ -132,6 +132,7 @@ async fn test_kafka_checkpoint_flushes() {
         min_epoch: 0,
         timestamp: (SystemTime::now()),
         then_stop: false,
+        unaligned: false,
     };
     sink_with_writes
         .sink
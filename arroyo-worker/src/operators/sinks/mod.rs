@@ -152,11 +152,13 @@ impl<K: Key, T: Data + Serialize> GrpcSink<K, T> {
         let controller_addr = std::env::var(arroyo_types::CONTROLLER_ADDR_ENV)
             .unwrap_or_else(|_| crate::LOCAL_CONTROLLER_ADDR.to_string());
 
-        self.client = Some(
-            ControllerGrpcClient::connect(controller_addr)
+        self.client = Some(ControllerGrpcClient::new(
+            arroyo_server_common::tls::grpc_endpoint(controller_addr)
+                .unwrap()
+                .connect()
                 .await
                 .unwrap(),
-        );
+        ));
     }
 
     async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<(), ()>) {
@@ -0,0 +1,252 @@
+use std::hash::{Hash, Hasher};
+
+use bincode::{Decode, Encode};
+use twox_hash::XxHash64;
+
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// A HyperLogLog sketch for approximate distinct counts. Sketches are merged by taking
+/// the max of each register, so they can be used as window bins. Values are hashed with a
+/// fixed seed, as sketches are checkpointed and merged across workers.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; HLL_REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn add<T: Hash>(&mut self, value: &T) {
+        let mut hasher = XxHash64::with_seed(0);
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        for (register, other) in self.registers.iter_mut().zip(other.registers) {
+            *register = (*register).max(other);
+        }
+        self
+    }
+
+    pub fn count(&self) -> i64 {
+        let m = HLL_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|register| 2f64.powi(-(*register as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|register| **register == 0)
+            .count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // small range correction
+            (m * (m / zeros as f64).ln()).round() as i64
+        } else {
+            estimate.round() as i64
+        }
+    }
+}
+
+const TDIGEST_COMPRESSION: f64 = 100.0;
+
+/// A merging t-digest for approximate percentiles. Values are buffered as unit-weight
+/// centroids and compressed once the buffer grows past a multiple of the compression.
+/// NaNs are ignored.
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Default)]
+pub struct TDigest {
+    // (mean, weight), sorted by mean after each compression
+    centroids: Vec<(f64, f64)>,
+    count: f64,
+}
+
+impl TDigest {
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.centroids.push((value, 1.0));
+        self.count += 1.0;
+        if self.centroids.len() > 10 * TDIGEST_COMPRESSION as usize {
+            self.compress();
+        }
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        self.centroids.extend(other.centroids);
+        self.count += other.count;
+        self.compress();
+        self
+    }
+
+    fn compress(&mut self) {
+        if self.centroids.is_empty() {
+            return;
+        }
+        self.centroids
+            .sort_by(|left, right| left.0.total_cmp(&right.0));
+        let mut compressed: Vec<(f64, f64)> = Vec::new();
+        let mut weight_so_far = 0.0;
+        for (mean, weight) in self.centroids.drain(..) {
+            if let Some(last) = compressed.last_mut() {
+                let q = (weight_so_far + (last.1 + weight) / 2.0) / self.count;
+                let limit = 4.0 * self.count * q * (1.0 - q) / TDIGEST_COMPRESSION;
+                if last.1 + weight <= limit.max(1.0) {
+                    last.0 = (last.0 * last.1 + mean * weight) / (last.1 + weight);
+                    last.1 += weight;
+                    continue;
+                }
+                weight_so_far += last.1;
+            }
+            compressed.push((mean, weight));
+        }
+        self.centroids = compressed;
+    }
+
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let mut digest = self.clone();
+        digest.compress();
+        let centroids = &digest.centroids;
+        match centroids.len() {
+            0 => return None,
+            1 => return Some(centroids[0].0),
+            _ => {}
+        }
+        let target = q.clamp(0.0, 1.0) * digest.count;
+        let mut weight_so_far = 0.0;
+        for (i, (mean, weight)) in centroids.iter().enumerate() {
+            let center = weight_so_far + weight / 2.0;
+            if target < center {
+                if i == 0 {
+                    return Some(*mean);
+                }
+                let (previous_mean, previous_weight) = centroids[i - 1];
+                let previous_center = weight_so_far - previous_weight / 2.0;
+                let fraction = (target - previous_center) / (center - previous_center);
+                return Some(previous_mean + fraction * (mean - previous_mean));
+            }
+            weight_so_far += weight;
+        }
+        centroids.last().map(|(mean, _)| *mean)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperloglog() {
+        assert_eq!(HyperLogLog::default().count(), 0);
+
+        let mut single = HyperLogLog::default();
+        for _ in 0..100 {
+            single.add(&"a");
+        }
+        assert_eq!(single.count(), 1);
+
+        let mut left = HyperLogLog::default();
+        let mut right = HyperLogLog::default();
+        for i in 0..100_000u64 {
+            left.add(&i);
+            right.add(&(i + 50_000));
+        }
+
+        // the standard error with 4096 registers is about 1.6%
+        let within = |count: i64, expected: f64| (count as f64 - expected).abs() / expected < 0.05;
+        assert!(within(left.count(), 100_000.0), "{}", left.count());
+
+        // merging counts the union
+        let merged = left.clone().merge(right);
+        assert!(within(merged.count(), 150_000.0), "{}", merged.count());
+        assert_eq!(left.clone().merge(HyperLogLog::default()), left);
+    }
+
+    #[test]
+    fn test_hyperloglog_hash_is_stable() {
+        // sketches are stored in checkpoints, so the hash can't change between runs
+        let mut hll = HyperLogLog::default();
+        hll.add(&42u64);
+        let mut expected = HyperLogLog::default();
+        let mut hasher = XxHash64::with_seed(0);
+        42u64.hash(&mut hasher);
+        let index = (hasher.finish() >> (64 - HLL_PRECISION)) as usize;
+        assert!(hll.registers[index] > 0);
+        expected.registers[index] = hll.registers[index];
+        assert_eq!(hll, expected);
+    }
+
+    #[test]
+    fn test_tdigest() {
+        assert_eq!(TDigest::default().quantile(0.5), None);
+
+        let mut single = TDigest::default();
+        single.add(7.0);
+        assert_eq!(single.quantile(0.0), Some(7.0));
+        assert_eq!(single.quantile(0.99), Some(7.0));
+
+        let mut left = TDigest::default();
+        let mut right = TDigest::default();
+        for i in 0..10_000 {
+            left.add(i as f64);
+            right.add((i + 10_000) as f64);
+        }
+
+        let within = |value: Option<f64>, expected: f64| (value.unwrap() - expected).abs() < 100.0;
+        assert!(
+            within(left.quantile(0.5), 5_000.0),
+            "{:?}",
+            left.quantile(0.5)
+        );
+        assert!(
+            within(left.quantile(0.99), 9_900.0),
+            "{:?}",
+            left.quantile(0.99)
+        );
+
+        let merged = left.merge(right);
+        assert!(
+            within(merged.quantile(0.5), 10_000.0),
+            "{:?}",
+            merged.quantile(0.5)
+        );
+        assert!(
+            within(merged.quantile(0.9), 18_000.0),
+            "{:?}",
+            merged.quantile(0.9)
+        );
+        assert!(
+            within(merged.quantile(1.0), 20_000.0),
+            "{:?}",
+            merged.quantile(1.0)
+        );
+    }
+
+    #[test]
+    fn test_tdigest_ignores_nan() {
+        let mut digest = TDigest::default();
+        digest.add(f64::NAN);
+        assert_eq!(digest.quantile(0.5), None);
+
+        for i in 0..2_000 {
+            digest.add(i as f64);
+            digest.add(f64::NAN);
+        }
+        let median = digest.quantile(0.5).unwrap();
+        assert!((median - 1_000.0).abs() < 20.0, "{}", median);
+    }
+}
@@ -13,7 +13,7 @@ use serde::de::DeserializeOwned;
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::num::NonZeroU32;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::select;
 use tracing::{debug, error, info, warn};
 
@@ -48,6 +48,7 @@ where
     serialization_mode: SerializationMode,
     client_configs: HashMap<String, String>,
     messages_per_second: NonZeroU32,
+    partition_watermarks: Option<(Duration, Option<Duration>)>,
     _t: PhantomData<T>,
 }
 
@@ -57,8 +58,118 @@ pub struct KafkaState {
     offset: i64,
 }
 
+// kept apart from KafkaState so that checkpoints from before partition watermarks still restore
+#[derive(Copy, Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
+pub struct PartitionEventTime {
+    partition: i32,
+    max_event_time: SystemTime,
+}
+
+#[derive(Debug)]
+struct PartitionTime {
+    max_event_time: Option<SystemTime>,
+    last_record: Instant,
+}
+
+/// Tracks the event times of each partition read by a subtask, so that its watermark is held
+/// back by the partition that lags the furthest behind rather than by the order in which the
+/// partitions' records happen to be interleaved.
+#[derive(Debug)]
+pub(crate) struct PartitionWatermarkTracker {
+    max_lateness: Duration,
+    idle_time: Option<Duration>,
+    partitions: HashMap<i32, PartitionTime>,
+    last_emitted: Option<Watermark>,
+}
+
+impl PartitionWatermarkTracker {
+    pub(crate) fn new(
+        max_lateness: Duration,
+        idle_time: Option<Duration>,
+        partitions: impl IntoIterator<Item = (i32, Option<SystemTime>)>,
+        now: Instant,
+    ) -> Self {
+        Self {
+            max_lateness,
+            idle_time,
+            partitions: partitions
+                .into_iter()
+                .map(|(partition, max_event_time)| {
+                    (
+                        partition,
+                        PartitionTime {
+                            max_event_time,
+                            last_record: now,
+                        },
+                    )
+                })
+                .collect(),
+            last_emitted: None,
+        }
+    }
+
+    pub(crate) fn observe(&mut self, partition: i32, event_time: SystemTime, now: Instant) {
+        // partitions we weren't assigned at startup are tracked from their first record
+        let time = self.partitions.entry(partition).or_insert(PartitionTime {
+            max_event_time: None,
+            last_record: now,
+        });
+        time.max_event_time = Some(
+            time.max_event_time
+                .map_or(event_time, |t| t.max(event_time)),
+        );
+        time.last_record = now;
+    }
+
+    pub(crate) fn max_event_time(&self, partition: i32) -> Option<SystemTime> {
+        self.partitions
+            .get(&partition)
+            .and_then(|time| time.max_event_time)
+    }
+
+    // The minimum watermark of the partitions that aren't idle, or Idle if they all are.
+    fn watermark(&self, now: Instant) -> Option<Watermark> {
+        let mut active = self
+            .partitions
+            .values()
+            .filter(|time| match self.idle_time {
+                Some(idle_time) => now.duration_since(time.last_record) < idle_time,
+                None => true,
+            })
+            .peekable();
+        if active.peek().is_none() {
+            return Some(Watermark::Idle);
+        }
+        active
+            .map(|time| time.max_event_time)
+            .reduce(|current, next| current.zip(next).map(|(current, next)| current.min(next)))
+            .flatten()
+            .map(|max_event_time| Watermark::EventTime(max_event_time - self.max_lateness))
+    }
+
+    /// The watermark to send downstream, if it has changed since the last one. Event-time
+    /// watermarks only move forward.
+    pub(crate) fn next_watermark(&mut self, now: Instant) -> Option<Watermark> {
+        let watermark = self.watermark(now)?;
+        let changed = match (self.last_emitted, watermark) {
+            (Some(Watermark::Idle), Watermark::Idle) => false,
+            (_, Watermark::Idle) => true,
+            (None | Some(Watermark::Idle), Watermark::EventTime(_)) => true,
+            (Some(Watermark::EventTime(last)), Watermark::EventTime(next)) => next > last,
+        };
+        if !changed {
+            return None;
+        }
+        self.last_emitted = Some(watermark);
+        Some(watermark)
+    }
+}
+
 pub fn tables() -> Vec<TableDescriptor> {
-    vec![arroyo_state::global_table("k", "kafka source state")]
+    vec![
+        arroyo_state::global_table("k", "kafka source state"),
+        arroyo_state::global_table("e", "kafka partition event times"),
+    ]
 }
 
 #[source_fn(out_k = (), out_t = T)]
@@ -84,10 +195,23 @@ where
                 .map(|(key, value)| (key.to_string(), value.to_string()))
                 .collect(),
             messages_per_second: NonZeroU32::new(messages_per_second).unwrap(),
+            partition_watermarks: None,
             _t: PhantomData,
         }
     }
 
+    /// Has the source emit watermarks that trail the minimum of the latest message timestamps
+    /// of each of its partitions by `max_lateness`. Partitions without messages for `idle_time`
+    /// don't hold the watermark back.
+    pub fn with_partition_watermarks(
+        mut self,
+        max_lateness: Duration,
+        idle_time: Option<Duration>,
+    ) -> Self {
+        self.partition_watermarks = Some((max_lateness, idle_time));
+        self
+    }
+
     fn name(&self) -> String {
         format!("kafka-{}", self.topic)
     }
@@ -96,7 +220,10 @@ where
         tables()
     }
 
-    async fn get_consumer(&mut self, ctx: &mut Context<(), T>) -> Result<StreamConsumer, ()> {
+    async fn get_consumer(
+        &mut self,
+        ctx: &mut Context<(), T>,
+    ) -> Result<(StreamConsumer, Option<PartitionWatermarkTracker>), ()> {
         info!("Creating kafka consumer for {}", self.bootstrap_servers);
         let mut client_config = ClientConfig::new();
 
@@ -125,6 +252,14 @@ where
         let has_state = !state.is_empty();
 
         let state: HashMap<i32, KafkaState> = state.iter().map(|s| (s.partition, **s)).collect();
+
+        let mut event_times: GlobalKeyedState<i32, PartitionEventTime, _> =
+            ctx.state.get_global_keyed_state('e').await;
+        let event_times: HashMap<i32, SystemTime> = event_times
+            .get_all()
+            .iter()
+            .map(|t| (t.partition, t.max_event_time))
+            .collect();
         let metadata = consumer
             .fetch_metadata(Some(&self.topic), Duration::from_secs(30))
             .expect("failed to fetch kafka metadata");
@@ -160,26 +295,44 @@ where
 
         consumer.assign(&topic_partitions).unwrap();
 
-        Ok(consumer)
+        // partitions can move between subtasks when the parallelism changes, so their event
+        // times are restored along with their offsets
+        let watermarks = self.partition_watermarks.map(|(max_lateness, idle_time)| {
+            PartitionWatermarkTracker::new(
+                max_lateness,
+                idle_time,
+                our_partitions
+                    .keys()
+                    .map(|(_, partition)| (*partition, event_times.get(partition).copied())),
+                Instant::now(),
+            )
+        });
+
+        Ok((consumer, watermarks))
     }
 
     async fn run(&mut self, ctx: &mut Context<(), T>) -> SourceFinishType {
-        let consumer = self.get_consumer(ctx).await.unwrap();
+        let (consumer, mut watermarks) = self.get_consumer(ctx).await.unwrap();
 
         let rate_limiter = RateLimiter::direct(Quota::per_second(self.messages_per_second));
         let mut offsets = HashMap::new();
+        let mut watermark_interval = tokio::time::interval(Duration::from_secs(1));
         loop {
             select! {
                 message = consumer.recv() => {
                     match message {
                         Ok(msg) => {
                             if let Some(v) = msg.payload() {
+                                let timestamp = from_millis(msg.timestamp().to_millis().unwrap() as u64);
                                 ctx.collector.collect(Record {
-                                    timestamp: from_millis(msg.timestamp().to_millis().unwrap() as u64),
+                                    timestamp,
                                     key: None,
                                     value: self.serialization_mode.deserialize_slice(&v).unwrap(),
                                 }).await;
                                 offsets.insert(msg.partition(), msg.offset());
+                                if let Some(watermarks) = &mut watermarks {
+                                    watermarks.observe(msg.partition(), timestamp, Instant::now());
+                                }
                                 rate_limiter.until_ready().await;
                             }
                         },
@@ -188,6 +341,12 @@ where
                         }
                     }
                 }
+                _ = watermark_interval.tick(), if watermarks.is_some() => {
+                    let watermark = watermarks.as_mut().unwrap().next_watermark(Instant::now());
+                    if let Some(watermark) = watermark {
+                        ctx.broadcast(Message::Watermark(watermark)).await;
+                    }
+                }
                 control_message = ctx.control_rx.recv() => {
                     match control_message {
                         Some(ControlMessage::Checkpoint(c)) => {
@@ -204,6 +363,18 @@ where
                                     &self.topic, *partition, Offset::Offset(*offset)).unwrap();
                             }
 
+                            if let Some(watermarks) = &watermarks {
+                                let mut event_times = ctx.state.get_global_keyed_state('e').await;
+                                for partition in offsets.keys() {
+                                    if let Some(max_event_time) = watermarks.max_event_time(*partition) {
+                                        event_times.insert(*partition, PartitionEventTime {
+                                            partition: *partition,
+                                            max_event_time,
+                                        }).await;
+                                    }
+                                }
+                            }
+
                             if let Err(e) = consumer.commit(&topic_partitions, CommitMode::Async) {
                                 // This is just used for progress tracking for metrics, so it's not a fatal error if it
                                 // fails. The actual offset is stored in state.
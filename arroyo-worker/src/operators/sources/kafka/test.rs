@@ -141,7 +141,7 @@ impl KafkaSourceWithReads {
     async fn assert_next_message_record_value(&mut self, expected_value: u64) {
         match self.data_recv.recv().await {
             Some(item) => {
-                let msg: Message<(), TestData> = item.into();
+                let msg: Message<(), TestData> = item.try_into().unwrap();
                 if let Message::Record(record) = msg {
                     assert_eq!(expected_value, record.value.i,);
                 } else {
@@ -156,7 +156,7 @@ impl KafkaSourceWithReads {
     async fn assert_next_message_checkpoint(&mut self, expected_epoch: u32) {
         match self.data_recv.recv().await {
             Some(item) => {
-                let msg: Message<(), TestData> = item.into();
+                let msg: Message<(), TestData> = item.try_into().unwrap();
                 if let Message::Barrier(barrier) = msg {
                     assert_eq!(expected_epoch, barrier.epoch);
                 } else {
@@ -211,6 +211,7 @@ async fn test_kafka() {
         min_epoch: 0,
         timestamp: (SystemTime::now()),
         then_stop: false,
+        unaligned: false,
     });
     reader.to_control_tx.send(barrier).await.unwrap();
     let checkpoint_completed = reader.assert_control_checkpoint(1).await;
@@ -262,3 +263,60 @@ async fn test_kafka() {
     producer.send_data(TestData { i: 21 });
     reader.assert_next_message_record_value(21).await;
 }
+
+#[test]
+fn test_partition_watermarks() {
+    use arroyo_types::Watermark;
+    use std::time::Instant;
+
+    let start = Instant::now();
+    let base = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+    let mut tracker = kafka::PartitionWatermarkTracker::new(
+        Duration::from_secs(5),
+        Some(Duration::from_secs(60)),
+        [(0, Some(base)), (1, None)],
+        start,
+    );
+
+    // a partition that hasn't seen a record yet holds back the watermark
+    assert_eq!(tracker.next_watermark(start), None);
+
+    tracker.observe(1, base + Duration::from_secs(20), start);
+    assert_eq!(
+        tracker.next_watermark(start),
+        Some(Watermark::EventTime(base - Duration::from_secs(5)))
+    );
+    assert_eq!(tracker.next_watermark(start), None);
+
+    // the lagging partition determines the watermark
+    tracker.observe(0, base + Duration::from_secs(10), start);
+    assert_eq!(
+        tracker.next_watermark(start),
+        Some(Watermark::EventTime(base + Duration::from_secs(5)))
+    );
+
+    // once partition 0 goes idle only partition 1 counts
+    let later = start + Duration::from_secs(61);
+    tracker.observe(
+        1,
+        base + Duration::from_secs(30),
+        later - Duration::from_secs(30),
+    );
+    assert_eq!(
+        tracker.next_watermark(later),
+        Some(Watermark::EventTime(base + Duration::from_secs(25)))
+    );
+
+    // when partition 0 comes back the watermark doesn't move backwards
+    tracker.observe(0, base + Duration::from_secs(12), later);
+    assert_eq!(tracker.next_watermark(later), None);
+    assert_eq!(
+        tracker.max_event_time(0),
+        Some(base + Duration::from_secs(12))
+    );
+
+    // and with every partition idle the source is idle
+    let idle = later + Duration::from_secs(61);
+    assert_eq!(tracker.next_watermark(idle), Some(Watermark::Idle));
+    assert_eq!(tracker.next_watermark(idle), None);
+}
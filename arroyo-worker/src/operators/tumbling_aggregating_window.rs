@@ -10,6 +10,9 @@ use tracing::debug;
 #[derive(StreamNode)]
 pub struct TumblingAggregatingWindowFunc<K: Key, T: Data, BinA: Data, OutT: Data> {
     width: Duration,
+    allowed_lateness: Duration,
+    early_fire_interval: Option<Duration>,
+    early_fire_count: Option<u64>,
     aggregator: fn(&BinA) -> OutT,
     bin_merger: fn(&T, Option<&BinA>) -> BinA,
     state: TumblingWindowState,
@@ -23,7 +26,7 @@ enum TumblingWindowState {
     BufferedData { earliest_bin_time: SystemTime },
 }
 
-#[process_fn(in_k = K, in_t = T, out_k = K, out_t = OutT)]
+#[process_fn(in_k = K, in_t = T, out_k = K, out_t = OutT, timer_t = SystemTime)]
 impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T, BinA, OutT> {
     fn name(&self) -> String {
         "KeyWindow".to_string()
@@ -37,6 +40,9 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
     ) -> Self {
         TumblingAggregatingWindowFunc {
             width,
+            allowed_lateness: Duration::ZERO,
+            early_fire_interval: None,
+            early_fire_count: None,
             aggregator,
             bin_merger,
             state: TumblingWindowState::NoData,
@@ -44,6 +50,30 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
         }
     }
 
+    /// Keeps the bins of fired windows for `allowed_lateness`, re-firing a window for each record
+    /// that arrives for it in that time.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
+
+    /// Emits the partial result of each open window every `interval` of processing time, starting
+    /// `interval` after the window receives its first record for a key.
+    pub fn with_early_fire_interval(mut self, interval: Duration) -> Self {
+        self.early_fire_interval = Some(interval);
+        self
+    }
+
+    /// Emits the partial result of an open window for a key after every `count` records for it.
+    pub fn with_early_fire_count(mut self, count: u64) -> Self {
+        self.early_fire_count = Some(count);
+        self
+    }
+
+    fn uses_processing_timers(&self) -> bool {
+        self.early_fire_interval.is_some()
+    }
+
     fn bin_start(&self, timestamp: SystemTime) -> SystemTime {
         if self.width == Duration::ZERO {
             return timestamp;
@@ -55,22 +85,58 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
         result
     }
 
+    // The earliest bin that can still take records, once the watermark has reached `watermark`.
+    fn lateness_bin(&self, watermark: SystemTime) -> SystemTime {
+        self.bin_start(
+            watermark
+                .checked_sub(self.allowed_lateness)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        )
+    }
+
     fn tables(&self) -> Vec<TableDescriptor> {
-        vec![TableDescriptor {
-            name: "a".to_string(),
-            description: "window state".to_string(),
-            table_type: TableType::TimeKeyMap as i32,
-            delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
-            write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
-            retention_micros: self.width.as_micros() as u64,
-        }]
+        vec![
+            TableDescriptor {
+                name: "a".to_string(),
+                description: "window state".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.width.as_micros() as u64,
+            },
+            TableDescriptor {
+                name: "l".to_string(),
+                description: "fired window state".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: (self.width + self.allowed_lateness).as_micros() as u64,
+            },
+            TableDescriptor {
+                name: "c".to_string(),
+                description: "records per window, for count triggers".to_string(),
+                table_type: TableType::TimeKeyMap as i32,
+                delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
+                write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
+                retention_micros: self.width.as_micros() as u64,
+            },
+        ]
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
+    async fn process_element(
+        &mut self,
+        record: &Record<K, T>,
+        ctx: &mut Context<K, OutT, SystemTime>,
+    ) {
         let bin_start = self.bin_start(record.timestamp);
 
         if let Some(watermark) = ctx.watermark() {
             if bin_start < self.bin_start(watermark) {
+                if bin_start < self.lateness_bin(watermark) {
+                    ctx.collect_late(record.clone()).await;
+                } else {
+                    self.refire(bin_start, record, ctx).await;
+                }
                 return;
             }
         }
@@ -84,15 +150,93 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
                 }
             }
         };
+        let mut key = record.key.clone().unwrap();
+        let fire_early = match self.early_fire_count {
+            Some(early_fire_count) => {
+                let mut count_map: TimeKeyMap<K, u64, _> =
+                    ctx.state.get_time_key_map('c', ctx.watermark()).await;
+                let count = count_map.get(bin_start, &mut key).copied().unwrap_or(0) + 1;
+                count_map.insert(bin_start, key.clone(), count);
+                count % early_fire_count == 0
+            }
+            None => false,
+        };
         let mut aggregating_map: TimeKeyMap<K, BinA, _> =
             ctx.state.get_time_key_map('a', ctx.watermark()).await;
-        let mut key = record.key.clone().unwrap();
         let bin_aggregate = aggregating_map.get(bin_start, &mut key);
+        let first_record = bin_aggregate.is_none();
         let new_value = (self.bin_merger)(&record.value, bin_aggregate);
-        aggregating_map.insert(bin_start, key, new_value);
+        let early_result = fire_early.then(|| (self.aggregator)(&new_value));
+        aggregating_map.insert(bin_start, key.clone(), new_value);
+
+        if let Some(value) = early_result {
+            ctx.collect(Record {
+                timestamp: self.window_end(bin_start),
+                key: Some(key.clone()),
+                value,
+            })
+            .await;
+        }
+        if let Some(interval) = self.early_fire_interval {
+            if first_record {
+                ctx.schedule_processing_timer(&mut key, SystemTime::now() + interval, bin_start)
+                    .await;
+            }
+        }
+    }
+
+    // Emits the partial result of the window starting at `bin_start` for an interval trigger,
+    // and schedules the next one.
+    async fn handle_timer(
+        &mut self,
+        mut key: K,
+        bin_start: SystemTime,
+        ctx: &mut Context<K, OutT, SystemTime>,
+    ) {
+        let Some(interval) = self.early_fire_interval else {
+            return;
+        };
+        let aggregating_map: TimeKeyMap<K, BinA, _> =
+            ctx.state.get_time_key_map('a', ctx.watermark()).await;
+        // the window has fired since the timer was scheduled
+        let Some(value) = aggregating_map
+            .get(bin_start, &mut key)
+            .map(|bin| (self.aggregator)(bin))
+        else {
+            return;
+        };
+        ctx.collect(Record {
+            timestamp: self.window_end(bin_start),
+            key: Some(key.clone()),
+            value,
+        })
+        .await;
+        ctx.schedule_processing_timer(&mut key, SystemTime::now() + interval, bin_start)
+            .await;
     }
 
-    async fn on_start(&mut self, ctx: &mut Context<K, OutT>) {
+    // Adds a late record to a window that has already fired, and emits the window's new result.
+    async fn refire(
+        &mut self,
+        bin_start: SystemTime,
+        record: &Record<K, T>,
+        ctx: &mut Context<K, OutT, SystemTime>,
+    ) {
+        let mut fired_map: TimeKeyMap<K, BinA, _> =
+            ctx.state.get_time_key_map('l', ctx.watermark()).await;
+        let mut key = record.key.clone().unwrap();
+        let bin_aggregate = (self.bin_merger)(&record.value, fired_map.get(bin_start, &mut key));
+        let value = (self.aggregator)(&bin_aggregate);
+        fired_map.insert(bin_start, key.clone(), bin_aggregate);
+        ctx.collect(Record {
+            timestamp: self.window_end(bin_start),
+            key: Some(key),
+            value,
+        })
+        .await;
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<K, OutT, SystemTime>) {
         let map = ctx
             .state
             .get_time_key_map::<K, BinA>('a', ctx.watermark())
@@ -122,7 +266,7 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
         }
     }
 
-    async fn advance(&mut self, ctx: &mut Context<K, OutT>) {
+    async fn advance(&mut self, ctx: &mut Context<K, OutT, SystemTime>) {
         debug!("advancing with state {:?}", self.state);
         let bin_start = match self.state {
             TumblingWindowState::BufferedData { earliest_bin_time } => {
@@ -134,12 +278,14 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
             ctx.state.get_time_key_map('a', ctx.watermark()).await;
         let window_end = self.window_end(bin_start);
         let mut records = vec![];
+        let mut bins = vec![];
         for (key, value) in aggregating_map.evict_for_timestamp(bin_start) {
             records.push(Record {
                 timestamp: window_end,
                 key: Some(key.clone()),
                 value: (self.aggregator)(&value),
             });
+            bins.push((key, value));
         }
         self.state = match aggregating_map.get_min_time() {
             Some(min_time) => TumblingWindowState::BufferedData {
@@ -147,6 +293,19 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
             },
             None => TumblingWindowState::NoData,
         };
+        if self.early_fire_count.is_some() {
+            let mut count_map: TimeKeyMap<K, u64, _> =
+                ctx.state.get_time_key_map('c', ctx.watermark()).await;
+            count_map.evict_for_timestamp(bin_start);
+        }
+        if !self.allowed_lateness.is_zero() {
+            // keep the bins around for records that arrive within the allowed lateness
+            let mut fired_map: TimeKeyMap<K, BinA, _> =
+                ctx.state.get_time_key_map('l', ctx.watermark()).await;
+            for (key, value) in bins {
+                fired_map.insert(bin_start, key, value);
+            }
+        }
 
         for record in records {
             debug!("emitting {:?}", record);
@@ -157,7 +316,7 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
     async fn handle_watermark(
         &mut self,
         _watermark: std::time::SystemTime,
-        ctx: &mut Context<K, OutT>,
+        ctx: &mut Context<K, OutT, SystemTime>,
     ) {
         let Some(watermark) = ctx.watermark() else {return};
         debug!(
@@ -168,17 +327,36 @@ impl<K: Key, T: Data, BinA: Data, OutT: Data> TumblingAggregatingWindowFunc<K, T
         while self.should_advance(watermark) {
             self.advance(ctx).await;
         }
-        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
-            .await;
+        if !self.allowed_lateness.is_zero() {
+            let mut fired_map: TimeKeyMap<K, BinA, _> =
+                ctx.state.get_time_key_map('l', ctx.watermark()).await;
+            // the bins that late records can no longer reach
+            if let Some(expired_bin) = self
+                .lateness_bin(watermark)
+                .checked_sub(Duration::from_nanos(1))
+            {
+                fired_map.evict_all_before_watermark(expired_bin);
+            }
+        }
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
     }
 
     async fn handle_checkpoint(
         &mut self,
         _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
-        ctx: &mut Context<K, OutT>,
+        ctx: &mut Context<K, OutT, SystemTime>,
     ) {
         let mut aggregating_map: TimeKeyMap<K, BinA, _> =
             ctx.state.get_time_key_map('a', ctx.watermark()).await;
         aggregating_map.flush().await;
+        let mut fired_map: TimeKeyMap<K, BinA, _> =
+            ctx.state.get_time_key_map('l', ctx.watermark()).await;
+        fired_map.flush().await;
+        let mut count_map: TimeKeyMap<K, u64, _> =
+            ctx.state.get_time_key_map('c', ctx.watermark()).await;
+        count_map.flush().await;
     }
 }
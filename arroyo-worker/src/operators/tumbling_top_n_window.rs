@@ -211,8 +211,10 @@ impl<K: Key, T: Data, SK: Ord + Send + 'static, OutT: Data> TumblingTopNWindowFu
         while self.should_advance(watermark) {
             self.advance(ctx).await;
         }
-        ctx.broadcast(arroyo_types::Message::Watermark(watermark))
-            .await;
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
     }
 
     async fn handle_checkpoint(
@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::engine::{Context, StreamNode};
+use arroyo_macro::process_fn;
+use arroyo_rpc::grpc::TableDescriptor;
+use arroyo_state::hash_key;
+use arroyo_state::tables::GlobalKeyedState;
+use arroyo_types::*;
+use tracing::debug;
+
+/// Aggregates records by key without a window. The aggregate for a key is emitted, keyed,
+/// every time it changes, or if there is an `emit_interval`, once per interval for the keys
+/// that changed. Keys that haven't been updated for `ttl` of event time are dropped.
+#[derive(StreamNode)]
+pub struct UpdatingAggregateOperator<K: Key, T: Data, BinA: Data, OutT: Data> {
+    ttl: Duration,
+    emit_interval: Option<Duration>,
+    aggregator: fn(&BinA) -> OutT,
+    bin_merger: fn(&T, Option<&BinA>) -> BinA,
+    // the aggregate for each key, along with the time it was last updated
+    aggregates: HashMap<K, (BinA, SystemTime)>,
+    // the keys last updated at each time, for expiring them
+    last_updated: BTreeMap<SystemTime, HashSet<K>>,
+    // keys whose updates haven't been emitted yet, when emitting on an interval
+    changed: HashSet<K>,
+    last_emit: Instant,
+    _t: PhantomData<T>,
+}
+
+#[process_fn(in_k = K, in_t = T, out_k = K, out_t = OutT)]
+impl<K: Key, T: Data, BinA: Data, OutT: Data> UpdatingAggregateOperator<K, T, BinA, OutT> {
+    fn name(&self) -> String {
+        "UpdatingAggregate".to_string()
+    }
+
+    pub fn new(
+        ttl: Duration,
+        emit_interval: Option<Duration>,
+        aggregator: fn(&BinA) -> OutT,
+        bin_merger: fn(&T, Option<&BinA>) -> BinA,
+    ) -> Self {
+        UpdatingAggregateOperator {
+            ttl,
+            emit_interval,
+            aggregator,
+            bin_merger,
+            aggregates: HashMap::new(),
+            last_updated: BTreeMap::new(),
+            changed: HashSet::new(),
+            last_emit: Instant::now(),
+            _t: PhantomData,
+        }
+    }
+
+    fn tables(&self) -> Vec<TableDescriptor> {
+        vec![arroyo_state::global_table("a", "aggregate state")]
+    }
+
+    async fn on_start(&mut self, ctx: &mut Context<K, OutT>) {
+        let task_info = ctx.task_info.clone();
+        let mut state: GlobalKeyedState<usize, Vec<(K, BinA, SystemTime)>, _> =
+            ctx.state.get_global_keyed_state('a').await;
+        // if the parallelism is unchanged each subtask takes back its own aggregates, otherwise
+        // they are redistributed by key
+        let restored: Vec<_> = if state.get_all().len() == task_info.parallelism {
+            state
+                .get(&task_info.task_index)
+                .cloned()
+                .unwrap_or_default()
+        } else {
+            state
+                .get_all()
+                .into_iter()
+                .flatten()
+                .filter(|(key, _, _)| task_info.key_range.contains(&hash_key(key)))
+                .cloned()
+                .collect()
+        };
+
+        for (key, aggregate, updated) in restored {
+            self.last_updated
+                .entry(updated)
+                .or_default()
+                .insert(key.clone());
+            self.aggregates.insert(key, (aggregate, updated));
+        }
+        if let Some(expiration) = ctx
+            .watermark()
+            .and_then(|watermark| watermark.checked_sub(self.ttl))
+        {
+            self.expire_aggregates(expiration);
+        }
+    }
+
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
+        let key = record.key.clone().unwrap();
+
+        let (current, previous_time) = match self.aggregates.remove(&key) {
+            Some((current, previous_time)) => (Some(current), Some(previous_time)),
+            None => (None, None),
+        };
+        let aggregate = (self.bin_merger)(&record.value, current.as_ref());
+        let update_time = previous_time.map_or(record.timestamp, |previous_time| {
+            previous_time.max(record.timestamp)
+        });
+        if let Some(previous_time) = previous_time {
+            if let Some(keys) = self.last_updated.get_mut(&previous_time) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    self.last_updated.remove(&previous_time);
+                }
+            }
+        }
+        self.last_updated
+            .entry(update_time)
+            .or_default()
+            .insert(key.clone());
+
+        let value = self
+            .emit_interval
+            .is_none()
+            .then(|| (self.aggregator)(&aggregate));
+        self.aggregates
+            .insert(key.clone(), (aggregate, update_time));
+
+        match value {
+            Some(value) => {
+                ctx.collect(Record {
+                    timestamp: record.timestamp,
+                    key: Some(key),
+                    value,
+                })
+                .await;
+            }
+            None => {
+                self.changed.insert(key);
+                self.emit_if_due(ctx).await;
+            }
+        }
+    }
+
+    async fn emit_if_due(&mut self, ctx: &mut Context<K, OutT>) {
+        let Some(emit_interval) = self.emit_interval else {
+            return;
+        };
+        if self.last_emit.elapsed() < emit_interval {
+            return;
+        }
+        self.last_emit = Instant::now();
+
+        let mut records = vec![];
+        for key in self.changed.drain() {
+            if let Some((aggregate, update_time)) = self.aggregates.get(&key) {
+                records.push(Record {
+                    timestamp: *update_time,
+                    key: Some(key),
+                    value: (self.aggregator)(aggregate),
+                });
+            }
+        }
+        for record in records {
+            ctx.collect(record).await;
+        }
+    }
+
+    // Drops the aggregates of keys that were last updated at or before `expiration`.
+    fn expire_aggregates(&mut self, expiration: SystemTime) {
+        while let Some(entry) = self.last_updated.first_entry() {
+            if *entry.key() > expiration {
+                break;
+            }
+            for key in entry.remove() {
+                debug!("expiring aggregate for {:?}", key);
+                self.aggregates.remove(&key);
+                self.changed.remove(&key);
+            }
+        }
+    }
+
+    async fn handle_watermark(&mut self, _watermark: SystemTime, ctx: &mut Context<K, OutT>) {
+        let Some(watermark) = ctx.watermark() else {
+            return;
+        };
+        self.emit_if_due(ctx).await;
+
+        if let Some(expiration) = watermark.checked_sub(self.ttl) {
+            self.expire_aggregates(expiration);
+        }
+        ctx.broadcast(arroyo_types::Message::Watermark(
+            arroyo_types::Watermark::EventTime(watermark),
+        ))
+        .await;
+    }
+
+    // Each checkpoint holds a single copy of every live aggregate, as global tables only keep
+    // the files of the latest checkpoint.
+    async fn handle_checkpoint(
+        &mut self,
+        _checkpoint_barrier: &arroyo_types::CheckpointBarrier,
+        ctx: &mut Context<K, OutT>,
+    ) {
+        let aggregates: Vec<_> = self
+            .aggregates
+            .iter()
+            .map(|(key, (aggregate, updated))| (key.clone(), aggregate.clone(), *updated))
+            .collect();
+        let task_index = ctx.task_info.task_index;
+        let mut state: GlobalKeyedState<usize, Vec<(K, BinA, SystemTime)>, _> =
+            ctx.state.get_global_keyed_state('a').await;
+        state.insert(task_index, aggregates).await;
+    }
+}
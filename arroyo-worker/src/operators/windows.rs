@@ -51,6 +51,7 @@ pub enum WindowOperation<T: Data, OutT: Data> {
 pub struct KeyedWindowFunc<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> {
     assigner: W,
     operation: WindowOperation<T, OutT>,
+    allowed_lateness: Duration,
     salt: u64,
     _phantom: PhantomData<(K, T, OutT)>,
 }
@@ -64,6 +65,7 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
         KeyedWindowFunc {
             assigner: TumblingWindowAssigner { size },
             operation,
+            allowed_lateness: Duration::ZERO,
             salt: SmallRng::from_entropy().next_u64(),
             _phantom: PhantomData,
         }
@@ -77,6 +79,7 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
         KeyedWindowFunc {
             assigner: SlidingWindowAssigner { size, slide },
             operation,
+            allowed_lateness: Duration::ZERO,
             salt: SmallRng::from_entropy().next_u64(),
             _phantom: PhantomData,
         }
@@ -87,11 +90,19 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
         KeyedWindowFunc {
             assigner: InstantWindowAssigner {},
             operation,
+            allowed_lateness: Duration::ZERO,
             salt: SmallRng::from_entropy().next_u64(),
             _phantom: PhantomData,
         }
     }
 
+    /// Keeps each window's records for `allowed_lateness` after it fires, re-firing it with the
+    /// updated result for every record that arrives in that time.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
+
     fn name(&self) -> String {
         "KeyWindow".to_string()
     }
@@ -103,43 +114,88 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
             table_type: TableType::KeyTimeMultiMap as i32,
             delete_behavior: TableDeleteBehavior::NoReadsBeforeWatermark as i32,
             write_behavior: TableWriteBehavior::NoWritesBeforeWatermark as i32,
-            retention_micros: self.assigner.safe_retention_duration().unwrap().as_micros() as u64,
+            retention_micros: (self.assigner.safe_retention_duration().unwrap()
+                + self.allowed_lateness)
+                .as_micros() as u64,
         }]
     }
 
-    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT>) {
+    async fn process_element(&mut self, record: &Record<K, T>, ctx: &mut Context<K, OutT, Window>) {
         let windows = self.assigner.windows(record.timestamp);
         let watermark = ctx.watermark().unwrap_or(SystemTime::UNIX_EPOCH);
         let mut has_window = false;
+        let mut fired_windows = vec![];
         let mut key = record.key.clone().unwrap();
         for w in windows {
             if w.end_time > watermark {
                 has_window = true;
 
                 ctx.schedule_timer(&mut key, w.end_time, w).await;
+            } else if w.end_time + self.allowed_lateness > watermark {
+                has_window = true;
+                fired_windows.push(w);
             }
         }
 
-        if has_window {
-            let key = record.key.as_ref().unwrap().clone();
-            let value = record.value.clone();
-            self.salt = self.salt.wrapping_add(1);
-            ctx.state
-                .get_key_time_multi_map('w')
-                .await
-                .insert(record.timestamp, key, value)
-                .await;
+        if !has_window {
+            ctx.collect_late(record.clone()).await;
+            return;
+        }
+
+        let value = record.value.clone();
+        self.salt = self.salt.wrapping_add(1);
+        ctx.state
+            .get_key_time_multi_map('w')
+            .await
+            .insert(record.timestamp, key.clone(), value)
+            .await;
+
+        // windows that have already fired are fired again with the late record
+        for w in fired_windows {
+            self.emit_window(&mut key, w, ctx).await;
         }
     }
 
-    async fn handle_timer(&mut self, mut key: K, window: Window, ctx: &mut Context<K, OutT>) {
+    async fn handle_timer(
+        &mut self,
+        mut key: K,
+        window: Window,
+        ctx: &mut Context<K, OutT, Window>,
+    ) {
+        self.emit_window(&mut key, window, ctx).await;
+
+        // clear everything before our start time (we're guaranteed that timers execute in order,
+        // so with fixed-width windows there won't be any earlier data); with allowed lateness the
+        // data is kept until the earliest window that can still fire again
+        let next = self.assigner.next(window);
+        let clear_before = if self.allowed_lateness.is_zero() {
+            next.start_time
+        } else {
+            window
+                .start_time
+                .checked_sub(self.allowed_lateness)
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        };
+        let mut state: KeyTimeMultiMap<K, T, _> = ctx.state.get_key_time_multi_map('w').await;
+
+        state
+            .clear_time_range(&mut key, SystemTime::UNIX_EPOCH, clear_before)
+            .await;
+    }
+
+    async fn emit_window(
+        &mut self,
+        key: &mut K,
+        window: Window,
+        ctx: &mut Context<K, OutT, Window>,
+    ) {
         let mut state = ctx.state.get_key_time_multi_map('w').await;
 
         match self.operation {
             WindowOperation::Aggregate(aggregator) => {
                 let value = {
                     let vs: Vec<&T> = state
-                        .get_time_range(&mut key, window.start_time, window.end_time)
+                        .get_time_range(key, window.start_time, window.end_time)
                         .await;
                     (aggregator)(vs)
                 };
@@ -155,7 +211,7 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
             WindowOperation::Flatten(flatten) => {
                 let values = {
                     let vs: Vec<&T> = state
-                        .get_time_range(&mut key, window.start_time, window.end_time)
+                        .get_time_range(key, window.start_time, window.end_time)
                         .await;
                     (flatten)(vs)
                 };
@@ -170,14 +226,5 @@ impl<K: Key, T: Data, OutT: Data, W: TimeWindowAssigner<K, T>> KeyedWindowFunc<K
                 }
             }
         }
-
-        // clear everything before our start time (we're guaranteed that timers execute in order,
-        // so with fixed-width windows there won't be any earlier data)
-        let next = self.assigner.next(window);
-        let mut state: KeyTimeMultiMap<K, T, _> = ctx.state.get_key_time_multi_map('w').await;
-
-        state
-            .clear_time_range(&mut key, SystemTime::UNIX_EPOCH, next.start_time)
-            .await;
     }
 }
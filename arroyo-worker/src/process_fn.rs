@@ -14,18 +14,23 @@ pub struct ProcessFnUtils {}
 impl ProcessFnUtils {
     pub async fn finished_timers<OutK: Key, OutT: Data, Timer: Data + Eq + PartialEq>(
         watermark: SystemTime,
-        ctx: &mut Context<OutK, OutT>,
+        ctx: &mut Context<OutK, OutT, Timer>,
     ) -> Vec<(OutK, TimerValue<OutK, Timer>)> {
         let mut state = ctx
             .state
-            .get_time_key_map(TIMER_TABLE, ctx.watermark())
+            .get_time_key_map::<OutK, Option<TimerValue<OutK, Timer>>>(TIMER_TABLE, ctx.watermark())
             .await;
-        state.evict_all_before_watermark(watermark)
+        // deleted timers leave a tombstone behind
+        state
+            .evict_all_before_watermark(watermark)
+            .into_iter()
+            .filter_map(|(key, timer)| Some((key, timer?)))
+            .collect()
     }
 
-    pub async fn send_event<OutK: Key, OutT: Data>(
+    pub async fn send_event<OutK: Key, OutT: Data, Timer: Data + Eq + PartialEq>(
         barrier: arroyo_types::CheckpointBarrier,
-        ctx: &mut Context<OutK, OutT>,
+        ctx: &mut Context<OutK, OutT, Timer>,
         event_type: TaskCheckpointEventType,
     ) {
         ctx.control_tx
@@ -183,8 +183,10 @@ pub async fn main() {
                         BuiltinSink::Web as i32,
                     )),
                     preview: false,
+                    parameters: Default::default(),
                 },
             )),
+            validate_only: false,
         })
         .await
         .unwrap()
@@ -199,6 +201,7 @@ pub async fn main() {
             pipeline_id: pipeline_id.clone(),
             checkpoint_interval_micros: 2_000_000,
             preview: false,
+            unaligned_checkpoints: false,
         })
         .await
         .unwrap()